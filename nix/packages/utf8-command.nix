@@ -40,6 +40,12 @@
       // {
         NEXTEST_HIDE_PROGRESS_BAR = "true";
       });
+    # Nextest doesn't run doctests, and the error messages are mostly tested there. Run
+    # everything with all the features enabled, which shouldn't change any behavior.
+    utf8-command-tests-all-features = craneLib.cargoTest (commonArgs
+      // {
+        cargoTestExtraArgs = "--all-features";
+      });
     utf8-command-clippy = craneLib.cargoClippy (commonArgs
       // {
         cargoClippyExtraArgs = "--all-targets -- --deny warnings";
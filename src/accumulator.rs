@@ -0,0 +1,293 @@
+use std::borrow::Cow;
+use std::process::ExitStatus;
+
+use crate::Error;
+use crate::StderrError;
+use crate::StdoutError;
+use crate::StreamDecodeError;
+use crate::Utf8Output;
+
+/// An incremental UTF-8 decoder, which is pushed chunks of a stream as they arrive.
+///
+/// Each call to [`Utf8Accumulator::push`] returns the newly decoded text. An incomplete sequence
+/// at the end of a chunk (at most 3 bytes) is held back until the next chunk completes it.
+///
+/// ```
+/// use utf8_command::Utf8Accumulator;
+///
+/// let dog = "🐶".as_bytes();
+/// let mut accumulator = Utf8Accumulator::new();
+/// assert_eq!(accumulator.push(b"puppy ").unwrap(), "puppy ");
+/// assert_eq!(accumulator.push(&dog[..2]).unwrap(), "");
+/// assert_eq!(accumulator.push(&dog[2..]).unwrap(), "🐶");
+/// assert_eq!(accumulator.finish().unwrap(), "puppy 🐶");
+/// ```
+///
+/// Errors report indexes into the whole stream:
+///
+/// ```
+/// use utf8_command::Utf8Accumulator;
+///
+/// let mut accumulator = Utf8Accumulator::new();
+/// accumulator.push(b"puppy ").unwrap();
+/// let err = accumulator.push(b"doggy \xc0").unwrap_err();
+/// assert_eq!(err.valid_up_to(), 12);
+/// assert_eq!(
+///     err.to_string(),
+///     "Output contained invalid utf-8 sequence of 1 bytes from index 12: [6 bytes] \"doggy �\""
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Utf8Accumulator {
+    decoded: String,
+    /// An incomplete sequence at the end of the last chunk.
+    pending: Vec<u8>,
+    /// The number of bytes pushed so far.
+    len: usize,
+    /// Whether to replace invalid sequences rather than failing.
+    lossy: bool,
+    /// The first decoding error, if any.
+    error: Option<StreamDecodeError>,
+}
+
+impl Utf8Accumulator {
+    /// Construct an empty accumulator.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace invalid UTF-8 sequences with U+FFFD REPLACEMENT CHARACTER rather than failing,
+    /// like [`String::from_utf8_lossy`].
+    ///
+    /// An incomplete sequence left over by [`Utf8Accumulator::finish`] is replaced, too.
+    pub fn lossy(mut self) -> Self {
+        self.lossy = true;
+        self
+    }
+
+    /// Decode a chunk of the stream, returning the newly decoded text.
+    ///
+    /// If the chunk contains invalid UTF-8, the valid text before the invalid sequence is kept,
+    /// the invalid sequence is skipped, and the error is returned. The rest of the chunk is
+    /// discarded, and [`Utf8Accumulator::finish`] will return the first error.
+    pub fn push(&mut self, bytes: &[u8]) -> Result<&str, StreamDecodeError> {
+        let start = self.decoded.len();
+        // The index in the stream of the first byte of `input`.
+        let offset = self.len - self.pending.len();
+        self.len += bytes.len();
+
+        let input = if self.pending.is_empty() {
+            Cow::Borrowed(bytes)
+        } else {
+            let mut input = std::mem::take(&mut self.pending);
+            input.extend_from_slice(bytes);
+            Cow::Owned(input)
+        };
+
+        let mut rest = &*input;
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    self.decoded.push_str(valid);
+                    break;
+                }
+                Err(err) => {
+                    let (valid, invalid) = rest.split_at(err.valid_up_to());
+                    // SAFETY: `from_utf8` checked that these bytes are valid.
+                    self.decoded
+                        .push_str(unsafe { std::str::from_utf8_unchecked(valid) });
+                    match err.error_len() {
+                        None => {
+                            self.pending.extend_from_slice(invalid);
+                            break;
+                        }
+                        Some(len) if self.lossy => {
+                            self.decoded.push(char::REPLACEMENT_CHARACTER);
+                            rest = &invalid[len..];
+                        }
+                        Some(_) => {
+                            let inner = String::from_utf8(input.to_vec())
+                                .expect_err("Input should be invalid UTF-8");
+                            let error = StreamDecodeError::at_offset(inner, offset);
+                            self.error.get_or_insert_with(|| error.clone());
+                            return Err(error);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(&self.decoded[start..])
+    }
+
+    /// Get the text decoded so far.
+    pub fn as_str(&self) -> &str {
+        &self.decoded
+    }
+
+    /// Take the text decoded so far, leaving the accumulator empty but ready to decode the rest
+    /// of the stream.
+    ///
+    /// This lets a long stream be decoded incrementally without keeping all of it in memory.
+    pub fn take(&mut self) -> String {
+        std::mem::take(&mut self.decoded)
+    }
+
+    /// Finish decoding the stream, returning all of the decoded text.
+    ///
+    /// Fails if any chunk contained invalid UTF-8, or if the stream ended with an incomplete
+    /// sequence (unless the accumulator is [lossy][Utf8Accumulator::lossy]).
+    pub fn finish(mut self) -> Result<String, StreamDecodeError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        if !self.pending.is_empty() {
+            if self.lossy {
+                self.decoded.push(char::REPLACEMENT_CHARACTER);
+            } else {
+                let offset = self.len - self.pending.len();
+                let inner = String::from_utf8(self.pending)
+                    .expect_err("An incomplete sequence should be invalid UTF-8");
+                return Err(StreamDecodeError::at_offset(inner, offset));
+            }
+        }
+        Ok(self.decoded)
+    }
+}
+
+impl Utf8Output {
+    /// Construct an output from accumulators which have been pushed the streams of a command.
+    ///
+    /// Errors are labeled with the stream they came from, like converting an [`Output`]:
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// use utf8_command::Utf8Accumulator;
+    /// use utf8_command::Utf8Output;
+    ///
+    /// let mut stdout = Utf8Accumulator::new();
+    /// stdout.push(b"puppy").unwrap();
+    /// let mut stderr = Utf8Accumulator::new();
+    /// stderr.push(b"doggy \xf0\x9f").unwrap();
+    ///
+    /// let err = Utf8Output::try_from_accumulators(ExitStatus::default(), stdout, stderr)
+    ///     .unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Stderr contained incomplete utf-8 byte sequence from index 6: [6 bytes] \"�\""
+    /// );
+    /// ```
+    ///
+    /// [`Output`]: std::process::Output
+    pub fn try_from_accumulators(
+        status: ExitStatus,
+        stdout: Utf8Accumulator,
+        stderr: Utf8Accumulator,
+    ) -> Result<Self, Error> {
+        let (stdout, stderr) = Error::from_owned_results(
+            status,
+            stdout.finish().map_err(StdoutError::from),
+            stderr.finish().map_err(StderrError::from),
+        )?;
+        Ok(Utf8Output {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// Split `bytes` at the given (sorted) indexes.
+    fn chunks(bytes: &[u8], mut splits: Vec<usize>) -> Vec<&[u8]> {
+        splits.retain(|split| *split <= bytes.len());
+        splits.sort();
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        for split in splits {
+            chunks.push(&bytes[start..split]);
+            start = split;
+        }
+        chunks.push(&bytes[start..]);
+        chunks
+    }
+
+    proptest! {
+        #[test]
+        fn test_reassembly(string in any::<String>(), splits in prop::collection::vec(0..64usize, 0..8)) {
+            let mut accumulator = Utf8Accumulator::new();
+            let mut pushed = String::new();
+            for chunk in chunks(string.as_bytes(), splits) {
+                pushed.push_str(accumulator.push(chunk).unwrap());
+            }
+            prop_assert_eq!(&pushed, &string);
+            prop_assert_eq!(accumulator.finish().unwrap(), string);
+        }
+
+        #[test]
+        fn test_lossy_matches_from_utf8_lossy(
+            bytes in prop::collection::vec(any::<u8>(), 0..64),
+            splits in prop::collection::vec(0..64usize, 0..8),
+        ) {
+            let mut accumulator = Utf8Accumulator::new().lossy();
+            for chunk in chunks(&bytes, splits) {
+                accumulator.push(chunk).unwrap();
+            }
+            prop_assert_eq!(
+                accumulator.finish().unwrap(),
+                String::from_utf8_lossy(&bytes).into_owned()
+            );
+        }
+
+        #[test]
+        fn test_strict_matches_from_utf8(
+            bytes in prop::collection::vec(any::<u8>(), 0..64),
+            splits in prop::collection::vec(0..64usize, 0..8),
+        ) {
+            let mut accumulator = Utf8Accumulator::new();
+            let mut first_error = None;
+            for chunk in chunks(&bytes, splits) {
+                if let Err(err) = accumulator.push(chunk) {
+                    first_error.get_or_insert(err.valid_up_to());
+                }
+            }
+            let finished = accumulator.finish();
+            match String::from_utf8(bytes) {
+                Ok(string) => prop_assert_eq!(finished.unwrap(), string),
+                Err(err) => {
+                    let finished = finished.unwrap_err();
+                    prop_assert_eq!(finished.valid_up_to(), err.utf8_error().valid_up_to());
+                    if let Some(first_error) = first_error {
+                        prop_assert_eq!(first_error, finished.valid_up_to());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_take() {
+        let mut accumulator = Utf8Accumulator::new();
+        accumulator.push(b"puppy \xf0\x9f").unwrap();
+        assert_eq!(accumulator.take(), "puppy ");
+        assert_eq!(accumulator.push(b"\x90\xb6").unwrap(), "🐶");
+        assert_eq!(accumulator.as_str(), "🐶");
+    }
+
+    #[test]
+    fn test_try_from_accumulators() {
+        let mut stdout = Utf8Accumulator::new();
+        stdout.push(b"puppy").unwrap();
+        let mut stderr = Utf8Accumulator::new().lossy();
+        stderr.push(b"doggy \xc0").unwrap();
+        let output =
+            Utf8Output::try_from_accumulators(ExitStatus::default(), stdout, stderr).unwrap();
+        assert_eq!(output.stdout, "puppy");
+        assert_eq!(output.stderr, "doggy \u{FFFD}");
+    }
+}
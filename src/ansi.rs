@@ -0,0 +1,150 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// The escape character, which starts an escape sequence.
+const ESC: char = '\x1b';
+/// The 8-bit Control Sequence Introducer, equivalent to `ESC [`.
+const CSI: char = '\u{9b}';
+/// The 8-bit Operating System Command, equivalent to `ESC ]`.
+const OSC: char = '\u{9d}';
+/// The BEL character, which can terminate an OSC sequence.
+const BEL: char = '\x07';
+/// The 8-bit String Terminator, equivalent to `ESC \`.
+const ST: char = '\u{9c}';
+
+/// Remove ANSI escape sequences (like colors, cursor movement, and hyperlinks) from `text`.
+///
+/// This works on decoded text, so multi-byte characters are never split. A sequence which is
+/// interrupted by a character which can't be part of it (like a U+FFFD REPLACEMENT CHARACTER
+/// from lossy decoding) is dropped up to that character, and the rest of the text is kept.
+pub(crate) fn strip_ansi(text: &mut String) {
+    if !text.contains([ESC, CSI, OSC]) {
+        return;
+    }
+
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ESC => match chars.peek() {
+                Some('[') => {
+                    chars.next();
+                    skip_csi(&mut chars);
+                }
+                Some(']' | 'P' | 'X' | '^' | '_') => {
+                    chars.next();
+                    skip_string(&mut chars);
+                }
+                // Intermediate bytes, followed by a final byte.
+                Some('\x20'..='\x2f') => {
+                    while chars.next_if(|c| matches!(c, '\x20'..='\x2f')).is_some() {}
+                    chars.next_if(|c| matches!(c, '\x30'..='\x7e'));
+                }
+                // A two-character escape sequence, like `ESC M` (reverse line feed).
+                Some('\x30'..='\x7e') => {
+                    chars.next();
+                }
+                // A lone escape character.
+                _ => {}
+            },
+            CSI => skip_csi(&mut chars),
+            OSC => skip_string(&mut chars),
+            c => stripped.push(c),
+        }
+    }
+
+    *text = stripped;
+}
+
+/// Skip the rest of a CSI sequence (like `ESC [ 3 1 m`), after the introducer.
+fn skip_csi(chars: &mut Peekable<Chars<'_>>) {
+    // Parameter bytes, then intermediate bytes.
+    while chars.next_if(|c| matches!(c, '\x30'..='\x3f')).is_some() {}
+    while chars.next_if(|c| matches!(c, '\x20'..='\x2f')).is_some() {}
+    // The final byte.
+    chars.next_if(|c| matches!(c, '\x40'..='\x7e'));
+}
+
+/// Skip the rest of a string sequence (like an OSC hyperlink), after the introducer.
+///
+/// The string ends with BEL or a String Terminator. If the string isn't terminated, the rest of
+/// the line is skipped.
+fn skip_string(chars: &mut Peekable<Chars<'_>>) {
+    while let Some(&c) = chars.peek() {
+        match c {
+            BEL | ST => {
+                chars.next();
+                return;
+            }
+            ESC => {
+                chars.next();
+                chars.next_if_eq(&'\\');
+                return;
+            }
+            '\n' => return,
+            _ => {
+                chars.next();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(text: &str) -> String {
+        let mut text = String::from(text);
+        strip_ansi(&mut text);
+        text
+    }
+
+    #[test]
+    fn test_no_escapes() {
+        assert_eq!(strip(""), "");
+        assert_eq!(strip("puppy 🐶\n"), "puppy 🐶\n");
+    }
+
+    #[test]
+    fn test_colors() {
+        assert_eq!(
+            strip("\x1b[1m\x1b[32m   Compiling\x1b[0m puppy v0.1.0"),
+            "   Compiling puppy v0.1.0"
+        );
+        assert_eq!(strip("\x1b[38;5;208m🐶\x1b[m"), "🐶");
+        assert_eq!(strip("\u{9b}31mdoggy\u{9b}0m"), "doggy");
+    }
+
+    #[test]
+    fn test_cursor_movement() {
+        assert_eq!(strip("50%\x1b[2K\x1b[1G100%"), "50%100%");
+        assert_eq!(strip("\x1b[?25lpuppy\x1b[?25h"), "puppy");
+        assert_eq!(strip("puppy\x1bMdoggy"), "puppydoggy");
+        assert_eq!(strip("\x1b(Bpuppy"), "puppy");
+    }
+
+    #[test]
+    fn test_osc_hyperlinks() {
+        assert_eq!(
+            strip("\x1b]8;;https://example.com\x1b\\puppy\x1b]8;;\x1b\\"),
+            "puppy"
+        );
+        assert_eq!(strip("\x1b]0;title\x07doggy"), "doggy");
+        assert_eq!(strip("\u{9d}0;title\u{9c}doggy"), "doggy");
+    }
+
+    #[test]
+    fn test_interrupted_sequences() {
+        // A sequence split by an invalid UTF-8 region (decoded lossily).
+        assert_eq!(strip("\x1b[3\u{fffd}1mpuppy"), "\u{fffd}1mpuppy");
+        // Multi-byte characters are never swallowed by a sequence.
+        assert_eq!(strip("\x1b[🐶"), "🐶");
+        assert_eq!(strip("\x1b🐶"), "🐶");
+        // Unterminated sequences at the end of the text.
+        assert_eq!(strip("puppy\x1b"), "puppy");
+        assert_eq!(strip("puppy\x1b[31"), "puppy");
+        assert_eq!(strip("puppy\x1b]8;;https://exa"), "puppy");
+        // An unterminated OSC sequence ends at the end of the line.
+        assert_eq!(strip("\x1b]0;title\npuppy"), "\npuppy");
+    }
+}
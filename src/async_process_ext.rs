@@ -0,0 +1,142 @@
+use std::future::Future;
+
+use async_process::Command;
+
+use crate::command::check_success;
+use crate::command::decode_output;
+use crate::CheckedError;
+use crate::CommandError;
+use crate::CommandInfo;
+use crate::SpawnError;
+use crate::Utf8Output;
+
+/// Extension methods for running an [`async_process::Command`] and decoding its output as
+/// UTF-8.
+///
+/// These are the asynchronous equivalents of [`CommandExt`][crate::CommandExt], with the same
+/// error types. `async-process` works with any runtime, including smol:
+///
+/// ```
+/// # futures_lite::future::block_on(async {
+/// use async_process::Command;
+/// use utf8_command::AsyncCommandExt;
+///
+/// let output = Command::new("echo").arg("puppy").output_utf8().await.unwrap();
+/// assert_eq!(output.stdout, "puppy\n");
+/// # });
+/// ```
+///
+/// With the `async-std` feature, this also works for `async_std::process::Command`, which is
+/// the same type.
+pub trait AsyncCommandExt {
+    /// Run the command and decode its output streams as UTF-8.
+    ///
+    /// See [`CommandExt::output_utf8`][crate::CommandExt::output_utf8].
+    fn output_utf8(&mut self) -> impl Future<Output = Result<Utf8Output, CommandError>> + Send;
+
+    /// Run the command, decode its output streams as UTF-8, and require it to exit
+    /// successfully.
+    ///
+    /// See [`CommandExt::output_utf8_checked`][crate::CommandExt::output_utf8_checked].
+    fn output_utf8_checked(
+        &mut self,
+    ) -> impl Future<Output = Result<Utf8Output, CheckedError>> + Send;
+
+    /// Run the command and decode its output streams as UTF-8, replacing invalid sequences
+    /// with U+FFFD REPLACEMENT CHARACTER.
+    ///
+    /// See [`Utf8Output::from_utf8_lossy`].
+    fn output_utf8_lossy(&mut self) -> impl Future<Output = Result<Utf8Output, SpawnError>> + Send;
+}
+
+impl AsyncCommandExt for Command {
+    async fn output_utf8(&mut self) -> Result<Utf8Output, CommandError> {
+        let info = command_info(self);
+        decode_output(self.output().await, Some(info), Utf8Output::try_from)
+    }
+
+    async fn output_utf8_checked(&mut self) -> Result<Utf8Output, CheckedError> {
+        let output = self.output_utf8().await?;
+        check_success(output, Some(command_info(self)))
+    }
+
+    async fn output_utf8_lossy(&mut self) -> Result<Utf8Output, SpawnError> {
+        let info = command_info(self);
+        let output = self
+            .output()
+            .await
+            .map_err(|err| SpawnError::new(err, Some(info)))?;
+        Ok(Utf8Output::from_utf8_lossy(output))
+    }
+}
+
+fn command_info(command: &Command) -> CommandInfo {
+    let info = CommandInfo::new(command.get_program(), command.get_args());
+    match command.get_current_dir() {
+        Some(dir) => info.with_current_dir(dir),
+        None => info,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::future::block_on;
+
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_utf8() {
+        block_on(async {
+            let output = Command::new("echo")
+                .arg("puppy")
+                .output_utf8_checked()
+                .await
+                .unwrap();
+            assert_eq!(output.stdout, "puppy\n");
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_utf8_invalid() {
+        block_on(async {
+            let mut command = Command::new("printf");
+            command.arg("puppy \\300");
+
+            let err = command.output_utf8().await.unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "`printf 'puppy \\300'`: \
+                Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+            );
+
+            let output = command.output_utf8_lossy().await.unwrap();
+            assert_eq!(output.stdout, "puppy \u{FFFD}");
+        });
+    }
+
+    #[test]
+    fn test_output_utf8_missing_binary() {
+        block_on(async {
+            let err = Command::new("utf8-command-this-program-does-not-exist")
+                .output_utf8()
+                .await
+                .unwrap_err();
+            assert!(matches!(err, CommandError::Io(_)));
+        });
+    }
+
+    #[cfg(all(unix, feature = "async-std"))]
+    #[test]
+    fn test_async_std() {
+        async_std::task::block_on(async {
+            let output = async_std::process::Command::new("echo")
+                .arg("puppy")
+                .output_utf8()
+                .await
+                .unwrap();
+            assert_eq!(output.stdout, "puppy\n");
+        });
+    }
+}
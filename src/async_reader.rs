@@ -0,0 +1,216 @@
+use std::future::poll_fn;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+
+use futures_io::AsyncRead;
+
+use crate::reader::DecodeBuffer;
+use crate::reader::Decoded;
+
+/// A reader which decodes the bytes of any [`futures_io::AsyncRead`] as UTF-8 incrementally.
+///
+/// This is the asynchronous equivalent of [`Utf8Reader`][crate::Utf8Reader], and works with any
+/// runtime which uses the `futures` I/O traits, like `smol` and `async-std`. Code points split
+/// across reads are handled correctly, and invalid UTF-8 fails with an [`std::io::Error`] of kind
+/// [`InvalidData`][std::io::ErrorKind::InvalidData] which wraps a
+/// [`StreamDecodeError`][crate::StreamDecodeError].
+///
+/// ```
+/// # futures_lite::future::block_on(async {
+/// use futures_lite::io::Cursor;
+/// use utf8_command::AsyncUtf8Reader;
+///
+/// let mut reader = AsyncUtf8Reader::new(Cursor::new("puppy 🐶"));
+/// let mut decoded = String::new();
+/// while let Some(chunk) = reader.read_str().await.unwrap() {
+///     decoded.push_str(chunk);
+/// }
+/// assert_eq!(decoded, "puppy 🐶");
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct AsyncUtf8Reader<R> {
+    inner: R,
+    buffer: DecodeBuffer,
+}
+
+impl<R: AsyncRead + Unpin> AsyncUtf8Reader<R> {
+    /// Construct a reader which decodes the bytes read from `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buffer: DecodeBuffer::default(),
+        }
+    }
+
+    /// Replace invalid UTF-8 sequences with U+FFFD REPLACEMENT CHARACTER rather than failing,
+    /// like [`String::from_utf8_lossy`].
+    ///
+    /// An incomplete sequence at the end of the stream is replaced, too.
+    pub fn lossy(mut self) -> Self {
+        self.buffer.lossy = true;
+        self
+    }
+
+    /// Read and decode the next chunk of the stream, or `None` at the end of the stream.
+    ///
+    /// Returned chunks are never empty.
+    pub async fn read_str(&mut self) -> std::io::Result<Option<&str>> {
+        let decoded = poll_fn(|cx| self.poll_decoded(cx)).await?;
+        Ok(self.buffer.get(decoded))
+    }
+
+    /// Poll for the next chunk of the stream, or `None` at the end of the stream.
+    ///
+    /// See [`AsyncUtf8Reader::read_str`].
+    pub fn poll_read_str(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<Option<&str>>> {
+        match self.poll_decoded(cx) {
+            Poll::Ready(Ok(decoded)) => Poll::Ready(Ok(self.buffer.get(decoded))),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Read until a chunk is decoded.
+    fn poll_decoded(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<Decoded>> {
+        loop {
+            let decoded = self.buffer.decode()?;
+            if decoded != Decoded::Pending {
+                return Poll::Ready(Ok(decoded));
+            }
+            let inner = &mut self.inner;
+            match self
+                .buffer
+                .fill(|buf| Pin::new(&mut *inner).poll_read(cx, buf))
+            {
+                Poll::Ready(result) => result?,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner reader.
+    ///
+    /// Reading from the inner reader directly may corrupt the decoded stream.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Get the inner reader, discarding any buffered bytes.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::future::block_on;
+    use futures_lite::io::Cursor;
+
+    use super::*;
+    use crate::StreamDecodeError;
+
+    /// A reader which returns one byte at a time, and is pending before each byte.
+    struct Trickle<'a> {
+        bytes: &'a [u8],
+        ready: bool,
+    }
+
+    impl<'a> Trickle<'a> {
+        fn new(bytes: &'a [u8]) -> Self {
+            Self {
+                bytes,
+                ready: false,
+            }
+        }
+    }
+
+    impl AsyncRead for Trickle<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<std::io::Result<usize>> {
+            if !std::mem::replace(&mut self.ready, false) {
+                self.ready = true;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            match self.bytes.split_first() {
+                Some((first, rest)) if !buf.is_empty() => {
+                    buf[0] = *first;
+                    self.bytes = rest;
+                    Poll::Ready(Ok(1))
+                }
+                _ => Poll::Ready(Ok(0)),
+            }
+        }
+    }
+
+    async fn read_all<R: AsyncRead + Unpin>(
+        reader: &mut AsyncUtf8Reader<R>,
+    ) -> std::io::Result<Vec<String>> {
+        let mut chunks = Vec::new();
+        while let Some(chunk) = reader.read_str().await? {
+            chunks.push(chunk.to_owned());
+        }
+        Ok(chunks)
+    }
+
+    #[test]
+    fn test_cursor() {
+        let mut reader = AsyncUtf8Reader::new(Cursor::new("puppy 🐶\ndoggy"));
+        assert_eq!(
+            block_on(read_all(&mut reader)).unwrap(),
+            ["puppy 🐶\ndoggy"]
+        );
+    }
+
+    #[test]
+    fn test_trickle() {
+        let mut reader = AsyncUtf8Reader::new(Trickle::new("a🐶b".as_bytes()));
+        assert_eq!(block_on(read_all(&mut reader)).unwrap(), ["a", "🐶", "b"]);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut reader = AsyncUtf8Reader::new(Trickle::new(b"pup \xc0 py"));
+        let mut decoded = String::new();
+        let err = block_on(async {
+            loop {
+                match reader.read_str().await {
+                    Ok(Some(chunk)) => decoded.push_str(chunk),
+                    Ok(None) => unreachable!("The stream contains an invalid sequence"),
+                    Err(err) => break err,
+                }
+            }
+        });
+        assert_eq!(decoded, "pup ");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let err = err
+            .get_ref()
+            .unwrap()
+            .downcast_ref::<StreamDecodeError>()
+            .unwrap();
+        assert_eq!(err.valid_up_to(), 4);
+        assert_eq!(
+            err.to_string(),
+            "Output contained invalid utf-8 sequence of 1 bytes from index 4: [4 bytes] \"�\""
+        );
+    }
+
+    #[test]
+    fn test_lossy() {
+        let mut reader = AsyncUtf8Reader::new(Trickle::new(b"\xc0pup\xf0\x9fpy\xf0\x9f")).lossy();
+        assert_eq!(
+            block_on(read_all(&mut reader)).unwrap().concat(),
+            "\u{FFFD}pup\u{FFFD}py\u{FFFD}"
+        );
+    }
+}
@@ -0,0 +1,200 @@
+use std::fmt::Display;
+use std::process::Output;
+
+use crate::Error;
+use crate::Utf8Output;
+
+/// Convert a batch of [`Output`]s into [`Utf8Output`]s, stopping at the first failure.
+///
+/// The error records the index of the [`Output`] which failed to decode.
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use std::process::Output;
+/// let outputs = vec![
+///     Output {
+///         status: ExitStatus::default(),
+///         stdout: Vec::from(b"puppy"),
+///         stderr: Vec::from(b""),
+///     },
+///     Output {
+///         status: ExitStatus::default(),
+///         stdout: Vec::from(b"doggy \xc0"),
+///         stderr: Vec::from(b""),
+///     },
+/// ];
+///
+/// let err = utf8_command::try_convert_all(outputs).unwrap_err();
+/// assert_eq!(err.index(), 1);
+/// assert_eq!(
+///     err.to_string(),
+///     "Output 1: Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"doggy �\""
+/// );
+/// ```
+pub fn try_convert_all(
+    outputs: impl IntoIterator<Item = Output>,
+) -> Result<Vec<Utf8Output>, BatchError> {
+    outputs
+        .into_iter()
+        .enumerate()
+        .map(|(index, output)| {
+            Utf8Output::try_from(output).map_err(|error| BatchError { index, error })
+        })
+        .collect()
+}
+
+/// Convert a batch of [`Output`]s into [`Utf8Output`]s, keeping the result for each [`Output`].
+///
+/// The results are in the same order as the [`Output`]s.
+pub fn convert_all(outputs: impl IntoIterator<Item = Output>) -> Vec<Result<Utf8Output, Error>> {
+    outputs.into_iter().map(Utf8Output::try_from).collect()
+}
+
+/// Convert a batch of [`Output`]s into [`Utf8Output`]s in parallel, stopping at the first
+/// failure.
+///
+/// With the `rayon` feature enabled, the outputs are decoded in parallel. Otherwise, this is the
+/// same as [`try_convert_all`].
+///
+/// The results and errors are identical to [`try_convert_all`]: the outputs are returned in
+/// their original order, and if several outputs fail to decode, the error is for the first one.
+pub fn try_convert_all_parallel(outputs: Vec<Output>) -> Result<Vec<Utf8Output>, BatchError> {
+    convert_all_parallel(outputs)
+        .into_iter()
+        .enumerate()
+        .map(|(index, result)| result.map_err(|error| BatchError { index, error }))
+        .collect()
+}
+
+/// Convert a batch of [`Output`]s into [`Utf8Output`]s in parallel, keeping the result for each
+/// [`Output`].
+///
+/// With the `rayon` feature enabled, the outputs are decoded in parallel. Otherwise, this is the
+/// same as [`convert_all`].
+///
+/// The results are in the same order as the [`Output`]s.
+pub fn convert_all_parallel(outputs: Vec<Output>) -> Vec<Result<Utf8Output, Error>> {
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        outputs.into_par_iter().map(Utf8Output::try_from).collect()
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    {
+        convert_all(outputs)
+    }
+}
+
+/// An error produced by [`try_convert_all`], recording which [`Output`] failed to decode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchError {
+    index: usize,
+    error: Error,
+}
+
+impl BatchError {
+    /// The index of the [`Output`] which failed to decode.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Get a reference to the decoding error.
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    /// Get the decoding error.
+    pub fn into_error(self) -> Error {
+        self.error
+    }
+}
+
+impl Display for BatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Output {}: {}", self.index, self.error)
+    }
+}
+
+impl std::error::Error for BatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::output;
+
+    fn batch() -> Vec<Output> {
+        vec![
+            output(b"puppy", b""),
+            output(b"doggy", b""),
+            output(b"\xc0", b""),
+            output(b"kitty", b""),
+            output(b"\xf0\x90", b""),
+        ]
+    }
+
+    #[test]
+    fn test_try_convert_all_success() {
+        let converted =
+            try_convert_all(vec![output(b"puppy", b""), output(b"doggy", b"")]).unwrap();
+        assert_eq!(
+            converted
+                .iter()
+                .map(|output| output.stdout.as_str())
+                .collect::<Vec<_>>(),
+            ["puppy", "doggy"]
+        );
+        assert_eq!(try_convert_all(vec![]), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_try_convert_all_first_failure() {
+        let err = try_convert_all(batch()).unwrap_err();
+        assert_eq!(err.index(), 2);
+        assert_eq!(err.error().inner().as_bytes(), b"\xc0");
+    }
+
+    #[test]
+    fn test_convert_all_indices() {
+        let results = convert_all(batch());
+        assert_eq!(results.len(), 5);
+        let failed: Vec<usize> = results
+            .iter()
+            .enumerate()
+            .filter_map(|(index, result)| result.is_err().then_some(index))
+            .collect();
+        assert_eq!(failed, [2, 4]);
+        assert_eq!(results[3].as_ref().unwrap().stdout, "kitty");
+        assert_eq!(
+            results[4].as_ref().unwrap_err().inner().as_bytes(),
+            b"\xf0\x90"
+        );
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() {
+        // A large batch, with a few failures scattered through it.
+        let outputs: Vec<Output> = (0..2000)
+            .map(|i| {
+                let mut stdout = format!("output {i} ").repeat(1000).into_bytes();
+                if i % 301 == 7 {
+                    stdout[i] = 0xc0;
+                }
+                output(&stdout, b"")
+            })
+            .collect();
+
+        assert_eq!(
+            convert_all_parallel(outputs.clone()),
+            convert_all(outputs.clone())
+        );
+
+        let err = try_convert_all_parallel(outputs.clone()).unwrap_err();
+        assert_eq!(err.index(), 7);
+        assert_eq!(err, try_convert_all(outputs).unwrap_err());
+    }
+}
@@ -0,0 +1,579 @@
+use std::io::Read;
+use std::io::Write;
+use std::process::Child;
+use std::process::Command;
+use std::process::ExitStatus;
+use std::process::Output;
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::lossy::decode_lossy;
+use crate::OutputLimit;
+use crate::Stream;
+
+/// The size of the chunks read from a child's pipes.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// How often to check whether a child has exited when waiting with a deadline.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// An event from one of a child's pipes.
+enum Event {
+    /// Bytes were read from the stream.
+    Data(Stream, Vec<u8>),
+    /// The stream was closed, or reading from it failed.
+    Closed(std::io::Result<()>),
+}
+
+/// A child's `stdout` and `stderr` pipes, drained concurrently by reader threads.
+///
+/// Each pipe is read on its own thread, so a child writing lots of output to one stream is never
+/// blocked waiting for the other to be read.
+#[derive(Debug)]
+pub(crate) struct Pipes {
+    receiver: Receiver<Event>,
+    open: usize,
+}
+
+impl Pipes {
+    /// Take the piped `stdout` and `stderr` handles from `child` and start reading them.
+    pub(crate) fn new(child: &mut Child) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let mut open = 0;
+        if let Some(stdout) = child.stdout.take() {
+            spawn_reader(Stream::Stdout, stdout, sender.clone());
+            open += 1;
+        }
+        if let Some(stderr) = child.stderr.take() {
+            spawn_reader(Stream::Stderr, stderr, sender);
+            open += 1;
+        }
+        Self { receiver, open }
+    }
+
+    /// Wait for the next chunk of output, or `None` once both pipes are closed.
+    pub(crate) fn recv(&mut self) -> std::io::Result<Option<(Stream, Vec<u8>)>> {
+        match self.recv_deadline(None)? {
+            Received::Data(stream, bytes) => Ok(Some((stream, bytes))),
+            Received::Closed => Ok(None),
+            Received::TimedOut => unreachable!("Timed out without a deadline"),
+        }
+    }
+
+    /// Wait for the next chunk of output until `deadline`, if any.
+    pub(crate) fn recv_deadline(&mut self, deadline: Option<Instant>) -> std::io::Result<Received> {
+        while self.open > 0 {
+            // The reader threads only hang up after sending `Closed`, which we count.
+            let event = match deadline {
+                Some(deadline) => match self
+                    .receiver
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                {
+                    Ok(event) => event,
+                    Err(RecvTimeoutError::Timeout) => return Ok(Received::TimedOut),
+                    Err(RecvTimeoutError::Disconnected) => {
+                        unreachable!("Reader threads hung up without closing")
+                    }
+                },
+                None => self
+                    .receiver
+                    .recv()
+                    .expect("Reader threads hung up without closing"),
+            };
+            match event {
+                Event::Data(stream, bytes) => return Ok(Received::Data(stream, bytes)),
+                Event::Closed(result) => {
+                    self.open -= 1;
+                    result?;
+                }
+            }
+        }
+        Ok(Received::Closed)
+    }
+
+    /// Get any output which has already been read, without waiting.
+    pub(crate) fn try_recv(&mut self) -> Option<(Stream, Vec<u8>)> {
+        while let Ok(event) = self.receiver.try_recv() {
+            match event {
+                Event::Data(stream, bytes) => return Some((stream, bytes)),
+                Event::Closed(_) => self.open -= 1,
+            }
+        }
+        None
+    }
+}
+
+/// The result of waiting for output with [`Pipes::recv_deadline`].
+pub(crate) enum Received {
+    /// Bytes were read from a stream.
+    Data(Stream, Vec<u8>),
+    /// Both pipes are closed.
+    Closed,
+    /// The deadline passed before any output arrived.
+    TimedOut,
+}
+
+fn spawn_reader(stream: Stream, mut pipe: impl Read + Send + 'static, sender: Sender<Event>) {
+    std::thread::spawn(move || {
+        let mut buffer = vec![0; CHUNK_SIZE];
+        let result = loop {
+            match pipe.read(&mut buffer) {
+                Ok(0) => break Ok(()),
+                Ok(read) => {
+                    if sender
+                        .send(Event::Data(stream, buffer[..read].to_vec()))
+                        .is_err()
+                    {
+                        // Nobody's listening anymore.
+                        return;
+                    }
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => break Err(err),
+            }
+        };
+        let _ = sender.send(Event::Closed(result));
+    });
+}
+
+/// Spawn `command` with its `stdout` and `stderr` piped.
+pub(crate) fn spawn_piped(command: &mut Command) -> std::io::Result<Child> {
+    command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+}
+
+/// Run `command` with `input` written to its `stdin`, capturing its `stdout` and `stderr`.
+///
+/// The input is written from its own thread while the output is drained, so large inputs and
+/// outputs can't deadlock. `stdin` is closed once the input is written. If the child exits
+/// without reading all of its input, the unread input is discarded.
+pub(crate) fn output_with_input(command: &mut Command, input: &[u8]) -> std::io::Result<Output> {
+    let mut child = spawn_piped(command.stdin(Stdio::piped()))?;
+    let mut stdin = child.stdin.take().expect("Stdin should be piped");
+    std::thread::scope(|scope| {
+        let writer = scope.spawn(move || match stdin.write_all(input) {
+            // The child exited or closed `stdin` without reading everything.
+            Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+            // `stdin` is dropped here, closing it.
+            result => result,
+        });
+        let output = child.wait_with_output();
+        writer.join().expect("Stdin writer thread panicked")?;
+        output
+    })
+}
+
+/// Run `command`, copying its `stdout` and `stderr` to the given writers as they arrive while
+/// also capturing them.
+///
+/// If writing to one of the writers fails, it's no longer written to, but the stream is still
+/// captured.
+pub(crate) fn tee(
+    command: &mut Command,
+    stdout: &mut dyn Write,
+    stderr: &mut dyn Write,
+) -> std::io::Result<Output> {
+    let mut child = spawn_piped(command)?;
+    let mut pipes = Pipes::new(&mut child);
+    let mut output = Output {
+        status: Default::default(),
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+    };
+    let mut teeing = [true, true];
+
+    let result = (|| {
+        while let Some((stream, bytes)) = pipes.recv()? {
+            let index = stream.select(0, 1);
+            if teeing[index] {
+                let writer: &mut dyn Write = match stream {
+                    Stream::Stdout => &mut *stdout,
+                    Stream::Stderr => &mut *stderr,
+                };
+                teeing[index] = writer
+                    .write_all(&bytes)
+                    .and_then(|()| writer.flush())
+                    .is_ok();
+            }
+            stream
+                .select(&mut output.stdout, &mut output.stderr)
+                .extend_from_slice(&bytes);
+        }
+        Ok(())
+    })();
+
+    if let Err(err) = result {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(err);
+    }
+
+    output.status = child.wait()?;
+    Ok(output)
+}
+
+/// Run `command`, calling `on_line` with each line of its `stdout` and `stderr` as it's written,
+/// and capturing them.
+///
+/// Lines are passed without their trailing `\n` or `\r\n`, and invalid UTF-8 in them is replaced
+/// with U+FFFD REPLACEMENT CHARACTER. A final line without a newline is passed when the stream
+/// is closed.
+pub(crate) fn output_with_lines(
+    command: &mut Command,
+    on_line: &mut dyn FnMut(Stream, &str),
+) -> std::io::Result<Output> {
+    let mut child = spawn_piped(command)?;
+    let mut pipes = Pipes::new(&mut child);
+    let mut output = Output {
+        status: Default::default(),
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+    };
+    // The index of the start of the current line in each stream.
+    let mut line_starts = [0, 0];
+
+    let result = (|| {
+        while let Some((stream, bytes)) = pipes.recv()? {
+            let buffer = stream.select(&mut output.stdout, &mut output.stderr);
+            let line_start = &mut line_starts[stream.select(0, 1)];
+            let old_len = buffer.len();
+            buffer.extend_from_slice(&bytes);
+            // Only search the new bytes for newlines, so long lines aren't searched repeatedly.
+            for newline in newlines(&bytes).map(|index| old_len + index) {
+                call_line(on_line, stream, &buffer[*line_start..newline]);
+                *line_start = newline + 1;
+            }
+        }
+        Ok(())
+    })();
+
+    if let Err(err) = result {
+        let _ = kill(&mut child);
+        return Err(err);
+    }
+
+    for (stream, buffer, line_start) in [
+        (Stream::Stdout, &output.stdout, line_starts[0]),
+        (Stream::Stderr, &output.stderr, line_starts[1]),
+    ] {
+        if line_start < buffer.len() {
+            call_line(on_line, stream, &buffer[line_start..]);
+        }
+    }
+
+    output.status = child.wait()?;
+    Ok(output)
+}
+
+/// The indexes of the newlines in `bytes`.
+fn newlines(bytes: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    bytes
+        .iter()
+        .enumerate()
+        .filter(|(_, byte)| **byte == b'\n')
+        .map(|(index, _)| index)
+}
+
+fn call_line(on_line: &mut dyn FnMut(Stream, &str), stream: Stream, line: &[u8]) {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    on_line(stream, &String::from_utf8_lossy(line));
+}
+
+/// Limits on how long a command captured with [`output_limited`] can run for, and how much
+/// output it can write.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Limits {
+    /// How long the command can run for in total.
+    pub(crate) timeout: Option<Duration>,
+    /// How long the command can run for without writing to either output stream.
+    pub(crate) idle_timeout: Option<Duration>,
+    /// How much output the command can write.
+    pub(crate) max_bytes: Option<OutputLimit>,
+}
+
+/// Why [`output_limited`] killed a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Stopped {
+    /// The command ran for longer than [`Limits::timeout`].
+    TimedOut,
+    /// The command didn't write any output for [`Limits::idle_timeout`]; it was idle for `idle`.
+    Stalled { idle: Duration },
+    /// The command wrote more than [`Limits::max_bytes`] of output, the last of it to `stream`.
+    Overflowed { stream: Stream },
+}
+
+/// Run `command`, capturing its output, and kill it if it exceeds any of the `limits`.
+///
+/// If the command is killed, the output contains whatever was captured before then, and its
+/// status is the status of the killed command. If it wrote too much output, the output is
+/// truncated to the limit, at a code point boundary.
+pub(crate) fn output_limited(
+    command: &mut Command,
+    limits: Limits,
+) -> std::io::Result<(Output, Option<Stopped>)> {
+    let start = Instant::now();
+    let mut child = spawn_piped(command)?;
+    let mut pipes = Pipes::new(&mut child);
+    let mut output = Output {
+        status: Default::default(),
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+    };
+    let mut last_output = start;
+
+    // The next deadline, and whether it's the idle timeout.
+    let deadline = |last_output: Instant| {
+        let timeout = limits.timeout.map(|timeout| (start + timeout, false));
+        let idle = limits.idle_timeout.map(|idle| (last_output + idle, true));
+        match (timeout, idle) {
+            (Some(timeout), Some(idle)) => Some(timeout.min(idle)),
+            (timeout, idle) => timeout.or(idle),
+        }
+    };
+    let stopped = |idle: bool, last_output: Instant| {
+        if idle {
+            Stopped::Stalled {
+                idle: last_output.elapsed(),
+            }
+        } else {
+            Stopped::TimedOut
+        }
+    };
+
+    let result = (|| {
+        loop {
+            let deadline = deadline(last_output);
+            match pipes.recv_deadline(deadline.map(|(instant, _)| instant))? {
+                Received::Data(stream, bytes) => {
+                    last_output = Instant::now();
+                    stream
+                        .select(&mut output.stdout, &mut output.stderr)
+                        .extend_from_slice(&bytes);
+                    if let Some(limit) = limits.max_bytes {
+                        if limit.truncate(&mut output, stream) {
+                            return Ok(Some(Stopped::Overflowed { stream }));
+                        }
+                    }
+                }
+                Received::Closed => break,
+                Received::TimedOut => {
+                    return Ok(deadline.map(|(_, idle)| stopped(idle, last_output)))
+                }
+            }
+        }
+        // The child may close its pipes before it exits.
+        match deadline(last_output) {
+            Some((instant, idle)) => match wait_deadline(&mut child, instant)? {
+                Some(status) => {
+                    output.status = status;
+                    Ok(None)
+                }
+                None => Ok(Some(stopped(idle, last_output))),
+            },
+            None => {
+                output.status = child.wait()?;
+                Ok(None)
+            }
+        }
+    })();
+
+    match result {
+        Ok(None) => Ok((output, None)),
+        Ok(Some(stopped)) => {
+            output.status = kill(&mut child)?;
+            if !matches!(stopped, Stopped::Overflowed { .. }) {
+                // Keep anything that was read before the child was killed.
+                while let Some((stream, bytes)) = pipes.try_recv() {
+                    stream
+                        .select(&mut output.stdout, &mut output.stderr)
+                        .extend_from_slice(&bytes);
+                }
+            }
+            Ok((output, Some(stopped)))
+        }
+        Err(err) => {
+            let _ = kill(&mut child);
+            Err(err)
+        }
+    }
+}
+
+/// Wait for `child` to exit until `deadline`, returning `None` if it's still running.
+pub(crate) fn wait_deadline(
+    child: &mut Child,
+    deadline: Instant,
+) -> std::io::Result<Option<ExitStatus>> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(None);
+        }
+        std::thread::sleep(remaining.min(POLL_INTERVAL));
+    }
+}
+
+/// Kill `child` and wait for it to exit.
+pub(crate) fn kill(child: &mut Child) -> std::io::Result<ExitStatus> {
+    match child.kill() {
+        Ok(()) => {}
+        // The child already exited.
+        Err(err) if err.kind() == std::io::ErrorKind::InvalidInput => {}
+        Err(err) => return Err(err),
+    }
+    child.wait()
+}
+
+/// Decode the output of a command which was stopped early, replacing invalid sequences with
+/// U+FFFD REPLACEMENT CHARACTER.
+///
+/// The command may have been stopped in the middle of writing a character, so an incomplete
+/// sequence at the end of a stream is dropped rather than replaced.
+pub(crate) fn decode_partial(mut bytes: Vec<u8>) -> String {
+    bytes.truncate(complete_len(&bytes));
+    decode_lossy(bytes)
+}
+
+/// The length of `bytes` without an incomplete UTF-8 sequence at its end.
+fn complete_len(bytes: &[u8]) -> usize {
+    // Sequences are at most 4 bytes long, so an incomplete one starts in the last 3 bytes.
+    for start in (bytes.len().saturating_sub(3)..bytes.len()).rev() {
+        // Skip continuation bytes.
+        if bytes[start] & 0b1100_0000 == 0b1000_0000 {
+            continue;
+        }
+        return match std::str::from_utf8(&bytes[start..]) {
+            Err(err) if err.valid_up_to() == 0 && err.error_len().is_none() => start,
+            _ => bytes.len(),
+        };
+    }
+    bytes.len()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tee() {
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let output = tee(
+            Command::new("sh").args(["-c", "echo puppy; echo doggy >&2; printf 'kitty'"]),
+            &mut stdout,
+            &mut stderr,
+        )
+        .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"puppy\nkitty");
+        assert_eq!(output.stderr, b"doggy\n");
+        assert_eq!(stdout, output.stdout);
+        assert_eq!(stderr, output.stderr);
+    }
+
+    #[test]
+    fn test_tee_large_output() {
+        // Much more than a pipe buffer on both streams, so a reader which only drains one stream
+        // at a time would deadlock.
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let output = tee(
+            Command::new("sh").args([
+                "-c",
+                "for i in $(seq 1 20000); do echo \"puppy $i\"; echo \"doggy $i\" >&2; done",
+            ]),
+            &mut stdout,
+            &mut stderr,
+        )
+        .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout.len(), stdout.len());
+        assert_eq!(output.stderr, stderr);
+        assert!(output.stdout.starts_with(b"puppy 1\npuppy 2\n"));
+        assert!(output.stderr.ends_with(b"doggy 20000\n"));
+    }
+
+    #[test]
+    fn test_tee_broken_writer() {
+        struct Broken;
+
+        impl Write for Broken {
+            fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut stderr = Vec::new();
+        let output = tee(
+            Command::new("sh").args(["-c", "echo puppy; echo doggy >&2"]),
+            &mut Broken,
+            &mut stderr,
+        )
+        .unwrap();
+        assert_eq!(output.stdout, b"puppy\n");
+        assert_eq!(stderr, b"doggy\n");
+    }
+
+    #[test]
+    fn test_output_with_lines() {
+        let mut lines = Vec::new();
+        let output = output_with_lines(
+            Command::new("sh").args([
+                "-c",
+                "echo puppy; printf 'doggy\\r\\n' >&2; printf 'kit'; sleep 0.1; \
+                printf 'ty\\n\\ncat \\300'; printf 'no newline' >&2",
+            ]),
+            &mut |stream, line| lines.push((stream, line.to_owned())),
+        )
+        .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"puppy\nkitty\n\ncat \xc0");
+        assert_eq!(output.stderr, b"doggy\r\nno newline");
+
+        let stdout = lines
+            .iter()
+            .filter(|(stream, _)| *stream == Stream::Stdout)
+            .map(|(_, line)| line.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(stdout, ["puppy", "kitty", "", "cat \u{FFFD}"]);
+        let stderr = lines
+            .iter()
+            .filter(|(stream, _)| *stream == Stream::Stderr)
+            .map(|(_, line)| line.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(stderr, ["doggy", "no newline"]);
+    }
+
+    #[test]
+    fn test_decode_partial() {
+        assert_eq!(decode_partial(Vec::from(b"puppy")), "puppy");
+        assert_eq!(decode_partial(Vec::from("puppy 🐶")), "puppy 🐶");
+        // A dog face emoji, cut off after 1, 2, and 3 of its 4 bytes.
+        let dog = "🐶".as_bytes();
+        for len in 1..4 {
+            let mut bytes = Vec::from(b"puppy ");
+            bytes.extend_from_slice(&dog[..len]);
+            assert_eq!(decode_partial(bytes), "puppy ");
+        }
+        // Invalid bytes which aren't at the end, or can't start a sequence, are still replaced.
+        assert_eq!(decode_partial(Vec::from(b"pup\xc0py")), "pup\u{FFFD}py");
+        assert_eq!(decode_partial(Vec::from(b"puppy \x80")), "puppy \u{FFFD}");
+        assert_eq!(
+            decode_partial(Vec::from(b"puppy \xf0\x9f\x90a")),
+            "puppy \u{FFFD}a"
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use std::string::FromUtf8Error;
+
+use crate::decode::string_from_utf8;
+
+/// Decode CESU-8 or Java's "modified UTF-8".
+///
+/// Supplementary characters encoded as a pair of 3-byte surrogates are converted to a 4-byte
+/// UTF-8 sequence, and the 2-byte NUL `C0 80` is converted to a NUL byte (or removed, if
+/// `strip_nul` is set). Valid UTF-8 is returned unchanged.
+///
+/// Other invalid sequences (including unpaired surrogates) fail with a [`FromUtf8Error`] whose
+/// bytes are the stream with the preceding sequences converted, so the error index is relative
+/// to the converted stream.
+pub(crate) fn decode_cesu8(bytes: Vec<u8>, strip_nul: bool) -> Result<String, FromUtf8Error> {
+    let bytes = match string_from_utf8(bytes) {
+        Ok(decoded) => return Ok(decoded),
+        Err(err) => err.into_bytes(),
+    };
+
+    let mut converted = Vec::with_capacity(bytes.len());
+    let mut rest = bytes.as_slice();
+    loop {
+        let valid_up_to = match std::str::from_utf8(rest) {
+            Ok(_) => {
+                converted.extend_from_slice(rest);
+                break;
+            }
+            Err(err) => err.valid_up_to(),
+        };
+        converted.extend_from_slice(&rest[..valid_up_to]);
+        rest = &rest[valid_up_to..];
+
+        if let [0xc0, 0x80, ..] = rest {
+            if !strip_nul {
+                converted.push(0);
+            }
+            rest = &rest[2..];
+        } else if let Some(c) = surrogate_pair(rest) {
+            let mut buffer = [0; 4];
+            converted.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            rest = &rest[6..];
+        } else {
+            converted.extend_from_slice(rest);
+            return Err(String::from_utf8(converted)
+                .expect_err("Converted bytes should contain the same decoding error"));
+        }
+    }
+
+    Ok(String::from_utf8(converted).expect("Converted bytes should be valid UTF-8"))
+}
+
+/// Decode a supplementary character encoded as a pair of 3-byte surrogates at the start of
+/// `bytes`.
+fn surrogate_pair(bytes: &[u8]) -> Option<char> {
+    let high = surrogate(bytes.get(..3)?)?;
+    let low = surrogate(bytes.get(3..6)?)?;
+    if !(0xd800..0xdc00).contains(&high) || !(0xdc00..0xe000).contains(&low) {
+        return None;
+    }
+    char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))
+}
+
+/// Decode a 3-byte encoded surrogate (`ED A0 80` through `ED BF BF`).
+fn surrogate(bytes: &[u8]) -> Option<u32> {
+    match *bytes {
+        [0xed, second @ 0xa0..=0xbf, third @ 0x80..=0xbf] => {
+            Some(0xd000 | (u32::from(second & 0x3f) << 6) | u32::from(third & 0x3f))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 🐶 (U+1F436) as a CESU-8 surrogate pair (U+D83D U+DC36).
+    const DOG: &[u8] = b"\xed\xa0\xbd\xed\xb0\xb6";
+
+    fn decode(bytes: &[u8]) -> Result<String, FromUtf8Error> {
+        decode_cesu8(bytes.to_vec(), false)
+    }
+
+    #[test]
+    fn test_surrogate_pairs() {
+        assert_eq!(decode(DOG).unwrap(), "🐶");
+        assert_eq!(
+            decode(&[b"puppy ".as_slice(), DOG, b" doggy ", DOG].concat()).unwrap(),
+            "puppy 🐶 doggy 🐶"
+        );
+        // Valid UTF-8 is unchanged.
+        assert_eq!(decode("puppy 🐶 é".as_bytes()).unwrap(), "puppy 🐶 é");
+    }
+
+    #[test]
+    fn test_modified_nul() {
+        assert_eq!(decode(b"pup\xc0\x80py").unwrap(), "pup\0py");
+        assert_eq!(
+            decode_cesu8(b"\xc0\x80pup\xc0\x80py\xc0\x80".to_vec(), true).unwrap(),
+            "puppy"
+        );
+    }
+
+    #[test]
+    fn test_malformed() {
+        // The error index is relative to the converted bytes.
+        let err = decode(&[DOG, b"puppy\xc0"].concat()).unwrap_err();
+        assert_eq!(err.utf8_error().valid_up_to(), 9);
+        assert_eq!(err.as_bytes(), ["🐶puppy".as_bytes(), b"\xc0"].concat());
+
+        // Unpaired surrogates.
+        let err = decode(b"puppy\xed\xa0\xbd").unwrap_err();
+        assert_eq!(err.utf8_error().valid_up_to(), 5);
+        let err = decode(b"puppy\xed\xb0\xb6\xed\xa0\xbd").unwrap_err();
+        assert_eq!(err.utf8_error().valid_up_to(), 5);
+        // A lone `C0`.
+        let err = decode(b"\xc0\x80\xc0").unwrap_err();
+        assert_eq!(err.as_bytes(), b"\0\xc0");
+        assert_eq!(err.utf8_error().valid_up_to(), 1);
+    }
+}
@@ -0,0 +1,157 @@
+use std::ops::Deref;
+
+use crate::Utf8Output;
+
+/// A [`Utf8Output`] from a command which is known to have exited successfully.
+///
+/// This can only be constructed by [`Utf8Output::exit_ok`] or [`Utf8Output::require_success`],
+/// so functions which take a `CheckedUtf8Output` don't need to check the exit status again.
+///
+/// ```
+/// # use std::process::Command;
+/// use utf8_command::prelude::*;
+/// use utf8_command::CheckedUtf8Output;
+///
+/// fn count_lines(output: &CheckedUtf8Output) -> usize {
+///     output.stdout().lines().count()
+/// }
+///
+/// let output = Command::new("echo")
+///     .arg("puppy")
+///     .output_utf8()
+///     .unwrap()
+///     .require_success()
+///     .unwrap();
+/// assert_eq!(count_lines(&output), 1);
+/// ```
+///
+/// This dereferences to the [`Utf8Output`], but can't be mutated through it, so the exit status
+/// can't be changed after it's checked:
+///
+/// ```compile_fail
+/// # use std::process::Command;
+/// # use std::process::ExitStatus;
+/// use utf8_command::prelude::*;
+///
+/// let mut output = Command::new("true")
+///     .output_utf8()
+///     .unwrap()
+///     .require_success()
+///     .unwrap();
+/// output.status = ExitStatus::default();
+/// ```
+///
+/// And it can't be constructed directly:
+///
+/// ```compile_fail
+/// # use std::process::ExitStatus;
+/// use utf8_command::CheckedUtf8Output;
+/// use utf8_command::Utf8Output;
+///
+/// let output = CheckedUtf8Output {
+///     output: Utf8Output {
+///         status: ExitStatus::default(),
+///         stdout: String::new(),
+///         stderr: String::new(),
+///     },
+/// };
+/// ```
+///
+/// With the `serde` feature, this serializes the same way as a [`Utf8Output`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckedUtf8Output {
+    output: Utf8Output,
+}
+
+impl CheckedUtf8Output {
+    /// Wrap an output whose status has already been checked.
+    pub(crate) fn new(output: Utf8Output) -> Self {
+        Self { output }
+    }
+
+    /// Get the command's `stdout`.
+    pub fn stdout(&self) -> &str {
+        &self.output.stdout
+    }
+
+    /// Get the command's `stderr`.
+    pub fn stderr(&self) -> &str {
+        &self.output.stderr
+    }
+
+    /// Get the decoded output, discarding the proof that the command succeeded.
+    pub fn into_inner(self) -> Utf8Output {
+        self.output
+    }
+}
+
+impl Deref for CheckedUtf8Output {
+    type Target = Utf8Output;
+
+    fn deref(&self) -> &Self::Target {
+        &self.output
+    }
+}
+
+impl From<CheckedUtf8Output> for Utf8Output {
+    fn from(value: CheckedUtf8Output) -> Self {
+        value.output
+    }
+}
+
+impl AsRef<Utf8Output> for CheckedUtf8Output {
+    fn as_ref(&self) -> &Utf8Output {
+        &self.output
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for CheckedUtf8Output {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.output.serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::utf8_output;
+
+    #[test]
+    fn test_checked() {
+        let checked = utf8_output("puppy\n", "doggy\n").require_success().unwrap();
+        assert_eq!(checked.stdout(), "puppy\n");
+        assert_eq!(checked.stderr(), "doggy\n");
+        assert!(checked.success());
+        assert_eq!(checked.into_inner(), utf8_output("puppy\n", "doggy\n"));
+
+        let checked = utf8_output("puppy\n", "doggy\n").exit_ok().unwrap();
+        assert_eq!(Utf8Output::from(checked), utf8_output("puppy\n", "doggy\n"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failed_status_is_unchecked() {
+        use std::os::unix::process::ExitStatusExt;
+        use std::process::ExitStatus;
+
+        use crate::test_util::utf8_output_with_status;
+
+        for status in [1 << 8, 9] {
+            let failed =
+                utf8_output_with_status(ExitStatus::from_raw(status), "puppy\n", "doggy\n");
+            assert!(failed.clone().exit_ok().is_err());
+            assert!(failed.require_success().is_err());
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize() {
+        let checked = utf8_output("puppy\n", "doggy\n").require_success().unwrap();
+        assert_eq!(
+            serde_json::to_value(&checked).unwrap(),
+            serde_json::to_value(utf8_output("puppy\n", "doggy\n")).unwrap()
+        );
+    }
+}
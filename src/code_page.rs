@@ -0,0 +1,185 @@
+use std::process::Output;
+
+use encoding_rs::Encoding;
+use encoding_rs::UTF_8;
+
+use crate::decode::string_from_utf8;
+use crate::lossy::decode_lossy;
+use crate::Utf8Output;
+
+/// The decoder used for a stream by [`Utf8Output::from_output_with_code_page_fallback`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamDecoder {
+    /// The stream was valid UTF-8.
+    Utf8,
+    /// The stream wasn't valid UTF-8, and was decoded lossily as UTF-8, because the system ANSI
+    /// code page isn't available.
+    Utf8Lossy,
+    /// The stream wasn't valid UTF-8, and was decoded with the system ANSI code page.
+    CodePage(&'static Encoding),
+}
+
+/// The result of decoding an [`Output`] with a fallback to the system ANSI code page.
+///
+/// Produced by [`Utf8Output::from_output_with_code_page_fallback`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodePageConversion {
+    /// The decoded output.
+    pub output: Utf8Output,
+    /// The decoder used for `stdout`.
+    pub stdout_decoder: StreamDecoder,
+    /// The decoder used for `stderr`.
+    pub stderr_decoder: StreamDecoder,
+}
+
+impl Utf8Output {
+    /// Decode an [`Output`], falling back to the system ANSI code page for streams which aren't
+    /// valid UTF-8.
+    ///
+    /// On Windows, many console tools print text in the active ANSI code page (like CP1252 or
+    /// CP932) rather than UTF-8. Streams which are valid UTF-8 are used as-is; other streams are
+    /// decoded with the code page reported by `GetACP`, replacing malformed sequences with
+    /// U+FFFD REPLACEMENT CHARACTER.
+    ///
+    /// On other platforms, or if the code page is UTF-8 or isn't supported by `encoding_rs`,
+    /// this is the same as [`Utf8Output::from_utf8_lossy`]. Requires the `code-page` feature.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::Utf8Output;
+    /// # use utf8_command::StreamDecoder;
+    /// let converted = Utf8Output::from_output_with_code_page_fallback(Output {
+    ///     status: ExitStatus::default(),
+    ///     stdout: Vec::from("café"),
+    ///     stderr: Vec::from(b""),
+    /// });
+    /// assert_eq!(converted.output.stdout, "café");
+    /// assert_eq!(converted.stdout_decoder, StreamDecoder::Utf8);
+    /// ```
+    pub fn from_output_with_code_page_fallback(
+        Output {
+            status,
+            stdout,
+            stderr,
+        }: Output,
+    ) -> CodePageConversion {
+        let code_page = ansi_code_page();
+        let (stdout, stdout_decoder) = decode_with_fallback(stdout, code_page);
+        let (stderr, stderr_decoder) = decode_with_fallback(stderr, code_page);
+        CodePageConversion {
+            output: Utf8Output {
+                status,
+                stdout,
+                stderr,
+            },
+            stdout_decoder,
+            stderr_decoder,
+        }
+    }
+}
+
+/// Decode `bytes` as UTF-8, falling back to `code_page` (or lossy UTF-8, if it's `None`).
+fn decode_with_fallback(
+    bytes: Vec<u8>,
+    code_page: Option<&'static Encoding>,
+) -> (String, StreamDecoder) {
+    let bytes = match string_from_utf8(bytes) {
+        Ok(decoded) => return (decoded, StreamDecoder::Utf8),
+        Err(err) => err.into_bytes(),
+    };
+
+    match code_page {
+        Some(encoding) if encoding != UTF_8 => {
+            let decoded = encoding.decode_without_bom_handling(&bytes).0.into_owned();
+            (decoded, StreamDecoder::CodePage(encoding))
+        }
+        _ => (decode_lossy(bytes), StreamDecoder::Utf8Lossy),
+    }
+}
+
+/// The encoding of the system ANSI code page, if it's supported by `encoding_rs`.
+#[cfg(windows)]
+fn ansi_code_page() -> Option<&'static Encoding> {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetACP() -> u32;
+    }
+
+    // SAFETY: `GetACP` has no preconditions.
+    let code_page = unsafe { GetACP() };
+    codepage::to_encoding_no_replacement(u16::try_from(code_page).ok()?)
+}
+
+/// There's no ANSI code page on this platform.
+#[cfg(not(windows))]
+fn ansi_code_page() -> Option<&'static Encoding> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::SHIFT_JIS;
+    use encoding_rs::WINDOWS_1252;
+
+    use super::*;
+    use crate::test_util::output;
+
+    #[test]
+    fn test_fallback() {
+        assert_eq!(
+            decode_with_fallback(Vec::from("café"), Some(WINDOWS_1252)),
+            (String::from("café"), StreamDecoder::Utf8)
+        );
+        assert_eq!(
+            decode_with_fallback(Vec::from(b"caf\xe9".as_slice()), Some(WINDOWS_1252)),
+            (String::from("café"), StreamDecoder::CodePage(WINDOWS_1252))
+        );
+        // Malformed sequences in the code page are replaced.
+        assert_eq!(
+            decode_with_fallback(Vec::from(b"\x82\xb1\xa0".as_slice()), Some(SHIFT_JIS)),
+            (
+                String::from("こ\u{fffd}"),
+                StreamDecoder::CodePage(SHIFT_JIS)
+            )
+        );
+        // A UTF-8 code page is the same as no code page.
+        for code_page in [Some(UTF_8), None] {
+            assert_eq!(
+                decode_with_fallback(Vec::from(b"caf\xe9".as_slice()), code_page),
+                (String::from("caf\u{fffd}"), StreamDecoder::Utf8Lossy)
+            );
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_non_windows_is_lossy() {
+        assert_eq!(ansi_code_page(), None);
+
+        let output = output(b"caf\xe9", "café".as_bytes());
+        let converted = Utf8Output::from_output_with_code_page_fallback(output.clone());
+        assert_eq!(converted.output, Utf8Output::from_utf8_lossy(output));
+        assert_eq!(converted.stdout_decoder, StreamDecoder::Utf8Lossy);
+        assert_eq!(converted.stderr_decoder, StreamDecoder::Utf8);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_windows_code_page() {
+        let code_page = ansi_code_page();
+        let output = output(b"caf\xe9", "café".as_bytes());
+        let converted = Utf8Output::from_output_with_code_page_fallback(output);
+        assert_eq!(converted.stderr_decoder, StreamDecoder::Utf8);
+        match code_page {
+            Some(encoding) if encoding != UTF_8 => {
+                assert_eq!(converted.stdout_decoder, StreamDecoder::CodePage(encoding));
+                assert_eq!(
+                    converted.output.stdout,
+                    encoding.decode_without_bom_handling(b"caf\xe9").0
+                );
+            }
+            _ => assert_eq!(converted.stdout_decoder, StreamDecoder::Utf8Lossy),
+        }
+    }
+}
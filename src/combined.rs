@@ -0,0 +1,266 @@
+use std::fmt::Display;
+use std::io::Read;
+use std::process::Command;
+use std::process::ExitStatus;
+use std::process::Stdio;
+use std::string::FromUtf8Error;
+
+use crate::CommandInfo;
+use crate::ErrorContext;
+use crate::FromUtf8ErrorMessage;
+use crate::SpawnError;
+use crate::ERROR_CONTEXT_BYTES;
+
+/// The output of a command whose `stdout` and `stderr` were captured together, from
+/// [`CommandExt::output_utf8_combined`][crate::CommandExt::output_utf8_combined].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CombinedUtf8Output {
+    /// The command's exit status.
+    pub status: ExitStatus,
+    /// The command's `stdout` and `stderr`, interleaved in the order they were written, decoded
+    /// as UTF-8.
+    pub output: String,
+}
+
+/// Run `command` with its `stdout` and `stderr` writing to the same pipe, and decode the result.
+pub(crate) fn output_combined(command: &mut Command) -> Result<CombinedUtf8Output, CombinedError> {
+    let info = CommandInfo::from(&*command);
+    let io_error = |err| SpawnError::new(err, Some(info.clone()));
+
+    let (mut reader, writer) = std::io::pipe().map_err(io_error)?;
+    let stderr_writer = writer.try_clone().map_err(io_error)?;
+    let child = command.stdout(writer).stderr(stderr_writer).spawn();
+    // The `Command` holds on to the write ends of the pipe, so the read end would never see
+    // EOF unless they're closed.
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    let mut child = child.map_err(io_error)?;
+
+    let mut output = Vec::new();
+    if let Err(err) = reader.read_to_end(&mut output) {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(io_error(err).into());
+    }
+    let status = child.wait().map_err(io_error)?;
+
+    match String::from_utf8(output) {
+        Ok(output) => Ok(CombinedUtf8Output { status, output }),
+        Err(inner) => Err(CombinedDecodeError::new(inner, status, Some(info)).into()),
+    }
+}
+
+/// A command's combined `stdout` and `stderr` contained invalid UTF-8.
+///
+/// Indexes are relative to the start of the combined output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CombinedDecodeError {
+    inner: FromUtf8Error,
+    status: ExitStatus,
+    command: Option<CommandInfo>,
+}
+
+impl CombinedDecodeError {
+    pub(crate) fn new(
+        inner: FromUtf8Error,
+        status: ExitStatus,
+        command: Option<CommandInfo>,
+    ) -> Self {
+        Self {
+            inner,
+            status,
+            command,
+        }
+    }
+
+    /// Get a reference to the inner [`FromUtf8Error`].
+    pub fn inner(&self) -> &FromUtf8Error {
+        &self.inner
+    }
+
+    /// Get the bytes of the combined output.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.inner.as_bytes()
+    }
+
+    /// Get the bytes of the combined output.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.inner.into_bytes()
+    }
+
+    /// The index in the combined output up to which the bytes were valid UTF-8.
+    pub fn valid_up_to(&self) -> usize {
+        self.inner.utf8_error().valid_up_to()
+    }
+
+    /// Get the command's exit status.
+    pub fn status(&self) -> ExitStatus {
+        self.status
+    }
+
+    /// Get the command which produced the output, if known.
+    pub fn command(&self) -> Option<&CommandInfo> {
+        self.command.as_ref()
+    }
+}
+
+impl Display for CombinedDecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(command) = &self.command {
+            write!(f, "`{command}`: ")?;
+        }
+        write!(
+            f,
+            "Output contained {}: {}",
+            FromUtf8ErrorMessage::new(&self.inner, None),
+            ErrorContext::new(&self.inner, ERROR_CONTEXT_BYTES)
+        )
+    }
+}
+
+impl std::error::Error for CombinedDecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.inner)
+    }
+}
+
+/// An error produced by
+/// [`CommandExt::output_utf8_combined`][crate::CommandExt::output_utf8_combined] and
+/// `CommandExt::output_utf8_pty`.
+#[derive(Debug)]
+pub enum CombinedError {
+    /// The command couldn't be run.
+    Io(SpawnError),
+    /// The command's output contained invalid UTF-8.
+    Utf8(CombinedDecodeError),
+}
+
+impl CombinedError {
+    /// Get the I/O error, if the command couldn't be run.
+    pub fn io_error(&self) -> Option<&std::io::Error> {
+        match self {
+            CombinedError::Io(err) => Some(err.io_error()),
+            CombinedError::Utf8(_) => None,
+        }
+    }
+
+    /// Get the UTF-8 decoding error, if the command's output contained invalid UTF-8.
+    pub fn utf8_error(&self) -> Option<&CombinedDecodeError> {
+        match self {
+            CombinedError::Io(_) => None,
+            CombinedError::Utf8(err) => Some(err),
+        }
+    }
+
+    /// Get the command which produced the error, if known.
+    pub fn command(&self) -> Option<&CommandInfo> {
+        match self {
+            CombinedError::Io(err) => err.command(),
+            CombinedError::Utf8(err) => err.command(),
+        }
+    }
+}
+
+impl From<std::io::Error> for CombinedError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(SpawnError::from(value))
+    }
+}
+
+impl From<SpawnError> for CombinedError {
+    fn from(value: SpawnError) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<CombinedDecodeError> for CombinedError {
+    fn from(value: CombinedDecodeError) -> Self {
+        Self::Utf8(value)
+    }
+}
+
+impl Display for CombinedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CombinedError::Io(err) => write!(f, "{err}"),
+            CombinedError::Utf8(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for CombinedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CombinedError::Io(err) => Some(err),
+            CombinedError::Utf8(err) => Some(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CommandExt;
+
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_utf8_combined() {
+        let output = Command::new("sh")
+            .args([
+                "-c",
+                "echo puppy; echo doggy >&2; printf kitty; printf ' cat\\n' >&2; exit 2",
+            ])
+            .output_utf8_combined()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(2));
+        assert_eq!(output.output, "puppy\ndoggy\nkitty cat\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_utf8_combined_invalid() {
+        let err = Command::new("sh")
+            .args(["-c", "echo puppy; printf 'doggy \\300' >&2"])
+            .output_utf8_combined()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`sh -c 'echo puppy; printf '\\''doggy \\300'\\'' >&2'`: \
+            Output contained invalid utf-8 sequence of 1 bytes from index 12: \"puppy\\ndoggy �\""
+        );
+        let err = err.utf8_error().unwrap();
+        assert_eq!(err.valid_up_to(), 12);
+        assert!(err.status().success());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_utf8_combined_leaves_stdio_piped() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo puppy; echo doggy >&2"]);
+        command.output_utf8_combined().unwrap();
+        // Afterwards, the streams are captured separately.
+        let output = command.output_utf8().unwrap();
+        assert_eq!(output.stdout, "puppy\n");
+        assert_eq!(output.stderr, "doggy\n");
+    }
+
+    #[test]
+    fn test_output_utf8_combined_missing_binary() {
+        let err = Command::new("utf8-command-this-program-does-not-exist")
+            .output_utf8_combined()
+            .unwrap_err();
+        assert!(err.io_error().is_some());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_output_utf8_combined_windows() {
+        let output = Command::new("cmd")
+            .args(["/C", "echo puppy& echo doggy 1>&2& echo kitty"])
+            .output_utf8_combined()
+            .unwrap();
+        let lines = output.output.lines().map(str::trim).collect::<Vec<_>>();
+        assert_eq!(lines, ["puppy", "doggy", "kitty"]);
+    }
+}
@@ -0,0 +1,1318 @@
+use std::fmt::Display;
+use std::process::Command;
+use std::process::ExitStatus;
+use std::process::Output;
+use std::process::Stdio;
+use std::time::Duration;
+
+use crate::signal::FailedStatus;
+use crate::status::status_in;
+use crate::CombinedError;
+use crate::CombinedUtf8Output;
+use crate::CommandInfo;
+use crate::Error;
+use crate::ErrorContext;
+use crate::OutputLimit;
+use crate::OverflowError;
+use crate::StalledError;
+use crate::Stream;
+use crate::TimeoutError;
+use crate::Utf8Output;
+use crate::Utf8OutputWithCommand;
+use crate::ERROR_CONTEXT_BYTES;
+
+/// Extension methods for running a [`Command`] and decoding its output as UTF-8.
+///
+/// ```
+/// # use std::process::Command;
+/// use utf8_command::prelude::*;
+///
+/// let output = Command::new("echo").arg("puppy").output_utf8().unwrap();
+/// assert_eq!(output.stdout, "puppy\n");
+/// ```
+///
+/// Errors record the command line, and include it in their messages:
+///
+/// ```
+/// # use std::process::Command;
+/// use utf8_command::prelude::*;
+///
+/// let err = Command::new("printf")
+///     .arg("puppy \\300")
+///     .output_utf8()
+///     .unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "`printf 'puppy \\300'`: \
+///     Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+/// );
+/// ```
+pub trait CommandExt {
+    /// Run the command with [`Command::output`] and decode its output streams as UTF-8.
+    ///
+    /// Fails with [`CommandError::Io`] if the command couldn't be run, and with
+    /// [`CommandError::Utf8`] if its output couldn't be decoded.
+    fn output_utf8(&mut self) -> Result<Utf8Output, CommandError>;
+
+    /// Run the command, require it to exit successfully, and return its `stdout` with leading
+    /// and trailing whitespace trimmed.
+    ///
+    /// If the command fails, the error includes its exit status and `stderr`:
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let stdout = Command::new("echo").arg("puppy").run_utf8().unwrap();
+    /// assert_eq!(stdout, "puppy");
+    ///
+    /// let err = Command::new("sh")
+    ///     .args(["-c", "echo doggy >&2; exit 1"])
+    ///     .run_utf8()
+    ///     .unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Command `sh -c 'echo doggy >&2; exit 1'` failed with exit status: 1; \
+    ///     stderr: \"doggy\\n\""
+    /// );
+    /// ```
+    fn run_utf8(&mut self) -> Result<String, RunError>;
+
+    /// Run the command, decode its output streams as UTF-8, and require it to exit
+    /// successfully.
+    ///
+    /// If the command exits unsuccessfully, the error contains the complete decoded output:
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    /// use utf8_command::CheckedError;
+    ///
+    /// let err = Command::new("sh")
+    ///     .args(["-c", "echo puppy; echo doggy >&2; exit 1"])
+    ///     .output_utf8_checked()
+    ///     .unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Command `sh -c 'echo puppy; echo doggy >&2; exit 1'` failed with exit status: 1; \
+    ///     stderr: \"doggy\\n\""
+    /// );
+    /// match err {
+    ///     CheckedError::Failed { output, .. } => assert_eq!(output.stdout, "puppy\n"),
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    fn output_utf8_checked(&mut self) -> Result<Utf8Output, CheckedError>;
+
+    /// Run the command, decode its output streams as UTF-8, and require it to exit with one of
+    /// `codes`.
+    ///
+    /// `0` only counts as success if it's one of `codes`, and a command killed by a signal
+    /// never counts as success. See [`Utf8Output::require_status_in`].
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// // `diff` exits with 1 if the files differ, and 2 if an error occurs.
+    /// let output = Command::new("diff")
+    ///     .args(["/dev/null", "/dev/null"])
+    ///     .output_utf8_checked_codes(&[0, 1])
+    ///     .unwrap();
+    /// assert_eq!(output.code(), Some(0));
+    /// ```
+    fn output_utf8_checked_codes(&mut self, codes: &[i32]) -> Result<Utf8Output, CheckedError>;
+
+    /// Run the command and decode its output streams as UTF-8, keeping a record of the
+    /// program, arguments, and working directory with the output.
+    ///
+    /// See [`Utf8OutputWithCommand`].
+    fn output_utf8_recorded(&mut self) -> Result<Utf8OutputWithCommand, CommandError>;
+
+    /// Run the command, copying its `stdout` and `stderr` to this process's `stdout` and
+    /// `stderr` as they're written, and decode the captured output streams as UTF-8.
+    ///
+    /// This is useful for long-running commands, like builds, where the user should see output
+    /// as it's produced. Both streams are read concurrently, so neither can block the other, but
+    /// writes to the two streams may be interleaved differently than if the command had
+    /// inherited them.
+    ///
+    /// Unlike [`Command::output`], `stdin` is inherited unless it's configured otherwise.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// // Prints "puppy" while it runs.
+    /// let output = Command::new("echo").arg("puppy").output_utf8_tee().unwrap();
+    /// assert_eq!(output.stdout, "puppy\n");
+    /// ```
+    fn output_utf8_tee(&mut self) -> Result<Utf8Output, CommandError>;
+
+    /// Run the command with `stderr` inherited, and decode its captured `stdout` as UTF-8.
+    ///
+    /// This is useful when the user should see the command's progress and errors live, but
+    /// its `stdout` needs to be parsed. Any `stdout` and `stderr` configuration on the command is
+    /// overwritten, and isn't restored afterwards: `stdout` is left set to [`Stdio::piped`] and
+    /// `stderr` to [`Stdio::inherit`]. If `stdout` is invalid UTF-8, the error is
+    /// [`Error::Stdout`].
+    ///
+    /// Returns the command's exit status and `stdout`.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// // Prints "doggy" to this process's `stderr`.
+    /// let (status, stdout) = Command::new("sh")
+    ///     .args(["-c", "echo puppy; echo doggy >&2"])
+    ///     .stdout_utf8()
+    ///     .unwrap();
+    /// assert!(status.success());
+    /// assert_eq!(stdout, "puppy\n");
+    /// ```
+    fn stdout_utf8(&mut self) -> Result<(ExitStatus, String), CommandError>;
+
+    /// Run the command with `stdout` inherited, and decode its captured `stderr` as UTF-8.
+    ///
+    /// Any `stdout` and `stderr` configuration on the command is overwritten, and isn't restored
+    /// afterwards: `stdout` is left set to [`Stdio::inherit`] and `stderr` to [`Stdio::piped`]. If
+    /// `stderr` is invalid UTF-8, the error is [`Error::Stderr`].
+    ///
+    /// Returns the command's exit status and `stderr`.
+    ///
+    /// See [`CommandExt::stdout_utf8`].
+    fn stderr_utf8(&mut self) -> Result<(ExitStatus, String), CommandError>;
+
+    /// Run the command and decode its output streams as UTF-8, killing it if it doesn't finish
+    /// within `timeout`.
+    ///
+    /// If the command times out, it's killed and the error contains the output it wrote before
+    /// then. Otherwise, this behaves like [`CommandExt::output_utf8`], except that `stdin` is
+    /// inherited unless it's configured otherwise.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// # use std::time::Duration;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let err = Command::new("sh")
+    ///     .args(["-c", "echo puppy; exec sleep 60"])
+    ///     .output_utf8_timeout(Duration::from_millis(100))
+    ///     .unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Command `sh -c 'echo puppy; exec sleep 60'` timed out after 100ms"
+    /// );
+    /// assert_eq!(err.output().unwrap().stdout, "puppy\n");
+    /// ```
+    ///
+    /// Note that only the command itself is killed, so if it spawns other processes which keep
+    /// its output streams open, they may continue running.
+    fn output_utf8_timeout(&mut self, timeout: Duration) -> Result<Utf8Output, TimeoutError>;
+
+    /// Run the command and decode its output streams as UTF-8, killing it if it doesn't write
+    /// to either stream for `idle_timeout`.
+    ///
+    /// This detects stalled commands, which would usually be writing output, regardless of how
+    /// long they run for in total. If the command stalls, it's killed and the error contains the
+    /// output it wrote before then. Otherwise, this behaves like [`CommandExt::output_utf8`],
+    /// except that `stdin` is inherited unless it's configured otherwise.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// # use std::time::Duration;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let err = Command::new("sh")
+    ///     .args(["-c", "echo puppy; exec sleep 60"])
+    ///     .output_utf8_idle_timeout(Duration::from_millis(100))
+    ///     .unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Command `sh -c 'echo puppy; exec sleep 60'` stalled: no output for 100ms"
+    /// );
+    /// assert_eq!(err.output().unwrap().stdout, "puppy\n");
+    /// ```
+    ///
+    /// Like [`CommandExt::output_utf8_timeout`], only the command itself is killed.
+    fn output_utf8_idle_timeout(
+        &mut self,
+        idle_timeout: Duration,
+    ) -> Result<Utf8Output, StalledError>;
+
+    /// Run the command and decode its output streams as UTF-8, killing it if it writes more
+    /// than `limit` bytes of output.
+    ///
+    /// The output is read as it's written rather than buffered in full, so a runaway command
+    /// can't exhaust memory. If the command writes too much, it's killed and the error contains
+    /// its output truncated to the limit, and which stream went over. Otherwise, this behaves
+    /// like [`CommandExt::output_utf8`], except that `stdin` is inherited unless it's configured
+    /// otherwise.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    /// use utf8_command::OutputLimit;
+    /// use utf8_command::Stream;
+    ///
+    /// let err = Command::new("yes")
+    ///     .arg("puppy")
+    ///     .output_utf8_max_bytes(OutputLimit::Combined(15))
+    ///     .unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Command `yes puppy` wrote more than 15 bytes of output; stopped at Stdout"
+    /// );
+    /// assert_eq!(err.stream(), Some(Stream::Stdout));
+    /// assert_eq!(err.output().unwrap().stdout, "puppy\npuppy\npup");
+    /// ```
+    fn output_utf8_max_bytes(&mut self, limit: OutputLimit) -> Result<Utf8Output, OverflowError>;
+
+    /// Run the command with its `stdout` and `stderr` writing to the same pipe, and decode the
+    /// combined output as UTF-8.
+    ///
+    /// The output is interleaved in the order it was written, like it would be in a terminal.
+    /// Decoding errors report indexes into the combined output.
+    ///
+    /// Unlike [`Command::output`], `stdin` is inherited unless it's configured otherwise. Any
+    /// `stdout` and `stderr` configuration on the command is overwritten, and both are left set
+    /// to [`Stdio::piped`] afterwards, since [`Command`] has no way to read its configuration back.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let output = Command::new("sh")
+    ///     .args(["-c", "echo puppy; echo doggy >&2; echo kitty"])
+    ///     .output_utf8_combined()
+    ///     .unwrap();
+    /// assert_eq!(output.output, "puppy\ndoggy\nkitty\n");
+    /// ```
+    fn output_utf8_combined(&mut self) -> Result<CombinedUtf8Output, CombinedError>;
+
+    /// Run the command, calling `on_line` with each line of its `stdout` and `stderr` as it's
+    /// written, and decode the captured output streams as UTF-8.
+    ///
+    /// Lines are passed without their trailing `\n` or `\r\n`, along with the [`Stream`] they
+    /// were written to. A final line without a trailing newline is passed once the stream is
+    /// closed. Both streams are read concurrently, so neither can block the other.
+    ///
+    /// Invalid UTF-8 in a line is replaced with U+FFFD REPLACEMENT CHARACTER before it's passed
+    /// to `on_line`, but the complete output is decoded like [`CommandExt::output_utf8`], so
+    /// invalid UTF-8 is still reported as [`CommandError::Utf8`].
+    ///
+    /// Unlike [`Command::output`], `stdin` is inherited unless it's configured otherwise.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    /// use utf8_command::Stream;
+    ///
+    /// let mut stdout_lines = Vec::new();
+    /// let output = Command::new("sh")
+    ///     .args(["-c", "echo puppy; echo doggy >&2; printf kitty"])
+    ///     .output_utf8_with_lines(|stream, line| {
+    ///         if stream == Stream::Stdout {
+    ///             stdout_lines.push(line.to_owned());
+    ///         }
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(stdout_lines, ["puppy", "kitty"]);
+    /// assert_eq!(output.stdout, "puppy\nkitty");
+    /// assert_eq!(output.stderr, "doggy\n");
+    /// ```
+    fn output_utf8_with_lines(
+        &mut self,
+        on_line: impl FnMut(Stream, &str),
+    ) -> Result<Utf8Output, CommandError>;
+
+    /// Run the command with `input` written to its `stdin`, and decode its output streams as
+    /// UTF-8.
+    ///
+    /// The input is written from a separate thread while `stdout` and `stderr` are read, so
+    /// large inputs and outputs won't deadlock when the pipes fill up. `stdin` is closed once
+    /// all of the input is written.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let output = Command::new("cat")
+    ///     .output_utf8_with_input("puppy\ndoggy\n")
+    ///     .unwrap();
+    /// assert_eq!(output.stdout, "puppy\ndoggy\n");
+    /// ```
+    fn output_utf8_with_input(&mut self, input: &str) -> Result<Utf8Output, CommandError>;
+
+    /// Spawn the command with its `stdout` and `stderr` captured, returning a guard which kills
+    /// the child if it's dropped before the child is waited for.
+    ///
+    /// Unlike [`Command::spawn`], `stdout` and `stderr` are always piped. `stdin` is inherited
+    /// unless it's configured otherwise.
+    ///
+    /// See [`Utf8ChildGuard`][crate::Utf8ChildGuard].
+    fn spawn_utf8(&mut self) -> Result<crate::Utf8ChildGuard, SpawnError>;
+
+    /// Run the command attached to a pseudo-terminal, and decode everything it writes to the
+    /// terminal as UTF-8.
+    ///
+    /// Some programs behave differently, or refuse to run, when they aren't attached to a
+    /// terminal. The child's `stdin`, `stdout`, and `stderr` are all the terminal, so its output
+    /// streams are merged, and nothing is written to its input. Stdio configured on the
+    /// [`Command`] is ignored.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    /// use utf8_command::PtyOptions;
+    ///
+    /// let output = Command::new("sh")
+    ///     .args(["-c", "[ -t 1 ] && echo puppy"])
+    ///     .output_utf8_pty(PtyOptions::new())
+    ///     .unwrap();
+    /// assert_eq!(output.output, "puppy\r\n");
+    /// ```
+    #[cfg(feature = "pty")]
+    fn output_utf8_pty(
+        &mut self,
+        options: crate::PtyOptions,
+    ) -> Result<crate::PtyUtf8Output, CombinedError>;
+
+    /// Run the command, spilling each output stream to a temporary file once it's longer than
+    /// `threshold` bytes, and validate its output as UTF-8.
+    ///
+    /// This keeps very large outputs out of memory. Spilled streams are validated by reading
+    /// them back from their files, and decoding errors report indexes into the whole stream.
+    ///
+    /// Unlike [`Command::output`], `stdin` is inherited unless it's configured otherwise.
+    ///
+    /// See [`SpooledUtf8Output`][crate::SpooledUtf8Output].
+    #[cfg(feature = "tempfile")]
+    fn output_utf8_spooled(
+        &mut self,
+        threshold: usize,
+    ) -> Result<crate::SpooledUtf8Output, CommandError>;
+
+    /// Run the command in a UTF-8 locale, so it writes UTF-8 even if the parent's locale uses a
+    /// different encoding (like `LANG=fr_FR.ISO-8859-1`).
+    ///
+    /// On Unix, this sets `LC_ALL` and `LANG` to `C.UTF-8`, or to `en_US.UTF-8` if `C.UTF-8`
+    /// isn't installed. Variables which are already set (or removed) on the command are left
+    /// alone; see [`CommandExt::force_utf8_locale`] to override them.
+    ///
+    /// On Windows, programs don't choose their encoding from environment variables, so this
+    /// does nothing.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let output = Command::new("sh")
+    ///     .args(["-c", "echo \"$LC_ALL\""])
+    ///     .utf8_locale()
+    ///     .output_utf8()
+    ///     .unwrap();
+    /// assert!(output.stdout.contains("UTF-8"));
+    /// ```
+    fn utf8_locale(&mut self) -> &mut Self;
+
+    /// Run the command in a UTF-8 locale, overriding any locale variables which are already set
+    /// on the command.
+    ///
+    /// See [`CommandExt::utf8_locale`].
+    fn force_utf8_locale(&mut self) -> &mut Self;
+}
+
+impl CommandExt for Command {
+    fn output_utf8(&mut self) -> Result<Utf8Output, CommandError> {
+        output_with(self, Utf8Output::try_from)
+    }
+
+    fn run_utf8(&mut self) -> Result<String, RunError> {
+        let output = self.output_utf8_checked()?;
+
+        let mut stdout = output.stdout;
+        stdout.truncate(stdout.trim_end().len());
+        stdout.drain(..stdout.len() - stdout.trim_start().len());
+        Ok(stdout)
+    }
+
+    fn output_utf8_checked(&mut self) -> Result<Utf8Output, CheckedError> {
+        let output = self.output_utf8()?;
+        check_success(output, Some(CommandInfo::from(&*self)))
+    }
+
+    fn output_utf8_checked_codes(&mut self, codes: &[i32]) -> Result<Utf8Output, CheckedError> {
+        let output = self.output_utf8()?;
+        if status_in(output.status, codes.iter().copied()) {
+            Ok(output)
+        } else {
+            Err(CheckedError::Failed {
+                output,
+                command: Some(CommandInfo::from(&*self)),
+            })
+        }
+    }
+
+    fn output_utf8_recorded(&mut self) -> Result<Utf8OutputWithCommand, CommandError> {
+        Ok(Utf8OutputWithCommand {
+            output: self.output_utf8()?,
+            command: CommandInfo::from(&*self),
+        })
+    }
+
+    fn output_utf8_tee(&mut self) -> Result<Utf8Output, CommandError> {
+        let info = CommandInfo::from(&*self);
+        let result = crate::capture::tee(self, &mut std::io::stdout(), &mut std::io::stderr());
+        decode_output(result, Some(info), Utf8Output::try_from)
+    }
+
+    fn stdout_utf8(&mut self) -> Result<(ExitStatus, String), CommandError> {
+        self.stdout(Stdio::piped()).stderr(Stdio::inherit());
+        // `stderr` isn't captured, so it's empty and can't fail to decode.
+        let output = output_with(self, Utf8Output::try_from)?;
+        Ok((output.status, output.stdout))
+    }
+
+    fn stderr_utf8(&mut self) -> Result<(ExitStatus, String), CommandError> {
+        self.stdout(Stdio::inherit()).stderr(Stdio::piped());
+        let output = output_with(self, Utf8Output::try_from)?;
+        Ok((output.status, output.stderr))
+    }
+
+    fn output_utf8_timeout(&mut self, timeout: Duration) -> Result<Utf8Output, TimeoutError> {
+        crate::timeout::output_timeout(self, timeout)
+    }
+
+    fn output_utf8_idle_timeout(
+        &mut self,
+        idle_timeout: Duration,
+    ) -> Result<Utf8Output, StalledError> {
+        crate::stall::output_idle_timeout(self, idle_timeout)
+    }
+
+    fn output_utf8_max_bytes(&mut self, limit: OutputLimit) -> Result<Utf8Output, OverflowError> {
+        crate::overflow::output_max_bytes(self, limit)
+    }
+
+    fn output_utf8_combined(&mut self) -> Result<CombinedUtf8Output, CombinedError> {
+        crate::combined::output_combined(self)
+    }
+
+    fn output_utf8_with_lines(
+        &mut self,
+        mut on_line: impl FnMut(Stream, &str),
+    ) -> Result<Utf8Output, CommandError> {
+        let info = CommandInfo::from(&*self);
+        let result = crate::capture::output_with_lines(self, &mut on_line);
+        decode_output(result, Some(info), Utf8Output::try_from)
+    }
+
+    fn output_utf8_with_input(&mut self, input: &str) -> Result<Utf8Output, CommandError> {
+        let info = CommandInfo::from(&*self);
+        let result = crate::capture::output_with_input(self, input.as_bytes());
+        decode_output(result, Some(info), Utf8Output::try_from)
+    }
+
+    fn spawn_utf8(&mut self) -> Result<crate::Utf8ChildGuard, SpawnError> {
+        crate::Utf8ChildGuard::spawn(self)
+    }
+
+    #[cfg(feature = "pty")]
+    fn output_utf8_pty(
+        &mut self,
+        options: crate::PtyOptions,
+    ) -> Result<crate::PtyUtf8Output, CombinedError> {
+        crate::pty::output_pty(self, options)
+    }
+
+    #[cfg(feature = "tempfile")]
+    fn output_utf8_spooled(
+        &mut self,
+        threshold: usize,
+    ) -> Result<crate::SpooledUtf8Output, CommandError> {
+        crate::spool::output_spooled(self, threshold)
+    }
+
+    fn utf8_locale(&mut self) -> &mut Self {
+        crate::locale::set_utf8_locale(self, false);
+        self
+    }
+
+    fn force_utf8_locale(&mut self) -> &mut Self {
+        crate::locale::set_utf8_locale(self, true);
+        self
+    }
+}
+
+/// Run `command` and decode its output with `decode`, recording the command line in errors.
+pub(crate) fn output_with(
+    command: &mut Command,
+    decode: impl FnOnce(Output) -> Result<Utf8Output, Error>,
+) -> Result<Utf8Output, CommandError> {
+    let info = CommandInfo::from(&*command);
+    decode_output(command.output(), Some(info), decode)
+}
+
+/// Decode the result of running a command with `decode`, recording the command line (if known)
+/// in errors.
+pub(crate) fn decode_output(
+    result: std::io::Result<Output>,
+    command: Option<CommandInfo>,
+    decode: impl FnOnce(Output) -> Result<Utf8Output, Error>,
+) -> Result<Utf8Output, CommandError> {
+    match result {
+        Ok(output) => decode(output).map_err(|err| {
+            CommandError::Utf8(match command {
+                Some(command) => err.with_command(command),
+                None => err,
+            })
+        }),
+        Err(source) => Err(CommandError::Io(SpawnError::new(source, command))),
+    }
+}
+
+/// Fail with [`CheckedError::Failed`] if `output`'s command exited unsuccessfully.
+pub(crate) fn check_success(
+    output: Utf8Output,
+    command: Option<CommandInfo>,
+) -> Result<Utf8Output, CheckedError> {
+    if output.status.success() {
+        Ok(output)
+    } else {
+        Err(CheckedError::Failed { output, command })
+    }
+}
+
+/// Write a message for a command which exited unsuccessfully, including (the end of) its
+/// `stderr`.
+pub(crate) fn fmt_failure(
+    f: &mut std::fmt::Formatter<'_>,
+    command: Option<&CommandInfo>,
+    status: ExitStatus,
+    stderr: &str,
+) -> std::fmt::Result {
+    let status = FailedStatus(status);
+    match command {
+        Some(command) => write!(f, "Command `{command}` {status}")?,
+        None => write!(f, "Command {status}")?,
+    }
+    if !stderr.is_empty() {
+        // Errors are usually at the end of `stderr`, so show the end of the stream.
+        write!(
+            f,
+            "; stderr: {}",
+            ErrorContext::at(stderr.as_bytes(), stderr.len(), ERROR_CONTEXT_BYTES)
+        )?;
+    }
+    Ok(())
+}
+
+/// A command couldn't be run. Wraps an [`std::io::Error`].
+#[derive(Debug)]
+pub struct SpawnError {
+    source: std::io::Error,
+    command: Option<CommandInfo>,
+}
+
+impl SpawnError {
+    pub(crate) fn new(source: std::io::Error, command: Option<CommandInfo>) -> Self {
+        Self { source, command }
+    }
+
+    /// Get a reference to the inner [`std::io::Error`].
+    pub fn io_error(&self) -> &std::io::Error {
+        &self.source
+    }
+
+    /// Get the inner [`std::io::Error`].
+    pub fn into_io_error(self) -> std::io::Error {
+        self.source
+    }
+
+    /// Get the kind of the inner [`std::io::Error`].
+    pub fn kind(&self) -> std::io::ErrorKind {
+        self.source.kind()
+    }
+
+    /// Get the command which couldn't be run, if known.
+    pub fn command(&self) -> Option<&CommandInfo> {
+        self.command.as_ref()
+    }
+}
+
+impl From<std::io::Error> for SpawnError {
+    fn from(source: std::io::Error) -> Self {
+        Self {
+            source,
+            command: None,
+        }
+    }
+}
+
+impl Display for SpawnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(command) = &self.command {
+            write!(f, "`{command}`: ")?;
+        }
+        write!(f, "{}", self.source)
+    }
+}
+
+impl std::error::Error for SpawnError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// An error produced by [`CommandExt::output_utf8`].
+///
+/// ```
+/// # use std::process::Command;
+/// use utf8_command::prelude::*;
+/// use utf8_command::CommandError;
+///
+/// let err = Command::new("this-program-does-not-exist")
+///     .output_utf8()
+///     .unwrap_err();
+/// assert!(matches!(err, CommandError::Io(_)));
+/// ```
+#[derive(Debug)]
+pub enum CommandError {
+    /// The command couldn't be run.
+    Io(SpawnError),
+    /// The command's output contained invalid UTF-8.
+    Utf8(Error),
+}
+
+impl CommandError {
+    /// Get the I/O error, if the command couldn't be run.
+    pub fn io_error(&self) -> Option<&std::io::Error> {
+        match self {
+            CommandError::Io(err) => Some(err.io_error()),
+            CommandError::Utf8(_) => None,
+        }
+    }
+
+    /// Get the UTF-8 decoding error, if the command's output contained invalid UTF-8.
+    pub fn utf8_error(&self) -> Option<&Error> {
+        match self {
+            CommandError::Io(_) => None,
+            CommandError::Utf8(err) => Some(err),
+        }
+    }
+
+    /// Get the command which produced the error, if known.
+    pub fn command(&self) -> Option<&CommandInfo> {
+        match self {
+            CommandError::Io(err) => err.command(),
+            CommandError::Utf8(err) => err.command(),
+        }
+    }
+}
+
+impl From<std::io::Error> for CommandError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(SpawnError::from(value))
+    }
+}
+
+impl From<SpawnError> for CommandError {
+    fn from(value: SpawnError) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<Error> for CommandError {
+    fn from(value: Error) -> Self {
+        Self::Utf8(value)
+    }
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::Io(err) => write!(f, "{err}"),
+            CommandError::Utf8(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CommandError::Io(err) => Some(err),
+            CommandError::Utf8(err) => Some(err),
+        }
+    }
+}
+
+/// An error produced by [`CommandExt::run_utf8`].
+#[derive(Debug)]
+pub enum RunError {
+    /// The command couldn't be run.
+    Io(SpawnError),
+    /// The command's output contained invalid UTF-8.
+    Utf8(Error),
+    /// The command exited unsuccessfully.
+    ///
+    /// When displayed, only the end of `stderr` is shown if it's long (currently, more than 1024
+    /// bytes).
+    Failed {
+        /// The command's exit status.
+        status: ExitStatus,
+        /// The contents of the command's `stderr` stream.
+        stderr: String,
+        /// The command which failed, if known.
+        command: Option<CommandInfo>,
+    },
+}
+
+impl RunError {
+    /// Get the exit status, if the command exited unsuccessfully.
+    pub fn status(&self) -> Option<ExitStatus> {
+        match self {
+            RunError::Failed { status, .. } => Some(*status),
+            RunError::Io(_) | RunError::Utf8(_) => None,
+        }
+    }
+
+    /// Get the command's `stderr`, if the command exited unsuccessfully.
+    pub fn stderr(&self) -> Option<&str> {
+        match self {
+            RunError::Failed { stderr, .. } => Some(stderr),
+            RunError::Io(_) | RunError::Utf8(_) => None,
+        }
+    }
+
+    /// Get the command which produced the error, if known.
+    pub fn command(&self) -> Option<&CommandInfo> {
+        match self {
+            RunError::Io(err) => err.command(),
+            RunError::Utf8(err) => err.command(),
+            RunError::Failed { command, .. } => command.as_ref(),
+        }
+    }
+}
+
+impl From<std::io::Error> for RunError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(SpawnError::from(value))
+    }
+}
+
+impl From<SpawnError> for RunError {
+    fn from(value: SpawnError) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<Error> for RunError {
+    fn from(value: Error) -> Self {
+        Self::Utf8(value)
+    }
+}
+
+impl From<CheckedError> for RunError {
+    fn from(value: CheckedError) -> Self {
+        match value {
+            CheckedError::Io(err) => Self::Io(err),
+            CheckedError::Utf8(err) => Self::Utf8(err),
+            CheckedError::Failed { output, command } => Self::Failed {
+                status: output.status,
+                stderr: output.stderr,
+                command,
+            },
+        }
+    }
+}
+
+impl Display for RunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunError::Io(err) => write!(f, "{err}"),
+            RunError::Utf8(err) => write!(f, "{err}"),
+            RunError::Failed {
+                status,
+                stderr,
+                command,
+            } => fmt_failure(f, command.as_ref(), *status, stderr),
+        }
+    }
+}
+
+impl std::error::Error for RunError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RunError::Io(err) => Some(err),
+            RunError::Utf8(err) => Some(err),
+            RunError::Failed { .. } => None,
+        }
+    }
+}
+
+/// An error produced by [`CommandExt::output_utf8_checked`].
+#[derive(Debug)]
+pub enum CheckedError {
+    /// The command couldn't be run.
+    Io(SpawnError),
+    /// The command's output contained invalid UTF-8.
+    Utf8(Error),
+    /// The command exited unsuccessfully.
+    ///
+    /// When displayed, only the end of `stderr` is shown if it's long (currently, more than 1024
+    /// bytes).
+    Failed {
+        /// The command's decoded output.
+        output: Utf8Output,
+        /// The command which failed, if known.
+        command: Option<CommandInfo>,
+    },
+}
+
+impl CheckedError {
+    /// Get the command's decoded output, if the command exited unsuccessfully.
+    pub fn output(&self) -> Option<&Utf8Output> {
+        match self {
+            CheckedError::Failed { output, .. } => Some(output),
+            CheckedError::Io(_) | CheckedError::Utf8(_) => None,
+        }
+    }
+
+    /// Get the command's decoded output, if the command exited unsuccessfully.
+    pub fn into_output(self) -> Option<Utf8Output> {
+        match self {
+            CheckedError::Failed { output, .. } => Some(output),
+            CheckedError::Io(_) | CheckedError::Utf8(_) => None,
+        }
+    }
+
+    /// Get the command which produced the error, if known.
+    pub fn command(&self) -> Option<&CommandInfo> {
+        match self {
+            CheckedError::Io(err) => err.command(),
+            CheckedError::Utf8(err) => err.command(),
+            CheckedError::Failed { command, .. } => command.as_ref(),
+        }
+    }
+}
+
+impl From<std::io::Error> for CheckedError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(SpawnError::from(value))
+    }
+}
+
+impl From<SpawnError> for CheckedError {
+    fn from(value: SpawnError) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<Error> for CheckedError {
+    fn from(value: Error) -> Self {
+        Self::Utf8(value)
+    }
+}
+
+impl From<CommandError> for CheckedError {
+    fn from(value: CommandError) -> Self {
+        match value {
+            CommandError::Io(err) => Self::Io(err),
+            CommandError::Utf8(err) => Self::Utf8(err),
+        }
+    }
+}
+
+impl Display for CheckedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckedError::Io(err) => write!(f, "{err}"),
+            CheckedError::Utf8(err) => write!(f, "{err}"),
+            CheckedError::Failed { output, command } => {
+                fmt_failure(f, command.as_ref(), output.status, &output.stderr)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CheckedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CheckedError::Io(err) => Some(err),
+            CheckedError::Utf8(err) => Some(err),
+            CheckedError::Failed { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+    use std::io::ErrorKind;
+
+    use super::*;
+    use crate::test_util::output;
+
+    fn echo(arg: &str) -> Command {
+        if cfg!(windows) {
+            let mut command = Command::new("cmd");
+            command.args(["/C", "echo", arg]);
+            command
+        } else {
+            let mut command = Command::new("echo");
+            command.arg(arg);
+            command
+        }
+    }
+
+    #[test]
+    fn test_output_utf8() {
+        let output = echo("puppy").output_utf8().unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout.trim_end(), "puppy");
+        assert_eq!(output.stderr, "");
+    }
+
+    #[test]
+    fn test_output_utf8_missing_binary() {
+        let err = Command::new("utf8-command-this-program-does-not-exist")
+            .output_utf8()
+            .unwrap_err();
+        assert_eq!(err.io_error().unwrap().kind(), ErrorKind::NotFound);
+        assert!(err.utf8_error().is_none());
+        assert!(err.source().unwrap().downcast_ref::<SpawnError>().is_some());
+        assert!(err
+            .to_string()
+            .starts_with("`utf8-command-this-program-does-not-exist`: "));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_utf8_invalid() {
+        let err = Command::new("printf")
+            .arg("puppy \\300")
+            .output_utf8()
+            .unwrap_err();
+        assert!(err.io_error().is_none());
+        assert_eq!(
+            err.to_string(),
+            "`printf 'puppy \\300'`: \
+            Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+        );
+        assert_eq!(
+            err.command().unwrap(),
+            &CommandInfo::new("printf", ["puppy \\300"])
+        );
+        assert!(err.source().unwrap().downcast_ref::<Error>().is_some());
+    }
+
+    #[test]
+    fn test_run_utf8() {
+        assert_eq!(echo("  puppy  ").run_utf8().unwrap(), "puppy");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_utf8_failed() {
+        let err = Command::new("sh")
+            .args(["-c", "echo puppy; echo doggy >&2; exit 3"])
+            .run_utf8()
+            .unwrap_err();
+        assert_eq!(err.status().unwrap().code(), Some(3));
+        assert_eq!(err.stderr(), Some("doggy\n"));
+        assert_eq!(
+            err.to_string(),
+            "Command `sh -c 'echo puppy; echo doggy >&2; exit 3'` failed with exit status: 3; \
+            stderr: \"doggy\\n\""
+        );
+        assert!(err.source().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_utf8_failed_long_stderr() {
+        let err = Command::new("sh")
+            .args(["-c", "for i in $(seq 1000); do echo $i >&2; done; exit 1"])
+            .run_utf8()
+            .unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.contains("failed with exit status: 1; stderr: [2869 bytes] \""),
+            "{message}"
+        );
+        assert!(message.ends_with("998\\n999\\n1000\\n\""), "{message}");
+    }
+
+    #[test]
+    fn test_run_utf8_missing_binary() {
+        let err = Command::new("utf8-command-this-program-does-not-exist")
+            .run_utf8()
+            .unwrap_err();
+        assert!(matches!(err, RunError::Io(_)));
+        assert_eq!(err.status(), None);
+    }
+
+    #[test]
+    fn test_output_utf8_checked() {
+        let output = echo("puppy").output_utf8_checked().unwrap();
+        assert_eq!(output.stdout.trim_end(), "puppy");
+    }
+
+    #[test]
+    fn test_output_utf8_checked_missing_binary() {
+        let err = Command::new("utf8-command-this-program-does-not-exist")
+            .output_utf8_checked()
+            .unwrap_err();
+        assert!(matches!(err, CheckedError::Io(_)));
+        assert!(err.output().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_utf8_checked_invalid() {
+        let err = Command::new("sh")
+            .args(["-c", "printf 'puppy \\300'; exit 1"])
+            .output_utf8_checked()
+            .unwrap_err();
+        assert!(matches!(err, CheckedError::Utf8(_)));
+        assert_eq!(
+            err.to_string(),
+            "`sh -c 'printf '\\''puppy \\300'\\''; exit 1'`: \
+            Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_utf8_checked_failed() {
+        let err = Command::new("sh")
+            .args(["-c", "echo puppy; echo doggy >&2; exit 2"])
+            .output_utf8_checked()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Command `sh -c 'echo puppy; echo doggy >&2; exit 2'` failed with exit status: 2; \
+            stderr: \"doggy\\n\""
+        );
+        assert!(err.source().is_none());
+        let output = err.into_output().unwrap();
+        assert_eq!(output.status.code(), Some(2));
+        assert_eq!(output.stdout, "puppy\n");
+        assert_eq!(output.stderr, "doggy\n");
+    }
+
+    #[test]
+    fn test_try_from_has_no_command() {
+        let err = Utf8Output::try_from(output(b"puppy \xc0", b"")).unwrap_err();
+        assert_eq!(err.command(), None);
+        assert_eq!(
+            err.to_string(),
+            "Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+        );
+    }
+
+    #[test]
+    fn test_output_utf8_recorded() {
+        let mut command = echo("puppy");
+        command.current_dir(std::env::temp_dir());
+        let recorded = command.output_utf8_recorded().unwrap();
+        assert_eq!(recorded.stdout.trim_end(), "puppy");
+        assert_eq!(recorded.command, CommandInfo::from(&command));
+        assert_eq!(
+            recorded.command.current_dir(),
+            Some(std::env::temp_dir().as_os_str())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_utf8_tee_invalid() {
+        let err = Command::new("sh")
+            .args(["-c", "printf 'puppy \\300' >&2"])
+            .output_utf8_tee()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`sh -c 'printf '\\''puppy \\300'\\'' >&2'`: \
+            Stderr contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+        );
+    }
+
+    /// A command which copies `stdin` to `stdout`.
+    fn cat() -> Command {
+        if cfg!(windows) {
+            let mut command = Command::new("findstr");
+            // Match every line.
+            command.args(["/R", "^"]);
+            command
+        } else {
+            Command::new("cat")
+        }
+    }
+
+    #[test]
+    fn test_output_utf8_with_input_large() {
+        // Much more than a pipe buffer in both directions, so writing all of the input before
+        // reading any output would deadlock.
+        let input = (0..500_000)
+            .map(|i| format!("puppy {i} 🐶\n"))
+            .collect::<String>();
+        assert!(input.len() > 5 * 1024 * 1024);
+        let output = cat().output_utf8_with_input(&input).unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout.replace("\r\n", "\n"), input);
+    }
+
+    #[test]
+    fn test_output_utf8_with_input_empty() {
+        let output = cat().output_utf8_with_input("").unwrap();
+        assert_eq!(output.stdout, "");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_utf8_with_input_unread() {
+        // The child exits without reading its input, so writing it fails with a broken pipe.
+        let input = "puppy\n".repeat(1024 * 1024);
+        let output = Command::new("sh")
+            .args(["-c", "echo doggy >&2"])
+            .output_utf8_with_input(&input)
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stderr, "doggy\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_utf8_with_input_invalid() {
+        let err = Command::new("sh")
+            .args(["-c", "cat; printf '\\300'"])
+            .output_utf8_with_input("puppy ")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`sh -c 'cat; printf '\\''\\300'\\'''`: \
+            Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+        );
+    }
+
+    /// Check that the child's file descriptor `fd` is the same file as this process's, by
+    /// comparing the targets of `/proc/self/fd/{fd}`.
+    #[cfg(target_os = "linux")]
+    fn inherits_fd(command: &mut Command, fd: u8) -> bool {
+        let ours = std::fs::read_link(format!("/proc/self/fd/{fd}")).unwrap();
+        // `$$` is the shell, whose file descriptors aren't redirected while `readlink` runs.
+        let script = format!(
+            "target=$(readlink /proc/$$/fd/{fd}); echo \"$target\" >&{}",
+            3 - fd
+        );
+        let (_, theirs) = if fd == 2 {
+            command.args(["-c", &script]).stdout_utf8().unwrap()
+        } else {
+            command.args(["-c", &script]).stderr_utf8().unwrap()
+        };
+        theirs.trim_end() == ours.to_str().unwrap()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_stdout_utf8_inherits_stream() {
+        assert!(inherits_fd(&mut Command::new("sh"), 2));
+        assert!(inherits_fd(&mut Command::new("sh"), 1));
+    }
+
+    #[test]
+    fn test_stdout_utf8() {
+        let (status, stdout) = echo("puppy").stdout_utf8().unwrap();
+        assert!(status.success());
+        assert_eq!(stdout.trim_end(), "puppy");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stdout_utf8_overrides_stdio() {
+        let (status, stdout) = Command::new("sh")
+            .args(["-c", "echo puppy; exit 2"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .stdout_utf8()
+            .unwrap();
+        assert_eq!(status.code(), Some(2));
+        assert_eq!(stdout, "puppy\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stdout_utf8_leaves_stdio_configured() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo puppy; echo doggy >&2"]);
+        command.stdout_utf8().unwrap();
+        // `stderr` is still inherited, so it isn't captured.
+        let output = command.output_utf8().unwrap();
+        assert_eq!(output.stdout, "puppy\n");
+        assert_eq!(output.stderr, "");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stdout_utf8_invalid() {
+        let err = Command::new("printf")
+            .arg("puppy \\300")
+            .stdout_utf8()
+            .unwrap_err();
+        assert!(matches!(err.utf8_error(), Some(Error::Stdout(_))));
+        assert_eq!(
+            err.to_string(),
+            "`printf 'puppy \\300'`: \
+            Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stderr_utf8() {
+        let (status, stderr) = Command::new("sh")
+            .args(["-c", "echo puppy; echo doggy >&2; exit 2"])
+            .stderr_utf8()
+            .unwrap();
+        assert_eq!(status.code(), Some(2));
+        assert_eq!(stderr, "doggy\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stderr_utf8_invalid() {
+        let err = Command::new("sh")
+            .args(["-c", "printf 'doggy \\300' >&2"])
+            .stderr_utf8()
+            .unwrap_err();
+        assert!(matches!(err.utf8_error(), Some(Error::Stderr(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_utf8_checked_codes() {
+        let grep = |pattern: &str| {
+            let mut command = Command::new("sh");
+            command.args(["-c", "printf 'puppy\\ndoggy\\n' | grep \"$0\"", pattern]);
+            command
+        };
+
+        let output = grep("puppy").output_utf8_checked_codes(&[0, 1]).unwrap();
+        assert_eq!(output.stdout, "puppy\n");
+
+        let output = grep("kitty").output_utf8_checked_codes(&[0, 1]).unwrap();
+        assert_eq!(output.code(), Some(1));
+        assert_eq!(output.stdout, "");
+
+        let err = grep("[").output_utf8_checked_codes(&[0, 1]).unwrap_err();
+        assert_eq!(err.output().unwrap().code(), Some(2));
+        let message = err.to_string();
+        assert!(
+            message.contains("` failed with exit status: 2; stderr: "),
+            "{message}"
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use std::fmt::Display;
+use std::process::Output;
+
+use crate::context::ErrorContext;
+use crate::Utf8Output;
+use crate::ERROR_CONTEXT_BYTES;
+
+/// One of the streams of an [`Output`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stream {
+    /// The `stdout` stream.
+    Stdout,
+    /// The `stderr` stream.
+    Stderr,
+}
+
+impl Display for Stream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stream::Stdout => write!(f, "Stdout"),
+            Stream::Stderr => write!(f, "Stderr"),
+        }
+    }
+}
+
+impl Stream {
+    /// Select the value for this stream.
+    pub(crate) fn select<T>(self, stdout: T, stderr: T) -> T {
+        match self {
+            Stream::Stdout => stdout,
+            Stream::Stderr => stderr,
+        }
+    }
+}
+
+/// The reason a stream's contents were rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentErrorKind {
+    /// The stream started with a UTF-8 byte order mark.
+    ///
+    /// See [`BomHandling::Reject`][crate::BomHandling::Reject].
+    Bom,
+    /// The stream contained a NUL byte (`\0`).
+    ///
+    /// See [`ConversionOptions::reject_nul`][crate::ConversionOptions::reject_nul].
+    Nul,
+    /// The stream contained a control character which isn't allowed.
+    ///
+    /// See [`ConversionOptions::control_characters`][crate::ConversionOptions::control_characters].
+    Control(char),
+    /// The stream contained a character which isn't ASCII.
+    ///
+    /// See [`Strictness::Ascii`][crate::Strictness::Ascii].
+    NonAscii(char),
+}
+
+/// A stream was valid UTF-8, but its contents were rejected by the [`ConversionOptions`].
+///
+/// The error keeps the decoded [`Utf8Output`], so nothing is lost.
+///
+/// [`ConversionOptions`]: crate::ConversionOptions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentError {
+    stream: Stream,
+    kind: ContentErrorKind,
+    offset: usize,
+    output: Box<Utf8Output>,
+}
+
+impl ContentError {
+    pub(crate) fn new(
+        stream: Stream,
+        kind: ContentErrorKind,
+        offset: usize,
+        output: Utf8Output,
+    ) -> Self {
+        Self {
+            stream,
+            kind,
+            offset,
+            output: Box::new(output),
+        }
+    }
+
+    /// The stream which was rejected.
+    pub fn stream(&self) -> Stream {
+        self.stream
+    }
+
+    /// The reason the stream was rejected.
+    pub fn kind(&self) -> ContentErrorKind {
+        self.kind
+    }
+
+    /// The byte offset in the stream of the rejected contents.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Get a reference to the decoded output.
+    pub fn output(&self) -> &Utf8Output {
+        &self.output
+    }
+
+    /// Get the decoded output.
+    pub fn into_utf8_output(self) -> Utf8Output {
+        *self.output
+    }
+
+    /// Reconstruct the [`Output`] this error was produced from.
+    pub fn into_output(self) -> Output {
+        self.output.into_output()
+    }
+}
+
+impl ContentError {
+    /// The context around the rejected contents, for the error message.
+    fn context(&self) -> ErrorContext<'_> {
+        let text = self.stream.select(&self.output.stdout, &self.output.stderr);
+        ErrorContext::at(text.as_bytes(), self.offset, ERROR_CONTEXT_BYTES)
+    }
+}
+
+impl Display for ContentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            ContentErrorKind::Bom => write!(f, "{} started with a byte order mark", self.stream),
+            ContentErrorKind::Nul => write!(
+                f,
+                "{} contained a NUL byte at index {}: {}",
+                self.stream,
+                self.offset,
+                self.context()
+            ),
+            ContentErrorKind::NonAscii(c) => write!(
+                f,
+                "{} contained non-ASCII character {c:?} at index {}: {}",
+                self.stream,
+                self.offset,
+                self.context()
+            ),
+            ContentErrorKind::Control(c) => write!(
+                f,
+                "{} contained control character {c:?} at index {}: {}",
+                self.stream,
+                self.offset,
+                self.context()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ContentError {}
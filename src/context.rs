@@ -1,29 +1,140 @@
+use std::borrow::Cow;
 use std::fmt::Display;
 use std::ops::Range;
 use std::string::FromUtf8Error;
 
-/// A [`std::string::FromUtf8Error`] formatted with the text decoded in a best-effort manner.
-pub(crate) struct FromUtf8ErrorContext<'inner> {
+/// The number of bytes discarded from each end of a stream when an error is truncated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Truncation {
+    pub(crate) before: usize,
+    pub(crate) after: usize,
+}
+
+/// Truncate the bytes in a [`FromUtf8Error`] to (about) `retain` bytes on either side of the
+/// decoding error.
+///
+/// The returned error has the same error, but its index is relative to the retained bytes; the
+/// [`Truncation`] records how many bytes were discarded, and is `None` if nothing was.
+pub(crate) fn truncate(inner: FromUtf8Error, retain: usize) -> (FromUtf8Error, Option<Truncation>) {
+    let bytes = inner.as_bytes();
+    let error_index = inner.utf8_error().valid_up_to();
+
+    // The bytes before the error index are valid UTF-8, so this lands on a codepoint boundary
+    // within 3 bytes.
+    let mut start = error_index.saturating_sub(retain);
+    while start < error_index && !is_codepoint_boundary(bytes[start]) {
+        start += 1;
+    }
+    // `retain` is larger than any invalid sequence, so the error is the same in the retained
+    // bytes.
+    let end = Ord::min(error_index.saturating_add(retain), bytes.len());
+
+    if start == 0 && end == bytes.len() {
+        return (inner, None);
+    }
+
+    let truncation = Truncation {
+        before: start,
+        after: bytes.len() - end,
+    };
+    let truncated = String::from_utf8(bytes[start..end].to_vec())
+        .expect_err("Truncated bytes should contain the same decoding error");
+    (truncated, Some(truncation))
+}
+
+/// The [`Display`] of a [`FromUtf8Error`], with the error index offset by the bytes discarded
+/// before it.
+pub(crate) struct FromUtf8ErrorMessage<'inner> {
     inner: &'inner FromUtf8Error,
+    offset: usize,
+}
+
+impl<'a> FromUtf8ErrorMessage<'a> {
+    pub(crate) fn new(inner: &'a FromUtf8Error, discarded: Option<Truncation>) -> Self {
+        Self {
+            inner,
+            offset: discarded.map(|discarded| discarded.before).unwrap_or(0),
+        }
+    }
+}
+
+impl<'a> Display for FromUtf8ErrorMessage<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.offset == 0 {
+            return write!(f, "{}", self.inner);
+        }
+
+        // Matches the `Display` implementation for `Utf8Error`.
+        let error = self.inner.utf8_error();
+        let index = error.valid_up_to() + self.offset;
+        match error.error_len() {
+            Some(len) => write!(
+                f,
+                "invalid utf-8 sequence of {len} bytes from index {index}"
+            ),
+            None => write!(f, "incomplete utf-8 byte sequence from index {index}"),
+        }
+    }
+}
+
+/// A window of bytes around an error, formatted with the text decoded in a best-effort manner.
+pub(crate) struct ErrorContext<'bytes> {
+    bytes: &'bytes [u8],
+    /// The index of the error in `bytes`; the window is centered on this index.
+    index: usize,
     max_size: usize,
+    /// Bytes which were discarded from the error, counted in the context.
+    discarded: Truncation,
+    /// Whether to move the ends of the window to UTF-8 code point boundaries.
+    utf8_boundaries: bool,
 }
 
-impl<'a> FromUtf8ErrorContext<'a> {
+impl<'a> ErrorContext<'a> {
+    /// The context around a [`FromUtf8Error`]'s decoding error.
     pub(crate) fn new(inner: &'a FromUtf8Error, max_size: usize) -> Self {
-        Self { inner, max_size }
+        Self::at(inner.as_bytes(), inner.utf8_error().valid_up_to(), max_size)
+    }
+
+    /// The context around `index` in `bytes`.
+    pub(crate) fn at(bytes: &'a [u8], index: usize, max_size: usize) -> Self {
+        Self {
+            bytes,
+            index,
+            max_size,
+            discarded: Truncation::default(),
+            utf8_boundaries: true,
+        }
+    }
+
+    /// Don't move the ends of the window to UTF-8 code point boundaries, for bytes which are in
+    /// some other encoding.
+    #[cfg(feature = "encoding")]
+    pub(crate) fn without_utf8_boundaries(mut self) -> Self {
+        self.utf8_boundaries = false;
+        self
+    }
+
+    /// Count bytes which were discarded from the error in the context.
+    pub(crate) fn discarded(mut self, discarded: Option<Truncation>) -> Self {
+        self.discarded = discarded.unwrap_or_default();
+        self
     }
 
     /// Get a 'window' of bytes to display in the error message.
     ///
     /// This is a range of (at most) `max_size` that the input can be sliced on to display the
-    /// portion of input around the encoding error.
+    /// portion of input around the error.
     fn window(&self) -> Range<usize> {
-        let bytes = self.inner.as_bytes();
+        let bytes = self.bytes;
         let mut range = self.window_unadjusted();
+        if !self.utf8_boundaries {
+            return range;
+        }
 
         if range.start != 0 && !is_codepoint_boundary(bytes[range.start]) {
-            // Note: I think this will always be adjusted up because the input up to the error
-            // index is valid UTF-8, and the lower bound is always before the error index.
+            // Note: For decoding errors, I think this will always be adjusted up because the input
+            // up to the error index is valid UTF-8, and the lower bound is always before the error
+            // index.
             range.start = self
                 .adjust_index_up(range.start)
                 .or_else(|| self.adjust_index_down(range.start))
@@ -44,14 +155,14 @@ impl<'a> FromUtf8ErrorContext<'a> {
     ///
     /// The indexes in this range have not been checked to make sure they lie on UTF-8 boundaries.
     fn window_unadjusted(&self) -> Range<usize> {
-        let bytes = self.inner.as_bytes();
+        let bytes = self.bytes;
         if bytes.len() <= self.max_size {
             return 0..bytes.len();
         }
 
         // Half the length of the window.
         let half_window = self.max_size / 2;
-        let error_index = self.inner.utf8_error().valid_up_to();
+        let error_index = self.index;
 
         let upper_bound = error_index + half_window;
         if upper_bound >= bytes.len() {
@@ -77,7 +188,7 @@ impl<'a> FromUtf8ErrorContext<'a> {
     fn adjust_index_down(&self, index: usize) -> Option<usize> {
         // Logic adapted from unstable `std` method:
         // https://github.com/rust-lang/rust/blob/a7e4de13c1785819f4d61da41f6704ed69d5f203/library/core/src/str/mod.rs#L264-L276
-        let bytes = self.inner.as_bytes();
+        let bytes = self.bytes;
         let lower_bound = index.saturating_sub(3);
         bytes[lower_bound..=index]
             .iter()
@@ -91,7 +202,7 @@ impl<'a> FromUtf8ErrorContext<'a> {
     fn adjust_index_up(&self, index: usize) -> Option<usize> {
         // Logic adapted from unstable `std` method:
         // https://github.com/rust-lang/rust/blob/a7e4de13c1785819f4d61da41f6704ed69d5f203/library/core/src/str/mod.rs#L302-L311
-        let bytes = self.inner.as_bytes();
+        let bytes = self.bytes;
         let upper_bound = Ord::min(index + 4, bytes.len());
         bytes[index..upper_bound]
             .iter()
@@ -100,15 +211,20 @@ impl<'a> FromUtf8ErrorContext<'a> {
     }
 }
 
-impl<'a> Display for FromUtf8ErrorContext<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let bytes = self.inner.as_bytes();
-        if bytes.len() <= self.max_size {
-            write!(f, "{:?}", String::from_utf8_lossy(bytes))
+impl<'a> ErrorContext<'a> {
+    /// Format the context, decoding the displayed bytes with `decode`.
+    pub(crate) fn fmt_with(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        decode: impl for<'b> Fn(&'b [u8]) -> Cow<'b, str>,
+    ) -> std::fmt::Result {
+        let bytes = self.bytes;
+        if bytes.len() <= self.max_size && self.discarded == Truncation::default() {
+            write!(f, "{:?}", decode(bytes))
         } else {
             let range = self.window();
-            let before = range.start;
-            let after = bytes.len() - range.end;
+            let before = range.start + self.discarded.before;
+            let after = bytes.len() - range.end + self.discarded.after;
 
             if before != 0 {
                 write!(f, "{} ", ByteCount(before))?;
@@ -116,7 +232,7 @@ impl<'a> Display for FromUtf8ErrorContext<'a> {
 
             // TODO: It might be nice to print the hex values of the bytes like `\x62` instead of
             // just `�` U+FFFD REPLACEMENT CHARACTER.
-            write!(f, "{:?}", String::from_utf8_lossy(&bytes[range]))?;
+            write!(f, "{:?}", decode(&bytes[range]))?;
 
             if after != 0 {
                 write!(f, " {}", ByteCount(after))?;
@@ -127,7 +243,13 @@ impl<'a> Display for FromUtf8ErrorContext<'a> {
     }
 }
 
-fn is_codepoint_boundary(byte: u8) -> bool {
+impl<'a> Display for ErrorContext<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_with(f, String::from_utf8_lossy)
+    }
+}
+
+pub(crate) fn is_codepoint_boundary(byte: u8) -> bool {
     // Stolen from a private `std` method:
     // https://github.com/rust-lang/rust/blob/a7e4de13c1785819f4d61da41f6704ed69d5f203/library/core/src/num/mod.rs#L1101-L1104
     // This is bit magic equivalent to: b < 128 || b >= 192
@@ -157,11 +279,7 @@ mod tests {
     #[test]
     fn test_simple() {
         assert_eq!(
-            FromUtf8ErrorContext {
-                inner: &err(b"puppy\xc0doggy"),
-                max_size: 32,
-            }
-            .to_string(),
+            ErrorContext::new(&err(b"puppy\xc0doggy"), 32).to_string(),
             "\"puppy�doggy\""
         );
     }
@@ -170,8 +288,8 @@ mod tests {
     fn test_truncation() {
         // Adjusts the lower bound up (3->4) and the upper bound down (35->32).
         assert_eq!(
-            FromUtf8ErrorContext {
-                inner: &err(b"\xf0\x9f\x98\x8a\
+            ErrorContext::new(
+                &err(b"\xf0\x9f\x98\x8a\
                     \xf0\x9f\x98\x8a\
                     \xe2\x9c\x93\
                     \xf0\x9f\x98\x8a\
@@ -182,8 +300,8 @@ mod tests {
                     \xf0\x9f\x98\x8a\
                     \xf0\x9f\x98\x8a\
                     \xf0\x9f\x98\x8a"),
-                max_size: 32,
-            }
+                32
+            )
             .to_string(),
             "[4 bytes] \"😊✓😊😊�😊😊😊\" [8 bytes]"
         );
@@ -193,8 +311,8 @@ mod tests {
     fn test_truncation_up() {
         // Adjusts the lower bound up (3->4) and the upper bound up (35->37).
         assert_eq!(
-            FromUtf8ErrorContext {
-                inner: &err(b"\xf0\x9f\x98\x8a\
+            ErrorContext::new(
+                &err(b"\xf0\x9f\x98\x8a\
                     \xf0\x9f\x98\x8a\
                     \xe2\x9c\x93\
                     \xf0\x9f\x98\x8a\
@@ -205,8 +323,8 @@ mod tests {
                     \xf0\x9f\x98\x8a\
                     \x80\x80\x80\x80\
                     \x80\x62\x80\x80"),
-                max_size: 32,
-            }
+                32
+            )
             .to_string(),
             "[4 bytes] \"😊✓😊😊�😊😊😊�����\" [3 bytes]"
         );
@@ -215,44 +333,57 @@ mod tests {
     #[test]
     fn test_truncation_near_end() {
         assert_eq!(
-            FromUtf8ErrorContext {
-                inner: &err(b"puppy puppy puppy puppy puppy \
+            ErrorContext::new(
+                &err(b"puppy puppy puppy puppy puppy \
                 doggy doggy doggy doggy\xc0doggy"),
-                max_size: 32,
-            }
+                32
+            )
             .to_string(),
             "[27 bytes] \"py doggy doggy doggy doggy�doggy\""
         );
 
         assert_eq!(
-            FromUtf8ErrorContext {
-                inner: &err(b"puppy puppy puppy puppy puppy \
+            ErrorContext::new(
+                &err(b"puppy puppy puppy puppy puppy \
                 doggy doggy doggy doggy\xc0"),
-                max_size: 32,
-            }
+                32
+            )
             .to_string(),
             "[22 bytes] \"y puppy doggy doggy doggy doggy�\""
         );
     }
 
+    #[test]
+    fn test_at_index() {
+        let bytes = "puppy ".repeat(10) + "\0" + &"doggy ".repeat(10);
+        assert_eq!(
+            ErrorContext::at(bytes.as_bytes(), 60, 32).to_string(),
+            "[44 bytes] \"ppy puppy puppy \\0doggy doggy dog\" [45 bytes]"
+        );
+        assert_eq!(
+            ErrorContext::at(b"\0puppy", 0, 32).to_string(),
+            "\"\\0puppy\""
+        );
+    }
+
     #[test]
     fn test_truncation_near_start() {
         assert_eq!(
-            FromUtf8ErrorContext {
-                inner: &err(b"puppy\xc0puppy puppy puppy puppy \
+            ErrorContext::new(
+                &err(b"puppy\xc0puppy puppy puppy puppy \
                 doggy doggy doggy doggy doggy"),
-                max_size: 32,
-            }
+                32
+            )
             .to_string(),
             "\"puppy�puppy puppy puppy puppy do\" [27 bytes]"
         );
 
         assert_eq!(
-            FromUtf8ErrorContext {
-                inner: &err(b"\xc0puppy puppy puppy puppy puppy \
+            ErrorContext::new(
+                &err(b"\xc0puppy puppy puppy puppy puppy \
                 doggy doggy doggy doggy"),
-                max_size: 32,
-            }
+                32
+            )
             .to_string(),
             "\"�puppy puppy puppy puppy puppy d\" [22 bytes]"
         );
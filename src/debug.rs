@@ -0,0 +1,162 @@
+use std::fmt::Debug;
+
+use crate::Utf8Output;
+
+/// The maximum length in bytes of each stream shown in full by [`Utf8Output`]'s `Debug`
+/// implementation.
+///
+/// Longer streams are shown as their first and last `DEBUG_STREAM_BYTES / 2` bytes (or a little
+/// less, to end on `char` boundaries), with the number of bytes left out in between.
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use utf8_command::Utf8Output;
+/// # use utf8_command::DEBUG_STREAM_BYTES;
+/// let output = Utf8Output {
+///     status: ExitStatus::default(),
+///     stdout: "a".repeat(DEBUG_STREAM_BYTES),
+///     stderr: "a".repeat(DEBUG_STREAM_BYTES + 1),
+/// };
+/// let debug = format!("{output:?}");
+/// assert!(debug.contains(&format!("stdout: {:?}", output.stdout)));
+/// assert!(debug.contains("[1 byte omitted]"));
+/// ```
+pub const DEBUG_STREAM_BYTES: usize = 4096;
+
+/// Formats like a derived `Debug` implementation, except streams longer than
+/// [`DEBUG_STREAM_BYTES`] are truncated so that debugging a command with a lot of output doesn't
+/// flood the terminal. The middle of a truncated stream is replaced with the number of bytes left
+/// out:
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use utf8_command::Utf8Output;
+/// let output = Utf8Output {
+///     status: ExitStatus::default(),
+///     stdout: "puppy\n".repeat(1000),
+///     stderr: String::new(),
+/// };
+/// let debug = format!("{output:?}");
+/// assert!(debug.contains("puppy\\npu\" [1904 bytes omitted] \"y\\npuppy\\n"));
+/// // The fields still hold the whole output.
+/// assert_eq!(output.stdout.len(), 6000);
+/// ```
+impl<S: AsRef<str>> Debug for Utf8Output<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Utf8Output")
+            .field("status", &self.status)
+            .field("stdout", &TruncatedStream(self.stdout.as_ref()))
+            .field("stderr", &TruncatedStream(self.stderr.as_ref()))
+            .finish()
+    }
+}
+
+/// A stream which is formatted like a `str`, with its middle replaced if it's long.
+struct TruncatedStream<'a>(&'a str);
+
+impl Debug for TruncatedStream<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = self.0;
+        if text.len() <= DEBUG_STREAM_BYTES {
+            return write!(f, "{text:?}");
+        }
+
+        let mut head_end = DEBUG_STREAM_BYTES / 2;
+        while !text.is_char_boundary(head_end) {
+            head_end -= 1;
+        }
+        let mut tail_start = text.len() - DEBUG_STREAM_BYTES / 2;
+        while !text.is_char_boundary(tail_start) {
+            tail_start += 1;
+        }
+
+        let omitted = tail_start - head_end;
+        write!(
+            f,
+            "{:?} [{omitted} {} omitted] {:?}",
+            &text[..head_end],
+            if omitted == 1 { "byte" } else { "bytes" },
+            &text[tail_start..]
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::utf8_output;
+
+    #[test]
+    fn test_debug_short() {
+        let output = utf8_output("puppy\n", "");
+        assert_eq!(
+            format!("{output:?}"),
+            format!(
+                "Utf8Output {{ status: {:?}, stdout: \"puppy\\n\", stderr: \"\" }}",
+                output.status
+            )
+        );
+    }
+
+    #[test]
+    fn test_debug_pretty() {
+        let output = utf8_output("puppy\n", "doggy");
+        let pretty = format!("{output:#?}");
+        assert!(pretty.starts_with("Utf8Output {\n    status: "), "{pretty}");
+        assert!(
+            pretty.ends_with("\n    stdout: \"puppy\\n\",\n    stderr: \"doggy\",\n}"),
+            "{pretty}"
+        );
+    }
+
+    #[test]
+    fn test_debug_threshold() {
+        // Exactly at the limit, the stream isn't truncated.
+        let stdout = "a".repeat(DEBUG_STREAM_BYTES);
+        let debug = format!("{:?}", utf8_output(&stdout, ""));
+        assert!(debug.contains(&format!("{stdout:?}")));
+        assert!(!debug.contains("omitted"));
+
+        // One byte over, it is.
+        let stdout = "a".repeat(DEBUG_STREAM_BYTES + 1);
+        let debug = format!("{:?}", utf8_output("", &stdout));
+        assert!(debug.contains(&format!(
+            "stderr: \"{}\" [1 byte omitted] \"{}\"",
+            "a".repeat(2048),
+            "a".repeat(2048)
+        )));
+    }
+
+    #[test]
+    fn test_debug_huge() {
+        let stdout = format!("puppy{}doggy", "-".repeat(50 * 1024 * 1024));
+        let output = utf8_output(&stdout, "kitty");
+        let debug = format!("{output:?}");
+        assert!(debug.len() < 2 * DEBUG_STREAM_BYTES, "{debug}");
+        assert!(debug.contains("stdout: \"puppy---"), "{debug}");
+        assert!(debug.contains("---doggy\", stderr: \"kitty\""), "{debug}");
+        assert!(debug.contains(&format!(" [{} bytes omitted] ", stdout.len() - 4096)));
+
+        let pretty = format!("{output:#?}");
+        assert!(pretty.contains(" bytes omitted] "), "{pretty}");
+        // The truncated stream is still on one line.
+        assert!(pretty.lines().any(
+            |line| line.starts_with("    stdout: \"puppy---") && line.ends_with("---doggy\",")
+        ));
+    }
+
+    #[test]
+    fn test_debug_char_boundaries() {
+        // 4-byte characters, so neither cut is on a `char` boundary.
+        let stdout = format!("a{}", "🐶".repeat(2000));
+        let debug = format!("{:?}", utf8_output(&stdout, ""));
+        assert!(!debug.contains('\u{FFFD}'));
+        // The head is shortened to 2045 bytes, and the tail to 2048 bytes.
+        assert!(debug.contains(&format!(
+            "\"a{}\" [{} bytes omitted] \"{}\"",
+            "🐶".repeat(511),
+            8001 - 2045 - 2048,
+            "🐶".repeat(512)
+        )));
+    }
+}
@@ -0,0 +1,270 @@
+use std::process::Output;
+use std::string::FromUtf8Error;
+
+use crate::Error;
+use crate::MixedOutput;
+use crate::RawStdoutOutput;
+use crate::StderrError;
+use crate::StdoutError;
+use crate::Utf8Output;
+
+/// Decode the contents of a `stdout` stream as UTF-8.
+///
+/// This is what converting an [`Output`] into a [`Utf8Output`] uses, so error messages are the
+/// same.
+///
+/// ```
+/// let stdout = utf8_command::decode_stdout(Vec::from(b"puppy")).unwrap();
+/// assert_eq!(stdout, "puppy");
+/// ```
+pub fn decode_stdout(bytes: Vec<u8>) -> Result<String, StdoutError> {
+    string_from_utf8(bytes).map_err(StdoutError::from)
+}
+
+/// Decode the contents of a `stderr` stream as UTF-8.
+///
+/// This is what converting an [`Output`] into a [`Utf8Output`] uses, so error messages are the
+/// same.
+///
+/// ```
+/// let err = utf8_command::decode_stderr(Vec::from(b"doggy \xc0")).unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "Stderr contained invalid utf-8 sequence of 1 bytes from index 6: \"doggy �\""
+/// );
+/// ```
+pub fn decode_stderr(bytes: Vec<u8>) -> Result<String, StderrError> {
+    string_from_utf8(bytes).map_err(StderrError::from)
+}
+
+/// Validate the contents of a `stdout` stream as UTF-8, without copying it.
+///
+/// This is what converting a `&Output` into a [`Utf8Output`] uses, so error messages are the
+/// same. The bytes are only copied if they're invalid, to construct the error.
+///
+/// ```
+/// let stdout = utf8_command::decode_stdout_ref(b"puppy").unwrap();
+/// assert_eq!(stdout, "puppy");
+/// ```
+pub fn decode_stdout_ref(bytes: &[u8]) -> Result<&str, StdoutError> {
+    str_from_utf8(bytes).map_err(StdoutError::from)
+}
+
+/// Validate the contents of a `stderr` stream as UTF-8, without copying it.
+///
+/// This is what converting a `&Output` into a [`Utf8Output`] uses, so error messages are the
+/// same. The bytes are only copied if they're invalid, to construct the error.
+///
+/// ```
+/// let err = utf8_command::decode_stderr_ref(b"doggy \xc0").unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "Stderr contained invalid utf-8 sequence of 1 bytes from index 6: \"doggy �\""
+/// );
+/// ```
+pub fn decode_stderr_ref(bytes: &[u8]) -> Result<&str, StderrError> {
+    str_from_utf8(bytes).map_err(StderrError::from)
+}
+
+/// Decode `bytes` as UTF-8.
+///
+/// With the `simd` feature, the bytes are validated with `simdutf8` first. If they're invalid,
+/// they're validated again with [`String::from_utf8`] to construct the [`FromUtf8Error`], so
+/// errors are identical either way.
+pub(crate) fn string_from_utf8(bytes: Vec<u8>) -> Result<String, FromUtf8Error> {
+    #[cfg(feature = "simd")]
+    if simdutf8::basic::from_utf8(&bytes).is_ok() {
+        // SAFETY: `simdutf8` just validated the bytes.
+        return Ok(unsafe { String::from_utf8_unchecked(bytes) });
+    }
+
+    String::from_utf8(bytes)
+}
+
+/// Validate `bytes` as UTF-8 without copying them.
+///
+/// The bytes are only copied if they're invalid, to construct the [`FromUtf8Error`]. Like
+/// [`string_from_utf8`], this uses `simdutf8` with the `simd` feature.
+fn str_from_utf8(bytes: &[u8]) -> Result<&str, FromUtf8Error> {
+    #[cfg(feature = "simd")]
+    let validated = simdutf8::basic::from_utf8(bytes);
+    #[cfg(not(feature = "simd"))]
+    let validated = std::str::from_utf8(bytes);
+
+    validated.map_err(|_| {
+        String::from_utf8(bytes.to_vec()).expect_err("Bytes should fail to validate twice")
+    })
+}
+
+/// Types which can be decoded from an [`Output`].
+///
+/// This lets downstream crates define their own decoded output types while reusing this crate's
+/// error types and messages. Implementations will usually decode the streams with
+/// [`decode_stdout`] and [`decode_stderr`] and combine the errors with [`Error::from_streams`]:
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use std::process::Output;
+/// use utf8_command::Error;
+/// use utf8_command::TryFromOutput;
+///
+/// /// Output which only keeps stdout, with its trailing newline removed.
+/// struct Line {
+///     status: ExitStatus,
+///     line: String,
+/// }
+///
+/// impl TryFromOutput for Line {
+///     fn try_from_output(output: Output) -> Result<Self, Error> {
+///         let (mut line, _stderr) = Error::from_streams(
+///             utf8_command::decode_stdout(output.stdout),
+///             utf8_command::decode_stderr(output.stderr),
+///         )?;
+///         if line.ends_with('\n') {
+///             line.pop();
+///         }
+///         Ok(Line {
+///             status: output.status,
+///             line,
+///         })
+///     }
+/// }
+///
+/// let line = Line::try_from_output(Output {
+///     status: ExitStatus::default(),
+///     stdout: Vec::from(b"puppy\n"),
+///     stderr: Vec::from(b""),
+/// })
+/// .unwrap();
+/// assert_eq!(line.line, "puppy");
+/// ```
+pub trait TryFromOutput: Sized {
+    /// Decode an [`Output`].
+    fn try_from_output(output: Output) -> Result<Self, Error>;
+}
+
+impl TryFromOutput for Utf8Output {
+    fn try_from_output(output: Output) -> Result<Self, Error> {
+        output.try_into()
+    }
+}
+
+impl TryFromOutput for MixedOutput {
+    fn try_from_output(output: Output) -> Result<Self, Error> {
+        Ok(output.try_into()?)
+    }
+}
+
+impl TryFromOutput for RawStdoutOutput {
+    fn try_from_output(output: Output) -> Result<Self, Error> {
+        Ok(output.try_into()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+    use crate::test_util::output;
+
+    /// A richer output type, like a downstream crate might define.
+    struct TimestampedOutput {
+        captured_at: SystemTime,
+        stdout: String,
+        stderr: String,
+    }
+
+    impl TryFromOutput for TimestampedOutput {
+        fn try_from_output(output: Output) -> Result<Self, Error> {
+            let (stdout, stderr) =
+                Error::from_streams(decode_stdout(output.stdout), decode_stderr(output.stderr))?;
+            Ok(TimestampedOutput {
+                captured_at: SystemTime::now(),
+                stdout,
+                stderr,
+            })
+        }
+    }
+
+    #[test]
+    fn test_downstream_implementation() {
+        let before = SystemTime::now();
+        let decoded = TimestampedOutput::try_from_output(output(b"puppy", b"doggy")).unwrap();
+        assert!(decoded.captured_at >= before);
+        assert_eq!(decoded.stdout, "puppy");
+        assert_eq!(decoded.stderr, "doggy");
+
+        // Errors are the same as for `Utf8Output`.
+        for (stdout, stderr) in [
+            (b"puppy\xc0".as_slice(), b"doggy".as_slice()),
+            (b"puppy", b"doggy\xc0"),
+            (b"puppy\xc0", b"doggy\xc0"),
+        ] {
+            assert_eq!(
+                TimestampedOutput::try_from_output(output(stdout, stderr))
+                    .err()
+                    .map(|err| err.to_string()),
+                Utf8Output::try_from_output(output(stdout, stderr))
+                    .err()
+                    .map(|err| err.to_string()),
+            );
+        }
+    }
+
+    #[test]
+    fn test_string_from_utf8_matches_std() {
+        let mut inputs: Vec<Vec<u8>> = vec![
+            Vec::new(),
+            Vec::from("puppy 🐶".as_bytes()),
+            Vec::from(b"\xc0".as_slice()),
+            Vec::from(b"puppy\xe2\x28\xa1".as_slice()),
+            Vec::from(b"\xf0\x90".as_slice()),
+        ];
+        // Long enough to hit the vectorized paths, with an error at every offset in a block.
+        for index in 0..128 {
+            let mut bytes = "doggy 🐕 ".repeat(64).into_bytes();
+            bytes[index] = 0xff;
+            inputs.push(bytes);
+        }
+
+        for bytes in inputs {
+            assert_eq!(
+                string_from_utf8(bytes.clone()),
+                String::from_utf8(bytes.clone())
+            );
+            assert_eq!(
+                str_from_utf8(&bytes).map(str::to_owned),
+                String::from_utf8(bytes)
+            );
+        }
+    }
+
+    #[test]
+    fn test_borrowed_matches_owned() {
+        for bytes in [
+            b"".as_slice(),
+            "puppy 🐶".as_bytes(),
+            b"\xc0",
+            b"puppy\xe2\x28\xa1",
+        ] {
+            assert_eq!(
+                decode_stdout_ref(bytes).map(str::to_owned),
+                decode_stdout(bytes.to_vec())
+            );
+            assert_eq!(
+                decode_stderr_ref(bytes).map(str::to_owned),
+                decode_stderr(bytes.to_vec())
+            );
+        }
+    }
+
+    #[test]
+    fn test_mixed_outputs() {
+        let err = MixedOutput::try_from_output(output(b"\xc0", b"\xc0")).unwrap_err();
+        assert!(matches!(err, Error::Stdout(_)));
+
+        let err = RawStdoutOutput::try_from_output(output(b"\xc0", b"\xc0")).unwrap_err();
+        assert!(matches!(err, Error::Stderr(_)));
+    }
+}
@@ -0,0 +1,147 @@
+use std::process::Output;
+
+use chardetng::EncodingDetector;
+use chardetng::Iso2022JpDetection;
+use chardetng::Utf8Detection;
+use encoding_rs::Encoding;
+use encoding_rs::UTF_8;
+
+use crate::decode::string_from_utf8;
+use crate::Utf8Output;
+
+/// The result of decoding an [`Output`] with detected encodings.
+///
+/// Produced by [`Utf8Output::from_output_detect_encoding`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectedConversion {
+    /// The decoded output.
+    pub output: Utf8Output,
+    /// The encoding `stdout` was decoded with. Use [`Encoding::name`] to get its name.
+    pub stdout_encoding: &'static Encoding,
+    /// The encoding `stderr` was decoded with. Use [`Encoding::name`] to get its name.
+    pub stderr_encoding: &'static Encoding,
+}
+
+impl Utf8Output {
+    /// Decode an [`Output`], guessing the encoding of each stream with [`chardetng`].
+    ///
+    /// This is a best-effort conversion for commands whose encoding can't be known ahead of
+    /// time. A stream which is valid UTF-8 (including plain ASCII) is always detected as UTF-8
+    /// and isn't copied. Otherwise, a byte order mark takes precedence over the guess, and
+    /// malformed sequences are replaced with U+FFFD REPLACEMENT CHARACTER. Requires the
+    /// `detect-encoding` feature.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::Utf8Output;
+    /// use utf8_command::encoding_rs::UTF_8;
+    /// use utf8_command::encoding_rs::WINDOWS_1252;
+    ///
+    /// let detected = Utf8Output::from_output_detect_encoding(Output {
+    ///     status: ExitStatus::default(),
+    ///     stdout: Vec::from(b"Le caf\xe9 est d\xe9j\xe0 ferm\xe9, tr\xe8s d\xe9sol\xe9."),
+    ///     stderr: Vec::from("déjà"),
+    /// });
+    /// assert_eq!(detected.output.stdout, "Le café est déjà fermé, très désolé.");
+    /// assert_eq!(detected.stdout_encoding, WINDOWS_1252);
+    /// assert_eq!(detected.output.stderr, "déjà");
+    /// assert_eq!(detected.stderr_encoding, UTF_8);
+    /// ```
+    ///
+    /// [`chardetng`]: https://docs.rs/chardetng
+    pub fn from_output_detect_encoding(
+        Output {
+            status,
+            stdout,
+            stderr,
+        }: Output,
+    ) -> DetectedConversion {
+        let (stdout, stdout_encoding) = decode_detected(stdout);
+        let (stderr, stderr_encoding) = decode_detected(stderr);
+        DetectedConversion {
+            output: Utf8Output {
+                status,
+                stdout,
+                stderr,
+            },
+            stdout_encoding,
+            stderr_encoding,
+        }
+    }
+}
+
+/// Guess the encoding of `bytes` and decode them with it.
+fn decode_detected(bytes: Vec<u8>) -> (String, &'static Encoding) {
+    let bytes = match string_from_utf8(bytes) {
+        Ok(decoded) => return (decoded, UTF_8),
+        Err(err) => err.into_bytes(),
+    };
+
+    let mut detector = EncodingDetector::new(Iso2022JpDetection::Deny);
+    detector.feed(&bytes, true);
+    let guess = detector.guess(None, Utf8Detection::Allow);
+    let (decoded, encoding, _) = guess.decode(&bytes);
+    (decoded.into_owned(), encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::SHIFT_JIS;
+    use encoding_rs::UTF_16LE;
+    use encoding_rs::WINDOWS_1252;
+
+    use super::*;
+    use crate::test_util::output;
+
+    fn detect(stdout: &[u8], stderr: &[u8]) -> DetectedConversion {
+        Utf8Output::from_output_detect_encoding(output(stdout, stderr))
+    }
+
+    #[test]
+    fn test_utf8_passes_through() {
+        for text in ["", "puppy", "café 🐶", "\u{feff}puppy", "\x1b$Bpuppy"] {
+            let detected = detect(text.as_bytes(), text.as_bytes());
+            assert_eq!(detected.output.stdout, text);
+            assert_eq!(detected.output.stderr, text);
+            assert_eq!(detected.stdout_encoding, UTF_8);
+            assert_eq!(detected.stderr_encoding, UTF_8);
+        }
+
+        let stdout = Vec::from("puppy 🐶".as_bytes());
+        let ptr = stdout.as_ptr();
+        let (decoded, _) = decode_detected(stdout);
+        assert_eq!(decoded.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn test_windows_1252() {
+        let detected = detect(
+            b"\x93Na\xefve\x94 r\xe9sum\xe9s are \xa35 \x96 caf\xe9 cr\xe8me br\xfbl\xe9e",
+            b"puppy",
+        );
+        assert_eq!(detected.stdout_encoding, WINDOWS_1252);
+        assert_eq!(
+            detected.output.stdout,
+            "“Naïve” résumés are £5 – café crème brûlée"
+        );
+        assert_eq!(detected.stderr_encoding, UTF_8);
+    }
+
+    #[test]
+    fn test_shift_jis() {
+        let detected = detect(
+            b"",
+            b"\x82\xb1\x82\xf1\x82\xc9\x82\xbf\x82\xcd\x81\x41\x90\xa2\x8a\x45",
+        );
+        assert_eq!(detected.stderr_encoding, SHIFT_JIS);
+        assert_eq!(detected.output.stderr, "こんにちは、世界");
+    }
+
+    #[test]
+    fn test_byte_order_mark() {
+        let detected = detect(b"\xff\xfep\x00u\x00p\x00", b"");
+        assert_eq!(detected.stdout_encoding, UTF_16LE);
+        assert_eq!(detected.output.stdout, "pup");
+    }
+}
@@ -0,0 +1,248 @@
+use std::fmt::Display;
+use std::process::ExitStatus;
+
+use crate::ErrorContext;
+use crate::Utf8Output;
+
+/// The maximum length of the excerpt of each stream shown by [`Utf8Output`]'s `Display`
+/// implementation.
+const EXCERPT_BYTES: usize = 64;
+
+/// The indentation of each line of a stream in [`Utf8Output`]'s alternate `Display` format.
+const INDENT: &str = "    ";
+
+/// Displays a summary of the output, suitable for logs.
+///
+/// The plain format (`{}`) is a single line with the exit code, the length of each stream, and
+/// the start of each non-empty stream (currently, the first 64 bytes), quoted and escaped. If a
+/// stream is longer, the number of bytes left out follows its excerpt, like `[936 bytes]`:
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use utf8_command::Utf8Output;
+/// let output = Utf8Output {
+///     status: ExitStatus::default(),
+///     stdout: String::from("puppy\ndoggy\n"),
+///     stderr: String::new(),
+/// };
+/// assert_eq!(
+///     output.to_string(),
+///     "status: 0, stdout: 12 bytes \"puppy\\ndoggy\\n\", stderr: 0 bytes"
+/// );
+/// ```
+///
+/// The alternate format (`{:#}`) shows the exit code and each stream in full, in labeled
+/// sections, with each line of the stream indented by four spaces. Streams which are empty or
+/// only whitespace are left out. Line endings (`\n` or `\r\n`) are normalized, empty lines
+/// aren't indented, and line endings at the end of a stream are dropped, so there's no newline
+/// at the end:
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use utf8_command::Utf8Output;
+/// let output = Utf8Output {
+///     status: ExitStatus::default(),
+///     stdout: String::from("puppy\n\ndoggy\r\n"),
+///     stderr: String::new(),
+/// };
+/// assert_eq!(
+///     format!("{output:#}"),
+///     "status: 0\nstdout:\n    puppy\n\n    doggy"
+/// );
+/// ```
+///
+/// The status is the exit code in decimal on every platform. On Unix, if the command was killed by
+/// a signal, the status is like `killed by SIGKILL` (or `killed by signal 64` for uncommon
+/// signals), followed by ` (core dumped)` if it dumped core.
+impl<S: AsRef<str>> Display for Utf8Output<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stdout = self.stdout.as_ref();
+        let stderr = self.stderr.as_ref();
+        write!(f, "status: ")?;
+        fmt_status(f, self.status)?;
+        if f.alternate() {
+            fmt_section(f, "stdout", stdout)?;
+            fmt_section(f, "stderr", stderr)
+        } else {
+            fmt_excerpt(f, "stdout", stdout)?;
+            fmt_excerpt(f, "stderr", stderr)
+        }
+    }
+}
+
+/// Write the exit code, or how the command was killed.
+fn fmt_status(f: &mut std::fmt::Formatter<'_>, status: ExitStatus) -> std::fmt::Result {
+    if let Some(code) = status.code() {
+        return write!(f, "{code}");
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            match crate::signal::signal_name(signal) {
+                Some(name) => write!(f, "killed by {name}")?,
+                None => write!(f, "killed by signal {signal}")?,
+            }
+            if status.core_dumped() {
+                write!(f, " (core dumped)")?;
+            }
+            return Ok(());
+        }
+    }
+
+    // Stopped or continued processes, which `Command` never waits for.
+    write!(f, "{status}")
+}
+
+/// Write `, {name}: N bytes "..."` for the plain format.
+fn fmt_excerpt(f: &mut std::fmt::Formatter<'_>, name: &str, text: &str) -> std::fmt::Result {
+    write!(f, ", {name}: {} bytes", text.len())?;
+    if !text.is_empty() {
+        write!(
+            f,
+            " {}",
+            ErrorContext::at(text.as_bytes(), 0, EXCERPT_BYTES)
+        )?;
+    }
+    Ok(())
+}
+
+/// Write a labeled section with indented lines for the alternate format, without the line endings
+/// at the end of `text`, unless `text` is only whitespace.
+fn fmt_section(f: &mut std::fmt::Formatter<'_>, name: &str, text: &str) -> std::fmt::Result {
+    if text.trim().is_empty() {
+        return Ok(());
+    }
+    let text = text.trim_end_matches(['\n', '\r']);
+    write!(f, "\n{name}:")?;
+    for line in text.lines() {
+        f.write_str("\n")?;
+        if !line.is_empty() {
+            write!(f, "{INDENT}{line}")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::exit_code;
+    use crate::test_util::utf8_output;
+
+    #[test]
+    fn test_display_empty() {
+        let output = utf8_output("", "");
+        assert_eq!(
+            output.to_string(),
+            "status: 0, stdout: 0 bytes, stderr: 0 bytes"
+        );
+        assert_eq!(format!("{output:#}"), "status: 0");
+    }
+
+    #[test]
+    fn test_display_multiline() {
+        let output = utf8_output("puppy\ndoggy\n", "warning: kitty\r\n\nerror: birdy");
+        assert_eq!(
+            output.to_string(),
+            "status: 0, stdout: 12 bytes \"puppy\\ndoggy\\n\", \
+             stderr: 29 bytes \"warning: kitty\\r\\n\\nerror: birdy\""
+        );
+        assert_eq!(
+            format!("{output:#}"),
+            "status: 0\n\
+             stdout:\n\
+             \x20   puppy\n\
+             \x20   doggy\n\
+             stderr:\n\
+             \x20   warning: kitty\n\
+             \n\
+             \x20   error: birdy"
+        );
+    }
+
+    #[test]
+    fn test_display_trailing_whitespace() {
+        let output = utf8_output("puppy\n\n", "\n");
+        assert_eq!(format!("{output:#}"), "status: 0\nstdout:\n    puppy");
+
+        let output = utf8_output(" \r\n", "\n\ndoggy \r\n\r\n");
+        assert_eq!(format!("{output:#}"), "status: 0\nstderr:\n\n\n    doggy ");
+    }
+
+    #[test]
+    fn test_display_stderr_only() {
+        let output = utf8_output("", "doggy\n");
+        assert_eq!(
+            output.to_string(),
+            "status: 0, stdout: 0 bytes, stderr: 6 bytes \"doggy\\n\""
+        );
+        assert_eq!(format!("{output:#}"), "status: 0\nstderr:\n    doggy");
+    }
+
+    #[test]
+    fn test_display_long() {
+        let stdout = "puppy ".repeat(1000);
+        let output = utf8_output(&stdout, "");
+        assert_eq!(
+            output.to_string(),
+            format!(
+                "status: 0, stdout: 6000 bytes \"{}pupp\" [5936 bytes], stderr: 0 bytes",
+                "puppy ".repeat(10)
+            )
+        );
+        // The alternate format isn't truncated.
+        assert_eq!(
+            format!("{output:#}").len(),
+            "status: 0\nstdout:\n    ".len() + 6000
+        );
+    }
+
+    #[test]
+    fn test_display_excerpt_char_boundary() {
+        let stdout = "🐶".repeat(100);
+        let message = utf8_output(&stdout, "").to_string();
+        assert!(!message.contains('\u{FFFD}'), "{message}");
+        assert!(
+            message.contains(&format!(
+                "stdout: 400 bytes \"{}\" [336 bytes]",
+                "🐶".repeat(16)
+            )),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn test_display_exit_code() {
+        let output = Utf8Output {
+            status: exit_code(2),
+            ..utf8_output("", "doggy\n")
+        };
+        assert_eq!(
+            output.to_string(),
+            "status: 2, stdout: 0 bytes, stderr: 6 bytes \"doggy\\n\""
+        );
+        assert_eq!(format!("{output:#}"), "status: 2\nstderr:\n    doggy");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_display_signal() {
+        use std::os::unix::process::ExitStatusExt;
+        use std::process::ExitStatus;
+
+        let mut output = utf8_output("puppy", "");
+        output.status = ExitStatus::from_raw(9);
+        assert_eq!(
+            output.to_string(),
+            "status: killed by SIGKILL, stdout: 5 bytes \"puppy\", stderr: 0 bytes"
+        );
+        // Core dumped.
+        output.status = ExitStatus::from_raw(11 | 0x80);
+        assert_eq!(
+            format!("{output:#}"),
+            "status: killed by SIGSEGV (core dumped)\nstdout:\n    puppy"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use duct::Expression;
+
+use crate::decode_stdout;
+use crate::CommandError;
+use crate::Error;
+use crate::Utf8Output;
+
+/// Extension methods for running a [`duct::Expression`] and decoding its output as UTF-8.
+///
+/// Like [`Expression::run`], these fail if any command in the expression exits unsuccessfully,
+/// unless the expression is [`unchecked`][Expression::unchecked]. Exit status failures are
+/// reported as [`CommandError::Io`], like `duct` does.
+///
+/// ```
+/// use duct::cmd;
+/// use utf8_command::DuctExpressionExt;
+///
+/// let stdout = cmd!("echo", "puppy").pipe(cmd!("tr", "p", "P")).read_utf8().unwrap();
+/// assert_eq!(stdout, "PuPPy");
+/// ```
+pub trait DuctExpressionExt {
+    /// Run the expression, capturing its `stdout` and decoding it as UTF-8.
+    ///
+    /// Like [`Expression::read`], trailing newlines are removed.
+    ///
+    /// ```
+    /// use duct::cmd;
+    /// use utf8_command::DuctExpressionExt;
+    ///
+    /// let err = cmd!("printf", "puppy \\300").read_utf8().unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+    /// );
+    /// ```
+    fn read_utf8(&self) -> Result<String, CommandError>;
+
+    /// Run the expression, capturing both of its output streams and decoding them as UTF-8.
+    ///
+    /// ```
+    /// use duct::cmd;
+    /// use utf8_command::DuctExpressionExt;
+    ///
+    /// let output = cmd!("sh", "-c", "echo puppy; echo doggy >&2; exit 1")
+    ///     .unchecked()
+    ///     .run_utf8()
+    ///     .unwrap();
+    /// assert_eq!(output.status.code(), Some(1));
+    /// assert_eq!(output.stdout, "puppy\n");
+    /// assert_eq!(output.stderr, "doggy\n");
+    /// ```
+    fn run_utf8(&self) -> Result<Utf8Output, CommandError>;
+}
+
+impl DuctExpressionExt for Expression {
+    fn read_utf8(&self) -> Result<String, CommandError> {
+        let output = self.stdout_capture().run()?;
+        let mut stdout = decode_stdout(output.stdout).map_err(Error::from)?;
+        stdout.truncate(stdout.trim_end_matches(['\n', '\r']).len());
+        Ok(stdout)
+    }
+
+    fn run_utf8(&self) -> Result<Utf8Output, CommandError> {
+        let output = self.stdout_capture().stderr_capture().run()?;
+        Ok(Utf8Output::try_from(output)?)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use duct::cmd;
+
+    use super::*;
+
+    #[test]
+    fn test_pipeline() {
+        let stdout = cmd!("printf", "puppy\\ndoggy\\n")
+            .pipe(cmd!("sort"))
+            .read_utf8()
+            .unwrap();
+        assert_eq!(stdout, "doggy\npuppy");
+    }
+
+    #[test]
+    fn test_run_utf8() {
+        let output = cmd!("printf", "puppy\\ndoggy\\n")
+            .pipe(cmd!("sh", "-c", "sort; echo kitty >&2"))
+            .run_utf8()
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, "doggy\npuppy\n");
+        assert_eq!(output.stderr, "kitty\n");
+    }
+
+    #[test]
+    fn test_invalid() {
+        let err = cmd!("printf", "puppy")
+            .pipe(cmd!("sh", "-c", "cat; printf ' \\300' >&2"))
+            .run_utf8()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Stderr contained invalid utf-8 sequence of 1 bytes from index 1: \" �\""
+        );
+        assert_eq!(err.utf8_error().unwrap().partial_stdout(), Some("puppy"));
+    }
+
+    #[test]
+    fn test_checked() {
+        let err = cmd!("false").read_utf8().unwrap_err();
+        assert!(err.io_error().is_some());
+
+        let stdout = cmd!("sh", "-c", "echo puppy; exit 1")
+            .unchecked()
+            .read_utf8()
+            .unwrap();
+        assert_eq!(stdout, "puppy");
+    }
+}
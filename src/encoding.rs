@@ -0,0 +1,265 @@
+use std::fmt::Display;
+use std::process::Output;
+
+use encoding_rs::DecoderResult;
+use encoding_rs::Encoding;
+
+use crate::context::ErrorContext;
+use crate::Stream;
+use crate::Utf8Output;
+use crate::ERROR_CONTEXT_BYTES;
+
+impl Utf8Output {
+    /// Decode an [`Output`] whose streams are in other encodings, like Shift_JIS or GBK.
+    ///
+    /// Each stream is decoded with the given [`Encoding`]; use [`Encoding::for_label`] to look
+    /// up an encoding by its WHATWG label. A byte order mark is decoded like any other
+    /// character. Malformed sequences fail the conversion; if both streams are malformed, only
+    /// `stdout` is reported. Requires the `encoding` feature.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::Utf8Output;
+    /// use utf8_command::encoding_rs::Encoding;
+    /// use utf8_command::encoding_rs::SHIFT_JIS;
+    ///
+    /// let output = Utf8Output::try_from_encodings(
+    ///     Output {
+    ///         status: ExitStatus::default(),
+    ///         stdout: Vec::from(b"\x82\xb1\x82\xf1\x82\xc9\x82\xbf\x82\xcd"),
+    ///         stderr: Vec::from(b"\xc4\xe3\xba\xc3"),
+    ///     },
+    ///     SHIFT_JIS,
+    ///     Encoding::for_label(b"gbk").unwrap(),
+    /// )
+    /// .unwrap();
+    /// assert_eq!(output.stdout, "こんにちは");
+    /// assert_eq!(output.stderr, "你好");
+    /// ```
+    pub fn try_from_encodings(
+        output: Output,
+        stdout_encoding: &'static Encoding,
+        stderr_encoding: &'static Encoding,
+    ) -> Result<Self, EncodingError> {
+        let decoded = decode(stdout_encoding, &output.stdout)
+            .map_err(|malformed| (Stream::Stdout, stdout_encoding, malformed))
+            .and_then(|stdout| {
+                decode(stderr_encoding, &output.stderr)
+                    .map(|stderr| (stdout, stderr))
+                    .map_err(|malformed| (Stream::Stderr, stderr_encoding, malformed))
+            });
+
+        match decoded {
+            Ok((stdout, stderr)) => Ok(Utf8Output {
+                status: output.status,
+                stdout,
+                stderr,
+            }),
+            Err((stream, encoding, malformed)) => Err(EncodingError {
+                stream,
+                encoding,
+                valid_up_to: malformed.valid_up_to,
+                error_len: malformed.error_len,
+                output: Box::new(output),
+            }),
+        }
+    }
+}
+
+/// The location of a malformed sequence.
+struct Malformed {
+    valid_up_to: usize,
+    error_len: usize,
+}
+
+/// Decode `bytes` with `encoding`, failing at the first malformed sequence.
+fn decode(encoding: &'static Encoding, bytes: &[u8]) -> Result<String, Malformed> {
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut decoded = String::new();
+    let mut read = 0;
+    loop {
+        let additional = decoder
+            .max_utf8_buffer_length_without_replacement(bytes.len() - read)
+            .unwrap_or(bytes.len() - read);
+        decoded.reserve(additional);
+
+        let (result, consumed) =
+            decoder.decode_to_string_without_replacement(&bytes[read..], &mut decoded, true);
+        read += consumed;
+        match result {
+            DecoderResult::InputEmpty => return Ok(decoded),
+            DecoderResult::OutputFull => {}
+            DecoderResult::Malformed(error_len, good_after) => {
+                let error_len = usize::from(error_len);
+                return Err(Malformed {
+                    valid_up_to: read - usize::from(good_after) - error_len,
+                    error_len,
+                });
+            }
+        }
+    }
+}
+
+/// A stream contained a malformed sequence in its [`Encoding`].
+///
+/// Produced by [`Utf8Output::try_from_encodings`]. The error keeps the original [`Output`], so
+/// nothing is lost.
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use std::process::Output;
+/// # use utf8_command::Utf8Output;
+/// # use utf8_command::encoding_rs::SHIFT_JIS;
+/// let err = Utf8Output::try_from_encodings(
+///     Output {
+///         status: ExitStatus::default(),
+///         stdout: Vec::from(b"puppy \xa0"),
+///         stderr: Vec::from(b""),
+///     },
+///     SHIFT_JIS,
+///     SHIFT_JIS,
+/// )
+/// .unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "Stdout contained invalid Shift_JIS sequence of 1 bytes from index 6: \"puppy �\""
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodingError {
+    stream: Stream,
+    encoding: &'static Encoding,
+    valid_up_to: usize,
+    error_len: usize,
+    output: Box<Output>,
+}
+
+impl EncodingError {
+    /// The stream which contained the malformed sequence.
+    pub fn stream(&self) -> Stream {
+        self.stream
+    }
+
+    /// The encoding the stream was decoded with.
+    pub fn encoding(&self) -> &'static Encoding {
+        self.encoding
+    }
+
+    /// The index in the stream up to which it was successfully decoded.
+    pub fn valid_up_to(&self) -> usize {
+        self.valid_up_to
+    }
+
+    /// The length of the malformed sequence, in bytes.
+    pub fn error_len(&self) -> usize {
+        self.error_len
+    }
+
+    /// Get a reference to the original output.
+    pub fn output(&self) -> &Output {
+        &self.output
+    }
+
+    /// Get the original output.
+    pub fn into_output(self) -> Output {
+        *self.output
+    }
+}
+
+impl Display for EncodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bytes = self.stream.select(&self.output.stdout, &self.output.stderr);
+        write!(
+            f,
+            "{} contained invalid {} sequence of {} bytes from index {}: ",
+            self.stream,
+            self.encoding.name(),
+            self.error_len,
+            self.valid_up_to
+        )?;
+        ErrorContext::at(bytes, self.valid_up_to, ERROR_CONTEXT_BYTES)
+            .without_utf8_boundaries()
+            .fmt_with(f, |bytes| {
+                self.encoding.decode_without_bom_handling(bytes).0
+            })
+    }
+}
+
+impl std::error::Error for EncodingError {}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::SHIFT_JIS;
+    use encoding_rs::UTF_8;
+
+    use super::*;
+    use crate::test_util::output;
+
+    /// "こんにちは、世界" in Shift_JIS.
+    const HELLO_WORLD: &[u8] = b"\x82\xb1\x82\xf1\x82\xc9\x82\xbf\x82\xcd\x81\x41\x90\xa2\x8a\x45";
+
+    #[test]
+    fn test_shift_jis() {
+        let converted =
+            Utf8Output::try_from_encodings(output(HELLO_WORLD, b"puppy"), SHIFT_JIS, UTF_8)
+                .unwrap();
+        assert_eq!(converted.stdout, "こんにちは、世界");
+        assert_eq!(converted.stderr, "puppy");
+
+        // Shift_JIS isn't valid UTF-8.
+        let err =
+            Utf8Output::try_from_encodings(output(b"", HELLO_WORLD), SHIFT_JIS, UTF_8).unwrap_err();
+        assert_eq!(err.stream(), Stream::Stderr);
+        assert_eq!(err.encoding(), UTF_8);
+        assert_eq!(err.valid_up_to(), 0);
+    }
+
+    #[test]
+    fn test_shift_jis_malformed() {
+        let mut stdout = HELLO_WORLD.to_vec();
+        // A lead byte followed by a byte which can't follow it.
+        stdout.splice(4..4, *b"\x82\x20");
+        let original = output(&stdout, b"");
+        let err =
+            Utf8Output::try_from_encodings(original.clone(), SHIFT_JIS, SHIFT_JIS).unwrap_err();
+        assert_eq!(err.stream(), Stream::Stdout);
+        assert_eq!(err.valid_up_to(), 4);
+        assert_eq!(err.error_len(), 1);
+        assert_eq!(
+            err.to_string(),
+            "Stdout contained invalid Shift_JIS sequence of 1 bytes from index 4: \
+            \"こん� にちは、世界\""
+        );
+        assert_eq!(err.into_output(), original);
+
+        // An incomplete sequence at the end.
+        let err = Utf8Output::try_from_encodings(output(b"puppy\x82", b""), SHIFT_JIS, SHIFT_JIS)
+            .unwrap_err();
+        assert_eq!(err.valid_up_to(), 5);
+        assert_eq!(err.error_len(), 1);
+    }
+
+    #[test]
+    fn test_malformed_context_is_truncated() {
+        let stdout = [
+            HELLO_WORLD.repeat(100),
+            b"\xa0".to_vec(),
+            HELLO_WORLD.repeat(100),
+        ]
+        .concat();
+        let err =
+            Utf8Output::try_from_encodings(output(&stdout, b""), SHIFT_JIS, SHIFT_JIS).unwrap_err();
+        assert_eq!(err.valid_up_to(), 1600);
+        let message = err.to_string();
+        assert!(
+            message.starts_with(
+                "Stdout contained invalid Shift_JIS sequence of 1 bytes from index 1600: \
+                [1088 bytes] \""
+            ),
+            "{message}"
+        );
+        assert!(message.contains("世界�こんにちは"), "{message}");
+        assert!(message.ends_with("\" [1089 bytes]"), "{message}");
+    }
+}
@@ -0,0 +1,139 @@
+use std::fmt::Write;
+
+/// Decode `bytes` as UTF-8, escaping invalid bytes as `\xNN` so the original bytes can be
+/// recovered with [`unescape_invalid_bytes`].
+///
+/// To keep this reversible, a literal backslash is doubled if it's followed by a backslash, an
+/// `x`, or an escaped byte. Other backslashes (like in Windows paths) are left alone.
+///
+/// ```
+/// use utf8_command::escape_invalid_bytes;
+/// use utf8_command::unescape_invalid_bytes;
+///
+/// let bytes = b"C:\\puppy \xc0 \\x41";
+/// let escaped = escape_invalid_bytes(bytes);
+/// assert_eq!(escaped, "C:\\puppy \\xc0 \\\\x41");
+/// assert_eq!(unescape_invalid_bytes(&escaped), bytes);
+/// ```
+pub fn escape_invalid_bytes(bytes: &[u8]) -> String {
+    hex_escape(bytes).0
+}
+
+/// Like [`escape_invalid_bytes`], but also returns the number of invalid sequences.
+pub(crate) fn hex_escape(bytes: &[u8]) -> (String, usize) {
+    let mut escaped = String::with_capacity(bytes.len());
+    let mut sequences = 0;
+    for chunk in bytes.utf8_chunks() {
+        let invalid = chunk.invalid();
+        let mut chars = chunk.valid().chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                // The next character in the escaped text.
+                let next = match chars.peek() {
+                    Some(&next) => Some(next),
+                    None if !invalid.is_empty() => Some('\\'),
+                    None => None,
+                };
+                if matches!(next, Some('\\' | 'x')) {
+                    escaped.push('\\');
+                }
+            }
+            escaped.push(c);
+        }
+
+        if !invalid.is_empty() {
+            sequences += 1;
+            for byte in invalid {
+                let _ = write!(escaped, "\\x{byte:02x}");
+            }
+        }
+    }
+    (escaped, sequences)
+}
+
+/// Recover the original bytes from text produced by [`escape_invalid_bytes`] (or
+/// [`InvalidBytes::HexEscape`]).
+///
+/// `\\` is unescaped to a backslash and `\xNN` is unescaped to the byte `NN`; everything else is
+/// kept as-is.
+///
+/// [`InvalidBytes::HexEscape`]: crate::InvalidBytes::HexEscape
+pub fn unescape_invalid_bytes(text: &str) -> Vec<u8> {
+    let mut bytes = text.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    while let [first, rest @ ..] = bytes {
+        match (first, rest) {
+            (b'\\', [b'\\', rest @ ..]) => {
+                unescaped.push(b'\\');
+                bytes = rest;
+            }
+            (b'\\', [b'x', high, low, rest @ ..])
+                if high.is_ascii_hexdigit() && low.is_ascii_hexdigit() =>
+            {
+                unescaped.push(hex_value(*high) << 4 | hex_value(*low));
+                bytes = rest;
+            }
+            _ => {
+                unescaped.push(*first);
+                bytes = rest;
+            }
+        }
+    }
+    unescaped
+}
+
+fn hex_value(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        b'A'..=b'F' => digit - b'A' + 10,
+        _ => unreachable!("Not a hex digit: {digit:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        let cases: [(&[u8], &str); 9] = [
+            (b"", ""),
+            (b"puppy", "puppy"),
+            (b"\xc0puppy\xf0\x9f\x90", "\\xc0puppy\\xf0\\x9f\\x90"),
+            (b"C:\\Users\\puppy", "C:\\Users\\puppy"),
+            (b"\\x41", "\\\\x41"),
+            (b"\\\\", "\\\\\\"),
+            (b"\\\xc0", "\\\\\\xc0"),
+            (b"puppy\\", "puppy\\"),
+            (b"\\xff", "\\\\xff"),
+        ];
+        for (bytes, escaped) in cases {
+            assert_eq!(escape_invalid_bytes(bytes), escaped, "{bytes:?}");
+            assert_eq!(unescape_invalid_bytes(escaped), bytes, "{escaped:?}");
+        }
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape_invalid_bytes("\\xC0\\xc0"), b"\xc0\xc0");
+        // Incomplete escapes are kept.
+        assert_eq!(unescape_invalid_bytes("\\x4"), b"\\x4");
+        assert_eq!(unescape_invalid_bytes("\\xgg\\"), b"\\xgg\\");
+    }
+
+    proptest! {
+        #[test]
+        fn test_round_trip(
+            bytes in prop::collection::vec(
+                prop::sample::select(b"ax4F\\ \x80\x9f\xc0\xc3\xe2\xf0\xff".as_slice()),
+                0..32,
+            ),
+        ) {
+            let escaped = escape_invalid_bytes(&bytes);
+            prop_assert_eq!(unescape_invalid_bytes(&escaped), bytes);
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::process::Output;
+
+use crate::Error;
+use crate::Utf8Output;
+
+/// Extension methods for decoding an [`Output`] as UTF-8.
+///
+/// This reads better than [`TryInto`] at the end of a chain of method calls, and doesn't need a
+/// type annotation:
+///
+/// ```
+/// # use std::process::Command;
+/// use utf8_command::prelude::*;
+///
+/// let output = Command::new("echo").arg("puppy").output().unwrap().utf8().unwrap();
+/// assert_eq!(output.stdout, "puppy\n");
+/// ```
+///
+/// The trait is also implemented for `&Output`, which doesn't consume the original:
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use std::process::Output;
+/// use utf8_command::prelude::*;
+///
+/// let output = Output {
+///     status: ExitStatus::default(),
+///     stdout: Vec::from(b"puppy"),
+///     stderr: Vec::from(b"doggy \xc0"),
+/// };
+/// assert_eq!(
+///     (&output).utf8().unwrap_err().to_string(),
+///     "Stderr contained invalid utf-8 sequence of 1 bytes from index 6: \"doggy �\""
+/// );
+/// assert_eq!((&output).utf8_lossy().stderr, "doggy �");
+/// ```
+pub trait OutputExt {
+    /// Decode the output streams as UTF-8.
+    ///
+    /// This is equivalent to [`Utf8Output::try_from`].
+    fn utf8(self) -> Result<Utf8Output, Error>;
+
+    /// Decode the output streams as UTF-8, replacing invalid sequences with U+FFFD REPLACEMENT
+    /// CHARACTER.
+    ///
+    /// This is equivalent to [`Utf8Output::from_utf8_lossy`].
+    fn utf8_lossy(self) -> Utf8Output;
+}
+
+impl OutputExt for Output {
+    fn utf8(self) -> Result<Utf8Output, Error> {
+        Utf8Output::try_from(self)
+    }
+
+    fn utf8_lossy(self) -> Utf8Output {
+        Utf8Output::from_utf8_lossy(self)
+    }
+}
+
+impl OutputExt for &Output {
+    fn utf8(self) -> Result<Utf8Output, Error> {
+        Utf8Output::try_from(self)
+    }
+
+    fn utf8_lossy(self) -> Utf8Output {
+        Utf8Output::from_utf8_lossy_ref(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::output;
+
+    #[test]
+    fn test_owned_and_borrowed_agree() {
+        let valid = output(b"puppy", b"doggy");
+        assert_eq!((&valid).utf8().unwrap(), valid.clone().utf8().unwrap());
+        assert_eq!((&valid).utf8_lossy(), valid.utf8_lossy());
+
+        let invalid = output(b"puppy \xc0", b"doggy");
+        assert_eq!(
+            (&invalid).utf8().unwrap_err().to_string(),
+            invalid.clone().utf8().unwrap_err().to_string()
+        );
+        assert_eq!((&invalid).utf8_lossy(), invalid.clone().utf8_lossy());
+        assert_eq!(invalid.utf8_lossy().stdout, "puppy \u{FFFD}");
+    }
+}
@@ -0,0 +1,311 @@
+use std::process::Child;
+use std::process::Command;
+use std::process::Output;
+
+use crate::capture::kill;
+use crate::capture::spawn_piped;
+use crate::capture::Pipes;
+use crate::command::decode_output;
+use crate::CommandError;
+use crate::CommandInfo;
+use crate::SpawnError;
+use crate::Stream;
+use crate::Utf8Output;
+
+/// A running child process whose output is being captured, from
+/// [`CommandExt::spawn_utf8`][crate::CommandExt::spawn_utf8].
+///
+/// The child's `stdout` and `stderr` are read on background threads as they're written, so the
+/// child never blocks on a full pipe.
+///
+/// If the guard is dropped before the child is waited for with [`Utf8ChildGuard::wait_utf8`] or
+/// [`Utf8ChildGuard::try_wait_utf8`] (for example, because a thread panicked or a task was
+/// cancelled), the child is killed and reaped, so it's never left running in the background.
+///
+/// ```
+/// # use std::process::Command;
+/// use utf8_command::prelude::*;
+///
+/// let child = Command::new("echo").arg("puppy").spawn_utf8().unwrap();
+/// let output = child.wait_utf8().unwrap();
+/// assert_eq!(output.stdout, "puppy\n");
+/// ```
+#[derive(Debug)]
+pub struct Utf8ChildGuard {
+    /// The child, or `None` once it's been waited for.
+    child: Option<Child>,
+    id: u32,
+    pipes: Pipes,
+    output: Output,
+    command: CommandInfo,
+}
+
+impl Utf8ChildGuard {
+    /// Spawn `command` with its `stdout` and `stderr` piped.
+    pub(crate) fn spawn(command: &mut Command) -> Result<Self, SpawnError> {
+        let info = CommandInfo::from(&*command);
+        let mut child = match spawn_piped(command) {
+            Ok(child) => child,
+            Err(err) => return Err(SpawnError::new(err, Some(info))),
+        };
+        Ok(Self {
+            id: child.id(),
+            pipes: Pipes::new(&mut child),
+            child: Some(child),
+            output: Output {
+                status: Default::default(),
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+            },
+            command: info,
+        })
+    }
+
+    /// Get the child's process ID.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Get the command which the child was spawned from.
+    pub fn command(&self) -> &CommandInfo {
+        &self.command
+    }
+
+    /// Kill the child.
+    ///
+    /// This doesn't wait for the child to exit; use [`Utf8ChildGuard::wait_utf8`] to get its
+    /// output afterwards. Killing a child which has already exited does nothing.
+    pub fn kill(&mut self) -> std::io::Result<()> {
+        let Some(child) = &mut self.child else {
+            return Ok(());
+        };
+        match child.kill() {
+            // The child already exited.
+            Err(err) if err.kind() == std::io::ErrorKind::InvalidInput => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Wait for the child to exit, and decode its output streams as UTF-8.
+    ///
+    /// See [`CommandExt::output_utf8`][crate::CommandExt::output_utf8].
+    pub fn wait_utf8(mut self) -> Result<Utf8Output, CommandError> {
+        let result = self.wait_output();
+        decode_output(result, Some(self.command.clone()), Utf8Output::try_from)
+    }
+
+    /// Check if the child has exited without blocking, and decode its output streams as UTF-8
+    /// if it has.
+    ///
+    /// Returns `Ok(None)` while the child is running. Each call reads any output which is
+    /// available, so the child never blocks on a full pipe no matter how rarely this is called.
+    /// Once the child exits, the rest of its output is read and decoded.
+    ///
+    /// After the output is returned, the child has been waited for, and further calls fail
+    /// with an I/O error.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// # use std::time::Duration;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let mut child = Command::new("echo").arg("puppy").spawn_utf8().unwrap();
+    /// let output = loop {
+    ///     match child.try_wait_utf8().unwrap() {
+    ///         Some(output) => break output,
+    ///         None => std::thread::sleep(Duration::from_millis(10)),
+    ///     }
+    /// };
+    /// assert_eq!(output.stdout, "puppy\n");
+    /// ```
+    pub fn try_wait_utf8(&mut self) -> Result<Option<Utf8Output>, CommandError> {
+        self.try_wait_output()
+            .transpose()
+            .map(|result| decode_output(result, Some(self.command.clone()), Utf8Output::try_from))
+            .transpose()
+    }
+
+    /// Read the available output, and the rest of it if the child has exited.
+    fn try_wait_output(&mut self) -> std::io::Result<Option<Output>> {
+        while let Some((stream, bytes)) = self.pipes.try_recv() {
+            self.push(stream, &bytes);
+        }
+        match self.child_for_wait()?.try_wait()? {
+            Some(_) => self.wait_output().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Read the rest of the child's output and wait for it to exit.
+    fn wait_output(&mut self) -> std::io::Result<Output> {
+        self.child_for_wait()?;
+        while let Some((stream, bytes)) = self.pipes.recv()? {
+            self.push(stream, &bytes);
+        }
+        self.output.status = self.child_for_wait()?.wait()?;
+        self.child = None;
+        Ok(std::mem::replace(
+            &mut self.output,
+            Output {
+                status: Default::default(),
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+            },
+        ))
+    }
+
+    fn push(&mut self, stream: Stream, bytes: &[u8]) {
+        stream
+            .select(&mut self.output.stdout, &mut self.output.stderr)
+            .extend_from_slice(bytes);
+    }
+
+    /// Get the child, or fail if it's already been waited for.
+    fn child_for_wait(&mut self) -> std::io::Result<&mut Child> {
+        self.child.as_mut().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "The child has already been waited for",
+            )
+        })
+    }
+}
+
+impl Drop for Utf8ChildGuard {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            let _ = kill(child);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use crate::CommandExt;
+
+    use super::*;
+
+    /// Check if a process is still running, by sending it signal 0.
+    fn is_running(pid: &str) -> bool {
+        Command::new("kill")
+            .args(["-0", pid])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap()
+            .success()
+    }
+
+    #[test]
+    fn test_wait_utf8() {
+        let child = Command::new("sh")
+            .args(["-c", "echo puppy; echo doggy >&2; exit 2"])
+            .spawn_utf8()
+            .unwrap();
+        let output = child.wait_utf8().unwrap();
+        assert_eq!(output.status.code(), Some(2));
+        assert_eq!(output.stdout, "puppy\n");
+        assert_eq!(output.stderr, "doggy\n");
+    }
+
+    #[test]
+    fn test_wait_utf8_invalid() {
+        let err = Command::new("printf")
+            .arg("puppy \\300")
+            .spawn_utf8()
+            .unwrap()
+            .wait_utf8()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`printf 'puppy \\300'`: \
+            Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+        );
+    }
+
+    #[test]
+    fn test_spawn_utf8_missing_binary() {
+        let err = Command::new("utf8-command-this-program-does-not-exist")
+            .spawn_utf8()
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_drop_kills_child() {
+        let dir = std::env::temp_dir().join(format!("utf8-command-guard-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pid_file = dir.join("pid");
+
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(format!("echo $$ > {}; exec sleep 60", pid_file.display()))
+            .spawn_utf8()
+            .unwrap();
+        let start = Instant::now();
+        let pid = loop {
+            match std::fs::read_to_string(&pid_file) {
+                Ok(pid) if pid.ends_with('\n') => break pid.trim().to_owned(),
+                _ => {
+                    assert!(start.elapsed() < Duration::from_secs(30));
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+            }
+        };
+        assert_eq!(pid, child.id().to_string());
+        assert!(is_running(&pid));
+
+        drop(child);
+        // The child has been killed and reaped, so signalling it fails.
+        assert!(!is_running(&pid));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_kill() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let mut child = Command::new("sleep").arg("60").spawn_utf8().unwrap();
+        child.kill().unwrap();
+        child.kill().unwrap();
+        let output = child.wait_utf8().unwrap();
+        assert_eq!(output.status.signal(), Some(9));
+    }
+
+    #[test]
+    fn test_try_wait_utf8() {
+        // More output than fits in a pipe buffer, so the child would block if the pipes weren't
+        // drained while it runs.
+        let mut child = Command::new("sh")
+            .args(["-c", "sleep 0.2; seq 1 100000; echo doggy >&2"])
+            .spawn_utf8()
+            .unwrap();
+        let start = Instant::now();
+        let mut polls = 0;
+        let output = loop {
+            polls += 1;
+            match child.try_wait_utf8().unwrap() {
+                Some(output) => break output,
+                None => {
+                    assert!(start.elapsed() < Duration::from_secs(30));
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+            }
+        };
+        assert!(polls > 1);
+        assert!(output.status.success());
+        assert_eq!(output.stdout.lines().count(), 100000);
+        assert!(output.stdout.ends_with("99999\n100000\n"));
+        assert_eq!(output.stderr, "doggy\n");
+
+        let err = child.try_wait_utf8().unwrap_err();
+        assert_eq!(
+            err.io_error().unwrap().kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+        child.kill().unwrap();
+    }
+}
@@ -0,0 +1,191 @@
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::process::Command;
+use std::sync::Arc;
+
+/// The program, arguments, and working directory of a [`Command`], recorded so that errors can
+/// say which command produced them.
+///
+/// When displayed, the command line is quoted like a POSIX shell would need it to be, and
+/// arguments which aren't valid UTF-8 are decoded lossily:
+///
+/// ```
+/// # use std::process::Command;
+/// use utf8_command::CommandInfo;
+///
+/// let mut command = Command::new("ffprobe");
+/// command.args(["-i", "my file.mkv", "it's"]);
+/// assert_eq!(
+///     CommandInfo::from(&command).to_string(),
+///     r#"ffprobe -i 'my file.mkv' 'it'\''s'"#
+/// );
+/// ```
+///
+/// The working directory isn't displayed, but is available with
+/// [`CommandInfo::current_dir`].
+///
+/// With the `serde` feature, this serializes as a struct of (lossily-decoded) strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandInfo {
+    data: Arc<CommandInfoData>,
+}
+
+/// The data in a [`CommandInfo`].
+///
+/// This is reference-counted, so that errors carrying a [`CommandInfo`] stay small and cloning
+/// them doesn't copy the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CommandInfoData {
+    program: OsString,
+    args: Vec<OsString>,
+    current_dir: Option<OsString>,
+}
+
+impl CommandInfo {
+    /// Construct a record of a command line.
+    pub fn new<I, S>(program: impl AsRef<OsStr>, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        Self {
+            data: Arc::new(CommandInfoData {
+                program: program.as_ref().to_owned(),
+                args: args
+                    .into_iter()
+                    .map(|arg| arg.as_ref().to_owned())
+                    .collect(),
+                current_dir: None,
+            }),
+        }
+    }
+
+    /// Record the working directory the command was run in.
+    pub fn with_current_dir(mut self, dir: impl AsRef<OsStr>) -> Self {
+        Arc::make_mut(&mut self.data).current_dir = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// The program that was run.
+    pub fn program(&self) -> &OsStr {
+        &self.data.program
+    }
+
+    /// The arguments the program was run with.
+    pub fn args(&self) -> &[OsString] {
+        &self.data.args
+    }
+
+    /// The working directory the command was run in, if it was set.
+    ///
+    /// If this is `None`, the command inherited the working directory of its parent process.
+    pub fn current_dir(&self) -> Option<&OsStr> {
+        self.data.current_dir.as_deref()
+    }
+
+    /// The program that was run, decoded lossily.
+    pub fn program_lossy(&self) -> Cow<'_, str> {
+        self.data.program.to_string_lossy()
+    }
+
+    /// The arguments the program was run with, decoded lossily.
+    pub fn args_lossy(&self) -> impl Iterator<Item = Cow<'_, str>> {
+        self.data.args.iter().map(|arg| arg.to_string_lossy())
+    }
+
+    /// The working directory the command was run in, if it was set, decoded lossily.
+    pub fn current_dir_lossy(&self) -> Option<Cow<'_, str>> {
+        self.data.current_dir.as_deref().map(OsStr::to_string_lossy)
+    }
+}
+
+impl From<&Command> for CommandInfo {
+    fn from(command: &Command) -> Self {
+        let info = Self::new(command.get_program(), command.get_args());
+        match command.get_current_dir() {
+            Some(dir) => info.with_current_dir(dir),
+            None => info,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for CommandInfo {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("CommandInfo", 3)?;
+        state.serialize_field("program", &self.program_lossy())?;
+        state.serialize_field("args", &self.args_lossy().collect::<Vec<_>>())?;
+        state.serialize_field("current_dir", &self.current_dir_lossy())?;
+        state.end()
+    }
+}
+
+impl Display for CommandInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_quoted(f, &self.data.program)?;
+        for arg in &self.data.args {
+            write!(f, " ")?;
+            write_quoted(f, arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write `word`, decoded lossily and single-quoted if a shell would otherwise split or expand
+/// it.
+fn write_quoted(f: &mut std::fmt::Formatter<'_>, word: &OsStr) -> std::fmt::Result {
+    let word = word.to_string_lossy();
+    let is_safe = |c: char| c.is_alphanumeric() || "-_./=:,+@%^".contains(c);
+    if !word.is_empty() && word.chars().all(is_safe) {
+        return write!(f, "{word}");
+    }
+    write!(f, "'{}'", word.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_command() {
+        let mut command = Command::new("ls");
+        command.arg("-l").current_dir("/tmp");
+        let info = CommandInfo::from(&command);
+        assert_eq!(info.program(), "ls");
+        assert_eq!(info.args(), ["-l"]);
+        assert_eq!(info.current_dir_lossy().as_deref(), Some("/tmp"));
+        assert_eq!(
+            info,
+            CommandInfo::new("ls", ["-l"]).with_current_dir("/tmp")
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            CommandInfo::new("echo", ["puppy"]).to_string(),
+            "echo puppy"
+        );
+        assert_eq!(
+            CommandInfo::new("sh", ["-c", "echo $HOME", ""]).to_string(),
+            "sh -c 'echo $HOME' ''"
+        );
+        assert_eq!(
+            CommandInfo::new("/usr/bin/env", ["PUPPY=doggy", "it's"]).to_string(),
+            r"/usr/bin/env PUPPY=doggy 'it'\''s'"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_display_non_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let info = CommandInfo::new("cat", [OsStr::from_bytes(b"puppy\xc0.txt")]);
+        assert_eq!(info.to_string(), "cat 'puppy\u{FFFD}.txt'");
+    }
+}
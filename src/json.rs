@@ -0,0 +1,231 @@
+use std::fmt::Display;
+
+use serde::de::DeserializeOwned;
+
+use crate::ErrorContext;
+use crate::Utf8Output;
+use crate::ERROR_CONTEXT_BYTES;
+
+impl<S: AsRef<str>> Utf8Output<S> {
+    /// Deserialize `stdout` as JSON, like the output of `cargo metadata` or
+    /// `docker inspect`.
+    ///
+    /// Whitespace around the JSON value is allowed, but anything else after it is an error.
+    ///
+    /// This doesn't check the command's exit status; use [`Utf8Output::require_success`] or
+    /// [`crate::CommandExt::output_utf8_checked`] first if the output of a failed command
+    /// shouldn't be parsed.
+    ///
+    /// ```
+    /// # use std::collections::BTreeMap;
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let output = Command::new("echo")
+    ///     .arg(r#"{"puppy": 1, "doggy": 2}"#)
+    ///     .output_utf8()
+    ///     .unwrap();
+    /// let counts: BTreeMap<String, u32> = output.stdout_json().unwrap();
+    /// assert_eq!(counts["doggy"], 2);
+    ///
+    /// let output = Command::new("echo").arg("puppy").output_utf8().unwrap();
+    /// assert_eq!(
+    ///     output.stdout_json::<u32>().unwrap_err().to_string(),
+    ///     "Stdout couldn't be parsed as JSON at index 0: \
+    ///      expected value at line 1 column 1: \"puppy\\n\""
+    /// );
+    /// ```
+    pub fn stdout_json<T: DeserializeOwned>(&self) -> Result<T, JsonOutputError> {
+        let stdout = self.stdout.as_ref();
+        serde_json::from_str(stdout).map_err(|error| JsonOutputError {
+            offset: offset(stdout, &error),
+            stdout: stdout.to_owned(),
+            error,
+        })
+    }
+}
+
+/// Get the byte offset in `text` of a [`serde_json::Error`], from its line and column.
+///
+/// The offset is clamped to the end of `text` and moved back to a `char` boundary.
+fn offset(text: &str, error: &serde_json::Error) -> usize {
+    if error.line() == 0 {
+        return 0;
+    }
+
+    let line_start = text
+        .split_inclusive('\n')
+        .take(error.line() - 1)
+        .map(str::len)
+        .sum::<usize>();
+    // Columns are counted in bytes, and point at the last byte which was read.
+    let mut offset = Ord::min(line_start + error.column().saturating_sub(1), text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// An error produced by [`Utf8Output::stdout_json`].
+///
+/// When displayed, this shows the part of `stdout` around the error.
+#[derive(Debug)]
+pub struct JsonOutputError {
+    stdout: String,
+    offset: usize,
+    error: serde_json::Error,
+}
+
+impl JsonOutputError {
+    /// Get the `stdout` which failed to parse.
+    pub fn stdout(&self) -> &str {
+        &self.stdout
+    }
+
+    /// Get the byte offset of the error in `stdout`.
+    ///
+    /// For errors which aren't at a particular position, this is 0.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Get the 1-based line number of the error in `stdout`, or 0 if the error isn't at a
+    /// particular position.
+    pub fn line(&self) -> usize {
+        self.error.line()
+    }
+
+    /// Get the 1-based column of the error in `stdout`, counted in bytes, or 0 if the error isn't
+    /// at a particular position.
+    pub fn column(&self) -> usize {
+        self.error.column()
+    }
+
+    /// Get a reference to the JSON error.
+    pub fn error(&self) -> &serde_json::Error {
+        &self.error
+    }
+
+    /// Get the JSON error.
+    pub fn into_error(self) -> serde_json::Error {
+        self.error
+    }
+}
+
+impl Display for JsonOutputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Stdout couldn't be parsed as JSON at index {}: {}: {}",
+            self.offset,
+            self.error,
+            ErrorContext::at(self.stdout.as_bytes(), self.offset, ERROR_CONTEXT_BYTES)
+        )
+    }
+}
+
+impl std::error::Error for JsonOutputError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde_json::json;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::test_util::utf8_output;
+
+    #[test]
+    fn test_stdout_json() {
+        let output = utf8_output("{\n  \"puppy\": [1, 2],\n  \"doggy\": null\n}\n", "");
+        assert_eq!(
+            output.stdout_json::<Value>().unwrap(),
+            json!({"puppy": [1, 2], "doggy": null})
+        );
+        let map = output
+            .stdout_json::<BTreeMap<String, Option<Vec<u8>>>>()
+            .unwrap();
+        assert_eq!(map["puppy"], Some(vec![1, 2]));
+        assert_eq!(map["doggy"], None);
+    }
+
+    #[test]
+    fn test_stdout_json_trailing_garbage() {
+        let err = utf8_output("[1, 2]\npuppy\n", "")
+            .stdout_json::<Vec<u8>>()
+            .unwrap_err();
+        assert_eq!(err.line(), 2);
+        assert_eq!(err.column(), 1);
+        assert_eq!(err.offset(), 7);
+        assert_eq!(&err.stdout()[err.offset()..], "puppy\n");
+        assert_eq!(
+            err.to_string(),
+            "Stdout couldn't be parsed as JSON at index 7: \
+             trailing characters at line 2 column 1: \"[1, 2]\\npuppy\\n\""
+        );
+    }
+
+    #[test]
+    fn test_stdout_json_not_json() {
+        let err = utf8_output("error: puppy not found\n", "")
+            .stdout_json::<Value>()
+            .unwrap_err();
+        assert_eq!(err.offset(), 0);
+        assert!(err.error().is_syntax());
+        assert_eq!(
+            err.to_string(),
+            "Stdout couldn't be parsed as JSON at index 0: \
+             expected value at line 1 column 1: \"error: puppy not found\\n\""
+        );
+    }
+
+    #[test]
+    fn test_stdout_json_empty() {
+        let err = utf8_output("", "").stdout_json::<Value>().unwrap_err();
+        assert!(err.error().is_eof());
+        assert_eq!(err.offset(), 0);
+    }
+
+    #[test]
+    fn test_stdout_json_wrong_type() {
+        let err = utf8_output("{\"puppy\": \"doggy\"}", "")
+            .stdout_json::<BTreeMap<String, u8>>()
+            .unwrap_err();
+        assert!(err.error().is_data());
+        assert_eq!(&err.stdout()[..=err.offset()], "{\"puppy\": \"doggy\"");
+    }
+
+    #[test]
+    fn test_stdout_json_context() {
+        // The context is a window around the error, with its ends on `char` boundaries.
+        let stdout = format!("[{}puppy{}]", "\"🐶\", ".repeat(300), ", 1".repeat(300));
+        let err = utf8_output(&stdout, "")
+            .stdout_json::<Vec<Value>>()
+            .unwrap_err();
+        assert_eq!(&err.stdout()[err.offset()..][..5], "puppy");
+        let message = err.to_string();
+        assert!(
+            message.starts_with(
+                "Stdout couldn't be parsed as JSON at index 2401: \
+                 expected value at line 1 column 2402: ["
+            ),
+            "{message}"
+        );
+        assert!(message.contains("🐶\\\", puppy, 1, 1"), "{message}");
+        assert!(message.ends_with(" bytes]"), "{message}");
+        assert!(!message.contains('\u{FFFD}'), "{message}");
+    }
+
+    #[test]
+    fn test_offset_char_boundary() {
+        let text = "\"🐶\" x";
+        let err = serde_json::from_str::<Value>(text).unwrap_err();
+        assert!(text.is_char_boundary(offset(text, &err)));
+        assert_eq!(&text[offset(text, &err)..], "x");
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::output_lines::numbered_lines;
+use crate::ErrorContext;
+use crate::Utf8Output;
+use crate::ERROR_CONTEXT_BYTES;
+
+impl<S: AsRef<str>> Utf8Output<S> {
+    /// Parse each line of `stdout` as a `KEY=VALUE` pair, like the output of `env`,
+    /// `git config --list`, or `systemctl show`.
+    ///
+    /// Each line is split on its first `=`, so values may contain `=`, and keys and values may be
+    /// empty. Pairs are returned in order, including pairs with duplicate keys. Lines are split
+    /// like [`str::lines`], and every line, including blank lines, must contain a `=`.
+    ///
+    /// Use [`Utf8Output::stdout_key_values_with`] to change the delimiter or skip blank and
+    /// comment lines.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let output = Command::new("env")
+    ///     .env_clear()
+    ///     .env("PUPPY", "doggy=1")
+    ///     .output_utf8()
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     output.stdout_key_values().unwrap(),
+    ///     [("PUPPY".to_owned(), "doggy=1".to_owned())]
+    /// );
+    /// ```
+    pub fn stdout_key_values(&self) -> Result<Vec<(String, String)>, KeyValueError> {
+        self.stdout_key_values_with(&KeyValueOptions::new())
+    }
+
+    /// Parse each line of `stdout` as a key-value pair, with the given `options`.
+    ///
+    /// See [`Utf8Output::stdout_key_values`].
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    /// use utf8_command::KeyValueOptions;
+    ///
+    /// let output = Command::new("printf")
+    ///     .arg("# Sizes\\npuppy: small\\n\\ndoggy: big\\n")
+    ///     .output_utf8()
+    ///     .unwrap();
+    /// let options = KeyValueOptions::new()
+    ///     .delimiter(':')
+    ///     .skip_blank_lines(true)
+    ///     .comment_prefix("#");
+    /// assert_eq!(
+    ///     output.stdout_key_values_with(&options).unwrap(),
+    ///     [
+    ///         ("puppy".to_owned(), " small".to_owned()),
+    ///         ("doggy".to_owned(), " big".to_owned()),
+    ///     ]
+    /// );
+    /// ```
+    pub fn stdout_key_values_with(
+        &self,
+        options: &KeyValueOptions,
+    ) -> Result<Vec<(String, String)>, KeyValueError> {
+        let mut pairs = Vec::new();
+        for (line_number, line) in numbered_lines(self.stdout.as_ref()) {
+            if options.skip(line) {
+                continue;
+            }
+            match line.split_once(options.delimiter) {
+                Some((key, value)) => pairs.push((key.to_owned(), value.to_owned())),
+                None => {
+                    return Err(KeyValueError {
+                        line_number,
+                        line: line.to_owned(),
+                        delimiter: options.delimiter,
+                    })
+                }
+            }
+        }
+        Ok(pairs)
+    }
+
+    /// Parse each line of `stdout` as a `KEY=VALUE` pair, into a map.
+    ///
+    /// If a key is repeated, the last value wins. See [`Utf8Output::stdout_key_values`].
+    pub fn stdout_key_value_map(&self) -> Result<HashMap<String, String>, KeyValueError> {
+        self.stdout_key_values().map(HashMap::from_iter)
+    }
+}
+
+/// Options for [`Utf8Output::stdout_key_values_with`].
+///
+/// The defaults split lines on `=`, and don't skip any lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyValueOptions {
+    delimiter: char,
+    skip_blank_lines: bool,
+    comment_prefix: Option<String>,
+}
+
+impl Default for KeyValueOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: '=',
+            skip_blank_lines: false,
+            comment_prefix: None,
+        }
+    }
+}
+
+impl KeyValueOptions {
+    /// Construct the default options, which split lines on `=`, and don't skip any lines.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the delimiter between keys and values. The default is `'='`.
+    ///
+    /// Lines are split on the first `delimiter`.
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Set whether to skip lines which are empty or only contain whitespace. The default is
+    /// `false`, so blank lines are malformed.
+    pub fn skip_blank_lines(mut self, skip: bool) -> Self {
+        self.skip_blank_lines = skip;
+        self
+    }
+
+    /// Skip lines starting with `prefix`, like `"#"`, after any leading whitespace.
+    pub fn comment_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.comment_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Whether to skip `line` rather than parsing it.
+    fn skip(&self, line: &str) -> bool {
+        let trimmed = line.trim_start();
+        (self.skip_blank_lines && trimmed.is_empty())
+            || self
+                .comment_prefix
+                .as_deref()
+                .is_some_and(|prefix| trimmed.starts_with(prefix))
+    }
+}
+
+/// An error produced by [`Utf8Output::stdout_key_values`] and related methods, when a line
+/// doesn't contain the delimiter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyValueError {
+    line_number: usize,
+    line: String,
+    delimiter: char,
+}
+
+impl KeyValueError {
+    /// Get the 1-based line number of the malformed line.
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    /// Get the malformed line, without its line ending.
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+}
+
+impl Display for KeyValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Stdout line {} doesn't contain {:?}: {}",
+            self.line_number,
+            self.delimiter,
+            ErrorContext::at(self.line.as_bytes(), 0, ERROR_CONTEXT_BYTES)
+        )
+    }
+}
+
+impl std::error::Error for KeyValueError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::utf8_output;
+
+    fn pairs<const N: usize>(pairs: [(&str, &str); N]) -> Vec<(String, String)> {
+        pairs
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), value.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn test_key_values() {
+        let output = utf8_output("PUPPY=1\nDOGGY=a=b\r\nKITTY=\n=birdy\n", "");
+        assert_eq!(
+            output.stdout_key_values().unwrap(),
+            pairs([
+                ("PUPPY", "1"),
+                ("DOGGY", "a=b"),
+                ("KITTY", ""),
+                ("", "birdy"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_key_values_empty() {
+        assert!(utf8_output("", "").stdout_key_values().unwrap().is_empty());
+        assert!(utf8_output("", "")
+            .stdout_key_value_map()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_key_values_duplicates() {
+        let output = utf8_output("core.editor=vi\nuser.name=puppy\ncore.editor=nano\n", "");
+        assert_eq!(
+            output.stdout_key_values().unwrap(),
+            pairs([
+                ("core.editor", "vi"),
+                ("user.name", "puppy"),
+                ("core.editor", "nano"),
+            ])
+        );
+        let map = output.stdout_key_value_map().unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["core.editor"], "nano");
+        assert_eq!(map["user.name"], "puppy");
+    }
+
+    #[test]
+    fn test_key_values_malformed() {
+        let err = utf8_output("PUPPY=1\nDOGGY\nKITTY=3\n", "")
+            .stdout_key_values()
+            .unwrap_err();
+        assert_eq!(err.line_number(), 2);
+        assert_eq!(err.line(), "DOGGY");
+        assert_eq!(
+            err.to_string(),
+            "Stdout line 2 doesn't contain '=': \"DOGGY\""
+        );
+
+        // Blank lines aren't skipped by default.
+        let err = utf8_output("PUPPY=1\n\n", "")
+            .stdout_key_value_map()
+            .unwrap_err();
+        assert_eq!(err.line_number(), 2);
+        assert_eq!(err.line(), "");
+    }
+
+    #[test]
+    fn test_key_values_options() {
+        let output = utf8_output(
+            "# Comment\n  # Indented\nPUPPY: 1\n \nDOGGY:2:3\n#KITTY: 4\n",
+            "",
+        );
+        let options = KeyValueOptions::new()
+            .delimiter(':')
+            .skip_blank_lines(true)
+            .comment_prefix("#");
+        assert_eq!(
+            output.stdout_key_values_with(&options).unwrap(),
+            pairs([("PUPPY", " 1"), ("DOGGY", "2:3")])
+        );
+    }
+
+    #[test]
+    fn test_key_values_comments_only() {
+        let output = utf8_output("; Comment\nPUPPY=1\n\n", "");
+        let options = KeyValueOptions::new().comment_prefix(";");
+        let err = output.stdout_key_values_with(&options).unwrap_err();
+        assert_eq!(err.line_number(), 3);
+
+        let options = KeyValueOptions::new().skip_blank_lines(true);
+        let err = output.stdout_key_values_with(&options).unwrap_err();
+        assert_eq!(err.line_number(), 1);
+        assert_eq!(
+            err.to_string(),
+            "Stdout line 1 doesn't contain '=': \"; Comment\""
+        );
+    }
+}
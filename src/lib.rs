@@ -1,18 +1,16 @@
 //! Provides the [`Utf8Output`] type, a UTF-8-decoded variant of [`std::process::Output`] (as
 //! produced by [`std::process::Command::output`]).
 //!
-//! Construct [`Utf8Output`] from [`Output`] via the [`TryInto`] or [`TryFrom`] traits:
+//! Construct [`Utf8Output`] from [`Output`] with [`OutputExt::utf8`] (or via the [`TryInto`] or
+//! [`TryFrom`] traits):
 //!
 //! ```
 //! # use std::process::Command;
 //! # use std::process::ExitStatus;
 //! # use utf8_command::Utf8Output;
-//! let output: Utf8Output = Command::new("echo")
-//!     .arg("puppy")
-//!     .output()
-//!     .unwrap()
-//!     .try_into()
-//!     .unwrap();
+//! use utf8_command::prelude::*;
+//!
+//! let output = Command::new("echo").arg("puppy").output().unwrap().utf8().unwrap();
 //! assert_eq!(
 //!     output,
 //!     Utf8Output {
@@ -29,17 +27,16 @@
 //! ```
 //! # use std::process::ExitStatus;
 //! # use std::process::Output;
-//! # use utf8_command::Utf8Output;
-//! # use utf8_command::Error;
+//! use utf8_command::prelude::*;
+//!
 //! let invalid = Output {
 //!     status: ExitStatus::default(),
 //!     stdout: Vec::from(b"puppy doggy \xc3\x28"), // Invalid 2-byte sequence.
 //!     stderr: Vec::from(b""),
 //! };
 //!
-//! let err: Result<Utf8Output, Error> = invalid.try_into();
 //! assert_eq!(
-//!     err.unwrap_err().to_string(),
+//!     invalid.utf8().unwrap_err().to_string(),
 //!     "Stdout contained invalid utf-8 sequence of 1 bytes from index 12: \"puppy doggy �(\""
 //! );
 //! ```
@@ -51,27 +48,316 @@ use std::fmt::Display;
 use std::process::ExitStatus;
 use std::process::Output;
 use std::string::FromUtf8Error;
+use std::sync::Arc;
+
+#[cfg(feature = "ansi")]
+mod ansi;
 
 mod context;
-use context::FromUtf8ErrorContext;
+use context::ErrorContext;
+use context::FromUtf8ErrorMessage;
+use context::Truncation;
+
+mod limit;
+
+#[cfg(feature = "encoding")]
+mod encoding;
+#[cfg(feature = "encoding")]
+pub use encoding::EncodingError;
+#[cfg(feature = "encoding")]
+pub use encoding_rs;
+
+#[cfg(feature = "detect-encoding")]
+mod detect;
+#[cfg(feature = "detect-encoding")]
+pub use detect::DetectedConversion;
+
+#[cfg(feature = "code-page")]
+mod code_page;
+#[cfg(feature = "code-page")]
+pub use code_page::CodePageConversion;
+#[cfg(feature = "code-page")]
+pub use code_page::StreamDecoder;
+
+mod lossy;
+use lossy::decode_lossy;
+use lossy::decode_lossy_counted;
+use lossy::decode_lossy_ranges;
+pub use lossy::LossyConversion;
+pub use lossy::LossyReport;
+
+mod content;
+pub use content::ContentError;
+pub use content::ContentErrorKind;
+pub use content::Stream;
+
+mod normalize;
+use normalize::chomp;
+pub use normalize::LineEndings;
+#[cfg(feature = "unicode-normalization")]
+pub use normalize::UnicodeNormalization;
+
+mod cesu8;
+
+mod escape;
+pub use escape::escape_invalid_bytes;
+pub use escape::unescape_invalid_bytes;
+
+mod redact;
+
+mod utf16;
+pub use utf16::Utf16Endianness;
+
+mod stream_errors;
+pub use stream_errors::StreamErrors;
+
+mod options;
+pub use options::BomHandling;
+pub use options::ControlCharacters;
+pub use options::ConversionError;
+pub use options::ConversionOptions;
+pub use options::ConversionReport;
+pub use options::InvalidBytes;
+pub use options::Strictness;
+
+mod info;
+pub use info::CommandInfo;
+
+mod ext;
+pub use ext::OutputExt;
+
+mod accumulator;
+pub use accumulator::Utf8Accumulator;
+
+#[cfg(feature = "futures-io")]
+mod async_reader;
+mod reader;
+#[cfg(feature = "futures-io")]
+pub use async_reader::AsyncUtf8Reader;
+pub use reader::Utf8Reader;
+
+mod lines;
+pub use lines::LineError;
+pub use lines::Utf8Lines;
+mod debug;
+pub use debug::DEBUG_STREAM_BYTES;
+mod display;
+mod output_lines;
+mod parse;
+pub use parse::ParseOutputError;
+mod key_values;
+pub use key_values::KeyValueError;
+pub use key_values::KeyValueOptions;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "json")]
+pub use json::JsonOutputError;
+#[cfg(feature = "csv")]
+mod table;
+#[cfg(feature = "csv")]
+pub use table::CsvOptions;
+#[cfg(feature = "csv")]
+pub use table::CsvOutputError;
+mod paths;
+
+mod capture;
+
+mod locale;
+
+mod command;
+pub use command::CheckedError;
+pub use command::CommandError;
+pub use command::CommandExt;
+pub use command::RunError;
+pub use command::SpawnError;
+
+mod signal;
+pub use signal::ntstatus_name;
+
+mod checked;
+pub use checked::CheckedUtf8Output;
+
+mod status;
+pub use status::FailedCommandError;
+pub use status::NonZeroExit;
+
+mod quiet;
+pub use quiet::QuietError;
+pub use quiet::StderrNotEmpty;
+
+mod timeout;
+pub use timeout::TimeoutError;
+
+mod stall;
+pub use stall::StalledError;
+
+mod overflow;
+pub use overflow::OutputLimit;
+pub use overflow::OverflowError;
+
+mod combined;
+pub use combined::CombinedDecodeError;
+pub use combined::CombinedError;
+pub use combined::CombinedUtf8Output;
+
+#[cfg(feature = "pty")]
+mod pty;
+#[cfg(feature = "pty")]
+pub use pty::PtyOptions;
+#[cfg(feature = "pty")]
+pub use pty::PtyUtf8Output;
+
+#[cfg(feature = "tempfile")]
+mod spool;
+#[cfg(feature = "tempfile")]
+pub use spool::SpooledUtf8Output;
+
+mod guard;
+pub use guard::Utf8ChildGuard;
+
+mod pipeline;
+pub use pipeline::pipeline;
+pub use pipeline::pipeline_with_stderr;
+pub use pipeline::PipelineError;
+pub use pipeline::PipelineStderr;
+
+mod run_all;
+pub use run_all::run_all_utf8;
+pub use run_all::try_run_all_utf8;
+pub use run_all::RunAllError;
+
+mod shell;
+pub use shell::sh;
+pub use shell::sh_checked;
+pub use shell::Shell;
+
+mod recorded;
+pub use recorded::Utf8OutputWithCommand;
+
+#[cfg(feature = "tokio")]
+mod tokio_ext;
+#[cfg(feature = "tokio")]
+pub use tokio_ext::TokioChildExt;
+#[cfg(feature = "tokio")]
+pub use tokio_ext::TokioCommandExt;
+#[cfg(feature = "tokio")]
+mod tokio_lines;
+#[cfg(feature = "tokio")]
+pub use tokio_lines::TokioUtf8Lines;
+
+#[cfg(feature = "async-process")]
+mod async_process_ext;
+#[cfg(feature = "async-process")]
+pub use async_process_ext::AsyncCommandExt;
+
+#[cfg(feature = "duct")]
+mod duct_ext;
+#[cfg(feature = "duct")]
+pub use duct_ext::DuctExpressionExt;
+
+#[cfg(feature = "xshell")]
+mod xshell_ext;
+#[cfg(feature = "xshell")]
+pub use xshell_ext::XshellCmdExt;
+#[cfg(feature = "xshell")]
+pub use xshell_ext::XshellError;
+
+#[cfg(feature = "assert-cmd")]
+pub mod predicates;
+
+#[cfg(feature = "snapbox")]
+mod snapbox_ext;
+
+mod wrapper;
+pub use wrapper::Command;
+pub use wrapper::OutputError;
+
+/// Re-exports of the crate's extension traits, for glob-importing.
+///
+/// ```
+/// use utf8_command::prelude::*;
+/// ```
+pub mod prelude {
+    pub use crate::CommandExt;
+    pub use crate::OutputExt;
+}
+
+mod output_ref;
+pub use output_ref::Utf8OutputRef;
+
+mod output_cow;
+pub use output_cow::Utf8OutputCow;
+
+mod mixed;
+pub use mixed::MixedOutput;
+pub use mixed::RawStdoutOutput;
+
+mod decode;
+pub use decode::decode_stderr;
+pub use decode::decode_stderr_ref;
+pub use decode::decode_stdout;
+pub use decode::decode_stdout_ref;
+use decode::string_from_utf8;
+pub use decode::TryFromOutput;
+
+mod policy;
+pub use policy::DecodePolicy;
+pub use policy::Lossy;
+pub use policy::StreamDecodeError;
+
+mod batch;
+pub use batch::convert_all;
+pub use batch::convert_all_parallel;
+pub use batch::try_convert_all;
+pub use batch::try_convert_all_parallel;
+pub use batch::BatchError;
+pub use policy::Strict;
+
+#[cfg(test)]
+mod test_util;
 
 const ERROR_CONTEXT_BYTES: usize = 1024;
 
+/// The number of bytes kept on each side of a decoding error when errors are truncated with
+/// [`ConversionOptions::truncate_errors`].
+const ERROR_RETAINED_BYTES: usize = 4096;
+
+/// Check that both streams of an [`Output`] are valid UTF-8, without allocating any [`String`]s.
+///
+/// This returns the same errors as converting the [`Output`] into a [`Utf8Output`]. The bytes of
+/// a stream are only copied if it's invalid, to construct the error.
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use std::process::Output;
+/// let output = Output {
+///     status: ExitStatus::default(),
+///     stdout: Vec::from(b"puppy"),
+///     stderr: Vec::from(b"doggy \xc0"),
+/// };
+///
+/// assert_eq!(
+///     utf8_command::validate(&output).unwrap_err().to_string(),
+///     "Stderr contained invalid utf-8 sequence of 1 bytes from index 6: \"doggy �\""
+/// );
+/// ```
+pub fn validate(output: &Output) -> Result<(), Error> {
+    Utf8OutputRef::try_from(output).map(|_| ())
+}
+
 /// A UTF-8-decoded variant of [`std::process::Output`] (as
 /// produced by [`std::process::Command::output`]).
 ///
-/// Construct [`Utf8Output`] from [`Output`] via the [`TryInto`] or [`TryFrom`] traits:
+/// Construct [`Utf8Output`] from [`Output`] with [`OutputExt::utf8`] (or via the [`TryInto`] or
+/// [`TryFrom`] traits):
 ///
 /// ```
 /// # use std::process::Command;
 /// # use std::process::ExitStatus;
 /// # use utf8_command::Utf8Output;
-/// let output: Utf8Output = Command::new("echo")
-///     .arg("puppy")
-///     .output()
-///     .unwrap()
-///     .try_into()
-///     .unwrap();
+/// use utf8_command::prelude::*;
+///
+/// let output = Command::new("echo").arg("puppy").output().unwrap().utf8().unwrap();
 /// assert_eq!(
 ///     output,
 ///     Utf8Output {
@@ -146,60 +432,237 @@ const ERROR_CONTEXT_BYTES: usize = 1024;
 ///     puppy puppy puppy puppy puppy puppy puppy pu\" [598 bytes]"
 /// );
 /// ```
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub struct Utf8Output {
+///
+/// The decoded streams are stored as [`String`]s by default, but any type which can be
+/// constructed from a [`String`] (like [`Box<str>`] or [`Arc<str>`][std::sync::Arc]) can be used
+/// instead, with [`Utf8Output::decode`] or [`Utf8Output::into_storage`]:
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use std::process::Output;
+/// # use std::sync::Arc;
+/// # use utf8_command::Utf8Output;
+/// let output: Utf8Output<Arc<str>> = Utf8Output::decode(Output {
+///     status: ExitStatus::default(),
+///     stdout: Vec::from(b"puppy"),
+///     stderr: Vec::from(b""),
+/// })
+/// .unwrap();
+/// assert_eq!(&*output.stdout, "puppy");
+/// ```
+///
+/// With the `serde` feature, this serializes as a struct with `status`, `stdout`, and `stderr`
+/// fields. The status is serialized as the exit code, or `null` if the process didn't exit
+/// normally (for example, if it was killed by a signal).
+#[derive(Clone, PartialEq, Eq)]
+pub struct Utf8Output<S = String> {
     /// The [`std::process::Command`]'s exit status.
     pub status: ExitStatus,
     /// The contents of the [`std::process::Command`]'s [`stdout` stream][stdout], decoded as
     /// UTF-8.
     ///
     /// [stdout]: https://linux.die.net/man/3/stdout
-    pub stdout: String,
+    pub stdout: S,
     /// The contents of the [`std::process::Command`]'s [`stderr` stream][stdout], decoded as
     /// UTF-8.
     ///
     /// [stdout]: https://linux.die.net/man/3/stdout
-    pub stderr: String,
+    pub stderr: S,
 }
 
-impl TryFrom<Output> for Utf8Output {
-    type Error = Error;
+impl Utf8Output {
+    /// Decode an [`Output`], replacing invalid UTF-8 sequences with U+FFFD REPLACEMENT
+    /// CHARACTER.
+    ///
+    /// Unlike the [`TryFrom`] implementations, this never fails. Streams which are already valid
+    /// UTF-8 are not copied.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::Utf8Output;
+    /// let output = Utf8Output::from_utf8_lossy(Output {
+    ///     status: ExitStatus::default(),
+    ///     stdout: Vec::from(b"puppy doggy \xc3\x28"), // Invalid 2-byte sequence.
+    ///     stderr: Vec::from(b"kitty"),
+    /// });
+    /// assert_eq!(output.stdout, "puppy doggy �(");
+    /// assert_eq!(output.stderr, "kitty");
+    /// ```
+    pub fn from_utf8_lossy(
+        Output {
+            status,
+            stdout,
+            stderr,
+        }: Output,
+    ) -> Self {
+        Utf8Output {
+            status,
+            stdout: decode_lossy(stdout),
+            stderr: decode_lossy(stderr),
+        }
+    }
 
-    fn try_from(
+    /// Like [`Utf8Output::from_utf8_lossy`], but also reports how many invalid sequences were
+    /// replaced in each stream.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::Utf8Output;
+    /// # use utf8_command::LossyReport;
+    /// let (output, report) = Utf8Output::from_utf8_lossy_with_report(Output {
+    ///     status: ExitStatus::default(),
+    ///     stdout: Vec::from(b"\xc0puppy\xc0\xc0"),
+    ///     stderr: Vec::from(b"doggy"),
+    /// });
+    /// assert_eq!(output.stdout, "�puppy��");
+    /// assert_eq!(
+    ///     report,
+    ///     LossyReport {
+    ///         stdout_replacements: 3,
+    ///         stderr_replacements: 0,
+    ///     }
+    /// );
+    /// ```
+    pub fn from_utf8_lossy_with_report(
         Output {
             status,
             stdout,
             stderr,
         }: Output,
-    ) -> Result<Self, Self::Error> {
-        let stdout =
-            String::from_utf8(stdout).map_err(|err| Error::Stdout(StdoutError { inner: err }))?;
-        let stderr =
-            String::from_utf8(stderr).map_err(|err| Error::Stderr(StderrError { inner: err }))?;
+    ) -> (Self, LossyReport) {
+        let (stdout, stdout_replacements) = decode_lossy_counted(stdout);
+        let (stderr, stderr_replacements) = decode_lossy_counted(stderr);
+        (
+            Utf8Output {
+                status,
+                stdout,
+                stderr,
+            },
+            LossyReport {
+                stdout_replacements,
+                stderr_replacements,
+            },
+        )
+    }
 
-        Ok(Utf8Output {
+    /// Like [`Utf8Output::from_utf8_lossy`], but also reports the byte ranges of each stream
+    /// which were replaced.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::Utf8Output;
+    /// let conversion = Utf8Output::from_utf8_lossy_with_ranges(Output {
+    ///     status: ExitStatus::default(),
+    ///     stdout: Vec::from(b"puppy \xf0\x9f\x90 doggy \xc0"),
+    ///     stderr: Vec::from(b""),
+    /// });
+    /// assert_eq!(conversion.output.stdout, "puppy � doggy �");
+    /// assert_eq!(conversion.stdout_invalid, vec![6..9, 16..17]);
+    /// assert!(conversion.stderr_invalid.is_empty());
+    /// ```
+    pub fn from_utf8_lossy_with_ranges(
+        Output {
             status,
             stdout,
             stderr,
-        })
+        }: Output,
+    ) -> LossyConversion {
+        let (stdout, stdout_invalid) = decode_lossy_ranges(stdout);
+        let (stderr, stderr_invalid) = decode_lossy_ranges(stderr);
+        LossyConversion {
+            output: Utf8Output {
+                status,
+                stdout,
+                stderr,
+            },
+            stdout_invalid,
+            stderr_invalid,
+        }
     }
-}
 
-impl TryFrom<&Output> for Utf8Output {
-    type Error = Error;
+    /// Remove exactly one trailing `\n` or `\r\n` from each stream.
+    ///
+    /// This is like shell command substitution (`$(...)`), except that only one line ending is
+    /// removed. Unlike [`str::trim_end`], other trailing whitespace is kept.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// # use utf8_command::Utf8Output;
+    /// let output = Utf8Output::try_from(
+    ///     Command::new("echo").arg("puppy").output().unwrap()
+    /// )
+    /// .unwrap()
+    /// .chomped();
+    /// assert_eq!(output.stdout, "puppy");
+    /// ```
+    pub fn chomped(mut self) -> Self {
+        chomp(&mut self.stdout);
+        chomp(&mut self.stderr);
+        self
+    }
 
-    fn try_from(
+    /// Decode an exit status, `stdout`, and `stderr` without constructing an [`Output`].
+    ///
+    /// This behaves exactly like converting an [`Output`] with [`TryFrom`].
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use utf8_command::Utf8Output;
+    /// let output = Utf8Output::try_from_parts(
+    ///     ExitStatus::default(),
+    ///     Vec::from(b"puppy"),
+    ///     Vec::from(b"doggy \xc0"),
+    /// );
+    /// assert_eq!(
+    ///     output.unwrap_err().to_string(),
+    ///     "Stderr contained invalid utf-8 sequence of 1 bytes from index 6: \"doggy �\""
+    /// );
+    /// ```
+    pub fn try_from_parts(
+        status: ExitStatus,
+        stdout: Vec<u8>,
+        stderr: Vec<u8>,
+    ) -> Result<Self, Error> {
+        Self::try_from((status, stdout, stderr))
+    }
+
+    /// Decode an [`Output`] with a custom [`DecodePolicy`] for each stream.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::Utf8Output;
+    /// # use utf8_command::Strict;
+    /// # use utf8_command::Lossy;
+    /// let output = Utf8Output::try_from_with_policy(
+    ///     Output {
+    ///         status: ExitStatus::default(),
+    ///         stdout: Vec::from(b"puppy"),
+    ///         stderr: Vec::from(b"doggy \xc0"),
+    ///     },
+    ///     &Strict,
+    ///     &Lossy,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(output.stderr, "doggy �");
+    /// ```
+    pub fn try_from_with_policy(
         Output {
             status,
             stdout,
             stderr,
-        }: &Output,
-    ) -> Result<Self, Self::Error> {
-        let stdout = String::from_utf8(stdout.to_vec())
-            .map_err(|err| Error::Stdout(StdoutError { inner: err }))?;
-        let stderr = String::from_utf8(stderr.to_vec())
-            .map_err(|err| Error::Stderr(StderrError { inner: err }))?;
-        let status = *status;
+        }: Output,
+        stdout_policy: &dyn DecodePolicy,
+        stderr_policy: &dyn DecodePolicy,
+    ) -> Result<Self, Error> {
+        let (stdout, stderr) = Error::from_owned_results(
+            status,
+            stdout_policy.decode(stdout).map_err(StdoutError::from),
+            stderr_policy.decode(stderr).map_err(StderrError::from),
+        )?;
 
         Ok(Utf8Output {
             status,
@@ -207,128 +670,1002 @@ impl TryFrom<&Output> for Utf8Output {
             stderr,
         })
     }
-}
 
-/// An error produced when converting [`Output`] to [`Utf8Output`], wrapping a [`FromUtf8Error`].
-///
-/// ```
-/// use std::process::ExitStatus;
-/// use std::process::Output;
-/// use utf8_command::Utf8Output;
-/// use utf8_command::Error;
-///
-/// let invalid = Output {
-///     status: ExitStatus::default(),
-///     stdout: Vec::from(b""),
-///     stderr: Vec::from(b"\xe2\x28\xa1"), // Invalid 3-byte sequence.
-/// };
-///
-/// let result: Result<Utf8Output, Error> = invalid.try_into();
-/// assert_eq!(
-///     result.unwrap_err().to_string(),
-///     "Stderr contained invalid utf-8 sequence of 1 bytes from index 0: \"�(�\""
-/// );
-/// ```
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Error {
-    /// The [`Output`]'s stdout field contained invalid UTF-8.
-    Stdout(StdoutError),
-    /// The [`Output`]'s stderr field contained invalid UTF-8.
-    Stderr(StderrError),
+    /// Decode a borrowed [`Output`], replacing invalid UTF-8 sequences with U+FFFD REPLACEMENT
+    /// CHARACTER.
+    ///
+    /// See [`Utf8Output::from_utf8_lossy`].
+    pub fn from_utf8_lossy_ref(
+        Output {
+            status,
+            stdout,
+            stderr,
+        }: &Output,
+    ) -> Self {
+        Utf8Output {
+            status: *status,
+            stdout: String::from_utf8_lossy(stdout).into_owned(),
+            stderr: String::from_utf8_lossy(stderr).into_owned(),
+        }
+    }
 }
 
-impl Error {
-    /// Get a reference to the inner [`FromUtf8Error`].
-    pub fn inner(&self) -> &FromUtf8Error {
-        match self {
-            Error::Stdout(err) => err.inner(),
-            Error::Stderr(err) => err.inner(),
+impl Utf8Output {
+    /// Convert the decoded streams into another storage type, like [`Box<str>`] or
+    /// [`Arc<str>`][std::sync::Arc].
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// # use std::sync::Arc;
+    /// use utf8_command::prelude::*;
+    /// use utf8_command::Utf8Output;
+    ///
+    /// let output: Utf8Output<Arc<str>> = Command::new("echo")
+    ///     .arg("puppy")
+    ///     .output_utf8()
+    ///     .unwrap()
+    ///     .into_storage();
+    /// assert_eq!(&*output.stdout, "puppy\n");
+    /// ```
+    pub fn into_storage<S: From<String>>(self) -> Utf8Output<S> {
+        Utf8Output {
+            status: self.status,
+            stdout: self.stdout.into(),
+            stderr: self.stderr.into(),
         }
     }
 }
 
-impl From<StdoutError> for Error {
-    fn from(value: StdoutError) -> Self {
-        Self::Stdout(value)
+impl<S: From<String>> Utf8Output<S> {
+    /// Decode an [`Output`], storing the decoded streams as `S`.
+    ///
+    /// This is like converting with [`TryFrom`], which always stores the streams as [`String`]s
+    /// (so that the storage type never needs to be written out).
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// # use utf8_command::Utf8Output;
+    /// let output = Utf8Output::<Box<str>>::decode(
+    ///     Command::new("echo").arg("puppy").output().unwrap()
+    /// )
+    /// .unwrap();
+    /// assert_eq!(&*output.stdout, "puppy\n");
+    /// ```
+    pub fn decode(output: Output) -> Result<Self, Error> {
+        Utf8Output::<String>::try_from(output).map(Utf8Output::into_storage)
     }
 }
 
-impl From<StderrError> for Error {
-    fn from(value: StderrError) -> Self {
-        Self::Stderr(value)
+impl<S: Into<String>> Utf8Output<S> {
+    /// Convert back into an [`Output`], encoding the streams as bytes.
+    ///
+    /// For [`String`] storage, this doesn't copy the streams.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::Utf8Output;
+    /// let original = Output {
+    ///     status: ExitStatus::default(),
+    ///     stdout: Vec::from("puppy 🐶"),
+    ///     stderr: Vec::from(b""),
+    /// };
+    /// let output: Utf8Output = original.clone().try_into().unwrap();
+    /// assert_eq!(output.into_output(), original);
+    /// ```
+    pub fn into_output(self) -> Output {
+        Output {
+            status: self.status,
+            stdout: self.stdout.into().into_bytes(),
+            stderr: self.stderr.into().into_bytes(),
+        }
     }
 }
 
-impl From<Error> for FromUtf8Error {
-    fn from(value: Error) -> Self {
-        match value {
-            Error::Stdout(err) => err.inner,
-            Error::Stderr(err) => err.inner,
+impl<S> Utf8Output<S> {
+    /// Get the decoded `stdout` stream, discarding the rest of the output.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let stdout = Command::new("echo").arg("puppy").output_utf8().unwrap().into_stdout();
+    /// assert_eq!(stdout, "puppy\n");
+    /// ```
+    #[must_use]
+    pub fn into_stdout(self) -> S {
+        self.stdout
+    }
+
+    /// Get the decoded `stderr` stream, discarding the rest of the output.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let stderr = Command::new("sh")
+    ///     .args(["-c", "echo puppy >&2"])
+    ///     .output_utf8()
+    ///     .unwrap()
+    ///     .into_stderr();
+    /// assert_eq!(stderr, "puppy\n");
+    /// ```
+    #[must_use]
+    pub fn into_stderr(self) -> S {
+        self.stderr
+    }
+
+    /// Split the output into its exit status, `stdout`, and `stderr`.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let (status, stdout, stderr) = Command::new("sh")
+    ///     .args(["-c", "echo puppy; echo doggy >&2"])
+    ///     .output_utf8()
+    ///     .unwrap()
+    ///     .into_parts();
+    /// assert!(status.success());
+    /// assert_eq!(stdout, "puppy\n");
+    /// assert_eq!(stderr, "doggy\n");
+    /// ```
+    #[must_use]
+    pub fn into_parts(self) -> (ExitStatus, S, S) {
+        (self.status, self.stdout, self.stderr)
+    }
+
+    /// Transform the decoded `stdout` stream, keeping the rest of the output.
+    ///
+    /// The stream is moved into `f`, so it can be modified in place.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let output = Command::new("sh")
+    ///     .args(["-c", "echo 'Puppy v1.0'; echo doggy"])
+    ///     .output_utf8()
+    ///     .unwrap()
+    ///     // Remove the banner line.
+    ///     .map_stdout(|mut stdout| {
+    ///         if let Some(end) = stdout.find('\n') {
+    ///             stdout.drain(..=end);
+    ///         }
+    ///         stdout
+    ///     });
+    /// assert_eq!(output.stdout, "doggy\n");
+    /// assert!(output.status.success());
+    /// ```
+    #[must_use]
+    pub fn map_stdout(self, f: impl FnOnce(S) -> S) -> Self {
+        Self {
+            stdout: f(self.stdout),
+            ..self
         }
     }
-}
 
-impl Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &self {
-            Error::Stdout(err) => write!(f, "{}", err),
-            Error::Stderr(err) => write!(f, "{}", err),
+    /// Transform the decoded `stderr` stream, keeping the rest of the output.
+    ///
+    /// See [`Utf8Output::map_stdout`].
+    #[must_use]
+    pub fn map_stderr(self, f: impl FnOnce(S) -> S) -> Self {
+        Self {
+            stderr: f(self.stderr),
+            ..self
         }
     }
-}
 
-impl std::error::Error for Error {}
+    /// Transform the decoded `stdout` stream with a fallible function, keeping the rest of the
+    /// output.
+    ///
+    /// If `f` fails, its error is returned and the output is discarded.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// fn strip_banner(stdout: String) -> Result<String, String> {
+    ///     match stdout.strip_prefix("Puppy v1.0\n") {
+    ///         Some(rest) => Ok(rest.to_owned()),
+    ///         None => Err(format!("Unexpected banner: {stdout:?}")),
+    ///     }
+    /// }
+    ///
+    /// let output = Command::new("echo")
+    ///     .arg("Doggy v2.0")
+    ///     .output_utf8()
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     output.try_map_stdout(strip_banner).unwrap_err(),
+    ///     "Unexpected banner: \"Doggy v2.0\\n\""
+    /// );
+    /// ```
+    pub fn try_map_stdout<E>(self, f: impl FnOnce(S) -> Result<S, E>) -> Result<Self, E> {
+        Ok(Self {
+            stdout: f(self.stdout)?,
+            ..self
+        })
+    }
 
-/// The [`Output`]'s `stdout` field contained invalid UTF-8. Wraps a [`FromUtf8Error`].
-///
-/// ```
-/// use utf8_command::StdoutError;
-///
-/// let invalid_utf8 = Vec::from(b"\x80"); // Invalid single byte.
-/// let inner_err = String::from_utf8(invalid_utf8).unwrap_err();
-/// let err = StdoutError::from(inner_err);
-/// assert_eq!(
-///     err.to_string(),
-///     "Stdout contained invalid utf-8 sequence of 1 bytes from index 0: \"�\""
-/// );
-/// ```
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct StdoutError {
-    inner: FromUtf8Error,
+    /// Transform the decoded `stderr` stream with a fallible function, keeping the rest of the
+    /// output.
+    ///
+    /// See [`Utf8Output::try_map_stdout`].
+    pub fn try_map_stderr<E>(self, f: impl FnOnce(S) -> Result<S, E>) -> Result<Self, E> {
+        Ok(Self {
+            stderr: f(self.stderr)?,
+            ..self
+        })
+    }
 }
 
-impl StdoutError {
-    /// Get a reference to the inner [`FromUtf8Error`].
-    pub fn inner(&self) -> &FromUtf8Error {
-        &self.inner
+impl<S: AsRef<str>> Utf8Output<S> {
+    /// Get the decoded `stdout` stream.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let output = Command::new("echo").arg("puppy").output_utf8().unwrap();
+    /// assert_eq!(output.stdout(), "puppy\n");
+    /// ```
+    #[must_use]
+    pub fn stdout(&self) -> &str {
+        self.stdout.as_ref()
     }
-}
 
-impl From<StdoutError> for FromUtf8Error {
-    fn from(value: StdoutError) -> Self {
-        value.inner
+    /// Get the decoded `stderr` stream.
+    ///
+    /// See [`Utf8Output::stdout`].
+    #[must_use]
+    pub fn stderr(&self) -> &str {
+        self.stderr.as_ref()
     }
-}
 
-impl From<FromUtf8Error> for StdoutError {
-    fn from(inner: FromUtf8Error) -> Self {
-        Self { inner }
+    /// Get the decoded `stdout` stream without trailing ASCII whitespace.
+    ///
+    /// This removes all trailing spaces, tabs, and line endings (both `\n` and `\r\n`), but
+    /// keeps leading whitespace and non-ASCII whitespace. If the stream is only whitespace, the
+    /// result is empty.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let output = Command::new("printf")
+    ///     .arg("  puppy\\r\\n\\n")
+    ///     .output_utf8()
+    ///     .unwrap();
+    /// assert_eq!(output.stdout_trimmed(), "  puppy");
+    /// ```
+    #[must_use]
+    pub fn stdout_trimmed(&self) -> &str {
+        trim_ascii_end(self.stdout.as_ref())
     }
-}
 
-impl Display for StdoutError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Stdout contained {}: {}",
-            self.inner,
-            FromUtf8ErrorContext::new(&self.inner, ERROR_CONTEXT_BYTES)
-        )
+    /// Get the decoded `stderr` stream without trailing ASCII whitespace.
+    ///
+    /// See [`Utf8Output::stdout_trimmed`].
+    #[must_use]
+    pub fn stderr_trimmed(&self) -> &str {
+        trim_ascii_end(self.stderr.as_ref())
     }
 }
 
-impl std::error::Error for StdoutError {}
-
+impl Utf8Output {
+    /// Get the decoded `stdout` stream without trailing ASCII whitespace, discarding the rest of
+    /// the output.
+    ///
+    /// The stream is truncated in place, so it isn't copied. See [`Utf8Output::stdout_trimmed`].
+    #[must_use]
+    pub fn into_stdout_trimmed(self) -> String {
+        let mut stdout = self.stdout;
+        stdout.truncate(trim_ascii_end(&stdout).len());
+        stdout
+    }
+
+    /// Get the decoded `stderr` stream without trailing ASCII whitespace, discarding the rest of
+    /// the output.
+    ///
+    /// The stream is truncated in place, so it isn't copied. See [`Utf8Output::stdout_trimmed`].
+    #[must_use]
+    pub fn into_stderr_trimmed(self) -> String {
+        let mut stderr = self.stderr;
+        stderr.truncate(trim_ascii_end(&stderr).len());
+        stderr
+    }
+}
+
+/// Remove trailing ASCII whitespace from `text`.
+fn trim_ascii_end(text: &str) -> &str {
+    text.trim_end_matches(|c: char| c.is_ascii_whitespace())
+}
+
+impl<S: Into<String>> From<Utf8Output<S>> for Output {
+    fn from(value: Utf8Output<S>) -> Self {
+        value.into_output()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<S: serde::Serialize> serde::Serialize for Utf8Output<S> {
+    fn serialize<T: serde::Serializer>(&self, serializer: T) -> Result<T::Ok, T::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Utf8Output", 3)?;
+        state.serialize_field("status", &self.status.code())?;
+        state.serialize_field("stdout", &self.stdout)?;
+        state.serialize_field("stderr", &self.stderr)?;
+        state.end()
+    }
+}
+
+impl TryFrom<Output> for Utf8Output {
+    type Error = Error;
+
+    fn try_from(
+        Output {
+            status,
+            stdout,
+            stderr,
+        }: Output,
+    ) -> Result<Self, Self::Error> {
+        Self::try_from((status, stdout, stderr))
+    }
+}
+
+/// Decode an exit status, `stdout`, and `stderr` without constructing an [`Output`].
+///
+/// Errors are the same as when converting an [`Output`]; see also [`Utf8Output::try_from_parts`].
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use utf8_command::Utf8Output;
+/// let output: Utf8Output = (
+///     ExitStatus::default(),
+///     Vec::from(b"puppy"),
+///     Vec::from(b"doggy"),
+/// )
+///     .try_into()
+///     .unwrap();
+/// assert_eq!(output.stdout, "puppy");
+/// assert_eq!(output.stderr, "doggy");
+/// ```
+impl TryFrom<(ExitStatus, Vec<u8>, Vec<u8>)> for Utf8Output {
+    type Error = Error;
+
+    fn try_from(
+        (status, stdout, stderr): (ExitStatus, Vec<u8>, Vec<u8>),
+    ) -> Result<Self, Self::Error> {
+        let (stdout, stderr) =
+            Error::from_owned_results(status, decode_stdout(stdout), decode_stderr(stderr))?;
+
+        Ok(Utf8Output {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+}
+
+impl TryFrom<&Output> for Utf8Output {
+    type Error = Error;
+
+    fn try_from(output: &Output) -> Result<Self, Self::Error> {
+        // Validate both streams before copying them, so that we don't copy a stream just to throw
+        // it away if the other stream is invalid.
+        let Utf8OutputRef {
+            status,
+            stdout,
+            stderr,
+        } = Utf8OutputRef::try_from(output)?;
+
+        Ok(Utf8Output {
+            status,
+            stdout: stdout.to_owned(),
+            stderr: stderr.to_owned(),
+        })
+    }
+}
+
+/// Decode an [`Output`] by taking its `stdout` and `stderr` buffers instead of copying them,
+/// leaving empty buffers behind.
+///
+/// If decoding fails, any stream which was taken but decoded successfully is put back into the
+/// [`Output`]. The bytes of the stream which failed to decode can be recovered from the error with
+/// [`FromUtf8Error::into_bytes`].
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use std::process::Output;
+/// # use std::string::FromUtf8Error;
+/// # use utf8_command::Utf8Output;
+/// # use utf8_command::Error;
+/// let mut output = Output {
+///     status: ExitStatus::default(),
+///     stdout: Vec::from(b"puppy"),
+///     stderr: Vec::from(b"doggy \xc0"),
+/// };
+///
+/// let err: Result<Utf8Output, Error> = (&mut output).try_into();
+/// // The valid stream is restored...
+/// assert_eq!(output.stdout, b"puppy");
+/// // ...and the invalid stream is recoverable from the error.
+/// let err = FromUtf8Error::from(err.unwrap_err());
+/// assert_eq!(err.into_bytes(), b"doggy \xc0");
+/// ```
+impl TryFrom<&mut Output> for Utf8Output {
+    type Error = Error;
+
+    fn try_from(output: &mut Output) -> Result<Self, Self::Error> {
+        let stdout = string_from_utf8(std::mem::take(&mut output.stdout))
+            .map_err(|err| Error::Stdout(StdoutError::from(err)).with_status(output.status))?;
+        let stderr = match string_from_utf8(std::mem::take(&mut output.stderr)) {
+            Ok(stderr) => stderr,
+            Err(err) => {
+                // Put back stdout, which decoded successfully.
+                output.stdout = stdout.into_bytes();
+                return Err(Error::Stderr(StderrError::from(err)).with_status(output.status));
+            }
+        };
+
+        Ok(Utf8Output {
+            status: output.status,
+            stdout,
+            stderr,
+        })
+    }
+}
+
+/// An error produced when converting [`Output`] to [`Utf8Output`], wrapping a [`FromUtf8Error`].
+///
+/// If both streams contain invalid UTF-8, the error for stdout is reported. Use
+/// [`Utf8Output::try_from_reporting_both`] to report errors from both streams.
+///
+/// ```
+/// use std::process::ExitStatus;
+/// use std::process::Output;
+/// use utf8_command::Utf8Output;
+/// use utf8_command::Error;
+///
+/// let invalid = Output {
+///     status: ExitStatus::default(),
+///     stdout: Vec::from(b""),
+///     stderr: Vec::from(b"\xe2\x28\xa1"), // Invalid 3-byte sequence.
+/// };
+///
+/// let result: Result<Utf8Output, Error> = invalid.try_into();
+/// assert_eq!(
+///     result.unwrap_err().to_string(),
+///     "Stderr contained invalid utf-8 sequence of 1 bytes from index 0: \"�(�\""
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The [`Output`]'s stdout field contained invalid UTF-8.
+    Stdout(StdoutError),
+    /// The [`Output`]'s stderr field contained invalid UTF-8.
+    Stderr(StderrError),
+}
+
+impl Error {
+    /// Get a reference to the inner [`FromUtf8Error`].
+    pub fn inner(&self) -> &FromUtf8Error {
+        match self {
+            Error::Stdout(err) => err.inner(),
+            Error::Stderr(err) => err.inner(),
+        }
+    }
+
+    /// Get the error for stdout, if stdout contained invalid UTF-8.
+    pub fn stdout_error(&self) -> Option<&StdoutError> {
+        match self {
+            Error::Stdout(err) => Some(err),
+            Error::Stderr(_) => None,
+        }
+    }
+
+    /// Get the error for stderr, if stderr contained invalid UTF-8.
+    pub fn stderr_error(&self) -> Option<&StderrError> {
+        match self {
+            Error::Stderr(err) => Some(err),
+            Error::Stdout(_) => None,
+        }
+    }
+
+    /// Get the decoded stdout, if stdout decoded successfully but stderr didn't.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::Utf8Output;
+    /// # use utf8_command::Error;
+    /// let invalid = Output {
+    ///     status: ExitStatus::default(),
+    ///     stdout: Vec::from(b"puppy"),
+    ///     stderr: Vec::from(b"doggy \xc0"),
+    /// };
+    ///
+    /// let err = Utf8Output::try_from(invalid).unwrap_err();
+    /// assert_eq!(err.partial_stdout(), Some("puppy"));
+    /// assert_eq!(err.partial_stderr(), None);
+    /// ```
+    ///
+    /// This is only available for errors produced by converting an owned [`Output`]; when
+    /// converting a borrowed [`Output`], the decoded stream isn't copied into the error.
+    pub fn partial_stdout(&self) -> Option<&str> {
+        match self {
+            Error::Stderr(err) => err.data.output.as_ref().and_then(ErrorOutput::decoded),
+            Error::Stdout(_) => None,
+        }
+    }
+
+    /// Get the decoded stderr, if stderr decoded successfully but stdout didn't.
+    ///
+    /// See [`Error::partial_stdout`].
+    pub fn partial_stderr(&self) -> Option<&str> {
+        match self {
+            Error::Stdout(err) => err.data.output.as_ref().and_then(ErrorOutput::decoded),
+            Error::Stderr(_) => None,
+        }
+    }
+
+    /// Get the [`Output`]'s exit status, if known.
+    ///
+    /// This is known for errors produced by converting an [`Output`], but not for errors
+    /// constructed directly from a [`FromUtf8Error`].
+    pub fn status(&self) -> Option<ExitStatus> {
+        match self {
+            Error::Stdout(err) => err.data.output.as_ref().map(|output| output.status),
+            Error::Stderr(err) => err.data.output.as_ref().map(|output| output.status),
+        }
+    }
+
+    /// Get the command which produced the [`Output`], if known.
+    ///
+    /// This is only known for errors produced by running a command, like with
+    /// [`CommandExt::output_utf8`], or if it's been attached with [`Error::with_command`].
+    pub fn command(&self) -> Option<&CommandInfo> {
+        match self {
+            Error::Stdout(err) => err.data.command.as_ref(),
+            Error::Stderr(err) => err.data.command.as_ref(),
+        }
+    }
+
+    /// Attach the command which produced the [`Output`], so that it's included in the error
+    /// message.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::CommandInfo;
+    /// # use utf8_command::Utf8Output;
+    /// let err = Utf8Output::try_from(Output {
+    ///     status: ExitStatus::default(),
+    ///     stdout: Vec::from(b"puppy \xc0"),
+    ///     stderr: Vec::from(b""),
+    /// })
+    /// .unwrap_err()
+    /// .with_command(CommandInfo::new("ffprobe", ["-i", "file.mkv"]));
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "`ffprobe -i file.mkv`: \
+    ///     Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+    /// );
+    /// ```
+    pub fn with_command(self, command: CommandInfo) -> Self {
+        match self {
+            Error::Stdout(err) => Error::Stdout(err.with_command(command)),
+            Error::Stderr(err) => Error::Stderr(err.with_command(command)),
+        }
+    }
+
+    /// Check if the bytes stored in this error were truncated.
+    ///
+    /// See [`ConversionOptions::truncate_errors`].
+    pub fn is_truncated(&self) -> bool {
+        self.stdout_error().is_some_and(StdoutError::is_truncated)
+            || self.stderr_error().is_some_and(StderrError::is_truncated)
+    }
+
+    /// The total number of bytes discarded from the streams stored in this error.
+    ///
+    /// See [`ConversionOptions::truncate_errors`].
+    pub fn discarded_bytes(&self) -> usize {
+        self.stdout_error().map_or(0, StdoutError::discarded_bytes)
+            + self.stderr_error().map_or(0, StderrError::discarded_bytes)
+    }
+
+    /// Reconstruct the [`Output`] this error was produced from.
+    ///
+    /// When converting an owned [`Output`], the error keeps the exit status and both streams, so
+    /// the original [`Output`] is reconstructed exactly:
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::Utf8Output;
+    /// # use utf8_command::Error;
+    /// let invalid = Output {
+    ///     status: ExitStatus::default(),
+    ///     stdout: Vec::from(b"puppy"),
+    ///     stderr: Vec::from(b"doggy \xc0"),
+    /// };
+    ///
+    /// let result: Result<Utf8Output, Error> = invalid.clone().try_into();
+    /// assert_eq!(result.unwrap_err().into_output(), invalid);
+    /// ```
+    ///
+    /// When converting a borrowed [`Output`], the stream which decoded successfully isn't copied
+    /// into the error (the caller still has it), so it will be empty here. Similarly, errors
+    /// constructed directly from a [`FromUtf8Error`] have a default exit status and an empty
+    /// second stream. If the error was truncated, the stream which failed to decode will only
+    /// contain the retained bytes.
+    pub fn into_output(self) -> Output {
+        match self {
+            Error::Stdout(err) => {
+                let data = err.into_data();
+                let (status, stderr) = ErrorOutput::into_parts(data.output);
+                Output {
+                    status,
+                    stdout: data.inner.into_bytes(),
+                    stderr,
+                }
+            }
+            Error::Stderr(err) => {
+                let data = err.into_data();
+                let (status, stdout) = ErrorOutput::into_parts(data.output);
+                Output {
+                    status,
+                    stdout,
+                    stderr: data.inner.into_bytes(),
+                }
+            }
+        }
+    }
+
+    /// Combine the results of decoding each stream of an owned [`Output`].
+    ///
+    /// Errors keep the exit status and the other stream, so that the [`Output`] can be
+    /// reconstructed with [`Error::into_output`].
+    fn from_owned_results(
+        status: ExitStatus,
+        stdout: Result<String, StdoutError>,
+        stderr: Result<String, StderrError>,
+    ) -> Result<(String, String), Self> {
+        match (stdout, stderr) {
+            (Ok(stdout), Ok(stderr)) => Ok((stdout, stderr)),
+            (stdout, stderr) => Err(Self::from_owned_streams(
+                status,
+                stdout.map(StreamContents::Decoded),
+                stderr.map(StreamContents::Decoded),
+            )),
+        }
+    }
+
+    /// Construct an error from the streams of an owned [`Output`], at least one of which failed
+    /// to decode.
+    ///
+    /// If both streams failed to decode, the error for stdout is reported, and stderr's bytes are
+    /// kept so that the [`Output`] can still be reconstructed.
+    fn from_owned_streams(
+        status: ExitStatus,
+        stdout: Result<StreamContents, StdoutError>,
+        stderr: Result<StreamContents, StderrError>,
+    ) -> Self {
+        match (stdout, stderr) {
+            (Ok(_), Ok(_)) => unreachable!("At least one stream should have failed to decode"),
+            (Err(stdout), Ok(stderr)) => Error::Stdout(stdout.with_output(status, Some(stderr))),
+            (Ok(stdout), Err(stderr)) => Error::Stderr(stderr.with_output(status, Some(stdout))),
+            (Err(stdout), Err(stderr)) => Error::Stdout(stdout.with_output(
+                status,
+                Some(StreamContents::Raw(stderr.into_data().inner.into_bytes())),
+            )),
+        }
+    }
+
+    /// Combine the results of decoding each stream.
+    ///
+    /// If both streams failed to decode, the error for stdout is reported, like when converting an
+    /// [`Output`].
+    ///
+    /// ```
+    /// # use utf8_command::Error;
+    /// let result = Error::from_streams(
+    ///     utf8_command::decode_stdout(Vec::from(b"puppy \xc0")),
+    ///     utf8_command::decode_stderr(Vec::from(b"doggy \xc0")),
+    /// );
+    /// assert!(matches!(result, Err(Error::Stdout(_))));
+    /// ```
+    pub fn from_streams<T, U>(
+        stdout: Result<T, StdoutError>,
+        stderr: Result<U, StderrError>,
+    ) -> Result<(T, U), Self> {
+        match (stdout, stderr) {
+            (Ok(stdout), Ok(stderr)) => Ok((stdout, stderr)),
+            (Err(stdout), _) => Err(Error::Stdout(stdout)),
+            (Ok(_), Err(stderr)) => Err(Error::Stderr(stderr)),
+        }
+    }
+
+    /// Truncate the bytes stored in the error to a bounded region around each decoding error.
+    fn truncate(self) -> Self {
+        match self {
+            Error::Stdout(err) => Error::Stdout(err.truncate()),
+            Error::Stderr(err) => Error::Stderr(err.truncate()),
+        }
+    }
+
+    /// Attach the [`Output`]'s exit status to the error.
+    fn with_status(self, status: ExitStatus) -> Self {
+        match self {
+            Error::Stdout(err) => Error::Stdout(err.with_output(status, None)),
+            Error::Stderr(err) => Error::Stderr(err.with_output(status, None)),
+        }
+    }
+}
+
+/// The contents of a stream which didn't fail to decode, kept in an error so that the original
+/// [`Output`] can be reconstructed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum StreamContents {
+    /// The stream was decoded successfully.
+    Decoded(String),
+    /// The stream wasn't decoded, either because it would have been decoded lossily or because
+    /// it's meant to be kept as raw bytes.
+    Raw(Vec<u8>),
+}
+
+impl StreamContents {
+    fn len(&self) -> usize {
+        match self {
+            StreamContents::Decoded(string) => string.len(),
+            StreamContents::Raw(bytes) => bytes.len(),
+        }
+    }
+
+    fn decoded(&self) -> Option<&str> {
+        match self {
+            StreamContents::Decoded(string) => Some(string),
+            StreamContents::Raw(_) => None,
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        match self {
+            StreamContents::Decoded(string) => string.into_bytes(),
+            StreamContents::Raw(bytes) => bytes,
+        }
+    }
+}
+
+/// The rest of the [`Output`] an error came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ErrorOutput {
+    /// The [`Output`]'s exit status.
+    status: ExitStatus,
+    /// The contents of the other stream, if it was owned by the conversion and didn't fail to
+    /// decode.
+    other: Option<StreamContents>,
+}
+
+impl ErrorOutput {
+    fn decoded(&self) -> Option<&str> {
+        self.other.as_ref().and_then(StreamContents::decoded)
+    }
+
+    /// Get the exit status and the other stream's bytes, defaulting if they weren't kept.
+    fn into_parts(output: Option<Self>) -> (ExitStatus, Vec<u8>) {
+        match output {
+            Some(output) => (
+                output.status,
+                output
+                    .other
+                    .map(StreamContents::into_bytes)
+                    .unwrap_or_default(),
+            ),
+            None => Default::default(),
+        }
+    }
+
+    /// Drop the other stream if it's larger than a truncated error would keep.
+    fn truncate(self) -> Self {
+        Self {
+            status: self.status,
+            other: self
+                .other
+                .filter(|other| other.len() <= 2 * ERROR_RETAINED_BYTES),
+        }
+    }
+}
+
+/// The data in a [`StdoutError`] or [`StderrError`].
+///
+/// This is reference-counted, so that the errors are a single pointer and cloning them doesn't
+/// copy the (potentially very large) stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct StreamErrorData {
+    inner: FromUtf8Error,
+    /// The rest of the [`Output`] the error came from, if known.
+    output: Option<ErrorOutput>,
+    /// The number of bytes discarded from `inner`, if it was truncated.
+    discarded: Option<Truncation>,
+    /// The command which produced the stream, if known.
+    command: Option<CommandInfo>,
+}
+
+impl StreamErrorData {
+    fn valid_up_to(&self) -> usize {
+        self.inner.utf8_error().valid_up_to() + self.discarded.map_or(0, |d| d.before)
+    }
+
+    fn discarded_bytes(&self) -> usize {
+        self.discarded.map_or(0, |d| d.before + d.after)
+    }
+
+    fn truncate(self) -> Self {
+        let (inner, discarded) = context::truncate(self.inner, ERROR_RETAINED_BYTES);
+        Self {
+            inner,
+            output: self.output.map(ErrorOutput::truncate),
+            discarded: discarded.or(self.discarded),
+            command: self.command,
+        }
+    }
+
+    /// Display the error, labeled with the stream it came from.
+    fn fmt_labeled(&self, stream: &str, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(command) = &self.command {
+            write!(f, "`{command}`: ")?;
+        }
+        write!(
+            f,
+            "{stream} contained {}: {}",
+            FromUtf8ErrorMessage::new(&self.inner, self.discarded),
+            ErrorContext::new(&self.inner, ERROR_CONTEXT_BYTES).discarded(self.discarded)
+        )
+    }
+}
+
+impl From<StdoutError> for Error {
+    fn from(value: StdoutError) -> Self {
+        Self::Stdout(value)
+    }
+}
+
+impl From<StderrError> for Error {
+    fn from(value: StderrError) -> Self {
+        Self::Stderr(value)
+    }
+}
+
+impl From<Error> for FromUtf8Error {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::Stdout(err) => err.into_data().inner,
+            Error::Stderr(err) => err.into_data().inner,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            Error::Stdout(err) => write!(f, "{}", err),
+            Error::Stderr(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// The [`Output`]'s `stdout` field contained invalid UTF-8. Wraps a [`FromUtf8Error`].
+///
+/// ```
+/// use utf8_command::StdoutError;
+///
+/// let invalid_utf8 = Vec::from(b"\x80"); // Invalid single byte.
+/// let inner_err = String::from_utf8(invalid_utf8).unwrap_err();
+/// let err = StdoutError::from(inner_err);
+/// assert_eq!(
+///     err.to_string(),
+///     "Stdout contained invalid utf-8 sequence of 1 bytes from index 0: \"�\""
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StdoutError {
+    data: Arc<StreamErrorData>,
+}
+
+impl StdoutError {
+    /// Get a reference to the inner [`FromUtf8Error`].
+    ///
+    /// If the error was truncated, this only contains the retained bytes, and its indexes are
+    /// relative to them. Use [`StdoutError::valid_up_to`] for the index in the original stream.
+    pub fn inner(&self) -> &FromUtf8Error {
+        &self.data.inner
+    }
+
+    /// Get the bytes of the stream which failed to decode.
+    ///
+    /// This is the same as `inner().as_bytes()`.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.data.inner.as_bytes()
+    }
+
+    /// The index in the original stream up to which the bytes were valid UTF-8.
+    ///
+    /// Unlike the inner [`FromUtf8Error`], this is correct even if the error was truncated.
+    pub fn valid_up_to(&self) -> usize {
+        self.data.valid_up_to()
+    }
+
+    /// Check if the bytes stored in this error were truncated.
+    pub fn is_truncated(&self) -> bool {
+        self.data.discarded.is_some()
+    }
+
+    /// The number of bytes discarded from the stream when this error was truncated.
+    pub fn discarded_bytes(&self) -> usize {
+        self.data.discarded_bytes()
+    }
+
+    /// Truncate the bytes stored in the error to a bounded region around the decoding error.
+    fn truncate(self) -> Self {
+        Self {
+            data: Arc::new(self.into_data().truncate()),
+        }
+    }
+
+    /// Get the error's data, cloning it if the error has been cloned.
+    fn into_data(self) -> StreamErrorData {
+        Arc::unwrap_or_clone(self.data)
+    }
+
+    /// Attach the rest of the [`Output`] this error came from.
+    fn with_output(mut self, status: ExitStatus, stderr: Option<StreamContents>) -> Self {
+        Arc::make_mut(&mut self.data).output = Some(ErrorOutput {
+            status,
+            other: stderr,
+        });
+        self
+    }
+
+    /// Attach the command which produced the stream.
+    fn with_command(mut self, command: CommandInfo) -> Self {
+        Arc::make_mut(&mut self.data).command = Some(command);
+        self
+    }
+}
+
+impl From<StdoutError> for FromUtf8Error {
+    fn from(value: StdoutError) -> Self {
+        value.into_data().inner
+    }
+}
+
+impl From<FromUtf8Error> for StdoutError {
+    fn from(inner: FromUtf8Error) -> Self {
+        Self {
+            data: Arc::new(StreamErrorData {
+                inner,
+                output: None,
+                discarded: None,
+                command: None,
+            }),
+        }
+    }
+}
+
+impl Display for StdoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.data.fmt_labeled("Stdout", f)
+    }
+}
+
+impl std::error::Error for StdoutError {}
+
 /// The [`Output`]'s `stderr` field contained invalid UTF-8. Wraps a [`FromUtf8Error`].
 ///
 /// ```
@@ -344,37 +1681,535 @@ impl std::error::Error for StdoutError {}
 /// ```
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StderrError {
-    inner: FromUtf8Error,
+    data: Arc<StreamErrorData>,
 }
 
 impl StderrError {
     /// Get a reference to the inner [`FromUtf8Error`].
+    ///
+    /// If the error was truncated, this only contains the retained bytes, and its indexes are
+    /// relative to them. Use [`StderrError::valid_up_to`] for the index in the original stream.
     pub fn inner(&self) -> &FromUtf8Error {
-        &self.inner
+        &self.data.inner
+    }
+
+    /// Get the bytes of the stream which failed to decode.
+    ///
+    /// This is the same as `inner().as_bytes()`.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.data.inner.as_bytes()
+    }
+
+    /// The index in the original stream up to which the bytes were valid UTF-8.
+    ///
+    /// Unlike the inner [`FromUtf8Error`], this is correct even if the error was truncated.
+    pub fn valid_up_to(&self) -> usize {
+        self.data.valid_up_to()
+    }
+
+    /// Check if the bytes stored in this error were truncated.
+    pub fn is_truncated(&self) -> bool {
+        self.data.discarded.is_some()
+    }
+
+    /// The number of bytes discarded from the stream when this error was truncated.
+    pub fn discarded_bytes(&self) -> usize {
+        self.data.discarded_bytes()
+    }
+
+    /// Truncate the bytes stored in the error to a bounded region around the decoding error.
+    fn truncate(self) -> Self {
+        Self {
+            data: Arc::new(self.into_data().truncate()),
+        }
+    }
+
+    /// Get the error's data, cloning it if the error has been cloned.
+    fn into_data(self) -> StreamErrorData {
+        Arc::unwrap_or_clone(self.data)
+    }
+
+    /// Attach the rest of the [`Output`] this error came from.
+    fn with_output(mut self, status: ExitStatus, stdout: Option<StreamContents>) -> Self {
+        Arc::make_mut(&mut self.data).output = Some(ErrorOutput {
+            status,
+            other: stdout,
+        });
+        self
+    }
+
+    /// Attach the command which produced the stream.
+    fn with_command(mut self, command: CommandInfo) -> Self {
+        Arc::make_mut(&mut self.data).command = Some(command);
+        self
     }
 }
 
 impl From<StderrError> for FromUtf8Error {
     fn from(value: StderrError) -> Self {
-        value.inner
+        value.into_data().inner
     }
 }
 
 impl From<FromUtf8Error> for StderrError {
     fn from(inner: FromUtf8Error) -> Self {
-        Self { inner }
+        Self {
+            data: Arc::new(StreamErrorData {
+                inner,
+                output: None,
+                discarded: None,
+                command: None,
+            }),
+        }
     }
 }
 
 impl Display for StderrError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Stderr contained {}: {}",
-            self.inner,
-            FromUtf8ErrorContext::new(&self.inner, ERROR_CONTEXT_BYTES)
-        )
+        self.data.fmt_labeled("Stderr", f)
     }
 }
 
 impl std::error::Error for StderrError {}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::test_util::exit_code;
+    use crate::test_util::output;
+
+    // Errors are reference-counted, so that `Result<Utf8Output, Error>` stays small.
+    const _: () = assert!(size_of::<StdoutError>() == size_of::<usize>());
+    const _: () = assert!(size_of::<StderrError>() == size_of::<usize>());
+    const _: () = assert!(size_of::<Error>() <= 3 * size_of::<usize>());
+
+    #[test]
+    fn test_from_utf8_lossy_stdout() {
+        for (input, expected) in [
+            (b"\xc0puppy".as_slice(), "\u{fffd}puppy"),
+            (b"pup\xe2\x28\xa1py", "pup\u{fffd}(\u{fffd}py"),
+            (b"puppy\xf0\x90", "puppy\u{fffd}"),
+        ] {
+            let owned = Utf8Output::from_utf8_lossy(output(input, b"doggy"));
+            assert_eq!(owned.stdout, expected);
+            assert_eq!(owned.stderr, "doggy");
+            assert_eq!(
+                Utf8Output::from_utf8_lossy_ref(&output(input, b"doggy")),
+                owned
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_utf8_lossy_stderr() {
+        for (input, expected) in [
+            (b"\xc0puppy".as_slice(), "\u{fffd}puppy"),
+            (b"pup\xe2\x28\xa1py", "pup\u{fffd}(\u{fffd}py"),
+            (b"puppy\xf0\x90", "puppy\u{fffd}"),
+        ] {
+            let owned = Utf8Output::from_utf8_lossy(output(b"doggy", input));
+            assert_eq!(owned.stdout, "doggy");
+            assert_eq!(owned.stderr, expected);
+            assert_eq!(
+                Utf8Output::from_utf8_lossy_ref(&output(b"doggy", input)),
+                owned
+            );
+        }
+    }
+
+    fn assert_storage<S>()
+    where
+        S: From<String> + AsRef<str> + Debug + PartialEq,
+    {
+        let valid = output(b"puppy", "doggy 🐶".as_bytes());
+        let owned: Utf8Output<S> = Utf8Output::decode(valid.clone()).unwrap();
+        let borrowed: Utf8Output<S> = Utf8Output::try_from(&valid).unwrap().into_storage();
+        assert_eq!(owned, borrowed);
+
+        let invalid = output(b"puppy", b"doggy \xc0");
+        let err = Utf8Output::<S>::decode(invalid.clone()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            Utf8Output::try_from(invalid).unwrap_err().to_string()
+        );
+        assert_eq!(owned.stdout.as_ref(), "puppy");
+        assert_eq!(owned.stderr.as_ref(), "doggy 🐶");
+    }
+
+    #[test]
+    fn test_try_from_infers_storage() {
+        // The storage type doesn't need to be written out when it isn't converted.
+        fn stdout_len(output: Output) -> Result<usize, Error> {
+            let output = Utf8Output::try_from(output)?;
+            Ok(output.stdout.len())
+        }
+        assert_eq!(stdout_len(output(b"puppy", b"")).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_storage_string() {
+        assert_storage::<String>();
+    }
+
+    #[test]
+    fn test_storage_box_str() {
+        assert_storage::<Box<str>>();
+    }
+
+    #[test]
+    fn test_storage_arc_str() {
+        assert_storage::<Arc<str>>();
+    }
+
+    #[test]
+    fn test_storage_rc_str() {
+        assert_storage::<Rc<str>>();
+    }
+
+    #[test]
+    fn test_storage_debug() {
+        let valid = output(b"puppy", b"doggy");
+        let string: Utf8Output = valid.clone().try_into().unwrap();
+        let arc: Utf8Output<Arc<str>> = Utf8Output::try_from(valid).unwrap().into_storage();
+        assert_eq!(format!("{string:?}"), format!("{arc:?}"));
+    }
+
+    #[test]
+    fn test_try_from_mut_takes_buffers() {
+        let mut output = output(b"puppy", b"doggy");
+        let stdout_ptr = output.stdout.as_ptr();
+        let stderr_ptr = output.stderr.as_ptr();
+
+        let converted: Utf8Output = (&mut output).try_into().unwrap();
+        assert_eq!(converted.stdout, "puppy");
+        assert_eq!(converted.stderr, "doggy");
+        assert_eq!(converted.stdout.as_ptr(), stdout_ptr);
+        assert_eq!(converted.stderr.as_ptr(), stderr_ptr);
+        assert!(output.stdout.is_empty());
+        assert_eq!(output.stdout.capacity(), 0);
+        assert!(output.stderr.is_empty());
+        assert_eq!(output.stderr.capacity(), 0);
+    }
+
+    #[test]
+    fn test_try_from_mut_stdout_error() {
+        let mut output = output(b"puppy\xc0", b"doggy");
+        let stdout_ptr = output.stdout.as_ptr();
+
+        let err = Utf8Output::try_from(&mut output).unwrap_err();
+        // Stderr was never taken.
+        assert_eq!(output.stderr, b"doggy");
+        let bytes = FromUtf8Error::from(err).into_bytes();
+        assert_eq!(bytes, b"puppy\xc0");
+        assert_eq!(bytes.as_ptr(), stdout_ptr);
+    }
+
+    #[test]
+    fn test_try_from_mut_stderr_error() {
+        let mut output = output(b"puppy", b"doggy\xc0");
+        let stdout_ptr = output.stdout.as_ptr();
+        let stderr_ptr = output.stderr.as_ptr();
+
+        let err = Utf8Output::try_from(&mut output).unwrap_err();
+        // Stdout is put back without copying.
+        assert_eq!(output.stdout, b"puppy");
+        assert_eq!(output.stdout.as_ptr(), stdout_ptr);
+        let bytes = FromUtf8Error::from(err).into_bytes();
+        assert_eq!(bytes, b"doggy\xc0");
+        assert_eq!(bytes.as_ptr(), stderr_ptr);
+    }
+
+    #[test]
+    fn test_stdout_error_is_reported_first() {
+        let cases = [
+            (b"puppy".as_slice(), b"doggy".as_slice(), None, None),
+            (b"puppy\xc0", b"doggy", Some(b"puppy\xc0".as_slice()), None),
+            (b"puppy", b"doggy\xc0", None, Some(b"doggy\xc0".as_slice())),
+            (
+                b"puppy\xc0",
+                b"doggy\xc0",
+                Some(b"puppy\xc0".as_slice()),
+                None,
+            ),
+        ];
+
+        for (stdout, stderr, stdout_err, stderr_err) in cases {
+            let results = [
+                Utf8Output::try_from(output(stdout, stderr)),
+                Utf8Output::try_from(&output(stdout, stderr)),
+                Utf8Output::try_from(&mut output(stdout, stderr)),
+                ConversionOptions::new()
+                    .convert(output(stdout, stderr))
+                    .map_err(|err| err.decode_error().cloned().unwrap()),
+            ];
+
+            for result in results {
+                match result {
+                    Ok(converted) => {
+                        assert!(stdout_err.is_none() && stderr_err.is_none());
+                        assert_eq!(converted.stdout.as_bytes(), stdout);
+                        assert_eq!(converted.stderr.as_bytes(), stderr);
+                    }
+                    Err(err) => {
+                        assert_eq!(
+                            err.stdout_error().map(|err| err.inner().as_bytes()),
+                            stdout_err
+                        );
+                        assert_eq!(
+                            err.stderr_error().map(|err| err.inner().as_bytes()),
+                            stderr_err
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_from_mut_both_errors() {
+        let mut output = output(b"puppy\xc0", b"doggy\xc0");
+        let err = Utf8Output::try_from(&mut output).unwrap_err();
+        assert!(matches!(err, Error::Stdout(_)));
+        assert!(output.stdout.is_empty());
+        // Stderr isn't taken if stdout fails to decode.
+        assert_eq!(output.stderr, b"doggy\xc0");
+    }
+
+    #[test]
+    fn test_into_output_round_trip() {
+        for (stdout, stderr) in [
+            (b"puppy\xc0".as_slice(), b"doggy".as_slice()),
+            (b"puppy", b"doggy\xc0"),
+            (b"puppy\xc0", b"doggy\xc0"),
+            (b"\xc0", b""),
+        ] {
+            let original = Output {
+                status: exit_code(1),
+                stdout: stdout.to_vec(),
+                stderr: stderr.to_vec(),
+            };
+            let err = Utf8Output::try_from(original.clone()).unwrap_err();
+            assert_eq!(err.status(), Some(original.status));
+            assert_eq!(err.into_output(), original);
+        }
+    }
+
+    #[test]
+    fn test_into_output_borrowed() {
+        let original = Output {
+            status: exit_code(1),
+            stdout: Vec::from(b"puppy".as_slice()),
+            stderr: Vec::from(b"doggy\xc0".as_slice()),
+        };
+        let err = Utf8Output::try_from(&original).unwrap_err();
+        assert_eq!(
+            err.into_output(),
+            Output {
+                status: original.status,
+                stdout: vec![],
+                stderr: original.stderr,
+            }
+        );
+    }
+
+    #[test]
+    fn test_partial_streams() {
+        let err = Utf8Output::try_from(output("puppy 🐶\n".as_bytes(), b"\xc0")).unwrap_err();
+        assert_eq!(err.partial_stdout(), Some("puppy 🐶\n"));
+        assert_eq!(err.partial_stderr(), None);
+
+        let err = Utf8Output::try_from(output(b"\xc0", "doggy 🐕\n".as_bytes())).unwrap_err();
+        assert_eq!(err.partial_stdout(), None);
+        assert_eq!(err.partial_stderr(), Some("doggy 🐕\n"));
+
+        let err = Utf8Output::try_from(output(b"\xc0", b"\xc0")).unwrap_err();
+        assert_eq!(err.partial_stdout(), None);
+        assert_eq!(err.partial_stderr(), None);
+
+        // Borrowed conversions don't copy the decoded stream.
+        let err = Utf8Output::try_from(&output(b"puppy", b"\xc0")).unwrap_err();
+        assert_eq!(err.partial_stdout(), None);
+    }
+
+    #[test]
+    fn test_validate_agrees_with_try_from() {
+        let corpus = [
+            b"".as_slice(),
+            b"puppy",
+            "doggy 🐶 ✓".as_bytes(),
+            b"\xc0",
+            b"puppy \xe2\x28\xa1",
+            b"doggy\xf0\x90",
+            b"\xf0\x9f\x98\x8a\xc0\xf0\x9f\x98\x8a",
+        ];
+
+        for stdout in corpus {
+            for stderr in corpus {
+                let output = output(stdout, stderr);
+                assert_eq!(validate(&output), Utf8Output::try_from(&output).map(|_| ()),);
+                assert_eq!(
+                    validate(&output).is_ok(),
+                    Utf8Output::try_from(output).is_ok(),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_from_parts_matches_output() {
+        for (stdout, stderr) in [
+            (b"puppy".as_slice(), b"doggy".as_slice()),
+            (b"puppy\xc0", b"doggy"),
+            (b"puppy", b"doggy\xc0"),
+            (b"puppy\xc0", b"doggy\xc0"),
+        ] {
+            let status = exit_code(1);
+            let from_output = Utf8Output::try_from(Output {
+                status,
+                stdout: stdout.to_vec(),
+                stderr: stderr.to_vec(),
+            });
+            assert_eq!(
+                Utf8Output::try_from_parts(status, stdout.to_vec(), stderr.to_vec()),
+                from_output
+            );
+            assert_eq!(
+                Utf8Output::try_from((status, stdout.to_vec(), stderr.to_vec())),
+                from_output
+            );
+        }
+    }
+
+    #[test]
+    fn test_into_output_round_trip_valid() {
+        for (stdout, stderr) in [
+            (b"".as_slice(), b"".as_slice()),
+            (b"puppy\n", b""),
+            ("🐶 ✓ é".as_bytes(), "doggy\r\n🐕".as_bytes()),
+        ] {
+            let original = Output {
+                status: exit_code(1),
+                stdout: stdout.to_vec(),
+                stderr: stderr.to_vec(),
+            };
+
+            let converted: Utf8Output = original.clone().try_into().unwrap();
+            assert_eq!(Output::from(converted), original);
+
+            let converted: Utf8Output<Box<str>> = Utf8Output::try_from(original.clone())
+                .unwrap()
+                .into_storage();
+            assert_eq!(converted.into_output(), original);
+        }
+    }
+
+    #[test]
+    fn test_into_output_does_not_copy() {
+        let converted: Utf8Output = output(b"puppy", b"doggy").try_into().unwrap();
+        let stdout_ptr = converted.stdout.as_ptr();
+        let stderr_ptr = converted.stderr.as_ptr();
+        let output = converted.into_output();
+        assert_eq!(output.stdout.as_ptr(), stdout_ptr);
+        assert_eq!(output.stderr.as_ptr(), stderr_ptr);
+    }
+
+    #[test]
+    fn test_consuming_accessors() {
+        let output = Utf8Output::try_from(output(b"puppy", b"doggy")).unwrap();
+        assert_eq!(output.stdout(), "puppy");
+        assert_eq!(output.stderr(), "doggy");
+        assert_eq!(output.clone().into_stdout(), "puppy");
+        assert_eq!(output.clone().into_stderr(), "doggy");
+
+        let stdout_ptr = output.stdout.as_ptr();
+        let (status, stdout, stderr) = output.into_parts();
+        assert!(status.success());
+        assert_eq!(stdout, "puppy");
+        assert_eq!(stderr, "doggy");
+        // The streams aren't copied.
+        assert_eq!(stdout.as_ptr(), stdout_ptr);
+    }
+
+    #[test]
+    fn test_accessors_generic() {
+        let output: Utf8Output<Arc<str>> = Utf8Output::try_from(output(b"puppy", b"doggy"))
+            .unwrap()
+            .into_storage();
+        assert_eq!(output.stdout(), "puppy");
+        assert_eq!(&*output.into_stderr(), "doggy");
+    }
+
+    #[test]
+    fn test_map_streams() {
+        let output = Utf8Output::try_from(output(b"puppy", b"doggy")).unwrap();
+        let stdout_ptr = output.stdout.as_ptr();
+        let stderr_ptr = output.stderr.as_ptr();
+
+        let mapped = output
+            .map_stdout(|mut stdout| {
+                stdout.make_ascii_uppercase();
+                stdout
+            })
+            .map_stderr(|mut stderr| {
+                stderr.truncate(3);
+                stderr
+            });
+        assert!(mapped.status.success());
+        assert_eq!(mapped.stdout, "PUPPY");
+        assert_eq!(mapped.stderr, "dog");
+        // The streams aren't copied.
+        assert_eq!(mapped.stdout.as_ptr(), stdout_ptr);
+        assert_eq!(mapped.stderr.as_ptr(), stderr_ptr);
+    }
+
+    #[test]
+    fn test_try_map_streams() {
+        let output = Utf8Output::try_from(output(b"puppy", b"doggy")).unwrap();
+        let reject_doggy = |stream: String| {
+            if stream.contains("doggy") {
+                Err("doggy")
+            } else {
+                Ok(stream + "!")
+            }
+        };
+
+        let mapped = output.clone().try_map_stdout(reject_doggy).unwrap();
+        assert_eq!(mapped.stdout, "puppy!");
+        assert_eq!(mapped.stderr, "doggy");
+        assert_eq!(mapped.status, output.status);
+
+        assert_eq!(output.try_map_stderr(reject_doggy).unwrap_err(), "doggy");
+    }
+
+    #[test]
+    fn test_trimmed() {
+        for (stdout, trimmed) in [
+            ("puppy\n", "puppy"),
+            ("puppy\r\n", "puppy"),
+            ("puppy\n\n\r\n", "puppy"),
+            ("  puppy \t\n", "  puppy"),
+            ("puppy\ndoggy\n", "puppy\ndoggy"),
+            // Non-ASCII whitespace is kept.
+            ("puppy\u{a0}\n", "puppy\u{a0}"),
+            (" \r\n\t\n", ""),
+            ("", ""),
+        ] {
+            let output =
+                Utf8Output::try_from(output(stdout.as_bytes(), stdout.as_bytes())).unwrap();
+            assert_eq!(output.stdout_trimmed(), trimmed);
+            assert_eq!(output.stderr_trimmed(), trimmed);
+            assert_eq!(output.clone().into_stderr_trimmed(), trimmed);
+
+            let capacity = output.stdout.capacity();
+            let stdout_ptr = output.stdout.as_ptr();
+            let stdout = output.into_stdout_trimmed();
+            assert_eq!(stdout, trimmed);
+            // The stream isn't reallocated.
+            assert_eq!(stdout.capacity(), capacity);
+            assert_eq!(stdout.as_ptr(), stdout_ptr);
+        }
+    }
+}
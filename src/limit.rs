@@ -0,0 +1,125 @@
+use crate::context::is_codepoint_boundary;
+
+/// Keep at most `max` bytes from the start of `bytes`, and return the number of bytes discarded.
+///
+/// The cut is moved back (by up to 3 bytes) to a code point boundary, so a multi-byte character
+/// is never split and the kept prefix decodes if the original did. The buffer is shrunk, so the
+/// discarded bytes are freed.
+pub(crate) fn keep_head(bytes: &mut Vec<u8>, max: usize) -> usize {
+    if bytes.len() <= max {
+        return 0;
+    }
+
+    let mut end = max;
+    while end > max.saturating_sub(3) && !is_codepoint_boundary(bytes[end]) {
+        end -= 1;
+    }
+    if !is_codepoint_boundary(bytes[end]) {
+        // This isn't valid UTF-8; don't keep fewer bytes than we have to.
+        end = max;
+    }
+
+    let discarded = bytes.len() - end;
+    bytes.truncate(end);
+    bytes.shrink_to_fit();
+    discarded
+}
+
+/// Keep at most `max` bytes from the end of `bytes`, and return the number of bytes discarded.
+///
+/// The cut is moved forward (by up to 3 bytes) to a code point boundary, so a multi-byte
+/// character is never split and the kept suffix decodes if the original did. The buffer is
+/// shrunk, so the discarded bytes are freed.
+pub(crate) fn keep_tail(bytes: &mut Vec<u8>, max: usize) -> usize {
+    if bytes.len() <= max {
+        return 0;
+    }
+
+    let cut = bytes.len() - max;
+    let mut start = cut;
+    while start < Ord::min(cut + 3, bytes.len()) && !is_codepoint_boundary(bytes[start]) {
+        start += 1;
+    }
+    if start < bytes.len() && !is_codepoint_boundary(bytes[start]) {
+        // This isn't valid UTF-8; don't keep fewer bytes than we have to.
+        start = cut;
+    }
+
+    bytes.drain(..start);
+    bytes.shrink_to_fit();
+    start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn head(bytes: &[u8], max: usize) -> (Vec<u8>, usize) {
+        let mut bytes = bytes.to_vec();
+        let discarded = keep_head(&mut bytes, max);
+        (bytes, discarded)
+    }
+
+    fn tail(bytes: &[u8], max: usize) -> (Vec<u8>, usize) {
+        let mut bytes = bytes.to_vec();
+        let discarded = keep_tail(&mut bytes, max);
+        (bytes, discarded)
+    }
+
+    #[test]
+    fn test_keep_head() {
+        assert_eq!(head(b"puppy", 10), (b"puppy".to_vec(), 0));
+        assert_eq!(head(b"puppy", 5), (b"puppy".to_vec(), 0));
+        assert_eq!(head(b"puppy", 3), (b"pup".to_vec(), 2));
+        assert_eq!(head(b"puppy", 0), (b"".to_vec(), 5));
+    }
+
+    #[test]
+    fn test_keep_head_boundary() {
+        let bytes = "a🐶b".as_bytes();
+        // Every cut inside the emoji moves back to before it.
+        for max in 1..5 {
+            assert_eq!(head(bytes, max), (b"a".to_vec(), 5), "{max}");
+        }
+        assert_eq!(head(bytes, 5), ("a🐶".as_bytes().to_vec(), 1));
+        assert_eq!(head("🐶".as_bytes(), 3), (b"".to_vec(), 4));
+    }
+
+    #[test]
+    fn test_keep_head_invalid() {
+        // A run of continuation bytes has no boundary to move back to.
+        assert_eq!(
+            head(b"\x80\x80\x80\x80\x80\x80", 5),
+            (b"\x80\x80\x80\x80\x80".to_vec(), 1)
+        );
+    }
+
+    #[test]
+    fn test_keep_tail() {
+        // The whole stream is smaller than the limit.
+        assert_eq!(tail(b"puppy", 10), (b"puppy".to_vec(), 0));
+        assert_eq!(tail(b"puppy", 5), (b"puppy".to_vec(), 0));
+        assert_eq!(tail(b"", 0), (b"".to_vec(), 0));
+        assert_eq!(tail(b"puppy", 3), (b"ppy".to_vec(), 2));
+        assert_eq!(tail(b"puppy", 0), (b"".to_vec(), 5));
+    }
+
+    #[test]
+    fn test_keep_tail_boundary() {
+        let bytes = "a🐶b".as_bytes();
+        // Every cut inside the emoji moves forward to after it.
+        for max in 2..5 {
+            assert_eq!(tail(bytes, max), (b"b".to_vec(), 5), "{max}");
+        }
+        assert_eq!(tail(bytes, 5), ("🐶b".as_bytes().to_vec(), 1));
+        assert_eq!(tail("🐶".as_bytes(), 3), (b"".to_vec(), 4));
+    }
+
+    #[test]
+    fn test_keep_tail_invalid() {
+        assert_eq!(
+            tail(b"\x80\x80\x80\x80\x80\x80", 5),
+            (b"\x80\x80\x80\x80\x80".to_vec(), 1)
+        );
+    }
+}
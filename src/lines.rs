@@ -0,0 +1,262 @@
+use std::fmt::Display;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::process::ChildStderr;
+use std::process::ChildStdout;
+
+use crate::StreamDecodeError;
+
+/// An iterator over the lines of a [`BufRead`], decoded as UTF-8.
+///
+/// Unlike [`BufRead::lines`], lines which aren't valid UTF-8 are reported with their line
+/// number and the usual context, and reading continues with the next line. Lines are split on
+/// `\n`, and a trailing `\r` is removed. A final line without a newline is still returned.
+///
+/// ```
+/// use utf8_command::Utf8Lines;
+///
+/// let mut lines = Utf8Lines::new(b"puppy\r\ndoggy \xc0\nkitty".as_slice());
+/// assert_eq!(lines.next().unwrap().unwrap(), "puppy");
+/// assert_eq!(
+///     lines.next().unwrap().unwrap_err().to_string(),
+///     "Line 2: Output contained invalid utf-8 sequence of 1 bytes from index 13: \
+///     [7 bytes] \"doggy �\""
+/// );
+/// assert_eq!(lines.next().unwrap().unwrap(), "kitty");
+/// assert!(lines.next().is_none());
+/// ```
+///
+/// This can be constructed from a child's `stdout` or `stderr`, which are buffered:
+///
+/// ```
+/// # use std::process::Command;
+/// # use std::process::Stdio;
+/// use utf8_command::Utf8Lines;
+///
+/// let mut child = Command::new("sh")
+///     .args(["-c", "echo puppy; echo doggy"])
+///     .stdout(Stdio::piped())
+///     .spawn()
+///     .unwrap();
+/// let lines = Utf8Lines::from(child.stdout.take().unwrap());
+/// let lines = lines.collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(lines, ["puppy", "doggy"]);
+/// child.wait().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Utf8Lines<R> {
+    inner: R,
+    /// The number of lines read so far.
+    line: usize,
+    /// The number of bytes read so far.
+    offset: usize,
+}
+
+impl<R: BufRead> Utf8Lines<R> {
+    /// Iterate over the lines of `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            line: 0,
+            offset: 0,
+        }
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl From<ChildStdout> for Utf8Lines<BufReader<ChildStdout>> {
+    fn from(value: ChildStdout) -> Self {
+        Self::new(BufReader::new(value))
+    }
+}
+
+impl From<ChildStderr> for Utf8Lines<BufReader<ChildStderr>> {
+    fn from(value: ChildStderr) -> Self {
+        Self::new(BufReader::new(value))
+    }
+}
+
+impl<R: BufRead> Iterator for Utf8Lines<R> {
+    type Item = Result<String, LineError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut bytes = Vec::new();
+        let read = match self.inner.read_until(b'\n', &mut bytes) {
+            Ok(0) => return None,
+            Ok(read) => read,
+            Err(error) => {
+                return Some(Err(LineError::Io {
+                    line: self.line + 1,
+                    error,
+                }))
+            }
+        };
+        self.line += 1;
+        let start = self.offset;
+        self.offset += read;
+        Some(decode_line(bytes, self.line, start))
+    }
+}
+
+/// Decode a line read with [`BufRead::read_until`], removing its line ending.
+///
+/// `line` is the 1-based line number, and `start` is the index of the line in the stream.
+pub(crate) fn decode_line(
+    mut bytes: Vec<u8>,
+    line: usize,
+    start: usize,
+) -> Result<String, LineError> {
+    if bytes.last() == Some(&b'\n') {
+        bytes.pop();
+        if bytes.last() == Some(&b'\r') {
+            bytes.pop();
+        }
+    }
+    String::from_utf8(bytes).map_err(|err| LineError::Utf8 {
+        line,
+        error: StreamDecodeError::at_offset(err, start),
+    })
+}
+
+/// An error reading a line with [`Utf8Lines`].
+#[derive(Debug)]
+pub enum LineError {
+    /// Reading the line failed.
+    Io {
+        /// The 1-based number of the line which couldn't be read.
+        line: usize,
+        /// The error from the reader.
+        error: std::io::Error,
+    },
+    /// The line contained invalid UTF-8.
+    ///
+    /// Indexes in the error are relative to the start of the stream.
+    Utf8 {
+        /// The 1-based number of the line which contained invalid UTF-8.
+        line: usize,
+        /// The decoding error.
+        error: StreamDecodeError,
+    },
+}
+
+impl LineError {
+    /// Get the 1-based number of the line which caused the error.
+    pub fn line(&self) -> usize {
+        match self {
+            LineError::Io { line, .. } | LineError::Utf8 { line, .. } => *line,
+        }
+    }
+
+    /// Get the I/O error, if reading the line failed.
+    pub fn io_error(&self) -> Option<&std::io::Error> {
+        match self {
+            LineError::Io { error, .. } => Some(error),
+            LineError::Utf8 { .. } => None,
+        }
+    }
+
+    /// Get the UTF-8 decoding error, if the line contained invalid UTF-8.
+    pub fn utf8_error(&self) -> Option<&StreamDecodeError> {
+        match self {
+            LineError::Io { .. } => None,
+            LineError::Utf8 { error, .. } => Some(error),
+        }
+    }
+}
+
+impl Display for LineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LineError::Io { line, error } => write!(f, "Line {line}: {error}"),
+            LineError::Utf8 { line, error } => write!(f, "Line {line}: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for LineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LineError::Io { error, .. } => Some(error),
+            LineError::Utf8 { error, .. } => Some(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn test_line_endings() {
+        let lines = Utf8Lines::new(b"puppy\r\n\ndoggy\r\r\nkitty\r".as_slice())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(lines, ["puppy", "", "doggy\r", "kitty\r"]);
+
+        let lines = Utf8Lines::new(b"".as_slice())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert!(lines.is_empty());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let results = Utf8Lines::new(b"puppy\n\xc0\ndoggy \xf0\x9f".as_slice()).collect::<Vec<_>>();
+        assert_eq!(results[0].as_ref().unwrap(), "puppy");
+
+        let err = results[1].as_ref().unwrap_err();
+        assert_eq!(err.line(), 2);
+        assert_eq!(err.utf8_error().unwrap().valid_up_to(), 6);
+
+        let err = results[2].as_ref().unwrap_err();
+        assert_eq!(err.line(), 3);
+        assert_eq!(
+            err.to_string(),
+            "Line 3: Output contained incomplete utf-8 byte sequence from index 14: \
+            [8 bytes] \"doggy �\""
+        );
+    }
+
+    #[test]
+    fn test_long_line() {
+        // Small reads into a small buffer, so a quadratic implementation would be very slow.
+        let line = "puppy ".repeat(500_000);
+        let input = format!("{line}\ndoggy");
+        let reader = BufReader::with_capacity(16, input.as_bytes().take(u64::MAX));
+        let lines = Utf8Lines::new(reader)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(lines, [line.as_str(), "doggy"]);
+    }
+
+    #[test]
+    fn test_io_error() {
+        struct Failing;
+
+        impl Read for Failing {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+        }
+
+        let mut lines = Utf8Lines::new(BufReader::new(b"puppy\n".chain(Failing)));
+        assert_eq!(lines.next().unwrap().unwrap(), "puppy");
+        let err = lines.next().unwrap().unwrap_err();
+        assert_eq!(err.line(), 2);
+        assert_eq!(
+            err.io_error().unwrap().kind(),
+            std::io::ErrorKind::BrokenPipe
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use std::process::Command;
+
+/// The environment variables which select a locale, in the order they're checked.
+#[cfg(unix)]
+const LOCALE_VARIABLES: [&str; 2] = ["LC_ALL", "LANG"];
+
+/// Set `LC_ALL` and `LANG` to a UTF-8 locale on Unix.
+///
+/// Variables which are already configured on `command` are left alone unless `force` is set.
+pub(crate) fn set_utf8_locale(command: &mut Command, force: bool) {
+    #[cfg(unix)]
+    {
+        for key in LOCALE_VARIABLES {
+            let configured = command.get_envs().any(|(name, _)| name == key);
+            if force || !configured {
+                command.env(key, utf8_locale_name());
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        // Windows programs don't choose their encoding with locale environment variables.
+        let _ = (command, force);
+    }
+}
+
+/// Get the name of a UTF-8 locale which is installed, preferring `C.UTF-8`.
+///
+/// `C.UTF-8` is missing on older systems, so if `locale -a` doesn't list it, `en_US.UTF-8` is
+/// used instead, if it's listed. The result is cached.
+#[cfg(unix)]
+fn utf8_locale_name() -> &'static str {
+    static NAME: std::sync::OnceLock<&'static str> = std::sync::OnceLock::new();
+    NAME.get_or_init(|| {
+        let available = match Command::new("locale").arg("-a").output() {
+            Ok(output) if output.status.success() => output.stdout,
+            // Assume `C.UTF-8` is available if we can't check.
+            _ => return "C.UTF-8",
+        };
+        let available = String::from_utf8_lossy(&available);
+        let is_available = |name: &str| {
+            available
+                .lines()
+                .any(|line| normalize_locale_name(line) == normalize_locale_name(name))
+        };
+        ["C.UTF-8", "en_US.UTF-8"]
+            .into_iter()
+            .find(|name| is_available(name))
+            .unwrap_or("C.UTF-8")
+    })
+}
+
+/// Normalize a locale name for comparison, because `locale -a` lists `C.UTF-8` as `C.utf8` on
+/// Linux.
+#[cfg(unix)]
+fn normalize_locale_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .filter(|c| *c != '-')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::CommandExt;
+
+    use super::*;
+
+    fn locale(command: &mut Command) -> Vec<String> {
+        command
+            .output_utf8_checked()
+            .unwrap()
+            .stdout
+            .lines()
+            .map(ToOwned::to_owned)
+            .collect()
+    }
+
+    #[test]
+    fn test_normalize_locale_name() {
+        assert_eq!(normalize_locale_name("C.UTF-8"), "c.utf8");
+        assert_eq!(normalize_locale_name("C.utf8\n"), "c.utf8");
+        assert_eq!(normalize_locale_name("en_US.UTF-8"), "en_us.utf8");
+    }
+
+    #[test]
+    fn test_utf8_locale() {
+        let name = utf8_locale_name();
+        let lines = locale(Command::new("locale").utf8_locale());
+        assert!(lines.contains(&format!("LANG={name}")), "{lines:?}");
+        assert!(lines.contains(&format!("LC_ALL={name}")), "{lines:?}");
+        assert!(lines.contains(&format!("LC_CTYPE=\"{name}\"")), "{lines:?}");
+    }
+
+    #[test]
+    fn test_utf8_locale_keeps_configured_variables() {
+        let name = utf8_locale_name();
+        let lines = locale(Command::new("locale").env("LANG", "POSIX").utf8_locale());
+        assert!(lines.contains(&"LANG=POSIX".to_owned()), "{lines:?}");
+        assert!(lines.contains(&format!("LC_ALL={name}")), "{lines:?}");
+    }
+
+    #[test]
+    fn test_force_utf8_locale() {
+        let name = utf8_locale_name();
+        let lines = locale(
+            Command::new("locale")
+                .env("LANG", "POSIX")
+                .env("LC_ALL", "POSIX")
+                .force_utf8_locale(),
+        );
+        assert!(lines.contains(&format!("LANG={name}")), "{lines:?}");
+        assert!(lines.contains(&format!("LC_ALL={name}")), "{lines:?}");
+    }
+}
@@ -0,0 +1,205 @@
+use std::ops::Range;
+
+use crate::Utf8Output;
+
+/// The number of invalid UTF-8 sequences replaced while lossily decoding an [`Output`].
+///
+/// Produced by [`Utf8Output::from_utf8_lossy_with_report`].
+///
+/// [`Output`]: std::process::Output
+/// [`Utf8Output::from_utf8_lossy_with_report`]: crate::Utf8Output::from_utf8_lossy_with_report
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LossyReport {
+    /// The number of invalid sequences in `stdout` replaced with U+FFFD REPLACEMENT CHARACTER.
+    pub stdout_replacements: usize,
+    /// The number of invalid sequences in `stderr` replaced with U+FFFD REPLACEMENT CHARACTER.
+    pub stderr_replacements: usize,
+}
+
+/// The result of lossily decoding an [`Output`], including the byte ranges which were replaced.
+///
+/// Produced by [`Utf8Output::from_utf8_lossy_with_ranges`].
+///
+/// [`Output`]: std::process::Output
+/// [`Utf8Output::from_utf8_lossy_with_ranges`]: crate::Utf8Output::from_utf8_lossy_with_ranges
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LossyConversion {
+    /// The decoded output.
+    pub output: Utf8Output,
+    /// The ranges of the original `stdout` bytes which were replaced with U+FFFD REPLACEMENT
+    /// CHARACTER, in ascending order.
+    ///
+    /// Each range corresponds to exactly one replacement character, so adjacent invalid
+    /// sequences produce adjacent ranges rather than one merged range.
+    pub stdout_invalid: Vec<Range<usize>>,
+    /// The ranges of the original `stderr` bytes which were replaced with U+FFFD REPLACEMENT
+    /// CHARACTER, in ascending order.
+    ///
+    /// See [`LossyConversion::stdout_invalid`].
+    pub stderr_invalid: Vec<Range<usize>>,
+}
+
+/// Decode `bytes` as UTF-8, replacing invalid sequences with U+FFFD REPLACEMENT CHARACTER.
+///
+/// If `bytes` is already valid UTF-8, the buffer is reused rather than copied.
+pub(crate) fn decode_lossy(bytes: Vec<u8>) -> String {
+    decode_lossy_counted(bytes).0
+}
+
+/// Like [`decode_lossy`], but also returns the number of invalid sequences which were replaced.
+///
+/// Each replacement corresponds to one U+FFFD REPLACEMENT CHARACTER in the output, exactly like
+/// [`String::from_utf8_lossy`].
+pub(crate) fn decode_lossy_counted(bytes: Vec<u8>) -> (String, usize) {
+    let mut replacements = 0;
+    let decoded = decode_lossy_with(bytes, |_| replacements += 1);
+    (decoded, replacements)
+}
+
+/// Like [`decode_lossy`], but also returns the ranges of `bytes` which were replaced.
+pub(crate) fn decode_lossy_ranges(bytes: Vec<u8>) -> (String, Vec<Range<usize>>) {
+    let mut ranges = Vec::new();
+    let decoded = decode_lossy_with(bytes, |range| ranges.push(range));
+    (decoded, ranges)
+}
+
+/// Decode `bytes` lossily, calling `on_invalid` with the range of each replaced sequence.
+fn decode_lossy_with(bytes: Vec<u8>, mut on_invalid: impl FnMut(Range<usize>)) -> String {
+    decode_lossy_replacing(bytes, |decoded, range, _invalid| {
+        decoded.push(char::REPLACEMENT_CHARACTER);
+        on_invalid(range);
+    })
+}
+
+/// Decode `bytes` lossily, calling `replace` with the decoded text so far, the range of each
+/// invalid sequence, and the invalid sequence itself.
+///
+/// Invalid sequences are split the same way as [`String::from_utf8_lossy`].
+pub(crate) fn decode_lossy_replacing(
+    bytes: Vec<u8>,
+    mut replace: impl FnMut(&mut String, Range<usize>, &[u8]),
+) -> String {
+    let err = match String::from_utf8(bytes) {
+        Ok(string) => return string,
+        Err(err) => err,
+    };
+
+    let bytes = err.as_bytes();
+    let mut decoded = String::with_capacity(bytes.len());
+    let mut index = 0;
+    for chunk in bytes.utf8_chunks() {
+        decoded.push_str(chunk.valid());
+        index += chunk.valid().len();
+
+        let invalid = chunk.invalid();
+        if !invalid.is_empty() {
+            replace(&mut decoded, index..index + invalid.len(), invalid);
+            index += invalid.len();
+        }
+    }
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn test_valid_reuses_buffer() {
+        let bytes = Vec::from(b"puppy doggy".as_slice());
+        let ptr = bytes.as_ptr();
+        let decoded = decode_lossy(bytes);
+        assert_eq!(decoded, "puppy doggy");
+        assert_eq!(decoded.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn test_invalid_start() {
+        assert_eq!(
+            decode_lossy(Vec::from(b"\xc0puppy".as_slice())),
+            "\u{fffd}puppy"
+        );
+    }
+
+    #[test]
+    fn test_invalid_middle() {
+        assert_eq!(
+            decode_lossy(Vec::from(b"puppy \xe2\x28\xa1 doggy".as_slice())),
+            "puppy \u{fffd}(\u{fffd} doggy"
+        );
+    }
+
+    #[test]
+    fn test_invalid_end() {
+        // Incomplete 4-byte sequence.
+        assert_eq!(
+            decode_lossy(Vec::from(b"puppy\xf0\x90".as_slice())),
+            "puppy\u{fffd}"
+        );
+    }
+
+    #[test]
+    fn test_counted() {
+        assert_eq!(
+            decode_lossy_counted(Vec::from(b"puppy".as_slice())),
+            (String::from("puppy"), 0)
+        );
+        // Consecutive invalid sequences are counted separately.
+        assert_eq!(
+            decode_lossy_counted(Vec::from(b"\xc0\xc0\x80".as_slice())),
+            (String::from("\u{fffd}\u{fffd}\u{fffd}"), 3)
+        );
+        // An incomplete sequence at the end counts once.
+        assert_eq!(
+            decode_lossy_counted(Vec::from(b"puppy\xf0\x9f\x98".as_slice())),
+            (String::from("puppy\u{fffd}"), 1)
+        );
+    }
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(
+            decode_lossy_ranges(Vec::from(b"puppy".as_slice())),
+            (String::from("puppy"), vec![])
+        );
+        // Multi-byte invalid sequences: a truncated 3-byte sequence, then a truncated 4-byte
+        // sequence.
+        assert_eq!(
+            decode_lossy_ranges(Vec::from(b"a\xe2\x9cb\xf0\x9f\x98c".as_slice())),
+            (String::from("a\u{fffd}b\u{fffd}c"), vec![1..3, 4..7])
+        );
+        // Adjacent invalid sequences are not merged.
+        assert_eq!(
+            decode_lossy_ranges(Vec::from(b"\xf0\x9f\xc0\x80\xe2\x9c".as_slice())),
+            (
+                String::from("\u{fffd}\u{fffd}\u{fffd}\u{fffd}"),
+                vec![0..2, 2..3, 3..4, 4..6]
+            )
+        );
+        // A truncated final code point.
+        let (decoded, ranges) = decode_lossy_ranges("puppy 🐶".as_bytes()[..9].to_vec());
+        assert_eq!(decoded, "puppy \u{fffd}");
+        assert_eq!(ranges, vec![Range { start: 6, end: 9 }]);
+    }
+
+    /// None of these bytes can form a literal U+FFFD (`\xef\xbf\xbd`), so every U+FFFD in
+    /// the reference output is a replacement.
+    const ALPHABET: &[u8] = b"a \n\x80\x8a\x93\x98\x9c\x9f\xa9\xc0\xc3\xe2\xf0\xff";
+
+    proptest! {
+        /// Compare against `String::from_utf8_lossy`, counting U+FFFD in its output.
+        #[test]
+        fn test_counted_reference(
+            bytes in prop::collection::vec(prop::sample::select(ALPHABET), 0..32),
+        ) {
+            let reference = String::from_utf8_lossy(&bytes);
+            let reference_count = reference.matches(char::REPLACEMENT_CHARACTER).count();
+
+            let (decoded, count) = decode_lossy_counted(bytes.clone());
+            prop_assert_eq!(decoded, reference);
+            prop_assert_eq!(count, reference_count);
+        }
+    }
+}
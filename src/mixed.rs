@@ -0,0 +1,277 @@
+use std::borrow::Cow;
+use std::process::ExitStatus;
+use std::process::Output;
+
+use crate::decode_stderr;
+use crate::decode_stdout;
+use crate::Error;
+use crate::StderrError;
+use crate::StdoutError;
+use crate::StreamContents;
+use crate::Utf8Output;
+
+/// A variant of [`std::process::Output`] with `stdout` decoded as UTF-8 and `stderr` left as raw
+/// bytes.
+///
+/// This is useful for programs which write text to `stdout` but arbitrary binary data to
+/// `stderr`. Only `stdout` is validated:
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use std::process::Output;
+/// # use utf8_command::MixedOutput;
+/// let output = MixedOutput::try_from(Output {
+///     status: ExitStatus::default(),
+///     stdout: Vec::from(b"puppy"),
+///     stderr: Vec::from(b"\x00\xc0\xff"),
+/// })
+/// .unwrap();
+/// assert_eq!(output.stdout, "puppy");
+/// assert_eq!(output.stderr, b"\x00\xc0\xff");
+/// assert_eq!(output.stderr_lossy(), "\0��");
+/// ```
+///
+/// If `stdout` is invalid, a [`StdoutError`] is returned:
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use std::process::Output;
+/// # use utf8_command::MixedOutput;
+/// let err = MixedOutput::try_from(Output {
+///     status: ExitStatus::default(),
+///     stdout: Vec::from(b"puppy \xc0"),
+///     stderr: Vec::from(b""),
+/// })
+/// .unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MixedOutput {
+    /// The [`std::process::Command`]'s exit status.
+    pub status: ExitStatus,
+    /// The contents of the [`std::process::Command`]'s `stdout` stream, decoded as UTF-8.
+    pub stdout: String,
+    /// The raw contents of the [`std::process::Command`]'s `stderr` stream.
+    pub stderr: Vec<u8>,
+}
+
+impl MixedOutput {
+    /// Decode `stderr` for display, replacing invalid UTF-8 sequences with U+FFFD REPLACEMENT
+    /// CHARACTER.
+    pub fn stderr_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.stderr)
+    }
+
+    /// Decode `stderr` as well, producing a [`Utf8Output`].
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::MixedOutput;
+    /// let output = MixedOutput {
+    ///     status: ExitStatus::default(),
+    ///     stdout: String::from("puppy"),
+    ///     stderr: Vec::from(b"doggy"),
+    /// };
+    /// assert_eq!(output.try_into_utf8_output().unwrap().stderr, "doggy");
+    /// ```
+    pub fn try_into_utf8_output(self) -> Result<Utf8Output, Error> {
+        let (stdout, stderr) =
+            Error::from_owned_results(self.status, Ok(self.stdout), decode_stderr(self.stderr))?;
+        Ok(Utf8Output {
+            status: self.status,
+            stdout,
+            stderr,
+        })
+    }
+}
+
+impl TryFrom<Output> for MixedOutput {
+    type Error = StdoutError;
+
+    fn try_from(
+        Output {
+            status,
+            stdout,
+            stderr,
+        }: Output,
+    ) -> Result<Self, Self::Error> {
+        match decode_stdout(stdout) {
+            Ok(stdout) => Ok(MixedOutput {
+                status,
+                stdout,
+                stderr,
+            }),
+            Err(err) => Err(err.with_output(status, Some(StreamContents::Raw(stderr)))),
+        }
+    }
+}
+
+impl From<Utf8Output> for MixedOutput {
+    fn from(value: Utf8Output) -> Self {
+        MixedOutput {
+            status: value.status,
+            stdout: value.stdout,
+            stderr: value.stderr.into_bytes(),
+        }
+    }
+}
+
+/// A variant of [`std::process::Output`] with `stdout` left as raw bytes and `stderr` decoded as
+/// UTF-8.
+///
+/// This is the counterpart to [`MixedOutput`], for programs like `tar -O` which write binary data
+/// to `stdout` and human-readable messages to `stderr`. Only `stderr` is validated:
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use std::process::Output;
+/// # use utf8_command::RawStdoutOutput;
+/// let output = RawStdoutOutput::try_from(Output {
+///     status: ExitStatus::default(),
+///     stdout: Vec::from(b"\x1f\x8b\x08\x00"),
+///     stderr: Vec::from(b"puppy\ndoggy\n"),
+/// })
+/// .unwrap();
+/// assert_eq!(output.stdout, b"\x1f\x8b\x08\x00");
+/// assert_eq!(output.stderr_lines().collect::<Vec<_>>(), ["puppy", "doggy"]);
+/// ```
+///
+/// If `stderr` is invalid, a [`StderrError`] is returned:
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use std::process::Output;
+/// # use utf8_command::RawStdoutOutput;
+/// let err = RawStdoutOutput::try_from(Output {
+///     status: ExitStatus::default(),
+///     stdout: Vec::from(b""),
+///     stderr: Vec::from(b"doggy \xc0"),
+/// })
+/// .unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "Stderr contained invalid utf-8 sequence of 1 bytes from index 6: \"doggy �\""
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawStdoutOutput {
+    /// The [`std::process::Command`]'s exit status.
+    pub status: ExitStatus,
+    /// The raw contents of the [`std::process::Command`]'s `stdout` stream.
+    pub stdout: Vec<u8>,
+    /// The contents of the [`std::process::Command`]'s `stderr` stream, decoded as UTF-8.
+    pub stderr: String,
+}
+
+impl RawStdoutOutput {
+    /// Iterate over the lines of `stderr`.
+    ///
+    /// Lines are split like [`str::lines`].
+    pub fn stderr_lines(&self) -> std::str::Lines<'_> {
+        self.stderr.lines()
+    }
+
+    /// Decode `stdout` as well, producing a [`Utf8Output`].
+    pub fn try_into_utf8_output(self) -> Result<Utf8Output, Error> {
+        let (stdout, stderr) =
+            Error::from_owned_results(self.status, decode_stdout(self.stdout), Ok(self.stderr))?;
+        Ok(Utf8Output {
+            status: self.status,
+            stdout,
+            stderr,
+        })
+    }
+}
+
+impl TryFrom<Output> for RawStdoutOutput {
+    type Error = StderrError;
+
+    fn try_from(
+        Output {
+            status,
+            stdout,
+            stderr,
+        }: Output,
+    ) -> Result<Self, Self::Error> {
+        match decode_stderr(stderr) {
+            Ok(stderr) => Ok(RawStdoutOutput {
+                status,
+                stdout,
+                stderr,
+            }),
+            Err(err) => Err(err.with_output(status, Some(StreamContents::Raw(stdout)))),
+        }
+    }
+}
+
+impl From<Utf8Output> for RawStdoutOutput {
+    fn from(value: Utf8Output) -> Self {
+        RawStdoutOutput {
+            status: value.status,
+            stdout: value.stdout.into_bytes(),
+            stderr: value.stderr,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::output;
+
+    #[test]
+    fn test_binary_stderr_is_unmodified() {
+        let stderr: Vec<u8> = (0..=255).collect();
+        let output = MixedOutput::try_from(output(b"puppy", &stderr)).unwrap();
+        assert_eq!(output.stdout, "puppy");
+        assert_eq!(output.stderr, stderr);
+    }
+
+    #[test]
+    fn test_invalid_stdout() {
+        let original = output(b"puppy\xc0", b"\xff");
+        let err = MixedOutput::try_from(original.clone()).unwrap_err();
+        assert_eq!(err.inner().as_bytes(), b"puppy\xc0");
+        // The raw stderr is kept in the error.
+        assert_eq!(Error::from(err).into_output(), original);
+    }
+
+    #[test]
+    fn test_try_into_utf8_output() {
+        let output = MixedOutput::try_from(output(b"puppy", b"doggy\xc0")).unwrap();
+        let err = output.try_into_utf8_output().unwrap_err();
+        assert_eq!(err.partial_stdout(), Some("puppy"));
+        assert_eq!(err.stderr_error().unwrap().inner().as_bytes(), b"doggy\xc0");
+    }
+
+    #[test]
+    fn test_binary_stdout_is_unmodified() {
+        let stdout = b"\xc0\xff\x00\xe2\x28\xa1\xf0\x90".to_vec();
+        let output = RawStdoutOutput::try_from(output(&stdout, b"puppy\r\ndoggy")).unwrap();
+        assert_eq!(output.stdout, stdout);
+        assert_eq!(
+            output.stderr_lines().collect::<Vec<_>>(),
+            ["puppy", "doggy"]
+        );
+    }
+
+    #[test]
+    fn test_invalid_stderr() {
+        let original = output(b"\xff", b"doggy\xc0");
+        let err = RawStdoutOutput::try_from(original.clone()).unwrap_err();
+        assert_eq!(err.inner().as_bytes(), b"doggy\xc0");
+        // The raw stdout is kept in the error.
+        assert_eq!(Error::from(err).into_output(), original);
+    }
+
+    #[test]
+    fn test_raw_stdout_try_into_utf8_output() {
+        let output = RawStdoutOutput::try_from(output(b"\xff", b"doggy")).unwrap();
+        let err = output.try_into_utf8_output().unwrap_err();
+        assert_eq!(err.partial_stderr(), Some("doggy"));
+    }
+}
@@ -0,0 +1,206 @@
+/// How to normalize line endings in the decoded streams.
+///
+/// See [`ConversionOptions::line_endings`][crate::ConversionOptions::line_endings].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEndings {
+    /// Keep line endings as they are.
+    #[default]
+    Keep,
+    /// Replace `\r\n` with `\n`. A `\r` which isn't followed by `\n` is kept.
+    Crlf,
+    /// Replace `\r\n` with `\n`, and replace any other `\r` with `\n` as well.
+    All,
+}
+
+impl LineEndings {
+    /// Normalize the line endings in `text`, in place.
+    pub(crate) fn normalize(self, text: &mut String) {
+        if self == LineEndings::Keep || !text.contains('\r') {
+            return;
+        }
+
+        // SAFETY: We only remove `\r` bytes or replace them with `\n`. Both are ASCII, so they're
+        // never part of a multi-byte sequence, and the text stays valid UTF-8.
+        let bytes = unsafe { text.as_mut_vec() };
+        let mut write = 0;
+        for read in 0..bytes.len() {
+            let byte = bytes[read];
+            if byte == b'\r' {
+                if bytes.get(read + 1) == Some(&b'\n') {
+                    // Drop the `\r` of a `\r\n`.
+                    continue;
+                }
+                if self == LineEndings::All {
+                    bytes[write] = b'\n';
+                    write += 1;
+                    continue;
+                }
+            }
+            bytes[write] = byte;
+            write += 1;
+        }
+        bytes.truncate(write);
+    }
+}
+
+/// A Unicode normalization form to normalize the decoded streams to.
+///
+/// See [`ConversionOptions::unicode_normalization`][crate::ConversionOptions::unicode_normalization].
+#[cfg(feature = "unicode-normalization")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnicodeNormalization {
+    /// Don't normalize the streams.
+    #[default]
+    Keep,
+    /// Normalization Form C (canonical composition).
+    Nfc,
+    /// Normalization Form D (canonical decomposition).
+    Nfd,
+    /// Normalization Form KC (compatibility composition).
+    Nfkc,
+    /// Normalization Form KD (compatibility decomposition).
+    Nfkd,
+}
+
+#[cfg(feature = "unicode-normalization")]
+impl UnicodeNormalization {
+    /// Normalize `text`. Text which is already normalized isn't copied.
+    pub(crate) fn normalize(self, text: &mut String) {
+        use unicode_normalization::UnicodeNormalization as _;
+
+        let normalized: String = match self {
+            UnicodeNormalization::Keep => return,
+            UnicodeNormalization::Nfc if unicode_normalization::is_nfc(text) => return,
+            UnicodeNormalization::Nfd if unicode_normalization::is_nfd(text) => return,
+            UnicodeNormalization::Nfkc if unicode_normalization::is_nfkc(text) => return,
+            UnicodeNormalization::Nfkd if unicode_normalization::is_nfkd(text) => return,
+            UnicodeNormalization::Nfc => text.nfc().collect(),
+            UnicodeNormalization::Nfd => text.nfd().collect(),
+            UnicodeNormalization::Nfkc => text.nfkc().collect(),
+            UnicodeNormalization::Nfkd => text.nfkd().collect(),
+        };
+        *text = normalized;
+    }
+}
+
+/// Remove exactly one trailing `\n` or `\r\n` from `text`, like shell command substitution
+/// (`$(...)`) but without removing more than one line ending.
+pub(crate) fn chomp(text: &mut String) {
+    text.truncate(chomp_str(text).len());
+}
+
+/// Remove exactly one trailing `\n` or `\r\n` from `text`.
+pub(crate) fn chomp_str(text: &str) -> &str {
+    text.strip_suffix("\r\n")
+        .or_else(|| text.strip_suffix('\n'))
+        .unwrap_or(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(line_endings: LineEndings, text: &str) -> String {
+        let mut text = String::from(text);
+        line_endings.normalize(&mut text);
+        text
+    }
+
+    #[test]
+    fn test_keep() {
+        assert_eq!(normalize(LineEndings::Keep, "a\r\nb\rc"), "a\r\nb\rc");
+    }
+
+    #[test]
+    fn test_crlf() {
+        let cases = [
+            ("", ""),
+            ("puppy", "puppy"),
+            ("puppy\r\ndoggy\r\n", "puppy\ndoggy\n"),
+            // A `\r\n` split by the end of the stream.
+            ("puppy\r", "puppy\r"),
+            ("\r", "\r"),
+            ("\n", "\n"),
+            ("puppy\rdoggy", "puppy\rdoggy"),
+            ("\r\r\n", "\r\n"),
+            ("\r\n\r", "\n\r"),
+            ("\n\r", "\n\r"),
+            ("🐶\r\n🐕\r", "🐶\n🐕\r"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(normalize(LineEndings::Crlf, text), expected, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn test_all() {
+        let cases = [
+            ("puppy\r\ndoggy\r\n", "puppy\ndoggy\n"),
+            ("puppy\r", "puppy\n"),
+            ("puppy\rdoggy", "puppy\ndoggy"),
+            ("\r\r\n", "\n\n"),
+            ("\n\r", "\n\n"),
+            ("🐶\r🐕", "🐶\n🐕"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(normalize(LineEndings::All, text), expected, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn test_chomp() {
+        let cases = [
+            ("", ""),
+            ("\n", ""),
+            ("\r\n", ""),
+            ("\r", "\r"),
+            ("puppy", "puppy"),
+            ("puppy\n", "puppy"),
+            ("puppy\n\n", "puppy\n"),
+            ("puppy\r\n\r\n", "puppy\r\n"),
+            ("puppy\ndoggy", "puppy\ndoggy"),
+            ("puppy \n", "puppy "),
+        ];
+        for (text, expected) in cases {
+            let mut text = String::from(text);
+            chomp(&mut text);
+            assert_eq!(text, expected);
+        }
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn test_unicode_normalization() {
+        let normalize = |form: UnicodeNormalization, text: &str| {
+            let mut text = String::from(text);
+            form.normalize(&mut text);
+            text
+        };
+
+        let decomposed = "cafe\u{301}";
+        assert_eq!(
+            normalize(UnicodeNormalization::Keep, decomposed),
+            decomposed
+        );
+        assert_eq!(normalize(UnicodeNormalization::Nfc, decomposed), "café");
+        assert_eq!(normalize(UnicodeNormalization::Nfd, "café"), decomposed);
+        assert_eq!(normalize(UnicodeNormalization::Nfc, "ﬁ"), "ﬁ");
+        assert_eq!(normalize(UnicodeNormalization::Nfkc, "ﬁ"), "fi");
+        assert_eq!(normalize(UnicodeNormalization::Nfkd, "ﬁé"), "fie\u{301}");
+
+        // Text which is already normalized isn't copied.
+        let mut text = String::from("café");
+        let ptr = text.as_ptr();
+        UnicodeNormalization::Nfc.normalize(&mut text);
+        assert_eq!(text.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn test_does_not_copy() {
+        let mut text = String::from("puppy\r\ndoggy\r\n");
+        let ptr = text.as_ptr();
+        LineEndings::Crlf.normalize(&mut text);
+        assert_eq!(text, "puppy\ndoggy\n");
+        assert_eq!(text.as_ptr(), ptr);
+    }
+}
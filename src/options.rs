@@ -0,0 +1,1943 @@
+use std::fmt::Display;
+use std::process::Output;
+use std::string::FromUtf8Error;
+
+use crate::cesu8::decode_cesu8;
+use crate::decode::string_from_utf8;
+use crate::escape::hex_escape;
+use crate::limit::keep_head;
+use crate::limit::keep_tail;
+use crate::lossy::decode_lossy_replacing;
+use crate::redact::redact;
+use crate::redact::redact_decoded;
+use crate::redact::Redaction;
+use crate::ContentError;
+use crate::ContentErrorKind;
+use crate::Error;
+use crate::LineEndings;
+use crate::StderrError;
+use crate::StdoutError;
+use crate::Stream;
+use crate::StreamContents;
+use crate::Utf16Endianness;
+use crate::Utf8Output;
+
+/// How strictly to decode a stream of an [`Output`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strictness {
+    /// Fail the conversion if the stream contains invalid UTF-8.
+    #[default]
+    Strict,
+    /// Replace invalid UTF-8 sequences with U+FFFD REPLACEMENT CHARACTER.
+    Lossy,
+    /// Fail the conversion if the stream contains anything other than ASCII.
+    ///
+    /// This is useful for machine-readable output like hashes and identifiers. Invalid UTF-8 is
+    /// reported as a decoding [`Error`], and valid UTF-8 which isn't ASCII is reported as a
+    /// [`ContentError`] with the offset of the first non-ASCII character. ASCII is checked after
+    /// a byte order mark is stripped (see [`ConversionOptions::bom`]).
+    ///
+    /// When used as a [`DecodePolicy`][crate::DecodePolicy], this is the same as
+    /// [`Strictness::Strict`], because a [`StreamDecodeError`][crate::StreamDecodeError] can only
+    /// report invalid UTF-8.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::ConversionOptions;
+    /// # use utf8_command::Strictness;
+    /// let err = ConversionOptions::new()
+    ///     .stdout(Strictness::Ascii)
+    ///     .convert(Output {
+    ///         status: ExitStatus::default(),
+    ///         stdout: Vec::from("it’s 3f2a9c"),
+    ///         stderr: Vec::from(b""),
+    ///     })
+    ///     .unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Stdout contained non-ASCII character '’' at index 2: \"it’s 3f2a9c\""
+    /// );
+    /// ```
+    Ascii,
+    /// Decode CESU-8 and Java's "modified UTF-8", as well as UTF-8.
+    ///
+    /// Some JVM tools encode supplementary characters (like emoji) as a pair of 3-byte
+    /// surrogates, and NUL as `C0 80`; both are invalid UTF-8. These are converted to a 4-byte
+    /// UTF-8 sequence and a NUL byte (or removed; see
+    /// [`ConversionOptions::strip_modified_utf8_nul`]). Other invalid sequences, including
+    /// unpaired surrogates, fail the conversion; the error index and bytes are relative to the
+    /// stream with the preceding sequences converted.
+    ///
+    /// When used as a [`DecodePolicy`][crate::DecodePolicy], `C0 80` is always converted to a
+    /// NUL byte.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::ConversionOptions;
+    /// # use utf8_command::Strictness;
+    /// let converted = ConversionOptions::new()
+    ///     .stdout(Strictness::Cesu8)
+    ///     .convert(Output {
+    ///         status: ExitStatus::default(),
+    ///         stdout: Vec::from(b"puppy \xed\xa0\xbd\xed\xb0\xb6\xc0\x80"),
+    ///         stderr: Vec::from(b""),
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(converted.stdout, "puppy 🐶\0");
+    /// ```
+    Cesu8,
+}
+
+impl Strictness {
+    /// Decode a stream.
+    ///
+    /// Returns the stream's contents if the stream failed to decode but the conversion shouldn't
+    /// fail, so that lossy decoding can be deferred until we know the other stream decoded
+    /// successfully.
+    fn decode(self, bytes: Vec<u8>, strip_nul: bool) -> Result<StreamContents, FromUtf8Error> {
+        if self == Strictness::Cesu8 {
+            return decode_cesu8(bytes, strip_nul).map(StreamContents::Decoded);
+        }
+
+        if self == Strictness::Ascii && bytes.is_ascii() {
+            // SAFETY: ASCII is valid UTF-8.
+            let decoded = unsafe { String::from_utf8_unchecked(bytes) };
+            return Ok(StreamContents::Decoded(decoded));
+        }
+
+        match (self, string_from_utf8(bytes)) {
+            (_, Ok(decoded)) => Ok(StreamContents::Decoded(decoded)),
+            (Strictness::Strict | Strictness::Ascii | Strictness::Cesu8, Err(err)) => Err(err),
+            (Strictness::Lossy, Err(err)) => Ok(StreamContents::Raw(err.into_bytes())),
+        }
+    }
+}
+
+/// What to do with invalid UTF-8 in a stream which is decoded lossily.
+///
+/// See [`ConversionOptions::invalid_bytes`]. This is also a [`DecodePolicy`], which never fails.
+///
+/// [`DecodePolicy`]: crate::DecodePolicy
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum InvalidBytes {
+    /// Replace each invalid sequence with U+FFFD REPLACEMENT CHARACTER, like
+    /// [`String::from_utf8_lossy`].
+    #[default]
+    Replace,
+    /// Replace each invalid sequence with the given string.
+    ///
+    /// Invalid sequences are split the same way as [`String::from_utf8_lossy`], so this inserts
+    /// exactly one replacement wherever it would insert U+FFFD REPLACEMENT CHARACTER.
+    ReplaceWith(String),
+    /// Replace each invalid byte with the given string.
+    ReplaceEachByteWith(String),
+    /// Remove invalid sequences.
+    ///
+    /// Use [`ConversionOptions::convert_with_report`] to find out how many sequences were
+    /// removed, so the data loss isn't silent.
+    Drop,
+    /// Escape each invalid byte as `\xNN`, so the original bytes can be recovered with
+    /// [`unescape_invalid_bytes`][crate::unescape_invalid_bytes].
+    ///
+    /// To keep this reversible, a literal backslash is doubled if it's followed by a backslash,
+    /// an `x`, or an escaped byte; this applies to lossy streams which are valid UTF-8, too. See
+    /// [`escape_invalid_bytes`][crate::escape_invalid_bytes].
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::ConversionOptions;
+    /// # use utf8_command::InvalidBytes;
+    /// # use utf8_command::Strictness;
+    /// let options = ConversionOptions::new()
+    ///     .stdout(Strictness::Lossy)
+    ///     .invalid_bytes(InvalidBytes::HexEscape);
+    /// let original = Output {
+    ///     status: ExitStatus::default(),
+    ///     stdout: Vec::from(b"puppy \xf0\x9f\x90 \\x41"),
+    ///     stderr: Vec::from(b""),
+    /// };
+    /// let converted = options.convert(original.clone()).unwrap();
+    /// assert_eq!(converted.stdout, "puppy \\xf0\\x9f\\x90 \\\\x41");
+    /// assert_eq!(
+    ///     utf8_command::unescape_invalid_bytes(&converted.stdout),
+    ///     original.stdout
+    /// );
+    /// ```
+    HexEscape,
+}
+
+impl InvalidBytes {
+    /// Decode `bytes`, handling invalid sequences according to `self`.
+    pub(crate) fn decode(&self, bytes: Vec<u8>) -> String {
+        self.decode_counted(bytes).0
+    }
+
+    /// Like [`InvalidBytes::decode`], but also returns the number of invalid sequences.
+    pub(crate) fn decode_counted(&self, bytes: Vec<u8>) -> (String, usize) {
+        if *self == InvalidBytes::HexEscape {
+            return hex_escape(&bytes);
+        }
+
+        let mut sequences = 0;
+        let decoded = decode_lossy_replacing(bytes, |decoded, _, invalid| {
+            sequences += 1;
+            match self {
+                InvalidBytes::Replace => decoded.push(char::REPLACEMENT_CHARACTER),
+                InvalidBytes::ReplaceWith(replacement) => decoded.push_str(replacement),
+                InvalidBytes::ReplaceEachByteWith(replacement) => {
+                    for _ in invalid {
+                        decoded.push_str(replacement);
+                    }
+                }
+                InvalidBytes::Drop => {}
+                InvalidBytes::HexEscape => unreachable!("Handled above"),
+            }
+        });
+        (decoded, sequences)
+    }
+}
+
+/// What to do with a UTF-8 byte order mark (U+FEFF) at the start of a stream.
+///
+/// Some Windows programs (like PowerShell) start their output with a byte order mark. Only a
+/// byte order mark at the very start of a stream is affected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BomHandling {
+    /// Keep the byte order mark in the decoded stream.
+    #[default]
+    Keep,
+    /// Remove the byte order mark from the decoded stream.
+    Strip,
+    /// Fail the conversion with a [`ContentError`] if a stream starts with a byte order mark.
+    Reject,
+}
+
+/// A UTF-8 byte order mark.
+const BOM: char = '\u{feff}';
+
+/// Which bytes of each stream to keep, if a stream is too long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StreamLimit {
+    /// Keep the first bytes of each stream.
+    Head(usize),
+    /// Keep the last bytes of each stream.
+    Tail(usize),
+}
+
+/// What to do with control characters in the decoded streams.
+///
+/// Control characters are the characters for which [`char::is_control`] is true: the C0
+/// controls (U+0000 to U+001F), DEL (U+007F), and the C1 controls (U+0080 to U+009F). Stray
+/// control characters can garble logs and terminals, and ESC can be used to inject escape
+/// sequences.
+///
+/// Characters in the allow-list are never affected; see
+/// [`ConversionOptions::allowed_control_characters`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ControlCharacters {
+    /// Keep control characters in the decoded streams.
+    #[default]
+    Keep,
+    /// Remove control characters from the decoded streams.
+    Strip,
+    /// Fail the conversion with a [`ContentError`] if a stream contains a control character.
+    Reject,
+}
+
+/// The control characters which are allowed by default: `\n`, `\t`, and `\r`.
+const DEFAULT_ALLOWED_CONTROL_CHARACTERS: &[char] = &['\n', '\t', '\r'];
+
+impl StreamContents {
+    /// Decode the stream lossily, returning the number of invalid sequences.
+    ///
+    /// Only streams decoded with [`Strictness::Lossy`] can contain invalid UTF-8, but valid
+    /// streams are also escaped if they're lossy and `invalid_bytes` is
+    /// [`InvalidBytes::HexEscape`].
+    fn into_lossy(self, strictness: Strictness, invalid_bytes: &InvalidBytes) -> (String, usize) {
+        match self {
+            StreamContents::Decoded(decoded)
+                if strictness == Strictness::Lossy
+                    && *invalid_bytes == InvalidBytes::HexEscape
+                    && decoded.contains('\\') =>
+            {
+                (hex_escape(decoded.as_bytes()).0, 0)
+            }
+            StreamContents::Decoded(decoded) => (decoded, 0),
+            StreamContents::Raw(bytes) => invalid_bytes.decode_counted(bytes),
+        }
+    }
+}
+
+/// Options for converting an [`Output`] into a [`Utf8Output`], configured separately for each
+/// stream.
+///
+/// By default, both streams are decoded strictly, which matches the [`TryFrom`] implementations
+/// for [`Utf8Output`].
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use std::process::Output;
+/// # use utf8_command::ConversionOptions;
+/// # use utf8_command::Strictness;
+/// let options = ConversionOptions::new()
+///     .stdout(Strictness::Strict)
+///     .stderr(Strictness::Lossy);
+///
+/// let output = options
+///     .convert(Output {
+///         status: ExitStatus::default(),
+///         stdout: Vec::from(b"puppy"),
+///         stderr: Vec::from(b"doggy \xc0"), // Invalid byte.
+///     })
+///     .unwrap();
+/// assert_eq!(output.stdout, "puppy");
+/// assert_eq!(output.stderr, "doggy �");
+///
+/// let err = options
+///     .convert(Output {
+///         status: ExitStatus::default(),
+///         stdout: Vec::from(b"puppy \xc0"),
+///         stderr: Vec::from(b"doggy"),
+///     })
+///     .unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConversionOptions {
+    stdout: Strictness,
+    stderr: Strictness,
+    invalid_bytes: InvalidBytes,
+    redactions: Vec<Redaction>,
+    truncate_errors: bool,
+    stream_limit: Option<StreamLimit>,
+    detect_utf16: bool,
+    strip_modified_utf8_nul: bool,
+    bom: BomHandling,
+    reject_nul: bool,
+    control_characters: ControlCharacters,
+    /// The allowed control characters, or `None` for [`DEFAULT_ALLOWED_CONTROL_CHARACTERS`].
+    allowed_control_characters: Option<Vec<char>>,
+    line_endings: LineEndings,
+    chomp: bool,
+    #[cfg(feature = "ansi")]
+    strip_ansi: bool,
+    #[cfg(feature = "unicode-normalization")]
+    unicode_normalization: crate::UnicodeNormalization,
+}
+
+impl ConversionOptions {
+    /// Construct the default options, which decode both streams strictly.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how strictly to decode `stdout`.
+    pub fn stdout(mut self, strictness: Strictness) -> Self {
+        self.stdout = strictness;
+        self
+    }
+
+    /// Set how strictly to decode `stderr`.
+    pub fn stderr(mut self, strictness: Strictness) -> Self {
+        self.stderr = strictness;
+        self
+    }
+
+    /// Set what to do with invalid UTF-8 in streams decoded with [`Strictness::Lossy`].
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::ConversionOptions;
+    /// # use utf8_command::InvalidBytes;
+    /// # use utf8_command::Strictness;
+    /// let output = Output {
+    ///     status: ExitStatus::default(),
+    ///     stdout: Vec::from(b"puppy \xf0\x9f\x90 doggy \xc0\xc0"),
+    ///     stderr: Vec::from(b""),
+    /// };
+    ///
+    /// let options = ConversionOptions::new().stdout(Strictness::Lossy);
+    /// let converted = options
+    ///     .clone()
+    ///     .invalid_bytes(InvalidBytes::ReplaceWith(String::from("<?>")))
+    ///     .convert(output.clone())
+    ///     .unwrap();
+    /// assert_eq!(converted.stdout, "puppy <?> doggy <?><?>");
+    ///
+    /// let converted = options
+    ///     .invalid_bytes(InvalidBytes::ReplaceEachByteWith(String::from("?")))
+    ///     .convert(output)
+    ///     .unwrap();
+    /// assert_eq!(converted.stdout, "puppy ??? doggy ??");
+    /// ```
+    pub fn invalid_bytes(mut self, invalid_bytes: InvalidBytes) -> Self {
+        self.invalid_bytes = invalid_bytes;
+        self
+    }
+
+    /// Redact a secret (like a token or password) from both streams, replacing it with
+    /// `[REDACTED]`.
+    ///
+    /// Secrets are redacted from the raw bytes before anything else, so they never appear in the
+    /// converted output, in error messages, or in the bytes kept by errors (like
+    /// [`FromUtf8Error::as_bytes`] and [`ConversionError::into_output`]). Indexes in errors are
+    /// relative to the redacted streams. Streams transcoded from UTF-16 (see
+    /// [`ConversionOptions::detect_utf16`]) or [CESU-8](Strictness::Cesu8) are redacted again
+    /// after decoding. This can be called multiple times to redact multiple secrets.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::ConversionOptions;
+    /// let options = ConversionOptions::new().redact("hunter2");
+    ///
+    /// let converted = options
+    ///     .convert(Output {
+    ///         status: ExitStatus::default(),
+    ///         stdout: Vec::from(b"logging in with hunter2"),
+    ///         stderr: Vec::from(b""),
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(converted.stdout, "logging in with [REDACTED]");
+    ///
+    /// let err = options
+    ///     .convert(Output {
+    ///         status: ExitStatus::default(),
+    ///         stdout: Vec::from(b"hunter2 \xc0"),
+    ///         stderr: Vec::from(b""),
+    ///     })
+    ///     .unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Stdout contained invalid utf-8 sequence of 1 bytes from index 11: \"[REDACTED] �\""
+    /// );
+    /// ```
+    pub fn redact(mut self, secret: impl Into<String>) -> Self {
+        self.redactions.push(Redaction::Literal(secret.into()));
+        self
+    }
+
+    /// Redact matches of a regular expression from both streams, replacing them with
+    /// `[REDACTED]`.
+    ///
+    /// See [`ConversionOptions::redact`]. The regular expression matches the raw bytes of each
+    /// stream, so a match can include invalid UTF-8 or part of a multi-byte character (for
+    /// example, with `(?-u:\xff)`); redacting it can make an invalid stream valid, or a valid
+    /// stream invalid. Requires the `regex` feature.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::ConversionOptions;
+    /// let converted = ConversionOptions::new()
+    ///     .redact_regex(regex::bytes::Regex::new("ghp_[A-Za-z0-9]+").unwrap())
+    ///     .convert(Output {
+    ///         status: ExitStatus::default(),
+    ///         stdout: Vec::from(b"GITHUB_TOKEN=ghp_abc123"),
+    ///         stderr: Vec::from(b""),
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(converted.stdout, "GITHUB_TOKEN=[REDACTED]");
+    /// ```
+    #[cfg(feature = "regex")]
+    pub fn redact_regex(mut self, regex: regex::bytes::Regex) -> Self {
+        self.redactions.push(Redaction::Regex(regex));
+        self
+    }
+
+    /// Set whether to truncate the bytes stored in errors.
+    ///
+    /// By default, an [`Error`] keeps the entire stream which failed to decode (and the other
+    /// stream, if it decoded successfully), so that the [`Output`] can be reconstructed with
+    /// [`Error::into_output`]. For very large outputs, this can keep a lot of memory alive.
+    ///
+    /// When enabled, errors only keep a few KiB on either side of each decoding error. The error
+    /// message is unchanged, including the index of the error and the number of bytes around the
+    /// displayed context. A stream which decoded successfully is only kept if it's similarly small.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::ConversionOptions;
+    /// let mut stdout = "puppy ".repeat(100_000).into_bytes();
+    /// stdout[300_000] = 0xc0;
+    /// let output = Output {
+    ///     status: ExitStatus::default(),
+    ///     stdout,
+    ///     stderr: Vec::from(b""),
+    /// };
+    ///
+    /// let full = ConversionOptions::new().convert(output.clone()).unwrap_err();
+    /// let truncated = ConversionOptions::new()
+    ///     .truncate_errors(true)
+    ///     .convert(output)
+    ///     .unwrap_err();
+    /// let truncated = truncated.decode_error().unwrap();
+    /// assert!(truncated.is_truncated());
+    /// assert!(truncated.inner().as_bytes().len() < 10_000);
+    /// assert_eq!(truncated.discarded_bytes() + truncated.inner().as_bytes().len(), 600_000);
+    /// assert_eq!(truncated.to_string(), full.to_string());
+    /// ```
+    pub fn truncate_errors(mut self, truncate: bool) -> Self {
+        self.truncate_errors = truncate;
+        self
+    }
+
+    /// Set the maximum number of bytes to decode from each stream.
+    ///
+    /// Bytes past the limit are discarded before decoding, so a command which prints far more
+    /// than expected can't exhaust memory during the conversion. The cut is moved back to a code
+    /// point boundary, so a multi-byte character is never split and the kept prefix of a valid
+    /// stream still decodes. Use [`ConversionOptions::convert_with_report`] to find out if a
+    /// stream was truncated.
+    ///
+    /// Indexes in errors are relative to the kept bytes, and [`ConversionError::into_output`]
+    /// returns the kept bytes.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::ConversionOptions;
+    /// let (converted, report) = ConversionOptions::new()
+    ///     .max_stream_bytes(8)
+    ///     .convert_with_report(Output {
+    ///         status: ExitStatus::default(),
+    ///         stdout: Vec::from("puppy 🐶🐶"),
+    ///         stderr: Vec::from(b"doggy"),
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(converted.stdout, "puppy ");
+    /// assert_eq!(converted.stderr, "doggy");
+    /// assert!(report.truncated_stdout());
+    /// assert_eq!(report.stdout_discarded, 8);
+    /// assert!(!report.truncated_stderr());
+    /// ```
+    pub fn max_stream_bytes(mut self, max: usize) -> Self {
+        self.stream_limit = Some(StreamLimit::Head(max));
+        self
+    }
+
+    /// Keep only the last `max` bytes of each stream.
+    ///
+    /// This is useful for long-running commands like builds, where only the end of the output
+    /// (like an error summary) is interesting. This is like
+    /// [`ConversionOptions::max_stream_bytes`], but the start of each stream is discarded
+    /// instead; the cut is moved forward to a code point boundary, so the kept suffix of a valid
+    /// stream still decodes. This replaces `max_stream_bytes`, and vice versa.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::ConversionOptions;
+    /// let (converted, report) = ConversionOptions::new()
+    ///     .tail_stream_bytes(11)
+    ///     .convert_with_report(Output {
+    ///         status: ExitStatus::default(),
+    ///         stdout: Vec::from("[1/3] 🐶\n[2/3] 🐶\n[3/3] 🐶\nerror: no"),
+    ///         stderr: Vec::from(b""),
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(converted.stdout, "\nerror: no");
+    /// assert_eq!(report.stdout_discarded, 32);
+    /// ```
+    pub fn tail_stream_bytes(mut self, max: usize) -> Self {
+        self.stream_limit = Some(StreamLimit::Tail(max));
+        self
+    }
+
+    /// Set whether to detect UTF-16 streams and transcode them to UTF-8.
+    ///
+    /// Some Windows tools (like `wmic` and PowerShell) write UTF-16LE, which is mostly NUL bytes
+    /// when decoded as UTF-8. When enabled, a stream is treated as UTF-16 if it starts with a
+    /// UTF-16 byte order mark (which is removed), or if it's at least 16 bytes long, has a NUL
+    /// byte in at least half of its even or odd positions and few in the others, and transcodes
+    /// without any unpaired surrogates. Unpaired surrogates in a stream with a byte order mark are
+    /// replaced with U+FFFD REPLACEMENT CHARACTER. Use [`ConversionOptions::convert_with_report`]
+    /// to find out which streams were transcoded.
+    ///
+    /// Without a byte order mark, this is a guess, and NUL-separated UTF-8 can be mistaken for
+    /// UTF-16: the output of `find -print0` (or `xargs -0` input) where every file name is one
+    /// ASCII character, like `a\0b\0c\0d\0e\0f\0g\0h\0`, is transcoded to `abcdefgh`. Only enable
+    /// this for commands which might write UTF-16.
+    ///
+    /// UTF-16 is detected after [`ConversionOptions::max_stream_bytes`] is applied. A transcoded
+    /// stream is never a decoding error, and errors keep its UTF-8 contents.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::ConversionOptions;
+    /// # use utf8_command::Utf16Endianness;
+    /// let (converted, report) = ConversionOptions::new()
+    ///     .detect_utf16(true)
+    ///     .convert_with_report(Output {
+    ///         status: ExitStatus::default(),
+    ///         stdout: Vec::from(b"\xff\xfeN\0a\0m\0e\0\r\0\n\0"),
+    ///         stderr: Vec::from(b"puppy"),
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(converted.stdout, "Name\r\n");
+    /// assert_eq!(report.stdout_utf16, Some(Utf16Endianness::Little));
+    /// assert_eq!(converted.stderr, "puppy");
+    /// assert_eq!(report.stderr_utf16, None);
+    /// ```
+    pub fn detect_utf16(mut self, detect: bool) -> Self {
+        self.detect_utf16 = detect;
+        self
+    }
+
+    /// Set whether to remove NULs encoded as `C0 80` in streams decoded with
+    /// [`Strictness::Cesu8`], rather than converting them to NUL bytes.
+    pub fn strip_modified_utf8_nul(mut self, strip: bool) -> Self {
+        self.strip_modified_utf8_nul = strip;
+        self
+    }
+
+    /// Set what to do with a byte order mark at the start of each stream.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::BomHandling;
+    /// # use utf8_command::ConversionOptions;
+    /// let output = Output {
+    ///     status: ExitStatus::default(),
+    ///     stdout: Vec::from(b"\xef\xbb\xbfversion 1.2"),
+    ///     stderr: Vec::from(b""),
+    /// };
+    ///
+    /// let converted = ConversionOptions::new()
+    ///     .bom(BomHandling::Strip)
+    ///     .convert(output.clone())
+    ///     .unwrap();
+    /// assert_eq!(converted.stdout, "version 1.2");
+    ///
+    /// let err = ConversionOptions::new()
+    ///     .bom(BomHandling::Reject)
+    ///     .convert(output)
+    ///     .unwrap_err();
+    /// assert_eq!(err.to_string(), "Stdout started with a byte order mark");
+    /// ```
+    pub fn bom(mut self, bom: BomHandling) -> Self {
+        self.bom = bom;
+        self
+    }
+
+    /// Set whether to fail the conversion if either stream contains a NUL byte (`\0`).
+    ///
+    /// NUL bytes are valid UTF-8, but they're usually a sign that a command printed binary data,
+    /// and they can't be passed to C APIs or used as arguments to other commands. This works with
+    /// both strict and lossy decoding, and the [`ContentError`] reports the first NUL byte in
+    /// the decoded stream.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::ConversionOptions;
+    /// let err = ConversionOptions::new()
+    ///     .reject_nul(true)
+    ///     .convert(Output {
+    ///         status: ExitStatus::default(),
+    ///         stdout: Vec::from(b"puppy\0doggy"),
+    ///         stderr: Vec::from(b""),
+    ///     })
+    ///     .unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Stdout contained a NUL byte at index 5: \"puppy\\0doggy\""
+    /// );
+    /// ```
+    pub fn reject_nul(mut self, reject_nul: bool) -> Self {
+        self.reject_nul = reject_nul;
+        self
+    }
+
+    /// Set what to do with control characters in both streams.
+    ///
+    /// Control characters are handled after ANSI escape sequences are stripped (see
+    /// `strip_ansi`), so a stream can be stripped of colors and then checked for any other
+    /// escape characters. The offset in a [`ContentError`] is in the decoded stream.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::ControlCharacters;
+    /// # use utf8_command::ConversionOptions;
+    /// let output = Output {
+    ///     status: ExitStatus::default(),
+    ///     stdout: Vec::from(b"puppy\x08\x08\tdoggy\n"),
+    ///     stderr: Vec::from(b""),
+    /// };
+    ///
+    /// let converted = ConversionOptions::new()
+    ///     .control_characters(ControlCharacters::Strip)
+    ///     .convert(output.clone())
+    ///     .unwrap();
+    /// assert_eq!(converted.stdout, "puppy\tdoggy\n");
+    ///
+    /// let err = ConversionOptions::new()
+    ///     .control_characters(ControlCharacters::Reject)
+    ///     .convert(output)
+    ///     .unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Stdout contained control character '\\u{8}' at index 5: \"puppy\\u{8}\\u{8}\\tdoggy\\n\""
+    /// );
+    /// ```
+    pub fn control_characters(mut self, control_characters: ControlCharacters) -> Self {
+        self.control_characters = control_characters;
+        self
+    }
+
+    /// Set which control characters are allowed by [`ConversionOptions::control_characters`].
+    ///
+    /// By default, `\n`, `\t`, and `\r` are allowed. This replaces the default allow-list.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::ControlCharacters;
+    /// # use utf8_command::ConversionOptions;
+    /// let converted = ConversionOptions::new()
+    ///     .control_characters(ControlCharacters::Strip)
+    ///     .allowed_control_characters(['\n'])
+    ///     .convert(Output {
+    ///         status: ExitStatus::default(),
+    ///         stdout: Vec::from(b"puppy\r\n\tdoggy\n"),
+    ///         stderr: Vec::from(b""),
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(converted.stdout, "puppy\ndoggy\n");
+    /// ```
+    pub fn allowed_control_characters(mut self, allowed: impl IntoIterator<Item = char>) -> Self {
+        self.allowed_control_characters = Some(allowed.into_iter().collect());
+        self
+    }
+
+    /// Is `c` a control character which isn't allowed?
+    fn is_disallowed_control(&self, c: char) -> bool {
+        c.is_control()
+            && !self
+                .allowed_control_characters
+                .as_deref()
+                .unwrap_or(DEFAULT_ALLOWED_CONTROL_CHARACTERS)
+                .contains(&c)
+    }
+
+    /// Set how to normalize line endings in both streams.
+    ///
+    /// Line endings are normalized after decoding, so U+FFFD REPLACEMENT CHARACTERs from lossy
+    /// decoding are never treated as part of a line ending. The streams are normalized in place.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::ConversionOptions;
+    /// # use utf8_command::LineEndings;
+    /// let output = Output {
+    ///     status: ExitStatus::default(),
+    ///     stdout: Vec::from(b"puppy\r\ndoggy\r\n"),
+    ///     stderr: Vec::from(b"50%\r100%\r\n"),
+    /// };
+    ///
+    /// let converted = ConversionOptions::new()
+    ///     .line_endings(LineEndings::Crlf)
+    ///     .convert(output.clone())
+    ///     .unwrap();
+    /// assert_eq!(converted.stdout, "puppy\ndoggy\n");
+    /// assert_eq!(converted.stderr, "50%\r100%\n");
+    ///
+    /// let converted = ConversionOptions::new()
+    ///     .line_endings(LineEndings::All)
+    ///     .convert(output)
+    ///     .unwrap();
+    /// assert_eq!(converted.stderr, "50%\n100%\n");
+    /// ```
+    pub fn line_endings(mut self, line_endings: LineEndings) -> Self {
+        self.line_endings = line_endings;
+        self
+    }
+
+    /// Set whether to remove a single trailing newline from each stream.
+    ///
+    /// See [`Utf8Output::chomped`]. This is done after line endings are normalized.
+    pub fn chomp(mut self, chomp: bool) -> Self {
+        self.chomp = chomp;
+        self
+    }
+
+    /// Set whether to strip ANSI escape sequences (like colors, cursor movement, and OSC
+    /// hyperlinks) from both streams.
+    ///
+    /// Escape sequences are stripped after decoding (and before line endings are normalized), so
+    /// multi-byte characters are never corrupted. Requires the `ansi` feature.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::ConversionOptions;
+    /// let converted = ConversionOptions::new()
+    ///     .strip_ansi(true)
+    ///     .convert(Output {
+    ///         status: ExitStatus::default(),
+    ///         stdout: Vec::from(b"\x1b[32mpuppy\x1b[0m"),
+    ///         stderr: Vec::from(b""),
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(converted.stdout, "puppy");
+    /// ```
+    #[cfg(feature = "ansi")]
+    pub fn strip_ansi(mut self, strip_ansi: bool) -> Self {
+        self.strip_ansi = strip_ansi;
+        self
+    }
+
+    /// Set a Unicode normalization form to normalize both streams to.
+    ///
+    /// This is useful for comparing output against string literals; for example, macOS
+    /// commands often print filenames in NFD. Streams are normalized after lossy decoding, and
+    /// streams which are already normalized aren't copied. Requires the `unicode-normalization`
+    /// feature.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::ConversionOptions;
+    /// # use utf8_command::UnicodeNormalization;
+    /// let converted = ConversionOptions::new()
+    ///     .unicode_normalization(UnicodeNormalization::Nfc)
+    ///     .convert(Output {
+    ///         status: ExitStatus::default(),
+    ///         stdout: Vec::from("cafe\u{301}.txt\n"),
+    ///         stderr: Vec::from(b""),
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(converted.stdout, "café.txt\n");
+    /// ```
+    #[cfg(feature = "unicode-normalization")]
+    pub fn unicode_normalization(mut self, form: crate::UnicodeNormalization) -> Self {
+        self.unicode_normalization = form;
+        self
+    }
+
+    /// Convert an [`Output`] into a [`Utf8Output`] using these options.
+    pub fn convert(&self, output: Output) -> Result<Utf8Output, ConversionError> {
+        self.convert_with_report(output).map(|(output, _)| output)
+    }
+
+    /// Like [`ConversionOptions::convert`], but also reports how each stream was truncated.
+    pub fn convert_with_report(
+        &self,
+        output: Output,
+    ) -> Result<(Utf8Output, ConversionReport), ConversionError> {
+        let Output {
+            status,
+            mut stdout,
+            mut stderr,
+        } = output;
+
+        redact(&mut stdout, &self.redactions);
+        redact(&mut stderr, &self.redactions);
+
+        let mut report = ConversionReport::default();
+        match self.stream_limit {
+            None => {}
+            Some(StreamLimit::Head(max)) => {
+                report.stdout_discarded = keep_head(&mut stdout, max);
+                report.stderr_discarded = keep_head(&mut stderr, max);
+            }
+            Some(StreamLimit::Tail(max)) => {
+                report.stdout_discarded = keep_tail(&mut stdout, max);
+                report.stderr_discarded = keep_tail(&mut stderr, max);
+            }
+        }
+
+        let stdout = self.decode_stream(
+            self.stdout,
+            stdout,
+            &mut report.stdout_utf16,
+            &mut report.stdout_utf16_replacements,
+        );
+        let stderr = self.decode_stream(
+            self.stderr,
+            stderr,
+            &mut report.stderr_utf16,
+            &mut report.stderr_utf16_replacements,
+        );
+
+        match (stdout, stderr) {
+            (Ok(stdout), Ok(stderr)) => {
+                let (stdout, stdout_invalid_sequences) =
+                    stdout.into_lossy(self.stdout, &self.invalid_bytes);
+                let (stderr, stderr_invalid_sequences) =
+                    stderr.into_lossy(self.stderr, &self.invalid_bytes);
+                report.stdout_invalid_sequences = stdout_invalid_sequences;
+                report.stderr_invalid_sequences = stderr_invalid_sequences;
+                let output = self.finish(Utf8Output {
+                    status,
+                    stdout,
+                    stderr,
+                })?;
+                Ok((output, report))
+            }
+            (stdout, stderr) => {
+                let err = Error::from_owned_streams(
+                    status,
+                    stdout.map_err(StdoutError::from),
+                    stderr.map_err(StderrError::from),
+                );
+                if self.truncate_errors {
+                    Err(err.truncate().into())
+                } else {
+                    Err(err.into())
+                }
+            }
+        }
+    }
+
+    /// Decode a stream, transcoding it from UTF-16 if it's detected (and recording that in the
+    /// report).
+    fn decode_stream(
+        &self,
+        strictness: Strictness,
+        bytes: Vec<u8>,
+        utf16: &mut Option<Utf16Endianness>,
+        utf16_replacements: &mut usize,
+    ) -> Result<StreamContents, FromUtf8Error> {
+        if self.detect_utf16 {
+            if let Some((endianness, bom)) = crate::utf16::detect(&bytes) {
+                let (decoded, replacements) = crate::utf16::transcode(&bytes[bom..], endianness);
+                // Without a byte order mark, replacements mean the guess was probably wrong.
+                if bom > 0 || replacements == 0 {
+                    *utf16 = Some(endianness);
+                    *utf16_replacements = replacements;
+                    let mut decoded = decoded;
+                    redact_decoded(&mut decoded, &self.redactions);
+                    return Ok(StreamContents::Decoded(decoded));
+                }
+            }
+        }
+        match strictness.decode(bytes, self.strip_modified_utf8_nul) {
+            // Secrets with supplementary characters are encoded differently in CESU-8.
+            Ok(StreamContents::Decoded(mut decoded)) if strictness == Strictness::Cesu8 => {
+                redact_decoded(&mut decoded, &self.redactions);
+                Ok(StreamContents::Decoded(decoded))
+            }
+            result => result,
+        }
+    }
+
+    /// Apply the options which act on the decoded streams.
+    fn finish(&self, mut output: Utf8Output) -> Result<Utf8Output, ConversionError> {
+        if self.bom == BomHandling::Reject {
+            for stream in [Stream::Stdout, Stream::Stderr] {
+                if stream
+                    .select(&output.stdout, &output.stderr)
+                    .starts_with(BOM)
+                {
+                    return Err(ContentError::new(stream, ContentErrorKind::Bom, 0, output).into());
+                }
+            }
+        }
+
+        if self.reject_nul {
+            for stream in [Stream::Stdout, Stream::Stderr] {
+                if let Some(offset) = stream.select(&output.stdout, &output.stderr).find('\0') {
+                    return Err(
+                        ContentError::new(stream, ContentErrorKind::Nul, offset, output).into(),
+                    );
+                }
+            }
+        }
+
+        if self.bom == BomHandling::Strip {
+            for stream in [&mut output.stdout, &mut output.stderr] {
+                if stream.starts_with(BOM) {
+                    stream.drain(..BOM.len_utf8());
+                }
+            }
+        }
+
+        for stream in [Stream::Stdout, Stream::Stderr] {
+            if stream.select(self.stdout, self.stderr) != Strictness::Ascii {
+                continue;
+            }
+            let text = stream.select(&output.stdout, &output.stderr);
+            if let Some((offset, c)) = text.char_indices().find(|(_, c)| !c.is_ascii()) {
+                let kind = ContentErrorKind::NonAscii(c);
+                return Err(ContentError::new(stream, kind, offset, output).into());
+            }
+        }
+
+        #[cfg(feature = "ansi")]
+        if self.strip_ansi {
+            crate::ansi::strip_ansi(&mut output.stdout);
+            crate::ansi::strip_ansi(&mut output.stderr);
+        }
+
+        match self.control_characters {
+            ControlCharacters::Keep => {}
+            ControlCharacters::Strip => {
+                for stream in [&mut output.stdout, &mut output.stderr] {
+                    if stream.contains(|c| self.is_disallowed_control(c)) {
+                        stream.retain(|c| !self.is_disallowed_control(c));
+                    }
+                }
+            }
+            ControlCharacters::Reject => {
+                for stream in [Stream::Stdout, Stream::Stderr] {
+                    let text = stream.select(&output.stdout, &output.stderr);
+                    if let Some((offset, c)) = text
+                        .char_indices()
+                        .find(|&(_, c)| self.is_disallowed_control(c))
+                    {
+                        let kind = ContentErrorKind::Control(c);
+                        return Err(ContentError::new(stream, kind, offset, output).into());
+                    }
+                }
+            }
+        }
+
+        #[cfg(feature = "unicode-normalization")]
+        {
+            self.unicode_normalization.normalize(&mut output.stdout);
+            self.unicode_normalization.normalize(&mut output.stderr);
+        }
+
+        self.line_endings.normalize(&mut output.stdout);
+        self.line_endings.normalize(&mut output.stderr);
+
+        if self.chomp {
+            output = output.chomped();
+        }
+
+        Ok(output)
+    }
+}
+
+/// How the streams were truncated and decoded by [`ConversionOptions::convert_with_report`].
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use std::process::Output;
+/// # use utf8_command::ConversionOptions;
+/// # use utf8_command::InvalidBytes;
+/// # use utf8_command::Strictness;
+/// let (converted, report) = ConversionOptions::new()
+///     .stdout(Strictness::Lossy)
+///     .invalid_bytes(InvalidBytes::Drop)
+///     .convert_with_report(Output {
+///         status: ExitStatus::default(),
+///         stdout: Vec::from(b"\xc0puppy \xf0\x9f\x90doggy"),
+///         stderr: Vec::from(b""),
+///     })
+///     .unwrap();
+/// assert_eq!(converted.stdout, "puppy doggy");
+/// assert_eq!(report.stdout_invalid_sequences, 2);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConversionReport {
+    /// The number of bytes discarded from `stdout` before decoding.
+    pub stdout_discarded: usize,
+    /// The number of bytes discarded from `stderr` before decoding.
+    pub stderr_discarded: usize,
+    /// The number of invalid sequences in `stdout` which were replaced or removed by lossy
+    /// decoding.
+    ///
+    /// See [`ConversionOptions::invalid_bytes`].
+    pub stdout_invalid_sequences: usize,
+    /// The number of invalid sequences in `stderr` which were replaced or removed by lossy
+    /// decoding.
+    pub stderr_invalid_sequences: usize,
+    /// The byte order `stdout` was transcoded from, if it was detected as UTF-16.
+    ///
+    /// See [`ConversionOptions::detect_utf16`].
+    pub stdout_utf16: Option<Utf16Endianness>,
+    /// The byte order `stderr` was transcoded from, if it was detected as UTF-16.
+    pub stderr_utf16: Option<Utf16Endianness>,
+    /// The number of unpaired surrogates (or trailing odd bytes) in `stdout` which were
+    /// replaced with U+FFFD REPLACEMENT CHARACTER, if it was transcoded from UTF-16.
+    pub stdout_utf16_replacements: usize,
+    /// The number of unpaired surrogates (or trailing odd bytes) in `stderr` which were
+    /// replaced with U+FFFD REPLACEMENT CHARACTER, if it was transcoded from UTF-16.
+    pub stderr_utf16_replacements: usize,
+}
+
+impl ConversionReport {
+    /// Was `stdout` truncated?
+    pub fn truncated_stdout(&self) -> bool {
+        self.stdout_discarded != 0
+    }
+
+    /// Was `stderr` truncated?
+    pub fn truncated_stderr(&self) -> bool {
+        self.stderr_discarded != 0
+    }
+}
+
+/// An error produced by [`ConversionOptions::convert`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConversionError {
+    /// A stream contained invalid UTF-8.
+    Decode(Error),
+    /// A stream was valid UTF-8, but its contents were rejected.
+    Content(ContentError),
+}
+
+impl ConversionError {
+    /// Get the UTF-8 decoding error, if a stream contained invalid UTF-8.
+    pub fn decode_error(&self) -> Option<&Error> {
+        match self {
+            ConversionError::Decode(err) => Some(err),
+            ConversionError::Content(_) => None,
+        }
+    }
+
+    /// Get the content error, if a stream's contents were rejected.
+    pub fn content_error(&self) -> Option<&ContentError> {
+        match self {
+            ConversionError::Decode(_) => None,
+            ConversionError::Content(err) => Some(err),
+        }
+    }
+
+    /// Reconstruct the [`Output`] this error was produced from.
+    ///
+    /// See [`Error::into_output`] and [`ContentError::into_output`].
+    pub fn into_output(self) -> Output {
+        match self {
+            ConversionError::Decode(err) => err.into_output(),
+            ConversionError::Content(err) => err.into_output(),
+        }
+    }
+}
+
+impl From<Error> for ConversionError {
+    fn from(value: Error) -> Self {
+        Self::Decode(value)
+    }
+}
+
+impl From<ContentError> for ConversionError {
+    fn from(value: ContentError) -> Self {
+        Self::Content(value)
+    }
+}
+
+impl Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConversionError::Decode(err) => write!(f, "{err}"),
+            ConversionError::Content(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConversionError::Decode(err) => Some(err),
+            ConversionError::Content(err) => Some(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::test_util::output;
+    use crate::test_util::utf8_output;
+
+    /// Convert an output which should fail to decode.
+    fn decode_err(options: &ConversionOptions, output: Output) -> Error {
+        match options.convert(output) {
+            Err(ConversionError::Decode(err)) => err,
+            result => panic!("Expected a decoding error: {result:?}"),
+        }
+    }
+
+    #[test]
+    fn test_default_is_strict() {
+        assert_eq!(
+            ConversionOptions::new().convert(output(b"puppy", b"doggy\xc0")),
+            Utf8Output::try_from(output(b"puppy", b"doggy\xc0")).map_err(ConversionError::from)
+        );
+    }
+
+    #[test]
+    fn test_strict_stdout_lossy_stderr() {
+        let options = ConversionOptions::new()
+            .stdout(Strictness::Strict)
+            .stderr(Strictness::Lossy);
+
+        let converted = options.convert(output(b"puppy", b"doggy\xc0")).unwrap();
+        assert_eq!(converted.stdout, "puppy");
+        assert_eq!(converted.stderr, "doggy\u{fffd}");
+
+        let err = options
+            .convert(output(b"puppy\xc0", b"doggy\xc0"))
+            .unwrap_err();
+        assert!(matches!(err, ConversionError::Decode(Error::Stdout(_))));
+
+        let err = options.convert(output(b"puppy\xc0", b"\xc0")).unwrap_err();
+        assert!(matches!(err, ConversionError::Decode(Error::Stdout(_))));
+    }
+
+    #[test]
+    fn test_lossy_stdout_strict_stderr() {
+        let options = ConversionOptions::new()
+            .stdout(Strictness::Lossy)
+            .stderr(Strictness::Strict);
+
+        let converted = options.convert(output(b"\xc0puppy", b"doggy")).unwrap();
+        assert_eq!(converted.stdout, "\u{fffd}puppy");
+        assert_eq!(converted.stderr, "doggy");
+
+        let err = options
+            .convert(output(b"\xc0puppy", b"doggy\xc0"))
+            .unwrap_err();
+        assert!(matches!(err, ConversionError::Decode(Error::Stderr(_))));
+    }
+
+    #[test]
+    fn test_into_output_keeps_lossy_stream() {
+        let options = ConversionOptions::new()
+            .stdout(Strictness::Strict)
+            .stderr(Strictness::Lossy);
+        let original = output(b"puppy\xc0", b"doggy\xc0");
+        let err = decode_err(&options, original.clone());
+        // The lossy stream wasn't decoded, because the conversion failed.
+        assert_eq!(err.partial_stderr(), None);
+        assert_eq!(err.into_output(), original);
+
+        let err = decode_err(&options, output(b"puppy\xc0", b"doggy"));
+        assert_eq!(err.partial_stderr(), Some("doggy"));
+    }
+
+    /// None of these bytes can form a literal U+FFFD (`\xef\xbf\xbd`).
+    const ALPHABET: &[u8] = b"a \n\x80\x8a\x93\x98\x9c\x9f\xa9\xc0\xc3\xe2\xf0\xff";
+
+    proptest! {
+        #[test]
+        fn test_invalid_bytes_matches_std(
+            bytes in prop::collection::vec(prop::sample::select(ALPHABET), 0..32),
+        ) {
+            let reference = String::from_utf8_lossy(&bytes).into_owned();
+            let replace = InvalidBytes::ReplaceWith(String::from("\u{fffd}"));
+            let custom = InvalidBytes::ReplaceWith(String::from("<?>"));
+
+            prop_assert_eq!(InvalidBytes::Replace.decode(bytes.clone()), reference.clone());
+            prop_assert_eq!(replace.decode(bytes.clone()), reference.clone());
+            prop_assert_eq!(custom.decode(bytes), reference.replace('\u{fffd}', "<?>"));
+        }
+    }
+
+    #[test]
+    fn test_invalid_bytes_each_byte() {
+        let each_byte = InvalidBytes::ReplaceEachByteWith(String::from("<?>"));
+        assert_eq!(each_byte.decode(Vec::from("puppy 🐶")), "puppy 🐶");
+        // A truncated 4-byte sequence, and two invalid bytes.
+        assert_eq!(
+            each_byte.decode(Vec::from(b"\xf0\x9f\x90puppy\xc0\xff".as_slice())),
+            "<?><?><?>puppy<?><?>"
+        );
+        assert_eq!(
+            InvalidBytes::ReplaceEachByteWith(String::new()).decode(Vec::from(b"\xc0p".as_slice())),
+            "p"
+        );
+
+        // Strict streams still fail.
+        let options = ConversionOptions::new()
+            .stderr(Strictness::Lossy)
+            .invalid_bytes(each_byte);
+        let converted = options.convert(output(b"puppy", b"\xe2\x28\xa1")).unwrap();
+        assert_eq!(converted.stderr, "<?>(<?>");
+        decode_err(&options, output(b"puppy\xc0", b""));
+    }
+
+    #[test]
+    fn test_invalid_bytes_drop() {
+        let options = ConversionOptions::new()
+            .stdout(Strictness::Lossy)
+            .stderr(Strictness::Lossy)
+            .invalid_bytes(InvalidBytes::Drop);
+
+        // Invalid sequences at the start and end of the streams.
+        let (converted, report) = options
+            .convert_with_report(output(b"\xc0puppy\xf0\x9f\x90", b"\xffdoggy\xe2\x28\xa1"))
+            .unwrap();
+        assert_eq!(converted.stdout, "puppy");
+        assert_eq!(converted.stderr, "doggy(");
+        assert_eq!(report.stdout_invalid_sequences, 2);
+        assert_eq!(report.stderr_invalid_sequences, 3);
+
+        // A stream of only invalid bytes.
+        let (converted, report) = options
+            .convert_with_report(output(b"\xc0\xc1\x80\xff", b"doggy"))
+            .unwrap();
+        assert_eq!(converted.stdout, "");
+        assert_eq!(report.stdout_invalid_sequences, 4);
+        assert_eq!(converted.stderr, "doggy");
+        assert_eq!(report.stderr_invalid_sequences, 0);
+    }
+
+    #[test]
+    fn test_invalid_bytes_hex_escape() {
+        let options = ConversionOptions::new()
+            .stdout(Strictness::Lossy)
+            .stderr(Strictness::Lossy)
+            .invalid_bytes(InvalidBytes::HexEscape);
+
+        let original = output(b"\xc0puppy\\x41\xf0\x9f\x90", b"C:\\puppy\\xyz");
+        let (converted, report) = options.convert_with_report(original.clone()).unwrap();
+        assert_eq!(converted.stdout, "\\xc0puppy\\\\x41\\xf0\\x9f\\x90");
+        assert_eq!(report.stdout_invalid_sequences, 2);
+        // Valid streams are escaped too.
+        assert_eq!(converted.stderr, "C:\\puppy\\\\xyz");
+        assert_eq!(report.stderr_invalid_sequences, 0);
+        assert_eq!(
+            crate::unescape_invalid_bytes(&converted.stdout),
+            original.stdout
+        );
+        assert_eq!(
+            crate::unescape_invalid_bytes(&converted.stderr),
+            original.stderr
+        );
+
+        // Strict streams aren't escaped.
+        let converted = ConversionOptions::new()
+            .invalid_bytes(InvalidBytes::HexEscape)
+            .convert(output(b"\\x41", b""))
+            .unwrap();
+        assert_eq!(converted.stdout, "\\x41");
+    }
+
+    #[test]
+    fn test_redact() {
+        const SECRET: &str = "hunter2";
+        let options = ConversionOptions::new()
+            .redact(SECRET)
+            .redact("sk-1234")
+            .stderr(Strictness::Lossy);
+
+        let converted = options
+            .convert(output(b"password=hunter2", b"\xc0sk-1234\xc0"))
+            .unwrap();
+        assert_eq!(converted.stdout, "password=[REDACTED]");
+        assert_eq!(converted.stderr, "\u{fffd}[REDACTED]\u{fffd}");
+        assert!(!format!("{converted:?}").contains(SECRET));
+
+        // Decoding errors, including the other stream.
+        for original in [
+            output(b"password=hunter2\xc0", b"hunter2"),
+            output(b"\xc0", b"hunter2"),
+        ] {
+            let err = decode_err(&options, original);
+            assert!(!err.to_string().contains(SECRET), "{err}");
+            assert!(!format!("{err:?}").contains(SECRET), "{err:?}");
+            let bytes = err.inner().as_bytes();
+            assert!(!String::from_utf8_lossy(bytes).contains(SECRET));
+            let output = err.into_output();
+            assert!(!String::from_utf8_lossy(&output.stdout).contains(SECRET));
+            assert!(!String::from_utf8_lossy(&output.stderr).contains(SECRET));
+        }
+
+        // Content errors.
+        let err = options
+            .clone()
+            .reject_nul(true)
+            .convert(output(b"hunter2\0", b""))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Stdout contained a NUL byte at index 10: \"[REDACTED]\\0\""
+        );
+        assert!(!format!("{err:?}").contains(SECRET));
+    }
+
+    #[test]
+    fn test_redact_transcoded() {
+        let options = ConversionOptions::new()
+            .redact("hunter2")
+            .redact("🐶")
+            .detect_utf16(true)
+            .stderr(Strictness::Cesu8);
+
+        let utf16: Vec<u8> = "password=hunter2"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        // U+1F436 as a CESU-8 surrogate pair.
+        let converted = options
+            .convert(output(&utf16, b"puppy \xed\xa0\xbd\xed\xb0\xb6"))
+            .unwrap();
+        assert_eq!(converted.stdout, "password=[REDACTED]");
+        assert_eq!(converted.stderr, "puppy [REDACTED]");
+
+        // The transcoded stream is kept redacted when the other stream fails to decode.
+        let err = decode_err(&options, output(&utf16, b"\xc0"));
+        let output = err.into_output();
+        assert_eq!(output.stdout, b"password=[REDACTED]");
+    }
+
+    #[test]
+    fn test_truncate_errors() {
+        let options = ConversionOptions::new().truncate_errors(true);
+
+        // Small streams aren't truncated.
+        let err = decode_err(&options, output(b"puppy", b"doggy\xc0"));
+        assert!(!err.is_truncated());
+        assert_eq!(err.discarded_bytes(), 0);
+        assert_eq!(err.partial_stdout(), Some("puppy"));
+
+        for (index, invalid) in [
+            (0, b"\xc0".as_slice()),
+            (4, b"\xe2\x28\xa1"),
+            (100_000, b"\xf0\x9f\x90\xf0"),
+            (299_992, b"\xf0\x90\x28"),
+            (299_996, b"\xc0"),
+        ] {
+            let mut stdout = "🐶".repeat(75_000).into_bytes();
+            stdout[index..index + invalid.len()].copy_from_slice(invalid);
+            let stderr = "doggy ".repeat(10_000);
+            let original = output(&stdout, stderr.as_bytes());
+
+            let full = decode_err(&ConversionOptions::new(), original.clone());
+            let err = decode_err(&options, original);
+
+            assert!(err.is_truncated());
+            assert_eq!(err.to_string(), full.to_string());
+            let stdout_err = err.stdout_error().unwrap();
+            assert_eq!(stdout_err.valid_up_to(), index);
+            assert_eq!(
+                stdout_err.valid_up_to(),
+                full.stdout_error().unwrap().valid_up_to()
+            );
+            assert_eq!(
+                stdout_err.inner().utf8_error().error_len(),
+                full.inner().utf8_error().error_len()
+            );
+            assert_eq!(
+                err.discarded_bytes() + err.inner().as_bytes().len(),
+                stdout.len()
+            );
+            assert!(err.inner().as_bytes().len() <= 2 * crate::ERROR_RETAINED_BYTES);
+            // The large stderr isn't kept.
+            assert_eq!(err.partial_stderr(), None);
+        }
+    }
+
+    #[test]
+    fn test_ascii() {
+        let options = ConversionOptions::new()
+            .stdout(Strictness::Ascii)
+            .stderr(Strictness::Lossy);
+
+        let converted = options
+            .convert(output(b"3f2a9c\n", b"doggy \xf0\x9f\x90\xb6\xc0"))
+            .unwrap();
+        assert_eq!(converted.stdout, "3f2a9c\n");
+        assert_eq!(converted.stderr, "doggy 🐶\u{fffd}");
+
+        // A multi-byte character is rejected.
+        let original = output("puppy “3f2a9c”".as_bytes(), b"");
+        let err = options.convert(original.clone()).unwrap_err();
+        let content = err.content_error().unwrap();
+        assert_eq!(content.stream(), Stream::Stdout);
+        assert_eq!(content.kind(), ContentErrorKind::NonAscii('“'));
+        assert_eq!(content.offset(), 6);
+        assert_eq!(
+            err.to_string(),
+            "Stdout contained non-ASCII character '“' at index 6: \"puppy “3f2a9c”\""
+        );
+        assert_eq!(err.into_output(), original);
+
+        // Invalid UTF-8 is a decoding error.
+        let err = decode_err(&options, output(b"\xf0\x9f\x90\xb6\xc0", b""));
+        assert_eq!(err.stdout_error().unwrap().valid_up_to(), 4);
+
+        // A stripped byte order mark isn't rejected.
+        let converted = options
+            .bom(BomHandling::Strip)
+            .convert(output(b"\xef\xbb\xbfpuppy", b""))
+            .unwrap();
+        assert_eq!(converted.stdout, "puppy");
+    }
+
+    #[test]
+    fn test_max_stream_bytes() {
+        let options = ConversionOptions::new().max_stream_bytes(6);
+
+        let (converted, report) = options
+            .convert_with_report(output(b"puppy", "doggy🐶".as_bytes()))
+            .unwrap();
+        assert_eq!(converted.stdout, "puppy");
+        // The cut is moved back to before the emoji.
+        assert_eq!(converted.stderr, "doggy");
+        assert_eq!(
+            report,
+            ConversionReport {
+                stdout_discarded: 0,
+                stderr_discarded: 4,
+                ..Default::default()
+            }
+        );
+        assert!(!report.truncated_stdout());
+        assert!(report.truncated_stderr());
+
+        // Invalid UTF-8 past the limit is never decoded.
+        let (converted, report) = options
+            .convert_with_report(output(b"puppy doggy\xc0", b""))
+            .unwrap();
+        assert_eq!(converted.stdout, "puppy ");
+        assert_eq!(report.stdout_discarded, 6);
+
+        // Errors are relative to the kept bytes.
+        let err = decode_err(&options, output(b"pup\xc0py doggy", b""));
+        assert_eq!(err.into_output(), output(b"pup\xc0py", b""));
+
+        // No limit by default.
+        let (_, report) = ConversionOptions::new()
+            .convert_with_report(output(&[b'a'; 100_000], b""))
+            .unwrap();
+        assert_eq!(report, ConversionReport::default());
+    }
+
+    #[test]
+    fn test_tail_stream_bytes() {
+        let options = ConversionOptions::new().tail_stream_bytes(6);
+
+        // The cut lands in the middle of an emoji, and is moved forward to after it.
+        let (converted, report) = options
+            .convert_with_report(output("🐶doggy".as_bytes(), b"puppy"))
+            .unwrap();
+        assert_eq!(converted.stdout, "doggy");
+        // The whole stream is smaller than the limit.
+        assert_eq!(converted.stderr, "puppy");
+        assert_eq!(
+            report,
+            ConversionReport {
+                stdout_discarded: 4,
+                stderr_discarded: 0,
+                ..Default::default()
+            }
+        );
+
+        // Invalid UTF-8 before the kept bytes is never decoded.
+        let (converted, report) = options
+            .convert_with_report(output(b"\xc0puppy doggy", b""))
+            .unwrap();
+        assert_eq!(converted.stdout, " doggy");
+        assert_eq!(report.stdout_discarded, 6);
+
+        // The last limit set wins.
+        let converted = options
+            .max_stream_bytes(6)
+            .convert(output(b"puppy doggy", b""))
+            .unwrap();
+        assert_eq!(converted.stdout, "puppy ");
+    }
+
+    #[test]
+    fn test_detect_utf16() {
+        let options = ConversionOptions::new().detect_utf16(true);
+        let utf16le =
+            |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+
+        // Only stdout is UTF-16, without a byte order mark.
+        let (converted, report) = options
+            .convert_with_report(output(&utf16le("Node,Name\r\n🐶,puppy\r\n"), b"doggy"))
+            .unwrap();
+        assert_eq!(converted.stdout, "Node,Name\r\n🐶,puppy\r\n");
+        assert_eq!(converted.stderr, "doggy");
+        assert_eq!(
+            report,
+            ConversionReport {
+                stdout_utf16: Some(Utf16Endianness::Little),
+                ..Default::default()
+            }
+        );
+
+        // Unpaired surrogates are replaced and reported.
+        let (converted, report) = options
+            .convert_with_report(output(b"", b"\xff\xfep\0\x3d\xd8u\0"))
+            .unwrap();
+        assert_eq!(converted.stderr, "p\u{fffd}u");
+        assert_eq!(report.stderr_utf16, Some(Utf16Endianness::Little));
+        assert_eq!(report.stderr_utf16_replacements, 1);
+
+        // Off by default.
+        let converted = ConversionOptions::new()
+            .convert(output(&utf16le("puppy"), b""))
+            .unwrap();
+        assert_eq!(converted.stdout, "p\0u\0p\0p\0y\0");
+    }
+
+    #[test]
+    fn test_detect_utf16_print0() {
+        let options = ConversionOptions::new().detect_utf16(true);
+
+        // `find -print0` output is NUL-separated UTF-8, not UTF-16.
+        let (converted, report) = options.convert_with_report(output(b"a\0b\0", b"")).unwrap();
+        assert_eq!(converted.stdout, "a\0b\0");
+        assert_eq!(report.stdout_utf16, None);
+
+        // Long enough to guess, but transcoding it would produce a lone surrogate.
+        let stdout = "./a\0./b\0./c\0/ب\0./e\0";
+        let (converted, report) = options
+            .convert_with_report(output(stdout.as_bytes(), b""))
+            .unwrap();
+        assert_eq!(converted.stdout, stdout);
+        assert_eq!(report.stdout_utf16, None);
+
+        // A known false positive: one-character names are indistinguishable from UTF-16LE.
+        let converted = options
+            .convert(output(b"a\0b\0c\0d\0e\0f\0g\0h\0", b""))
+            .unwrap();
+        assert_eq!(converted.stdout, "abcdefgh");
+    }
+
+    #[test]
+    fn test_detect_utf16_other_stream_invalid() {
+        let options = ConversionOptions::new().detect_utf16(true);
+        let err = decode_err(&options, output(b"\xff\xfep\0", b"doggy\xc0"));
+        assert_eq!(err.partial_stdout(), Some("p"));
+        assert_eq!(err.stderr_error().unwrap().valid_up_to(), 5);
+    }
+
+    #[test]
+    fn test_cesu8() {
+        // 🐶 as a CESU-8 surrogate pair.
+        let dog = b"\xed\xa0\xbd\xed\xb0\xb6";
+        let stdout = [b"puppy ".as_slice(), dog, b"\xc0\x80"].concat();
+        let options = ConversionOptions::new().stdout(Strictness::Cesu8);
+
+        let converted = options.convert(output(&stdout, b"doggy")).unwrap();
+        assert_eq!(converted.stdout, "puppy 🐶\0");
+        let converted = options
+            .clone()
+            .strip_modified_utf8_nul(true)
+            .convert(output(&stdout, b"doggy"))
+            .unwrap();
+        assert_eq!(converted.stdout, "puppy 🐶");
+
+        // Only the configured stream accepts CESU-8.
+        let err = decode_err(&options, output(b"", &stdout));
+        assert_eq!(err.stderr_error().unwrap().valid_up_to(), 6);
+
+        // Malformed input is reported with the usual error.
+        let err = decode_err(&options, output(&[dog.as_slice(), b" \xc0"].concat(), b""));
+        assert_eq!(
+            err.to_string(),
+            "Stdout contained invalid utf-8 sequence of 1 bytes from index 5: \"🐶 �\""
+        );
+    }
+
+    #[test]
+    fn test_bom() {
+        let bom_only = output(b"\xef\xbb\xbf", b"");
+        let bom_twice = output(b"\xef\xbb\xbf\xef\xbb\xbfpuppy", b"\xef\xbb\xbfdoggy");
+        let no_bom = output(b"puppy \xef\xbb\xbf", b"doggy");
+
+        let keep = ConversionOptions::new();
+        assert_eq!(keep.convert(bom_only.clone()).unwrap().stdout, "\u{feff}");
+        assert_eq!(
+            keep.convert(bom_twice.clone()).unwrap().stdout,
+            "\u{feff}\u{feff}puppy"
+        );
+
+        let strip = ConversionOptions::new().bom(BomHandling::Strip);
+        assert_eq!(strip.convert(bom_only.clone()).unwrap().stdout, "");
+        let converted = strip.convert(bom_twice.clone()).unwrap();
+        // Only one byte order mark at the start is removed.
+        assert_eq!(converted.stdout, "\u{feff}puppy");
+        assert_eq!(converted.stderr, "doggy");
+        // A byte order mark which isn't at the start is kept.
+        assert_eq!(
+            strip.convert(no_bom.clone()).unwrap().stdout,
+            "puppy \u{feff}"
+        );
+
+        let reject = ConversionOptions::new().bom(BomHandling::Reject);
+        let err = reject.convert(bom_only.clone()).unwrap_err();
+        let content = err.content_error().unwrap();
+        assert_eq!(content.stream(), Stream::Stdout);
+        assert_eq!(content.kind(), ContentErrorKind::Bom);
+        assert_eq!(content.offset(), 0);
+        assert_eq!(err.into_output(), bom_only);
+        let err = reject
+            .convert(output(b"puppy", b"\xef\xbb\xbf"))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Stderr started with a byte order mark");
+        assert_eq!(
+            reject.convert(no_bom.clone()).unwrap(),
+            keep.convert(no_bom).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_bom_then_invalid() {
+        let original = output(b"\xef\xbb\xbf\xc0puppy", b"");
+        for bom in [BomHandling::Keep, BomHandling::Strip, BomHandling::Reject] {
+            let err = decode_err(&ConversionOptions::new().bom(bom), original.clone());
+            // The byte order mark is counted in the index.
+            assert_eq!(err.stdout_error().unwrap().valid_up_to(), 3);
+            assert_eq!(err.into_output(), original);
+        }
+
+        let converted = ConversionOptions::new()
+            .stdout(Strictness::Lossy)
+            .bom(BomHandling::Strip)
+            .convert(original)
+            .unwrap();
+        assert_eq!(converted.stdout, "\u{fffd}puppy");
+    }
+
+    #[test]
+    fn test_reject_nul() {
+        let options = ConversionOptions::new().reject_nul(true);
+        for (stdout, index) in [
+            (b"\0puppy".as_slice(), 0),
+            (b"puppy\0doggy\0", 5),
+            (b"puppy\0", 5),
+        ] {
+            let original = output(stdout, b"");
+            let err = options.convert(original.clone()).unwrap_err();
+            let content = err.content_error().unwrap();
+            assert_eq!(content.stream(), Stream::Stdout);
+            assert_eq!(content.kind(), ContentErrorKind::Nul);
+            assert_eq!(content.offset(), index);
+            assert_eq!(err.into_output(), original);
+        }
+
+        let err = options.convert(output(b"puppy", b"doggy\0")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Stderr contained a NUL byte at index 5: \"doggy\\0\""
+        );
+
+        // Off by default.
+        assert_eq!(
+            ConversionOptions::new()
+                .convert(output(b"\0", b""))
+                .unwrap()
+                .stdout,
+            "\0"
+        );
+    }
+
+    #[test]
+    fn test_reject_nul_lossy() {
+        let options = ConversionOptions::new()
+            .stdout(Strictness::Lossy)
+            .reject_nul(true);
+        // The offset is in the decoded stream.
+        let err = options
+            .convert(output(b"\xc0\xc0\0puppy", b""))
+            .unwrap_err();
+        assert_eq!(err.content_error().unwrap().offset(), 6);
+        assert_eq!(
+            err.to_string(),
+            "Stdout contained a NUL byte at index 6: \"\u{fffd}\u{fffd}\\0puppy\""
+        );
+
+        // Invalid UTF-8 in a strict stream is reported first.
+        let err = options
+            .convert(output(b"\0puppy", b"doggy\xc0"))
+            .unwrap_err();
+        assert!(matches!(err, ConversionError::Decode(Error::Stderr(_))));
+    }
+
+    #[test]
+    fn test_reject_control_characters() {
+        let options = ConversionOptions::new().control_characters(ControlCharacters::Reject);
+        for (stdout, index, c) in [
+            // ESC.
+            (b"\x1b[31mpuppy".as_slice(), 0, '\x1b'),
+            // DEL.
+            (b"puppy\x7f", 5, '\x7f'),
+            // C1 controls are valid UTF-8, but they're still control characters.
+            ("🐶\u{9b}31m".as_bytes(), 4, '\u{9b}'),
+            ("puppy\u{85}doggy".as_bytes(), 5, '\u{85}'),
+        ] {
+            let original = output(stdout, b"");
+            let err = options.convert(original.clone()).unwrap_err();
+            let content = err.content_error().unwrap();
+            assert_eq!(content.stream(), Stream::Stdout);
+            assert_eq!(content.kind(), ContentErrorKind::Control(c));
+            assert_eq!(content.offset(), index);
+            assert_eq!(err.into_output(), original);
+        }
+
+        let err = options
+            .convert(output(b"puppy\r\n\tdoggy\n", b"\x1b"))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Stderr contained control character '\\u{1b}' at index 0: \"\\u{1b}\""
+        );
+
+        // The allow-list can be replaced.
+        let options = options.allowed_control_characters(['\x1b']);
+        assert!(options.convert(output(b"\x1b[31m", b"")).is_ok());
+        assert_eq!(
+            options
+                .convert(output(b"puppy\n", b""))
+                .unwrap_err()
+                .content_error()
+                .unwrap()
+                .kind(),
+            ContentErrorKind::Control('\n')
+        );
+    }
+
+    #[test]
+    fn test_strip_control_characters() {
+        let options = ConversionOptions::new()
+            .stderr(Strictness::Lossy)
+            .control_characters(ControlCharacters::Strip);
+        let converted = options
+            .convert(output(
+                "\x1b[31m🐶\x7f\r\n\u{9b}\tpuppy".as_bytes(),
+                b"\xc0\x00doggy\x08",
+            ))
+            .unwrap();
+        assert_eq!(converted.stdout, "[31m🐶\r\n\tpuppy");
+        assert_eq!(converted.stderr, "\u{fffd}doggy");
+
+        let converted = options
+            .allowed_control_characters([])
+            .convert(output(b"puppy\r\n", b""))
+            .unwrap();
+        assert_eq!(converted.stdout, "puppy");
+    }
+
+    #[cfg(feature = "ansi")]
+    #[test]
+    fn test_control_characters_after_strip_ansi() {
+        let options = ConversionOptions::new()
+            .strip_ansi(true)
+            .control_characters(ControlCharacters::Reject);
+        let converted = options
+            .convert(output(b"\x1b[32mpuppy\x1b[0m\n", b""))
+            .unwrap();
+        assert_eq!(converted.stdout, "puppy\n");
+
+        let err = options
+            .convert(output(b"\x1b[32mpuppy\x08", b""))
+            .unwrap_err();
+        assert_eq!(err.content_error().unwrap().offset(), 5);
+    }
+
+    #[test]
+    fn test_line_endings_lossy() {
+        let options = ConversionOptions::new()
+            .stdout(Strictness::Lossy)
+            .stderr(Strictness::Lossy)
+            .line_endings(LineEndings::Crlf);
+
+        let converted = options
+            .convert(output(b"puppy\r\xc0\n\r\n", b"\xc0\r\n\r"))
+            .unwrap();
+        assert_eq!(converted.stdout, "puppy\r\u{fffd}\n\n");
+        assert_eq!(converted.stderr, "\u{fffd}\n\r");
+
+        // Strict decoding still fails, and the error has the original line endings.
+        let original = output(b"puppy\r\n\xc0", b"doggy\r\n");
+        let err = ConversionOptions::new()
+            .line_endings(LineEndings::All)
+            .convert(original.clone())
+            .unwrap_err();
+        assert_eq!(err.into_output(), original);
+    }
+
+    #[test]
+    fn test_chomp() {
+        let options = ConversionOptions::new().chomp(true);
+        let converted = options.convert(output(b"puppy\n\n", b"\r\n")).unwrap();
+        assert_eq!(converted.stdout, "puppy\n");
+        assert_eq!(converted.stderr, "");
+
+        // Line endings are normalized first.
+        let converted = options
+            .line_endings(LineEndings::All)
+            .convert(output(b"puppy\r\r", b"doggy\r"))
+            .unwrap();
+        assert_eq!(converted.stdout, "puppy\n");
+        assert_eq!(converted.stderr, "doggy");
+    }
+
+    #[cfg(feature = "ansi")]
+    #[test]
+    fn test_strip_ansi() {
+        let options = ConversionOptions::new()
+            .stdout(Strictness::Lossy)
+            .strip_ansi(true)
+            .line_endings(LineEndings::Crlf);
+        let converted = options
+            .convert(output(
+                b"\x1b[31m\xc0\x1b[0m\r\n\x1b[3\xc01m",
+                b"\x1b]8;;https://example.com\x07doggy\x1b]8;;\x07\r\x1b[K\n",
+            ))
+            .unwrap();
+        assert_eq!(converted.stdout, "\u{fffd}\n\u{fffd}1m");
+        // Line endings are normalized after escape sequences are stripped.
+        assert_eq!(converted.stderr, "doggy\n");
+
+        // Off by default.
+        let converted = ConversionOptions::new()
+            .convert(output(b"\x1b[31mpuppy", b""))
+            .unwrap();
+        assert_eq!(converted.stdout, "\x1b[31mpuppy");
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn test_unicode_normalization() {
+        use crate::UnicodeNormalization;
+
+        // Simulated `ls` output on macOS, with a decomposed "é".
+        let ls = output("cafe\u{301}.txt\npuppy.txt\n".as_bytes(), b"");
+        let converted = ConversionOptions::new()
+            .unicode_normalization(UnicodeNormalization::Nfc)
+            .convert(ls.clone())
+            .unwrap();
+        assert!(converted.stdout.lines().any(|line| line == "café.txt"));
+
+        // Off by default.
+        let converted = ConversionOptions::new().convert(ls).unwrap();
+        assert!(!converted.stdout.lines().any(|line| line == "café.txt"));
+
+        // Composes with lossy decoding.
+        let converted = ConversionOptions::new()
+            .stdout(Strictness::Lossy)
+            .unicode_normalization(UnicodeNormalization::Nfc)
+            .convert(output(b"cafe\xcc\x81\xc0", b""))
+            .unwrap();
+        assert_eq!(converted.stdout, "café\u{fffd}");
+    }
+
+    #[test]
+    fn test_both_lossy() {
+        let options = ConversionOptions::new()
+            .stdout(Strictness::Lossy)
+            .stderr(Strictness::Lossy);
+
+        // The options can be reused.
+        for _ in 0..2 {
+            assert_eq!(
+                options.convert(output(b"\xc0", b"\xf0\x90")).unwrap(),
+                utf8_output("\u{fffd}", "\u{fffd}")
+            );
+        }
+    }
+}
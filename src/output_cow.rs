@@ -0,0 +1,117 @@
+use std::borrow::Cow;
+use std::process::ExitStatus;
+use std::process::Output;
+
+use crate::Utf8Output;
+
+/// A lossily-decoded view of a [`std::process::Output`] which only allocates for streams
+/// containing invalid UTF-8.
+///
+/// Streams which are valid UTF-8 are borrowed from the [`Output`]; invalid UTF-8 sequences are
+/// replaced with U+FFFD REPLACEMENT CHARACTER, like [`String::from_utf8_lossy`].
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use std::process::Output;
+/// # use utf8_command::Utf8OutputCow;
+/// let output = Output {
+///     status: ExitStatus::default(),
+///     stdout: Vec::from(b"puppy"),
+///     stderr: Vec::from(b"doggy \xc0"),
+/// };
+/// let view = Utf8OutputCow::from(&output);
+/// assert_eq!(view.stdout, "puppy");
+/// assert!(view.stdout_is_borrowed());
+/// assert_eq!(view.stderr, "doggy �");
+/// assert!(!view.stderr_is_borrowed());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Utf8OutputCow<'a> {
+    /// The [`std::process::Command`]'s exit status.
+    pub status: ExitStatus,
+    /// The contents of the [`std::process::Command`]'s `stdout` stream, decoded lossily.
+    pub stdout: Cow<'a, str>,
+    /// The contents of the [`std::process::Command`]'s `stderr` stream, decoded lossily.
+    pub stderr: Cow<'a, str>,
+}
+
+impl Utf8OutputCow<'_> {
+    /// True if `stdout` was valid UTF-8 and is borrowed from the original [`Output`].
+    pub fn stdout_is_borrowed(&self) -> bool {
+        matches!(self.stdout, Cow::Borrowed(_))
+    }
+
+    /// True if `stderr` was valid UTF-8 and is borrowed from the original [`Output`].
+    pub fn stderr_is_borrowed(&self) -> bool {
+        matches!(self.stderr, Cow::Borrowed(_))
+    }
+
+    /// Convert into an owned [`Utf8Output`], copying any borrowed streams.
+    pub fn into_owned(self) -> Utf8Output {
+        Utf8Output {
+            status: self.status,
+            stdout: self.stdout.into_owned(),
+            stderr: self.stderr.into_owned(),
+        }
+    }
+}
+
+impl<'a> From<&'a Output> for Utf8OutputCow<'a> {
+    fn from(
+        Output {
+            status,
+            stdout,
+            stderr,
+        }: &'a Output,
+    ) -> Self {
+        Utf8OutputCow {
+            status: *status,
+            stdout: String::from_utf8_lossy(stdout),
+            stderr: String::from_utf8_lossy(stderr),
+        }
+    }
+}
+
+impl From<Utf8OutputCow<'_>> for Utf8Output {
+    fn from(value: Utf8OutputCow<'_>) -> Self {
+        value.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::output;
+    use crate::test_util::utf8_output;
+
+    #[test]
+    fn test_valid_is_borrowed() {
+        let output = output(b"puppy", b"doggy");
+        let view = Utf8OutputCow::from(&output);
+        assert!(matches!(view.stdout, Cow::Borrowed("puppy")));
+        assert!(matches!(view.stderr, Cow::Borrowed("doggy")));
+        assert!(view.stdout_is_borrowed());
+        assert!(view.stderr_is_borrowed());
+    }
+
+    #[test]
+    fn test_invalid_is_owned() {
+        let output = output(b"\xc0puppy", b"doggy\xf0\x90");
+        let view = Utf8OutputCow::from(&output);
+        assert!(!view.stdout_is_borrowed());
+        assert!(!view.stderr_is_borrowed());
+        assert_eq!(
+            view.into_owned(),
+            utf8_output("\u{fffd}puppy", "doggy\u{fffd}")
+        );
+    }
+
+    #[test]
+    fn test_matches_lossy() {
+        let output = output(b"pup\xe2\x28\xa1py", b"doggy");
+        assert_eq!(
+            Utf8Output::from(Utf8OutputCow::from(&output)),
+            Utf8Output::from_utf8_lossy_ref(&output)
+        );
+    }
+}
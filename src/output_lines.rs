@@ -0,0 +1,231 @@
+use std::str::Lines;
+use std::str::SplitTerminator;
+
+use crate::Utf8Output;
+
+impl<S: AsRef<str>> Utf8Output<S> {
+    /// Iterate over the lines of `stdout`.
+    ///
+    /// Lines are split like [`str::lines`]: each line ends with `\n` or `\r\n`, which isn't
+    /// included in the line, and the last line doesn't need a line ending. Empty output has no
+    /// lines.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let output = Command::new("printf")
+    ///     .arg("puppy\\r\\ndoggy\\nkitty")
+    ///     .output_utf8()
+    ///     .unwrap();
+    /// let lines = output.stdout_lines().collect::<Vec<_>>();
+    /// assert_eq!(lines, ["puppy", "doggy", "kitty"]);
+    /// ```
+    pub fn stdout_lines(&self) -> Lines<'_> {
+        self.stdout.as_ref().lines()
+    }
+
+    /// Iterate over the lines of `stderr`.
+    ///
+    /// See [`Utf8Output::stdout_lines`].
+    pub fn stderr_lines(&self) -> Lines<'_> {
+        self.stderr.as_ref().lines()
+    }
+
+    /// Iterate over the lines of `stdout` with their 1-based line numbers.
+    ///
+    /// See [`Utf8Output::stdout_lines`].
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let output = Command::new("printf")
+    ///     .arg("puppy\\ndoggy\\n")
+    ///     .output_utf8()
+    ///     .unwrap();
+    /// for (number, line) in output.stdout_numbered_lines() {
+    ///     println!("{number}: {line}");
+    /// }
+    /// assert_eq!(output.stdout_numbered_lines().last(), Some((2, "doggy")));
+    /// ```
+    pub fn stdout_numbered_lines(&self) -> impl Iterator<Item = (usize, &str)> {
+        numbered_lines(self.stdout.as_ref())
+    }
+
+    /// Iterate over the lines of `stderr` with their 1-based line numbers.
+    ///
+    /// See [`Utf8Output::stdout_numbered_lines`].
+    pub fn stderr_numbered_lines(&self) -> impl Iterator<Item = (usize, &str)> {
+        numbered_lines(self.stderr.as_ref())
+    }
+
+    /// Collect the lines of `stdout`.
+    ///
+    /// See [`Utf8Output::stdout_lines`].
+    #[must_use]
+    pub fn stdout_lines_owned(&self) -> Vec<String> {
+        self.stdout_lines().map(ToOwned::to_owned).collect()
+    }
+
+    /// Collect the lines of `stderr`.
+    ///
+    /// See [`Utf8Output::stdout_lines`].
+    #[must_use]
+    pub fn stderr_lines_owned(&self) -> Vec<String> {
+        self.stderr_lines().map(ToOwned::to_owned).collect()
+    }
+
+    /// Iterate over the records of `stdout`, separated by `delimiter`.
+    ///
+    /// A single trailing `delimiter` which terminates the last record is ignored, rather than
+    /// producing an empty record, and empty output has no records. Everything else, including
+    /// empty records in the middle of the output and line endings, is kept.
+    ///
+    /// See [`Utf8Output::stdout_nul_records`] for NUL-delimited output.
+    pub fn stdout_records(&self, delimiter: char) -> SplitTerminator<'_, char> {
+        self.stdout.as_ref().split_terminator(delimiter)
+    }
+
+    /// Iterate over the records of `stderr`, separated by `delimiter`.
+    ///
+    /// See [`Utf8Output::stdout_records`].
+    pub fn stderr_records(&self, delimiter: char) -> SplitTerminator<'_, char> {
+        self.stderr.as_ref().split_terminator(delimiter)
+    }
+
+    /// Iterate over the NUL-delimited records of `stdout`, like the output of `find -print0`,
+    /// `git ls-files -z`, or `grep -Z`.
+    ///
+    /// Records may contain newlines. See [`Utf8Output::stdout_records`].
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let output = Command::new("printf")
+    ///     .arg("puppy.txt\\0doggy\\nkitty.txt\\0")
+    ///     .output_utf8()
+    ///     .unwrap();
+    /// let records = output.stdout_nul_records().collect::<Vec<_>>();
+    /// assert_eq!(records, ["puppy.txt", "doggy\nkitty.txt"]);
+    /// ```
+    pub fn stdout_nul_records(&self) -> SplitTerminator<'_, char> {
+        self.stdout_records('\0')
+    }
+}
+
+/// Iterate over the lines of `text` with their 1-based line numbers.
+///
+/// Lines are split like [`str::lines`]. Use this wherever lines of output are numbered, so the
+/// numbering is consistent.
+pub(crate) fn numbered_lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::utf8_output;
+
+    #[test]
+    fn test_lines() {
+        let output = utf8_output("puppy\ndoggy\r\nkitty\n", "birdy\n\nfishy");
+        assert_eq!(
+            output.stdout_lines().collect::<Vec<_>>(),
+            ["puppy", "doggy", "kitty"]
+        );
+        assert_eq!(
+            output.stderr_lines().collect::<Vec<_>>(),
+            ["birdy", "", "fishy"]
+        );
+        assert_eq!(output.stdout_lines_owned(), ["puppy", "doggy", "kitty"]);
+        assert_eq!(output.stderr_lines_owned(), ["birdy", "", "fishy"]);
+    }
+
+    #[test]
+    fn test_lines_without_terminator() {
+        let output = utf8_output("puppy\ndoggy", "");
+        assert_eq!(output.stdout_lines_owned(), ["puppy", "doggy"]);
+    }
+
+    #[test]
+    fn test_lines_empty() {
+        let output = utf8_output("", "\n");
+        assert_eq!(output.stdout_lines().count(), 0);
+        assert_eq!(output.stdout_numbered_lines().count(), 0);
+        assert!(output.stdout_lines_owned().is_empty());
+        // A single line ending is one empty line.
+        assert_eq!(output.stderr_lines_owned(), [""]);
+    }
+
+    #[test]
+    fn test_numbered_lines() {
+        let output = utf8_output("puppy\ndoggy\r\n", "kitty");
+        assert_eq!(
+            output.stdout_numbered_lines().collect::<Vec<_>>(),
+            [(1, "puppy"), (2, "doggy")]
+        );
+        assert_eq!(
+            output.stderr_numbered_lines().collect::<Vec<_>>(),
+            [(1, "kitty")]
+        );
+    }
+
+    #[test]
+    fn test_nul_records() {
+        let output = utf8_output("./puppy\0./doggy\nkitty\0./birdy \r\n\0", "");
+        assert_eq!(
+            output.stdout_nul_records().collect::<Vec<_>>(),
+            ["./puppy", "./doggy\nkitty", "./birdy \r\n"]
+        );
+    }
+
+    #[test]
+    fn test_records_edge_cases() {
+        for (stdout, records) in [
+            ("", &[][..]),
+            ("\0", &[""]),
+            ("puppy", &["puppy"]),
+            ("puppy\0\0", &["puppy", ""]),
+            ("\0puppy\0", &["", "puppy"]),
+        ] {
+            let output = utf8_output(stdout, stdout);
+            assert_eq!(output.stdout_nul_records().collect::<Vec<_>>(), records);
+            assert_eq!(output.stderr_records('\0').collect::<Vec<_>>(), records);
+        }
+    }
+
+    #[test]
+    fn test_records_delimiter() {
+        let output = utf8_output("puppy:doggy:kitty\n", "");
+        assert_eq!(
+            output.stdout_records(':').collect::<Vec<_>>(),
+            ["puppy", "doggy", "kitty\n"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_print0() {
+        use crate::CommandExt;
+
+        let dir = std::env::temp_dir().join(format!("utf8-command-records-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["puppy", "doggy\nkitty", " birdy "] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+
+        let output = std::process::Command::new("find")
+            .args([".", "-type", "f", "-print0"])
+            .current_dir(&dir)
+            .output_utf8()
+            .unwrap();
+        let mut records = output.stdout_nul_records().collect::<Vec<_>>();
+        records.sort();
+        assert_eq!(records, ["./ birdy ", "./doggy\nkitty", "./puppy"]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
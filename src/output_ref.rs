@@ -0,0 +1,121 @@
+use std::process::ExitStatus;
+use std::process::Output;
+
+use crate::decode_stderr_ref;
+use crate::decode_stdout_ref;
+use crate::Error;
+use crate::Utf8Output;
+
+/// A borrowed, UTF-8-validated view of a [`std::process::Output`].
+///
+/// Unlike converting a `&Output` into a [`Utf8Output`], this doesn't copy the output streams;
+/// they're only validated.
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use std::process::Output;
+/// # use utf8_command::Utf8OutputRef;
+/// let output = Output {
+///     status: ExitStatus::default(),
+///     stdout: Vec::from(b"puppy"),
+///     stderr: Vec::from(b"doggy"),
+/// };
+/// let view = Utf8OutputRef::try_from(&output).unwrap();
+/// assert_eq!(view.stdout, "puppy");
+/// assert_eq!(view.stderr, "doggy");
+/// ```
+///
+/// Errors are the same as for [`Utf8Output`]:
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use std::process::Output;
+/// # use utf8_command::Utf8OutputRef;
+/// let output = Output {
+///     status: ExitStatus::default(),
+///     stdout: Vec::from(b""),
+///     stderr: Vec::from(b"\xc0"),
+/// };
+/// assert_eq!(
+///     Utf8OutputRef::try_from(&output).unwrap_err().to_string(),
+///     "Stderr contained invalid utf-8 sequence of 1 bytes from index 0: \"�\""
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Utf8OutputRef<'a> {
+    /// The [`std::process::Command`]'s exit status.
+    pub status: ExitStatus,
+    /// The contents of the [`std::process::Command`]'s `stdout` stream.
+    pub stdout: &'a str,
+    /// The contents of the [`std::process::Command`]'s `stderr` stream.
+    pub stderr: &'a str,
+}
+
+impl Utf8OutputRef<'_> {
+    /// Copy the streams into an owned [`Utf8Output`].
+    pub fn to_owned(&self) -> Utf8Output {
+        Utf8Output {
+            status: self.status,
+            stdout: self.stdout.to_owned(),
+            stderr: self.stderr.to_owned(),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Output> for Utf8OutputRef<'a> {
+    type Error = Error;
+
+    fn try_from(
+        Output {
+            status,
+            stdout,
+            stderr,
+        }: &'a Output,
+    ) -> Result<Self, Self::Error> {
+        let (stdout, stderr) =
+            Error::from_streams(decode_stdout_ref(stdout), decode_stderr_ref(stderr))
+                .map_err(|err| err.with_status(*status))?;
+
+        Ok(Utf8OutputRef {
+            status: *status,
+            stdout,
+            stderr,
+        })
+    }
+}
+
+impl From<Utf8OutputRef<'_>> for Utf8Output {
+    fn from(value: Utf8OutputRef<'_>) -> Self {
+        value.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::output;
+
+    #[test]
+    fn test_borrows() {
+        let output = output(b"puppy", b"doggy");
+        let view = Utf8OutputRef::try_from(&output).unwrap();
+        assert_eq!(view.stdout.as_ptr(), output.stdout.as_ptr());
+        assert_eq!(view.stderr.as_ptr(), output.stderr.as_ptr());
+        assert_eq!(view.to_owned(), Utf8Output::try_from(&output).unwrap());
+    }
+
+    #[test]
+    fn test_errors_match_owned() {
+        for (stdout, stderr) in [
+            (b"\xc0".as_slice(), b"".as_slice()),
+            (b"puppy", b"doggy\xf0\x90"),
+            (b"\xe2\x28\xa1", b"\xc0"),
+        ] {
+            let output = output(stdout, stderr);
+            assert_eq!(
+                Utf8OutputRef::try_from(&output).unwrap_err(),
+                Utf8Output::try_from(&output).unwrap_err()
+            );
+        }
+    }
+}
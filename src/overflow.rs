@@ -0,0 +1,266 @@
+use std::fmt::Display;
+use std::process::Command;
+use std::process::Output;
+
+use crate::capture::decode_partial;
+use crate::capture::output_limited;
+use crate::capture::Limits;
+use crate::capture::Stopped;
+use crate::limit::keep_head;
+use crate::CommandInfo;
+use crate::Error;
+use crate::SpawnError;
+use crate::Stream;
+use crate::Utf8Output;
+
+/// A limit on how much output a command can write, for
+/// [`CommandExt::output_utf8_max_bytes`][crate::CommandExt::output_utf8_max_bytes].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputLimit {
+    /// Limit the total size of `stdout` and `stderr`, in bytes.
+    Combined(usize),
+    /// Limit the size of each of `stdout` and `stderr` separately, in bytes.
+    PerStream(usize),
+}
+
+impl OutputLimit {
+    /// If `stream` is over the limit, truncate it to the limit and return `true`.
+    pub(crate) fn truncate(self, output: &mut Output, stream: Stream) -> bool {
+        let (bytes, other) = match stream {
+            Stream::Stdout => (&mut output.stdout, &output.stderr),
+            Stream::Stderr => (&mut output.stderr, &output.stdout),
+        };
+        let max = match self {
+            OutputLimit::Combined(max) => max.saturating_sub(other.len()),
+            OutputLimit::PerStream(max) => max,
+        };
+        keep_head(bytes, max) > 0
+    }
+}
+
+impl Display for OutputLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputLimit::Combined(max) => write!(f, "{max} bytes of output"),
+            OutputLimit::PerStream(max) => write!(f, "{max} bytes per stream"),
+        }
+    }
+}
+
+/// Run `command` and decode its output, killing it if it writes more output than `limit`.
+pub(crate) fn output_max_bytes(
+    command: &mut Command,
+    limit: OutputLimit,
+) -> Result<Utf8Output, OverflowError> {
+    let info = CommandInfo::from(&*command);
+    let limits = Limits {
+        max_bytes: Some(limit),
+        ..Default::default()
+    };
+    match output_limited(command, limits) {
+        Ok((output, None)) => {
+            Utf8Output::try_from(output).map_err(|err| OverflowError::Utf8(err.with_command(info)))
+        }
+        Ok((output, Some(stopped))) => Err(OverflowError::Overflowed {
+            limit,
+            stream: match stopped {
+                Stopped::Overflowed { stream } => stream,
+                Stopped::TimedOut | Stopped::Stalled { .. } => {
+                    unreachable!("Only a byte limit was set")
+                }
+            },
+            output: Utf8Output {
+                status: output.status,
+                stdout: decode_partial(output.stdout),
+                stderr: decode_partial(output.stderr),
+            },
+            command: Some(info),
+        }),
+        Err(err) => Err(OverflowError::Io(SpawnError::new(err, Some(info)))),
+    }
+}
+
+/// An error produced by
+/// [`CommandExt::output_utf8_max_bytes`][crate::CommandExt::output_utf8_max_bytes].
+#[derive(Debug)]
+pub enum OverflowError {
+    /// The command couldn't be run.
+    Io(SpawnError),
+    /// The command's output contained invalid UTF-8.
+    Utf8(Error),
+    /// The command wrote more output than allowed, and was killed.
+    Overflowed {
+        /// The limit which was exceeded.
+        limit: OutputLimit,
+        /// The stream which went over the limit.
+        stream: Stream,
+        /// The command's output up to the limit.
+        ///
+        /// Invalid UTF-8 is replaced with U+FFFD REPLACEMENT CHARACTER, and an incomplete
+        /// sequence at the end of a stream is removed.
+        output: Utf8Output,
+        /// The command which wrote too much output, if known.
+        command: Option<CommandInfo>,
+    },
+}
+
+impl OverflowError {
+    /// Get the command's truncated output, if it wrote too much output.
+    pub fn output(&self) -> Option<&Utf8Output> {
+        match self {
+            OverflowError::Overflowed { output, .. } => Some(output),
+            OverflowError::Io(_) | OverflowError::Utf8(_) => None,
+        }
+    }
+
+    /// Get the command's truncated output, if it wrote too much output.
+    pub fn into_output(self) -> Option<Utf8Output> {
+        match self {
+            OverflowError::Overflowed { output, .. } => Some(output),
+            OverflowError::Io(_) | OverflowError::Utf8(_) => None,
+        }
+    }
+
+    /// Get the stream which went over the limit, if the command wrote too much output.
+    pub fn stream(&self) -> Option<Stream> {
+        match self {
+            OverflowError::Overflowed { stream, .. } => Some(*stream),
+            OverflowError::Io(_) | OverflowError::Utf8(_) => None,
+        }
+    }
+
+    /// Get the command which produced the error, if known.
+    pub fn command(&self) -> Option<&CommandInfo> {
+        match self {
+            OverflowError::Io(err) => err.command(),
+            OverflowError::Utf8(err) => err.command(),
+            OverflowError::Overflowed { command, .. } => command.as_ref(),
+        }
+    }
+}
+
+impl From<std::io::Error> for OverflowError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(SpawnError::from(value))
+    }
+}
+
+impl From<SpawnError> for OverflowError {
+    fn from(value: SpawnError) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<Error> for OverflowError {
+    fn from(value: Error) -> Self {
+        Self::Utf8(value)
+    }
+}
+
+impl Display for OverflowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OverflowError::Io(err) => write!(f, "{err}"),
+            OverflowError::Utf8(err) => write!(f, "{err}"),
+            OverflowError::Overflowed {
+                limit,
+                stream,
+                command,
+                ..
+            } => {
+                match command {
+                    Some(command) => write!(f, "Command `{command}`")?,
+                    None => write!(f, "Command")?,
+                }
+                write!(f, " wrote more than {limit}; stopped at {stream}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for OverflowError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OverflowError::Io(err) => Some(err),
+            OverflowError::Utf8(err) => Some(err),
+            OverflowError::Overflowed { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    use crate::CommandExt;
+
+    use super::*;
+    use crate::test_util::output;
+
+    #[test]
+    fn test_truncate() {
+        let mut out = output(b"puppy", b"doggy");
+        assert!(!OutputLimit::PerStream(5).truncate(&mut out, Stream::Stdout));
+        assert!(OutputLimit::Combined(8).truncate(&mut out, Stream::Stdout));
+        assert_eq!(out.stdout, b"pup");
+
+        // The cut is moved back to a code point boundary.
+        let mut out = output("pup🐶".as_bytes(), b"");
+        assert!(OutputLimit::PerStream(5).truncate(&mut out, Stream::Stdout));
+        assert_eq!(out.stdout, b"pup");
+
+        let mut out = output(b"puppy", b"doggy");
+        assert!(OutputLimit::Combined(4).truncate(&mut out, Stream::Stderr));
+        assert_eq!(out.stderr, b"");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_utf8_max_bytes() {
+        let output = Command::new("sh")
+            .args(["-c", "echo puppy; echo doggy >&2"])
+            .output_utf8_max_bytes(OutputLimit::Combined(12))
+            .unwrap();
+        assert_eq!(output.stdout, "puppy\n");
+        assert_eq!(output.stderr, "doggy\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_utf8_max_bytes_overflowed() {
+        use std::os::unix::process::ExitStatusExt;
+        use std::time::Duration;
+        use std::time::Instant;
+
+        let start = Instant::now();
+        let err = Command::new("yes")
+            .arg("puppy 🐶")
+            .output_utf8_max_bytes(OutputLimit::PerStream(1024 * 1024 + 7))
+            .unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(30));
+        assert_eq!(err.stream(), Some(Stream::Stdout));
+        assert_eq!(
+            err.to_string(),
+            "Command `yes 'puppy 🐶'` wrote more than 1048583 bytes per stream; stopped at Stdout"
+        );
+
+        let output = err.into_output().unwrap();
+        assert_eq!(output.status.signal(), Some(9));
+        // Each line is 11 bytes, and the last one is cut off in the middle of the emoji.
+        assert_eq!(output.stdout.len(), 95325 * 11 + "puppy ".len());
+        assert!(output.stdout.ends_with("puppy 🐶\npuppy "));
+        assert!(!output.stdout.contains('\u{FFFD}'));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_utf8_max_bytes_combined() {
+        let err = Command::new("sh")
+            .args(["-c", "echo puppy; sleep 0.2; echo doggy >&2; exec sleep 60"])
+            .output_utf8_max_bytes(OutputLimit::Combined(8))
+            .unwrap_err();
+        assert_eq!(err.stream(), Some(Stream::Stderr));
+        let output = err.output().unwrap();
+        assert_eq!(output.stdout, "puppy\n");
+        assert_eq!(output.stderr, "do");
+    }
+}
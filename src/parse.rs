@@ -0,0 +1,160 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::ErrorContext;
+use crate::Stream;
+use crate::Utf8Output;
+use crate::ERROR_CONTEXT_BYTES;
+
+impl<S: AsRef<str>> Utf8Output<S> {
+    /// Parse `stdout` as a `T`, after removing trailing ASCII whitespace.
+    ///
+    /// This is useful for commands which print a single value, like `nproc` or
+    /// `git rev-list --count HEAD`. Leading whitespace is kept, so it must be handled by `T`'s
+    /// [`FromStr`] implementation.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let output = Command::new("echo").arg("42").output_utf8().unwrap();
+    /// assert_eq!(output.stdout_parse::<u32>().unwrap(), 42);
+    ///
+    /// let output = Command::new("echo").arg("puppy").output_utf8().unwrap();
+    /// assert_eq!(
+    ///     output.stdout_parse::<u32>().unwrap_err().to_string(),
+    ///     "Stdout couldn't be parsed: invalid digit found in string: \"puppy\""
+    /// );
+    /// ```
+    pub fn stdout_parse<T: FromStr>(&self) -> Result<T, ParseOutputError<T::Err>> {
+        parse(Stream::Stdout, self.stdout_trimmed())
+    }
+
+    /// Parse `stderr` as a `T`, after removing trailing ASCII whitespace.
+    ///
+    /// See [`Utf8Output::stdout_parse`].
+    pub fn stderr_parse<T: FromStr>(&self) -> Result<T, ParseOutputError<T::Err>> {
+        parse(Stream::Stderr, self.stderr_trimmed())
+    }
+}
+
+fn parse<T: FromStr>(stream: Stream, text: &str) -> Result<T, ParseOutputError<T::Err>> {
+    text.parse().map_err(|error| ParseOutputError {
+        stream,
+        text: text.to_owned(),
+        error,
+    })
+}
+
+/// An error produced by [`Utf8Output::stdout_parse`] or [`Utf8Output::stderr_parse`].
+///
+/// When displayed, this shows the output which failed to parse, or only the start of it if it's
+/// long (currently, more than 1024 bytes).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOutputError<E> {
+    stream: Stream,
+    text: String,
+    error: E,
+}
+
+impl<E> ParseOutputError<E> {
+    /// Get the stream which failed to parse.
+    pub fn stream(&self) -> Stream {
+        self.stream
+    }
+
+    /// Get the text which failed to parse, after trailing whitespace was removed.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Get a reference to the parsing error.
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    /// Get the parsing error.
+    pub fn into_error(self) -> E {
+        self.error
+    }
+}
+
+impl<E: Display> Display for ParseOutputError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} couldn't be parsed: {}: {}",
+            self.stream,
+            self.error,
+            ErrorContext::at(self.text.as_bytes(), 0, ERROR_CONTEXT_BYTES)
+        )
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for ParseOutputError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+    use std::num::ParseIntError;
+
+    use super::*;
+    use crate::test_util::utf8_output;
+
+    #[test]
+    fn test_parse_int() {
+        let output = utf8_output("16\n", "-3\r\n");
+        assert_eq!(output.stdout_parse::<usize>().unwrap(), 16);
+        assert_eq!(output.stderr_parse::<i8>().unwrap(), -3);
+    }
+
+    #[test]
+    fn test_parse_float() {
+        let output = utf8_output("0.25 \t\n", "");
+        assert_eq!(output.stdout_parse::<f64>().unwrap(), 0.25);
+    }
+
+    #[test]
+    fn test_parse_bool() {
+        assert!(utf8_output("true\n", "").stdout_parse::<bool>().unwrap());
+    }
+
+    #[test]
+    fn test_parse_failed() {
+        let err = utf8_output(" 16\n", "").stdout_parse::<u32>().unwrap_err();
+        assert_eq!(err.stream(), Stream::Stdout);
+        assert_eq!(err.text(), " 16");
+        assert_eq!(
+            err.to_string(),
+            "Stdout couldn't be parsed: invalid digit found in string: \" 16\""
+        );
+        assert!(err.source().unwrap().is::<ParseIntError>());
+
+        let err = utf8_output("", "puppy: not found\n")
+            .stderr_parse::<f32>()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Stderr couldn't be parsed: invalid float literal: \"puppy: not found\""
+        );
+    }
+
+    #[test]
+    fn test_parse_failed_long() {
+        let stdout = format!("puppy{}", " doggy".repeat(1000));
+        let err = utf8_output(&stdout, "").stdout_parse::<u8>().unwrap_err();
+        assert_eq!(err.text(), stdout);
+        let message = err.to_string();
+        assert!(
+            message.starts_with(
+                "Stdout couldn't be parsed: invalid digit found in string: \"puppy doggy"
+            ),
+            "{message}"
+        );
+        assert!(message.len() < 1200, "{message}");
+    }
+}
@@ -0,0 +1,126 @@
+use std::path::PathBuf;
+
+use crate::normalize::chomp_str;
+use crate::Utf8Output;
+
+impl<S: AsRef<str>> Utf8Output<S> {
+    /// Interpret `stdout` as a path, like the output of `git rev-parse --show-toplevel`,
+    /// `which`, or `mktemp -d`.
+    ///
+    /// Exactly one trailing `\n` or `\r\n` is removed; other whitespace is kept, because it may
+    /// be part of the path.
+    ///
+    /// This is a textual conversion. `stdout` has already been decoded as UTF-8, so paths which
+    /// aren't valid UTF-8 (which are possible on Unix) can't be represented; if they need to be
+    /// supported, use the raw bytes of the [`std::process::Output`] instead.
+    ///
+    /// ```
+    /// # use std::path::Path;
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let output = Command::new("sh")
+    ///     .args(["-c", "cd / && pwd"])
+    ///     .output_utf8()
+    ///     .unwrap();
+    /// assert_eq!(output.stdout_path(), Path::new("/"));
+    /// ```
+    #[must_use]
+    pub fn stdout_path(&self) -> PathBuf {
+        PathBuf::from(chomp_str(self.stdout.as_ref()))
+    }
+
+    /// Interpret each line of `stdout` as a path, like the output of `git ls-files`.
+    ///
+    /// Lines are split like [`str::lines`], and empty lines are skipped. See
+    /// [`Utf8Output::stdout_path`] for the limitations of textual paths, and
+    /// [`Utf8Output::stdout_nul_records`] for paths which may contain newlines.
+    #[must_use]
+    pub fn stdout_paths(&self) -> Vec<PathBuf> {
+        self.stdout_lines()
+            .filter(|line| !line.is_empty())
+            .map(PathBuf::from)
+            .collect()
+    }
+
+    /// Interpret `stdout` as a [`camino::Utf8PathBuf`].
+    ///
+    /// See [`Utf8Output::stdout_path`].
+    #[cfg(feature = "camino")]
+    #[must_use]
+    pub fn stdout_utf8_path(&self) -> camino::Utf8PathBuf {
+        camino::Utf8PathBuf::from(chomp_str(self.stdout.as_ref()))
+    }
+
+    /// Interpret each line of `stdout` as a [`camino::Utf8PathBuf`].
+    ///
+    /// See [`Utf8Output::stdout_paths`].
+    #[cfg(feature = "camino")]
+    #[must_use]
+    pub fn stdout_utf8_paths(&self) -> Vec<camino::Utf8PathBuf> {
+        self.stdout_lines()
+            .filter(|line| !line.is_empty())
+            .map(camino::Utf8PathBuf::from)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::test_util::utf8_output;
+
+    #[test]
+    fn test_stdout_path() {
+        for (stdout, path) in [
+            ("/home/puppy\n", "/home/puppy"),
+            ("/home/puppy", "/home/puppy"),
+            (
+                "C:\\Users\\puppy\\Documents\r\n",
+                "C:\\Users\\puppy\\Documents",
+            ),
+            // Only one line ending is removed, and other whitespace is kept.
+            ("/tmp/puppy \n\n", "/tmp/puppy \n"),
+            ("", ""),
+        ] {
+            assert_eq!(utf8_output(stdout, "").stdout_path(), Path::new(path));
+        }
+    }
+
+    #[test]
+    fn test_stdout_paths() {
+        let output = utf8_output(
+            "src/lib.rs\n\nsrc/paths.rs\r\nC:\\Program Files\\puppy.exe",
+            "",
+        );
+        assert_eq!(
+            output.stdout_paths(),
+            [
+                Path::new("src/lib.rs"),
+                Path::new("src/paths.rs"),
+                Path::new("C:\\Program Files\\puppy.exe"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stdout_paths_empty() {
+        assert!(utf8_output("", "").stdout_paths().is_empty());
+        assert!(utf8_output("\n\r\n", "").stdout_paths().is_empty());
+    }
+
+    #[cfg(feature = "camino")]
+    #[test]
+    fn test_stdout_utf8_path() {
+        let output = utf8_output("C:\\Users\\puppy\r\n/home/doggy\n", "");
+        assert_eq!(output.stdout_utf8_path(), "C:\\Users\\puppy\r\n/home/doggy");
+        assert_eq!(
+            output.stdout_utf8_paths(),
+            [
+                camino::Utf8Path::new("C:\\Users\\puppy"),
+                camino::Utf8Path::new("/home/doggy"),
+            ]
+        );
+    }
+}
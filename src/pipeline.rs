@@ -0,0 +1,510 @@
+use std::fmt::Display;
+use std::io::Read;
+use std::process::ChildStderr;
+use std::process::Command;
+use std::process::ExitStatus;
+use std::process::Output;
+use std::process::Stdio;
+use std::thread::JoinHandle;
+
+use crate::capture::kill;
+use crate::command::fmt_failure;
+use crate::CommandInfo;
+use crate::Error;
+use crate::SpawnError;
+use crate::Utf8Output;
+
+/// What to do with the `stderr` of every stage of a pipeline but the last.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PipelineStderr {
+    /// Leave each stage's `stderr` as it's configured on its [`Command`], which inherits the
+    /// parent's `stderr` by default.
+    #[default]
+    Inherit,
+    /// Capture each stage's `stderr`, and prepend it to the last stage's `stderr` in the output,
+    /// in the order of the stages.
+    Capture,
+}
+
+/// Run `commands` as a pipeline, like `ps aux | grep puppy` in a shell, and decode the output
+/// of the last command as UTF-8.
+///
+/// Each command's `stdout` is connected to the next command's `stdin`. The first command's
+/// `stdin` is inherited unless it's configured otherwise, and the `stderr` of every command but
+/// the last is inherited; see [`pipeline_with_stderr`] to capture it.
+///
+/// Like `set -o pipefail` in a shell, the pipeline fails with [`PipelineError::Failed`] if any
+/// command exits unsuccessfully. The output's status is the last command's status.
+///
+/// The commands' configuration is changed to connect them, and isn't restored afterwards, since
+/// [`Command`] has no way to read it back: every command's `stdout` is left set to
+/// [`Stdio::piped`], the `stdin` of every command but the first is left set to
+/// [`Stdio::inherit`], and the last command's `stderr` is left set to [`Stdio::piped`].
+///
+/// ```
+/// # use std::process::Command;
+/// use utf8_command::pipeline;
+///
+/// let mut printf = Command::new("printf");
+/// printf.arg("puppy\\ndoggy\\nkitty\\n");
+/// let mut grep = Command::new("grep");
+/// grep.arg("y$");
+///
+/// let output = pipeline(&mut [printf, grep]).unwrap();
+/// assert_eq!(output.stdout, "puppy\ndoggy\nkitty\n");
+/// ```
+///
+/// # Panics
+///
+/// If `commands` is empty.
+pub fn pipeline(commands: &mut [Command]) -> Result<Utf8Output, PipelineError> {
+    pipeline_with_stderr(commands, PipelineStderr::Inherit)
+}
+
+/// Run `commands` as a pipeline, handling the `stderr` of every command but the last as
+/// configured by `stderr`.
+///
+/// See [`pipeline`]. With [`PipelineStderr::Capture`], every command's `stderr` is left set to
+/// [`Stdio::piped`] afterwards, and each command's `stderr` is decoded separately, so
+/// [`PipelineError::Utf8`] reports the stage which wrote invalid UTF-8.
+///
+/// ```
+/// # use std::process::Command;
+/// use utf8_command::pipeline_with_stderr;
+/// use utf8_command::PipelineStderr;
+///
+/// let mut first = Command::new("sh");
+/// first.args(["-c", "echo puppy; echo doggy >&2"]);
+/// let mut second = Command::new("sh");
+/// second.args(["-c", "cat; echo kitty >&2"]);
+///
+/// let output = pipeline_with_stderr(&mut [first, second], PipelineStderr::Capture).unwrap();
+/// assert_eq!(output.stdout, "puppy\n");
+/// assert_eq!(output.stderr, "doggy\nkitty\n");
+/// ```
+///
+/// # Panics
+///
+/// If `commands` is empty.
+pub fn pipeline_with_stderr(
+    commands: &mut [Command],
+    stderr: PipelineStderr,
+) -> Result<Utf8Output, PipelineError> {
+    assert!(
+        !commands.is_empty(),
+        "A pipeline needs at least one command"
+    );
+    let infos = commands.iter().map(CommandInfo::from).collect::<Vec<_>>();
+    let last = commands.len() - 1;
+
+    let mut children = Vec::with_capacity(commands.len());
+    let mut stderr_readers = Vec::new();
+    let mut previous_stdout = None;
+    for (stage, command) in commands.iter_mut().enumerate() {
+        if let Some(stdout) = previous_stdout.take() {
+            command.stdin(Stdio::from(stdout));
+        }
+        command.stdout(Stdio::piped());
+        if stage == last || stderr == PipelineStderr::Capture {
+            command.stderr(Stdio::piped());
+        }
+        let child = command.spawn();
+        if stage > 0 {
+            // The `Command` holds on to the read end of the previous pipe, so the previous stage
+            // would never see a broken pipe if this one exits early, unless it's closed.
+            command.stdin(Stdio::inherit());
+        }
+        let mut child = match child {
+            Ok(child) => child,
+            Err(err) => {
+                for mut child in children {
+                    let _ = kill(&mut child);
+                }
+                return Err(PipelineError::Io {
+                    stage,
+                    error: SpawnError::new(err, Some(infos[stage].clone())),
+                });
+            }
+        };
+        if stage < last {
+            previous_stdout = child.stdout.take();
+            if let Some(stderr) = child.stderr.take() {
+                stderr_readers.push((stage, spawn_stderr_reader(stderr)));
+            }
+        }
+        children.push(child);
+    }
+
+    let last_child = children.pop().expect("A pipeline has at least one command");
+    let result = last_child.wait_with_output();
+    let mut statuses = Vec::with_capacity(infos.len());
+    let mut io_error = None;
+    for (stage, mut child) in children.into_iter().enumerate() {
+        match child.wait() {
+            Ok(status) => statuses.push(status),
+            Err(err) => {
+                io_error.get_or_insert((stage, err));
+                statuses.push(ExitStatus::default());
+            }
+        }
+    }
+    let mut captured_stderr = Vec::with_capacity(stderr_readers.len());
+    for (stage, reader) in stderr_readers {
+        let bytes = reader.join().expect("Stderr reader thread panicked");
+        captured_stderr.push((stage, bytes));
+    }
+
+    let output = match result {
+        Ok(output) => output,
+        Err(err) => {
+            return Err(PipelineError::Io {
+                stage: last,
+                error: SpawnError::new(err, Some(infos[last].clone())),
+            })
+        }
+    };
+    if let Some((stage, err)) = io_error {
+        return Err(PipelineError::Io {
+            stage,
+            error: SpawnError::new(err, Some(infos[stage].clone())),
+        });
+    }
+    statuses.push(output.status);
+
+    // A failed stage is reported even if some output is invalid UTF-8, so everything is decoded
+    // lossily in that case.
+    if !statuses.iter().all(ExitStatus::success) {
+        let mut stderr = vec![None; infos.len()];
+        for (stage, bytes) in captured_stderr {
+            stderr[stage] = Some(String::from_utf8_lossy(&bytes).into_owned());
+        }
+        let mut output = Utf8Output::from_utf8_lossy(output);
+        stderr[last] = Some(output.stderr.clone());
+        output.stderr = stderr.iter().flatten().map(String::as_str).collect();
+        return Err(PipelineError::Failed {
+            statuses,
+            commands: infos,
+            stderr,
+            output: Box::new(output),
+        });
+    }
+
+    // Each stage's `stderr` is decoded on its own, so errors are reported for the right stage.
+    let mut stderr = String::new();
+    for (stage, bytes) in captured_stderr {
+        let decoded = Utf8Output::try_from(Output {
+            status: statuses[stage],
+            stdout: Vec::new(),
+            stderr: bytes,
+        })
+        .map_err(|err| PipelineError::Utf8 {
+            stage,
+            error: err.with_command(infos[stage].clone()),
+        })?;
+        stderr.push_str(&decoded.stderr);
+    }
+    let mut output = Utf8Output::try_from(output).map_err(|err| PipelineError::Utf8 {
+        stage: last,
+        error: err.with_command(infos[last].clone()),
+    })?;
+    if !stderr.is_empty() {
+        stderr.push_str(&output.stderr);
+        output.stderr = stderr;
+    }
+    Ok(output)
+}
+
+/// Read a stage's `stderr` to the end on a separate thread, so it never blocks on a full pipe.
+fn spawn_stderr_reader(mut stderr: ChildStderr) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut bytes = Vec::new();
+        // A read error just cuts off the captured output; the stage's status reports failures.
+        let _ = stderr.read_to_end(&mut bytes);
+        bytes
+    })
+}
+
+/// An error produced by [`pipeline`].
+#[derive(Debug)]
+pub enum PipelineError {
+    /// A stage of the pipeline couldn't be run, or waiting for it failed.
+    Io {
+        /// The 0-based index of the stage.
+        stage: usize,
+        /// The error running the stage.
+        error: SpawnError,
+    },
+    /// Every stage exited successfully, but the output of the last stage or the captured
+    /// `stderr` of another stage contained invalid UTF-8.
+    Utf8 {
+        /// The 0-based index of the stage.
+        stage: usize,
+        /// The decoding error.
+        error: Error,
+    },
+    /// At least one stage exited unsuccessfully.
+    Failed {
+        /// The exit status of each stage.
+        statuses: Vec<ExitStatus>,
+        /// The command of each stage.
+        commands: Vec<CommandInfo>,
+        /// The `stderr` of each stage, if it was captured.
+        ///
+        /// The last stage's `stderr` is always captured; the other stages' `stderr` is only
+        /// captured with [`PipelineStderr::Capture`]. The output's `stderr` is all of these,
+        /// concatenated. Invalid UTF-8 is replaced with `U+FFFD REPLACEMENT CHARACTER`.
+        stderr: Vec<Option<String>>,
+        /// The output of the pipeline, decoded lossily.
+        output: Box<Utf8Output>,
+    },
+}
+
+impl PipelineError {
+    /// Get the 0-based index of the stage which failed.
+    ///
+    /// If several stages exited unsuccessfully, this is the first of them.
+    pub fn stage(&self) -> usize {
+        match self {
+            PipelineError::Io { stage, .. } | PipelineError::Utf8 { stage, .. } => *stage,
+            PipelineError::Failed { statuses, .. } => statuses
+                .iter()
+                .position(|status| !status.success())
+                .expect("At least one stage should have failed"),
+        }
+    }
+
+    /// Get the exit status of each stage, if the pipeline ran to completion but a stage failed.
+    pub fn statuses(&self) -> Option<&[ExitStatus]> {
+        match self {
+            PipelineError::Failed { statuses, .. } => Some(statuses),
+            PipelineError::Io { .. } | PipelineError::Utf8 { .. } => None,
+        }
+    }
+
+    /// Get the `stderr` of a stage, if the pipeline ran to completion but a stage failed, and
+    /// the stage's `stderr` was captured.
+    ///
+    /// See [`PipelineError::Failed`].
+    pub fn stage_stderr(&self, stage: usize) -> Option<&str> {
+        match self {
+            PipelineError::Failed { stderr, .. } => stderr.get(stage)?.as_deref(),
+            PipelineError::Io { .. } | PipelineError::Utf8 { .. } => None,
+        }
+    }
+
+    /// Get the output of the pipeline, if it ran to completion but a stage failed.
+    pub fn output(&self) -> Option<&Utf8Output> {
+        match self {
+            PipelineError::Failed { output, .. } => Some(output),
+            PipelineError::Io { .. } | PipelineError::Utf8 { .. } => None,
+        }
+    }
+
+    /// Get the output of the pipeline, if it ran to completion but a stage failed.
+    pub fn into_output(self) -> Option<Utf8Output> {
+        match self {
+            PipelineError::Failed { output, .. } => Some(*output),
+            PipelineError::Io { .. } | PipelineError::Utf8 { .. } => None,
+        }
+    }
+}
+
+impl Display for PipelineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PipelineError::Io { stage, error } => write!(f, "Pipeline stage {stage}: {error}"),
+            PipelineError::Utf8 { stage, error } => write!(f, "Pipeline stage {stage}: {error}"),
+            PipelineError::Failed {
+                commands, stderr, ..
+            } => {
+                let stage = self.stage();
+                let status = self.statuses().expect("The pipeline failed")[stage];
+                write!(f, "Pipeline stage {stage}: ")?;
+                // Only the failing stage's `stderr` is shown, if it was captured.
+                let stderr = stderr[stage].as_deref().unwrap_or_default();
+                fmt_failure(f, Some(&commands[stage]), status, stderr)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PipelineError::Io { error, .. } => Some(error),
+            PipelineError::Utf8 { error, .. } => Some(error),
+            PipelineError::Failed { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A command which writes some lines, cross-platform.
+    fn lines() -> Command {
+        if cfg!(windows) {
+            let mut command = Command::new("cmd");
+            command.args(["/C", "echo puppy&& echo doggy&& echo kitty"]);
+            command
+        } else {
+            let mut command = Command::new("printf");
+            command.arg("puppy\\ndoggy\\nkitty\\n");
+            command
+        }
+    }
+
+    /// A command which prints the lines of `stdin` containing `pattern`, cross-platform.
+    fn grep(pattern: &str) -> Command {
+        let mut command = Command::new(if cfg!(windows) { "findstr" } else { "grep" });
+        command.arg(pattern);
+        command
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let output = pipeline(&mut [lines(), grep("gg")]).unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout.trim_end(), "doggy");
+    }
+
+    #[test]
+    fn test_pipeline_single_command() {
+        let output = pipeline(&mut [lines()]).unwrap();
+        assert_eq!(output.stdout.lines().count(), 3);
+    }
+
+    #[test]
+    fn test_pipeline_missing_binary() {
+        let err = pipeline(&mut [
+            lines(),
+            Command::new("utf8-command-this-program-does-not-exist"),
+        ])
+        .unwrap_err();
+        assert_eq!(err.stage(), 1);
+        assert!(matches!(err, PipelineError::Io { .. }));
+        assert!(err
+            .to_string()
+            .starts_with("Pipeline stage 1: `utf8-command-this-program-does-not-exist`: "));
+    }
+
+    #[test]
+    fn test_pipeline_failed() {
+        let err = pipeline(&mut [lines(), grep("birdy")]).unwrap_err();
+        assert_eq!(err.stage(), 1);
+        let statuses = err.statuses().unwrap();
+        assert!(statuses[0].success());
+        assert_eq!(statuses[1].code(), Some(1));
+        assert_eq!(err.output().unwrap().stdout, "");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pipeline_three_stages() {
+        let mut seq = Command::new("seq");
+        seq.args(["1", "100000"]);
+        let mut tail = Command::new("tail");
+        tail.args(["-n", "1"]);
+        let output = pipeline(&mut [seq, grep("0"), tail]).unwrap();
+        assert_eq!(output.stdout, "100000\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pipeline_early_exit() {
+        // `yes` only stops once `head` exits and it gets a broken pipe.
+        let mut yes = Command::new("yes");
+        yes.arg("puppy");
+        let mut head = Command::new("head");
+        head.args(["-n", "2"]);
+        let err = pipeline(&mut [yes, head]).unwrap_err();
+        // `yes` is killed by `SIGPIPE`.
+        assert_eq!(err.stage(), 0);
+        assert_eq!(err.output().unwrap().stdout, "puppy\npuppy\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pipeline_failed_message() {
+        let mut first = Command::new("sh");
+        first.args(["-c", "echo puppy; echo doggy >&2; exit 2"]);
+        let mut second = Command::new("sh");
+        second.args(["-c", "cat; echo kitty >&2"]);
+        let err = pipeline_with_stderr(&mut [first, second], PipelineStderr::Capture).unwrap_err();
+        // Only the failing stage's `stderr` is shown.
+        assert_eq!(
+            err.to_string(),
+            "Pipeline stage 0: Command `sh -c 'echo puppy; echo doggy >&2; exit 2'` \
+            failed with exit status: 2; stderr: \"doggy\\n\""
+        );
+        assert_eq!(err.stage_stderr(0), Some("doggy\n"));
+        assert_eq!(err.stage_stderr(1), Some("kitty\n"));
+        let output = err.output().unwrap();
+        assert_eq!(output.stdout, "puppy\n");
+        assert_eq!(output.stderr, "doggy\nkitty\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pipeline_failed_message_inherit() {
+        let mut first = Command::new("sh");
+        first.args(["-c", "exit 2"]);
+        let mut second = Command::new("sh");
+        second.args(["-c", "cat; echo kitty >&2"]);
+        let err = pipeline(&mut [first, second]).unwrap_err();
+        // The first stage's `stderr` wasn't captured, and the last stage's isn't shown for it.
+        assert_eq!(
+            err.to_string(),
+            "Pipeline stage 0: Command `sh -c 'exit 2'` failed with exit status: 2"
+        );
+        assert_eq!(err.stage_stderr(0), None);
+        assert_eq!(err.stage_stderr(1), Some("kitty\n"));
+        assert_eq!(err.output().unwrap().stderr, "kitty\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pipeline_invalid() {
+        let mut printf = Command::new("printf");
+        printf.arg("puppy \\300");
+        let err = pipeline(&mut [printf, Command::new("cat")]).unwrap_err();
+        assert_eq!(err.stage(), 1);
+        assert_eq!(
+            err.to_string(),
+            "Pipeline stage 1: `cat`: \
+            Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pipeline_invalid_stderr() {
+        let mut first = Command::new("sh");
+        first.args(["-c", "printf 'puppy \\300' >&2"]);
+        let err = pipeline_with_stderr(&mut [first, Command::new("cat")], PipelineStderr::Capture)
+            .unwrap_err();
+        assert_eq!(err.stage(), 0);
+        assert_eq!(
+            err.to_string(),
+            "Pipeline stage 0: `sh -c 'printf '\\''puppy \\300'\\'' >&2'`: \
+            Stderr contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pipeline_failed_invalid_stderr() {
+        let mut first = Command::new("sh");
+        first.args(["-c", "printf '\\300' >&2; exit 3"]);
+        let mut second = Command::new("sh");
+        second.args(["-c", "cat; printf '\\360\\237' >&2"]);
+        let err = pipeline_with_stderr(&mut [first, second], PipelineStderr::Capture).unwrap_err();
+        // The failure is reported, not the invalid UTF-8.
+        assert_eq!(err.stage(), 0);
+        assert_eq!(err.statuses().unwrap()[0].code(), Some(3));
+        assert_eq!(err.stage_stderr(0), Some("\u{fffd}"));
+        assert_eq!(err.stage_stderr(1), Some("\u{fffd}"));
+        assert_eq!(err.output().unwrap().stderr, "\u{fffd}\u{fffd}");
+    }
+}
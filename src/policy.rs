@@ -0,0 +1,314 @@
+use std::fmt::Display;
+use std::string::FromUtf8Error;
+use std::sync::Arc;
+
+use crate::cesu8::decode_cesu8;
+use crate::context::ErrorContext;
+use crate::context::FromUtf8ErrorMessage;
+use crate::context::Truncation;
+use crate::decode::string_from_utf8;
+use crate::lossy::decode_lossy;
+use crate::InvalidBytes;
+use crate::StderrError;
+use crate::StdoutError;
+use crate::Strictness;
+use crate::ERROR_CONTEXT_BYTES;
+
+/// A policy for decoding the raw bytes of a stream, used with
+/// [`Utf8Output::try_from_with_policy`].
+///
+/// This trait is object-safe, so policies can be stored as `Box<dyn DecodePolicy>`.
+///
+/// For example, a policy which decodes invalid UTF-8 as Latin-1:
+///
+/// ```
+/// # use std::process::ExitStatus;
+/// # use std::process::Output;
+/// use utf8_command::DecodePolicy;
+/// use utf8_command::StreamDecodeError;
+/// use utf8_command::Utf8Output;
+/// use utf8_command::Strict;
+///
+/// struct Latin1Fallback;
+///
+/// impl DecodePolicy for Latin1Fallback {
+///     fn decode(&self, bytes: Vec<u8>) -> Result<String, StreamDecodeError> {
+///         match String::from_utf8(bytes) {
+///             Ok(decoded) => Ok(decoded),
+///             Err(err) => Ok(err.as_bytes().iter().map(|&byte| char::from(byte)).collect()),
+///         }
+///     }
+/// }
+///
+/// let output = Utf8Output::try_from_with_policy(
+///     Output {
+///         status: ExitStatus::default(),
+///         stdout: Vec::from(b"caf\xe9"),
+///         stderr: Vec::from(b""),
+///     },
+///     &Latin1Fallback,
+///     &Strict,
+/// )
+/// .unwrap();
+/// assert_eq!(output.stdout, "café");
+/// ```
+///
+/// [`Utf8Output::try_from_with_policy`]: crate::Utf8Output::try_from_with_policy
+pub trait DecodePolicy {
+    /// Decode the raw bytes of a stream.
+    fn decode(&self, bytes: Vec<u8>) -> Result<String, StreamDecodeError>;
+}
+
+/// A [`DecodePolicy`] which fails if the stream contains invalid UTF-8.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Strict;
+
+impl DecodePolicy for Strict {
+    fn decode(&self, bytes: Vec<u8>) -> Result<String, StreamDecodeError> {
+        Ok(string_from_utf8(bytes)?)
+    }
+}
+
+/// A [`DecodePolicy`] which replaces invalid UTF-8 sequences with U+FFFD REPLACEMENT CHARACTER.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Lossy;
+
+impl DecodePolicy for Lossy {
+    fn decode(&self, bytes: Vec<u8>) -> Result<String, StreamDecodeError> {
+        Ok(decode_lossy(bytes))
+    }
+}
+
+impl DecodePolicy for Strictness {
+    fn decode(&self, bytes: Vec<u8>) -> Result<String, StreamDecodeError> {
+        match self {
+            Strictness::Strict | Strictness::Ascii => Strict.decode(bytes),
+            Strictness::Lossy => Lossy.decode(bytes),
+            Strictness::Cesu8 => Ok(decode_cesu8(bytes, false)?),
+        }
+    }
+}
+
+impl DecodePolicy for InvalidBytes {
+    fn decode(&self, bytes: Vec<u8>) -> Result<String, StreamDecodeError> {
+        Ok(InvalidBytes::decode(self, bytes))
+    }
+}
+
+impl<P: DecodePolicy + ?Sized> DecodePolicy for &P {
+    fn decode(&self, bytes: Vec<u8>) -> Result<String, StreamDecodeError> {
+        (**self).decode(bytes)
+    }
+}
+
+impl<P: DecodePolicy + ?Sized> DecodePolicy for Box<P> {
+    fn decode(&self, bytes: Vec<u8>) -> Result<String, StreamDecodeError> {
+        (**self).decode(bytes)
+    }
+}
+
+/// An error decoding a stream which isn't labeled as `stdout` or `stderr`. Wraps a
+/// [`FromUtf8Error`].
+///
+/// This converts into [`StdoutError`] or [`StderrError`] to label it with the stream it came
+/// from.
+///
+/// ```
+/// use utf8_command::StreamDecodeError;
+/// use utf8_command::StdoutError;
+///
+/// let inner_err = String::from_utf8(Vec::from(b"puppy \xc0")).unwrap_err();
+/// let err = StreamDecodeError::from(inner_err);
+/// assert_eq!(
+///     err.to_string(),
+///     "Output contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+/// );
+/// assert_eq!(
+///     StdoutError::from(err).to_string(),
+///     "Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamDecodeError {
+    inner: FromUtf8Error,
+    /// The number of bytes in the stream before the bytes in `inner`, if the stream is being
+    /// decoded incrementally.
+    offset: usize,
+}
+
+impl StreamDecodeError {
+    /// An error in a stream which is being decoded incrementally, where `inner`'s bytes start
+    /// `offset` bytes into the stream.
+    pub(crate) fn at_offset(inner: FromUtf8Error, offset: usize) -> Self {
+        Self { inner, offset }
+    }
+
+    /// Get a reference to the inner [`FromUtf8Error`].
+    ///
+    /// If the stream was decoded incrementally, this only contains some of the stream's bytes,
+    /// and its indexes are relative to them. Use [`StreamDecodeError::valid_up_to`] for the
+    /// index in the stream.
+    pub fn inner(&self) -> &FromUtf8Error {
+        &self.inner
+    }
+
+    /// The index in the stream up to which the bytes were valid UTF-8.
+    pub fn valid_up_to(&self) -> usize {
+        self.inner.utf8_error().valid_up_to() + self.offset
+    }
+
+    /// The bytes of the stream which aren't stored in the error.
+    fn discarded(&self) -> Option<Truncation> {
+        (self.offset != 0).then_some(Truncation {
+            before: self.offset,
+            after: 0,
+        })
+    }
+}
+
+impl From<FromUtf8Error> for StreamDecodeError {
+    fn from(inner: FromUtf8Error) -> Self {
+        Self { inner, offset: 0 }
+    }
+}
+
+impl From<StreamDecodeError> for FromUtf8Error {
+    fn from(value: StreamDecodeError) -> Self {
+        value.inner
+    }
+}
+
+impl From<StreamDecodeError> for StdoutError {
+    fn from(value: StreamDecodeError) -> Self {
+        let discarded = value.discarded();
+        let mut err = StdoutError::from(value.inner);
+        Arc::make_mut(&mut err.data).discarded = discarded;
+        err
+    }
+}
+
+impl From<StreamDecodeError> for StderrError {
+    fn from(value: StreamDecodeError) -> Self {
+        let discarded = value.discarded();
+        let mut err = StderrError::from(value.inner);
+        Arc::make_mut(&mut err.data).discarded = discarded;
+        err
+    }
+}
+
+impl Display for StreamDecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Output contained {}: {}",
+            FromUtf8ErrorMessage::new(&self.inner, self.discarded()),
+            ErrorContext::new(&self.inner, ERROR_CONTEXT_BYTES).discarded(self.discarded())
+        )
+    }
+}
+
+impl std::error::Error for StreamDecodeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::output;
+    use crate::Error;
+    use crate::Utf8Output;
+
+    /// Decodes invalid bytes as `\xNN` escapes.
+    struct Escape;
+
+    impl DecodePolicy for Escape {
+        fn decode(&self, bytes: Vec<u8>) -> Result<String, StreamDecodeError> {
+            let mut decoded = String::new();
+            for chunk in bytes.utf8_chunks() {
+                decoded.push_str(chunk.valid());
+                for byte in chunk.invalid() {
+                    decoded.push_str(&format!("\\x{byte:02x}"));
+                }
+            }
+            Ok(decoded)
+        }
+    }
+
+    #[test]
+    fn test_custom_policy() {
+        let converted =
+            Utf8Output::try_from_with_policy(output(b"puppy\xc0", b"doggy"), &Escape, &Strict)
+                .unwrap();
+        assert_eq!(converted.stdout, "puppy\\xc0");
+        assert_eq!(converted.stderr, "doggy");
+    }
+
+    #[test]
+    fn test_boxed_policies() {
+        let policies: Vec<Box<dyn DecodePolicy>> = vec![
+            Box::new(Strict),
+            Box::new(Lossy),
+            Box::new(Escape),
+            Box::new(Strictness::Lossy),
+            Box::new(InvalidBytes::ReplaceWith(String::from("?"))),
+        ];
+        let decoded: Vec<_> = policies
+            .iter()
+            .map(|policy| policy.decode(Vec::from(b"\xc0".as_slice())).ok())
+            .collect();
+        assert_eq!(
+            decoded,
+            [
+                None,
+                Some(String::from("\u{fffd}")),
+                Some(String::from("\\xc0")),
+                Some(String::from("\u{fffd}")),
+                Some(String::from("?")),
+            ]
+        );
+
+        let converted =
+            Utf8Output::try_from_with_policy(output(b"\xc0", b"\xc0"), &policies[1], &*policies[2])
+                .unwrap();
+        assert_eq!(converted.stdout, "\u{fffd}");
+        assert_eq!(converted.stderr, "\\xc0");
+    }
+
+    #[test]
+    fn test_strict_errors_match_try_from() {
+        for (stdout, stderr) in [
+            (b"puppy\xc0".as_slice(), b"doggy".as_slice()),
+            (b"puppy", b"doggy\xc0"),
+            (b"puppy\xc0", b"doggy\xc0"),
+        ] {
+            let err = Utf8Output::try_from_with_policy(output(stdout, stderr), &Strict, &Strict)
+                .unwrap_err();
+            assert_eq!(
+                err,
+                Utf8Output::try_from(output(stdout, stderr)).unwrap_err()
+            );
+        }
+
+        let err = Utf8Output::try_from_with_policy(output(b"\xc0", b"\xc0"), &Lossy, &Strict)
+            .unwrap_err();
+        assert!(matches!(err, Error::Stderr(_)));
+    }
+
+    #[test]
+    fn test_offset() {
+        let inner = String::from_utf8(Vec::from(b"doggy \xc0")).unwrap_err();
+        let err = StreamDecodeError::at_offset(inner, 6);
+        assert_eq!(err.valid_up_to(), 12);
+        assert_eq!(
+            err.to_string(),
+            "Output contained invalid utf-8 sequence of 1 bytes from index 12: \
+            [6 bytes] \"doggy �\""
+        );
+
+        let err = StderrError::from(err);
+        assert_eq!(err.valid_up_to(), 12);
+        assert_eq!(
+            err.to_string(),
+            "Stderr contained invalid utf-8 sequence of 1 bytes from index 12: \
+            [6 bytes] \"doggy �\""
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! [`predicates_core::Predicate`]s on decoded output streams, for use with `assert_cmd`.
+//!
+//! These fail with this crate's error messages if the stream isn't valid UTF-8:
+//!
+//! ```
+//! use assert_cmd::Command;
+//! use utf8_command::predicates::stderr_contains;
+//! use utf8_command::predicates::stdout_eq;
+//!
+//! Command::new("sh")
+//!     .args(["-c", "echo puppy; echo doggy >&2"])
+//!     .assert()
+//!     .stdout(stdout_eq("puppy\n"))
+//!     .stderr(stderr_contains("dog"));
+//!
+//! let err = Command::new("printf")
+//!     .arg("puppy \\300")
+//!     .assert()
+//!     .try_stdout(stdout_eq("puppy"))
+//!     .unwrap_err();
+//! assert!(err.to_string().contains(
+//!     "Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+//! ));
+//! ```
+//!
+//! An [`assert_cmd::assert::Assert`]'s output can also be converted into a [`Utf8Output`]:
+//!
+//! ```
+//! use assert_cmd::Command;
+//! use utf8_command::Utf8Output;
+//!
+//! let assert = Command::new("echo").arg("puppy").assert().success();
+//! let output = Utf8Output::try_from(&assert).unwrap();
+//! assert_eq!(output.stdout, "puppy\n");
+//! ```
+
+use std::fmt::Display;
+
+use assert_cmd::assert::Assert;
+use predicates_core::reflection::Case;
+use predicates_core::reflection::Parameter;
+use predicates_core::reflection::PredicateReflection;
+use predicates_core::reflection::Product;
+use predicates_core::Predicate;
+
+use crate::decode_stderr_ref;
+use crate::decode_stdout_ref;
+use crate::Error;
+use crate::Stream;
+use crate::Utf8Output;
+
+/// A [`Predicate`] on the decoded contents of a stream.
+///
+/// Construct this with [`stdout_eq`], [`stdout_contains`], [`stderr_eq`], or
+/// [`stderr_contains`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamPredicate {
+    stream: Stream,
+    comparison: Comparison,
+    expected: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Eq,
+    Contains,
+}
+
+/// Check that `stdout` is valid UTF-8 and equal to `expected`.
+pub fn stdout_eq(expected: impl Into<String>) -> StreamPredicate {
+    StreamPredicate::new(Stream::Stdout, Comparison::Eq, expected)
+}
+
+/// Check that `stdout` is valid UTF-8 and contains `expected`.
+pub fn stdout_contains(expected: impl Into<String>) -> StreamPredicate {
+    StreamPredicate::new(Stream::Stdout, Comparison::Contains, expected)
+}
+
+/// Check that `stderr` is valid UTF-8 and equal to `expected`.
+pub fn stderr_eq(expected: impl Into<String>) -> StreamPredicate {
+    StreamPredicate::new(Stream::Stderr, Comparison::Eq, expected)
+}
+
+/// Check that `stderr` is valid UTF-8 and contains `expected`.
+pub fn stderr_contains(expected: impl Into<String>) -> StreamPredicate {
+    StreamPredicate::new(Stream::Stderr, Comparison::Contains, expected)
+}
+
+impl StreamPredicate {
+    fn new(stream: Stream, comparison: Comparison, expected: impl Into<String>) -> Self {
+        Self {
+            stream,
+            comparison,
+            expected: expected.into(),
+        }
+    }
+
+    /// Decode the stream, with an error message labeled with the stream.
+    fn decode<'a>(&self, bytes: &'a [u8]) -> Result<&'a str, String> {
+        match self.stream {
+            Stream::Stdout => decode_stdout_ref(bytes).map_err(|err| err.to_string()),
+            Stream::Stderr => decode_stderr_ref(bytes).map_err(|err| err.to_string()),
+        }
+    }
+
+    fn matches(&self, actual: &str) -> bool {
+        match self.comparison {
+            Comparison::Eq => actual == self.expected,
+            Comparison::Contains => actual.contains(&self.expected),
+        }
+    }
+}
+
+impl Predicate<[u8]> for StreamPredicate {
+    fn eval(&self, variable: &[u8]) -> bool {
+        self.decode(variable)
+            .is_ok_and(|actual| self.matches(actual))
+    }
+
+    fn find_case<'a>(&'a self, expected: bool, variable: &[u8]) -> Option<Case<'a>> {
+        let decoded = self.decode(variable);
+        let result = decoded.as_ref().is_ok_and(|actual| self.matches(actual));
+        if result != expected {
+            return None;
+        }
+
+        let case = Case::new(Some(self), result);
+        Some(match decoded {
+            Ok(actual) => case.add_product(Product::new("var", format!("{actual:?}"))),
+            Err(message) => case.add_product(Product::new("error", message)),
+        })
+    }
+}
+
+impl PredicateReflection for StreamPredicate {
+    fn parameters<'a>(&'a self) -> Box<dyn Iterator<Item = Parameter<'a>> + 'a> {
+        Box::new(std::iter::once(Parameter::new("expected", &self.expected)))
+    }
+}
+
+impl Display for StreamPredicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stream = self.stream.select("stdout", "stderr");
+        match self.comparison {
+            Comparison::Eq => write!(f, "{stream} == {:?}", self.expected),
+            Comparison::Contains => write!(f, "{stream} contains {:?}", self.expected),
+        }
+    }
+}
+
+impl TryFrom<&Assert> for Utf8Output {
+    type Error = Error;
+
+    fn try_from(assert: &Assert) -> Result<Self, Self::Error> {
+        Self::try_from(assert.get_output())
+    }
+}
+
+impl TryFrom<Assert> for Utf8Output {
+    type Error = Error;
+
+    fn try_from(assert: Assert) -> Result<Self, Self::Error> {
+        Self::try_from(assert.get_output().clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval() {
+        assert!(stdout_eq("puppy").eval(b"puppy"));
+        assert!(!stdout_eq("puppy").eval(b"puppy\n"));
+        assert!(stderr_contains("pup").eval(b"puppy"));
+        assert!(!stderr_contains("dog").eval(b"puppy"));
+        assert!(!stderr_contains("pup").eval(b"puppy \xc0"));
+    }
+
+    #[test]
+    fn test_find_case() {
+        let predicate = stderr_contains("pup");
+        assert!(predicate.find_case(true, b"puppy \xc0").is_none());
+
+        let case = predicate.find_case(false, b"puppy \xc0").unwrap();
+        let products = case
+            .products()
+            .map(|product| (product.name().to_owned(), product.value().to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            products,
+            [(
+                String::from("error"),
+                String::from(
+                    "Stderr contained invalid utf-8 sequence of 1 bytes from index 6: \
+                    \"puppy �\""
+                )
+            )]
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(stdout_eq("puppy").to_string(), "stdout == \"puppy\"");
+        assert_eq!(
+            stderr_contains("doggy").to_string(),
+            "stderr contains \"doggy\""
+        );
+    }
+}
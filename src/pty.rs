@@ -0,0 +1,231 @@
+use std::io::Read;
+use std::process::Command;
+use std::process::ExitStatus;
+
+use portable_pty::native_pty_system;
+use portable_pty::CommandBuilder;
+use portable_pty::PtySize;
+
+use crate::CombinedDecodeError;
+use crate::CombinedError;
+use crate::CommandInfo;
+use crate::SpawnError;
+
+/// Options for running a command attached to a pseudo-terminal with
+/// [`CommandExt::output_utf8_pty`][crate::CommandExt::output_utf8_pty].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PtyOptions {
+    rows: u16,
+    cols: u16,
+    strip_ansi: bool,
+}
+
+impl Default for PtyOptions {
+    fn default() -> Self {
+        Self {
+            rows: 24,
+            cols: 80,
+            strip_ansi: false,
+        }
+    }
+}
+
+impl PtyOptions {
+    /// Construct the default options: an 80x24 terminal, with escape sequences kept.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the size of the terminal, in rows and columns of text.
+    pub fn size(mut self, rows: u16, cols: u16) -> Self {
+        self.rows = rows;
+        self.cols = cols;
+        self
+    }
+
+    /// Set whether to strip ANSI escape sequences (like colors and cursor movement) from the
+    /// output.
+    ///
+    /// Programs often print more escape sequences when they're attached to a terminal.
+    pub fn strip_ansi(mut self, strip_ansi: bool) -> Self {
+        self.strip_ansi = strip_ansi;
+        self
+    }
+}
+
+/// The output of a command which was run attached to a pseudo-terminal, from
+/// [`CommandExt::output_utf8_pty`][crate::CommandExt::output_utf8_pty].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PtyUtf8Output {
+    /// The command's exit status.
+    ///
+    /// If the command was killed by a signal, this is reported as exit code 1, because the
+    /// signal number isn't available.
+    pub status: ExitStatus,
+    /// Everything the command wrote to the terminal, decoded as UTF-8.
+    ///
+    /// The terminal translates `\n` to `\r\n`.
+    pub output: String,
+}
+
+/// Run `command` attached to a pseudo-terminal, and decode everything it writes to the
+/// terminal.
+pub(crate) fn output_pty(
+    command: &Command,
+    options: PtyOptions,
+) -> Result<PtyUtf8Output, CombinedError> {
+    let info = CommandInfo::from(command);
+    let (status, output) =
+        run(command, options).map_err(|err| SpawnError::new(err, Some(info.clone())))?;
+    let mut output = String::from_utf8(output)
+        .map_err(|inner| CombinedDecodeError::new(inner, status, Some(info)))?;
+    if options.strip_ansi {
+        crate::ansi::strip_ansi(&mut output);
+    }
+    Ok(PtyUtf8Output { status, output })
+}
+
+fn run(command: &Command, options: PtyOptions) -> std::io::Result<(ExitStatus, Vec<u8>)> {
+    let pair = native_pty_system()
+        .openpty(PtySize {
+            rows: options.rows,
+            cols: options.cols,
+            ..Default::default()
+        })
+        .map_err(std::io::Error::other)?;
+    let mut child = pair
+        .slave
+        .spawn_command(command_builder(command))
+        .map_err(std::io::Error::other)?;
+    // Only the child should have the terminal open, so reads see EOF once it exits.
+    drop(pair.slave);
+
+    let mut reader = pair
+        .master
+        .try_clone_reader()
+        .map_err(std::io::Error::other)?;
+    let reader = std::thread::spawn(move || {
+        let mut output = Vec::new();
+        match reader.read_to_end(&mut output) {
+            Ok(_) => Ok(output),
+            // On Linux, reading from a terminal which nothing else has open fails with `EIO`.
+            Err(err) if cfg!(unix) && err.raw_os_error() == Some(5) => Ok(output),
+            Err(err) => Err(err),
+        }
+    });
+
+    let status = child.wait();
+    // On Windows, reads only see EOF once the terminal is closed.
+    drop(pair.master);
+    let output = reader.join().expect("Terminal reader thread panicked")?;
+    Ok((exit_status(status?), output))
+}
+
+/// Translate a [`std::process::Command`] to the `portable-pty` equivalent.
+fn command_builder(command: &Command) -> CommandBuilder {
+    let mut builder = CommandBuilder::new(command.get_program());
+    builder.args(command.get_args());
+    for (key, value) in command.get_envs() {
+        match value {
+            Some(value) => builder.env(key, value),
+            None => builder.env_remove(key),
+        }
+    }
+    match command.get_current_dir() {
+        Some(dir) => builder.cwd(dir),
+        None => {
+            if let Ok(dir) = std::env::current_dir() {
+                builder.cwd(dir);
+            }
+        }
+    }
+    builder
+}
+
+#[cfg(unix)]
+fn exit_status(status: portable_pty::ExitStatus) -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+    ExitStatus::from_raw(((status.exit_code() & 0xff) << 8) as i32)
+}
+
+#[cfg(windows)]
+fn exit_status(status: portable_pty::ExitStatus) -> ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+    ExitStatus::from_raw(status.exit_code())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::CommandExt;
+
+    use super::*;
+
+    #[test]
+    fn test_is_a_tty() {
+        let mut command = Command::new("sh");
+        command.args([
+            "-c",
+            "if [ -t 0 ] && [ -t 1 ] && [ -t 2 ]; then echo tty; else echo pipe; fi",
+        ]);
+        let output = command.output_utf8_pty(PtyOptions::new()).unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.output, "tty\r\n");
+
+        let output = command.output_utf8().unwrap();
+        assert_eq!(output.stdout, "pipe\n");
+    }
+
+    #[test]
+    fn test_size() {
+        let output = Command::new("stty")
+            .arg("size")
+            .output_utf8_pty(PtyOptions::new().size(40, 100))
+            .unwrap();
+        assert_eq!(output.output, "40 100\r\n");
+    }
+
+    #[test]
+    fn test_status_and_environment() {
+        let output = Command::new("sh")
+            .args(["-c", "echo \"$PUPPY\" >&2; exit 3"])
+            .env("PUPPY", "doggy")
+            .output_utf8_pty(PtyOptions::new())
+            .unwrap();
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(output.output, "doggy\r\n");
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        let mut command = Command::new("printf");
+        command.arg("\\033[31mpuppy\\033[0m");
+        let output = command.output_utf8_pty(PtyOptions::new()).unwrap();
+        assert_eq!(output.output, "\x1b[31mpuppy\x1b[0m");
+
+        let output = command
+            .output_utf8_pty(PtyOptions::new().strip_ansi(true))
+            .unwrap();
+        assert_eq!(output.output, "puppy");
+    }
+
+    #[test]
+    fn test_invalid() {
+        let err = Command::new("printf")
+            .arg("puppy \\300")
+            .output_utf8_pty(PtyOptions::new())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`printf 'puppy \\300'`: \
+            Output contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+        );
+    }
+
+    #[test]
+    fn test_missing_binary() {
+        let err = Command::new("utf8-command-this-program-does-not-exist")
+            .output_utf8_pty(PtyOptions::new())
+            .unwrap_err();
+        assert!(err.io_error().is_some());
+    }
+}
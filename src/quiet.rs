@@ -0,0 +1,254 @@
+use std::fmt::Display;
+
+use crate::CheckedUtf8Output;
+use crate::ErrorContext;
+use crate::FailedCommandError;
+use crate::Utf8Output;
+use crate::ERROR_CONTEXT_BYTES;
+
+impl Utf8Output {
+    /// Require the command to have written nothing to `stderr` except whitespace.
+    ///
+    /// This is useful for commands which print warnings on `stderr` but still exit successfully,
+    /// when warnings should be treated as errors. Trailing ASCII whitespace is ignored, like a
+    /// lone newline; use [`Utf8Output::require_empty_stderr_strict`] to reject any output at all.
+    ///
+    /// The exit status isn't checked; see [`Utf8Output::require_success_and_quiet`].
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let err = Command::new("sh")
+    ///     .args(["-c", "echo 'warning: puppy is deprecated' >&2"])
+    ///     .output_utf8()
+    ///     .unwrap()
+    ///     .require_empty_stderr()
+    ///     .unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Command wrote to stderr: \"warning: puppy is deprecated\\n\""
+    /// );
+    /// ```
+    pub fn require_empty_stderr(self) -> Result<Self, StderrNotEmpty> {
+        if self.stderr_trimmed().is_empty() {
+            Ok(self)
+        } else {
+            Err(StderrNotEmpty { output: self })
+        }
+    }
+
+    /// Require the command to have written nothing to `stderr`, not even whitespace.
+    ///
+    /// See [`Utf8Output::require_empty_stderr`].
+    pub fn require_empty_stderr_strict(self) -> Result<Self, StderrNotEmpty> {
+        if self.stderr.is_empty() {
+            Ok(self)
+        } else {
+            Err(StderrNotEmpty { output: self })
+        }
+    }
+
+    /// Require the command to have exited successfully, and to have written nothing to `stderr`
+    /// except whitespace.
+    ///
+    /// See [`Utf8Output::require_success`] and [`Utf8Output::require_empty_stderr`].
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let output = Command::new("echo")
+    ///     .arg("puppy")
+    ///     .output_utf8()
+    ///     .unwrap()
+    ///     .require_success_and_quiet()
+    ///     .unwrap();
+    /// assert_eq!(output.stdout(), "puppy\n");
+    /// ```
+    pub fn require_success_and_quiet(self) -> Result<CheckedUtf8Output, QuietError> {
+        let output = self.require_success()?.into_inner();
+        Ok(CheckedUtf8Output::new(output.require_empty_stderr()?))
+    }
+}
+
+/// An error produced by [`Utf8Output::require_empty_stderr`] when a command writes to `stderr`.
+///
+/// When displayed, only the start of `stderr` is shown if it's long (currently, more than 1024
+/// bytes), because the first warning is usually the most useful.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StderrNotEmpty {
+    output: Utf8Output,
+}
+
+impl StderrNotEmpty {
+    /// Get a reference to the command's output.
+    pub fn output(&self) -> &Utf8Output {
+        &self.output
+    }
+
+    /// Get the command's output.
+    pub fn into_output(self) -> Utf8Output {
+        self.output
+    }
+}
+
+impl Display for StderrNotEmpty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Command wrote to stderr: {}",
+            ErrorContext::at(self.output.stderr.as_bytes(), 0, ERROR_CONTEXT_BYTES)
+        )
+    }
+}
+
+impl std::error::Error for StderrNotEmpty {}
+
+/// An error produced by [`Utf8Output::require_success_and_quiet`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuietError {
+    /// The command exited unsuccessfully.
+    Failed(FailedCommandError),
+    /// The command exited successfully, but wrote to `stderr`.
+    StderrNotEmpty(StderrNotEmpty),
+}
+
+impl QuietError {
+    /// Get a reference to the command's output.
+    pub fn output(&self) -> &Utf8Output {
+        match self {
+            QuietError::Failed(err) => err.output(),
+            QuietError::StderrNotEmpty(err) => err.output(),
+        }
+    }
+
+    /// Get the command's output.
+    pub fn into_output(self) -> Utf8Output {
+        match self {
+            QuietError::Failed(err) => err.into_output(),
+            QuietError::StderrNotEmpty(err) => err.into_output(),
+        }
+    }
+}
+
+impl From<FailedCommandError> for QuietError {
+    fn from(value: FailedCommandError) -> Self {
+        Self::Failed(value)
+    }
+}
+
+impl From<StderrNotEmpty> for QuietError {
+    fn from(value: StderrNotEmpty) -> Self {
+        Self::StderrNotEmpty(value)
+    }
+}
+
+impl Display for QuietError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QuietError::Failed(err) => write!(f, "{err}"),
+            QuietError::StderrNotEmpty(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for QuietError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            QuietError::Failed(err) => Some(err),
+            QuietError::StderrNotEmpty(err) => Some(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::exit_code;
+    use crate::test_util::utf8_output;
+    use crate::test_util::utf8_output_with_status;
+
+    #[test]
+    fn test_empty_stderr() {
+        let quiet = utf8_output("puppy\n", "");
+        assert_eq!(quiet.clone().require_empty_stderr().unwrap(), quiet);
+        assert_eq!(quiet.clone().require_empty_stderr_strict().unwrap(), quiet);
+        assert_eq!(
+            quiet
+                .clone()
+                .require_success_and_quiet()
+                .unwrap()
+                .into_inner(),
+            quiet
+        );
+    }
+
+    #[test]
+    fn test_whitespace_stderr() {
+        let output = utf8_output("puppy\n", "\n \r\n\t");
+        assert_eq!(output.clone().require_empty_stderr().unwrap(), output);
+        assert!(output.clone().require_success_and_quiet().is_ok());
+
+        let err = output.clone().require_empty_stderr_strict().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Command wrote to stderr: \"\\n \\r\\n\\t\""
+        );
+        assert_eq!(err.into_output(), output);
+    }
+
+    #[test]
+    fn test_nonempty_stderr() {
+        let output = utf8_output("puppy\n", "warning: doggy\n");
+        let err = output.clone().require_empty_stderr().unwrap_err();
+        assert_eq!(err.output(), &output);
+        assert_eq!(
+            err.to_string(),
+            "Command wrote to stderr: \"warning: doggy\\n\""
+        );
+        assert!(output.clone().require_empty_stderr_strict().is_err());
+
+        let err = output.clone().require_success_and_quiet().unwrap_err();
+        assert!(matches!(err, QuietError::StderrNotEmpty(_)));
+        assert_eq!(
+            err.to_string(),
+            "Command wrote to stderr: \"warning: doggy\\n\""
+        );
+        assert_eq!(err.into_output(), output);
+    }
+
+    #[test]
+    fn test_long_stderr() {
+        let stderr = format!("warning: kitty\n{}", "warning: puppy\n".repeat(100));
+        let message = utf8_output("puppy\n", &stderr)
+            .require_empty_stderr()
+            .unwrap_err()
+            .to_string();
+        assert!(message.len() < 1200, "{message}");
+        assert!(
+            message.starts_with("Command wrote to stderr: \"warning: kitty\\nwarning: puppy"),
+            "{message}"
+        );
+        assert!(message.ends_with(" bytes]"), "{message}");
+    }
+
+    #[test]
+    fn test_success_and_quiet_failed() {
+        // Exit code 1, with whitespace on `stderr`.
+        let failed = utf8_output_with_status(exit_code(1), "puppy\n", "\n");
+        let err = failed.clone().require_success_and_quiet().unwrap_err();
+        assert!(matches!(err, QuietError::Failed(_)));
+        assert_eq!(
+            err.to_string(),
+            "Command failed with exit status: 1; stderr: \"\\n\""
+        );
+        assert_eq!(err.output(), &failed);
+
+        // Failures are reported before `stderr` is checked.
+        let err = utf8_output_with_status(exit_code(1), "puppy\n", "warning: doggy\n")
+            .require_success_and_quiet()
+            .unwrap_err();
+        assert!(matches!(err, QuietError::Failed(_)));
+    }
+}
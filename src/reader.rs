@@ -0,0 +1,342 @@
+use std::io::Read;
+use std::ops::Range;
+use std::task::Poll;
+
+use crate::StreamDecodeError;
+
+/// The number of bytes to read from the inner reader at a time.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// A reader which decodes the bytes of any [`Read`] as UTF-8 incrementally, like a
+/// [`ChildStdout`][std::process::ChildStdout].
+///
+/// Code points split across reads are handled correctly: at most 3 bytes of an incomplete
+/// sequence are kept between calls to [`Utf8Reader::read_str`].
+///
+/// ```
+/// use utf8_command::Utf8Reader;
+///
+/// let mut reader = Utf8Reader::new("puppy 🐶".as_bytes());
+/// let mut decoded = String::new();
+/// while let Some(chunk) = reader.read_str().unwrap() {
+///     decoded.push_str(chunk);
+/// }
+/// assert_eq!(decoded, "puppy 🐶");
+/// ```
+///
+/// By default, invalid UTF-8 fails with an [`std::io::Error`] of kind
+/// [`InvalidData`][std::io::ErrorKind::InvalidData] which wraps a [`StreamDecodeError`], after
+/// the valid bytes before it are returned:
+///
+/// ```
+/// use utf8_command::StreamDecodeError;
+/// use utf8_command::Utf8Reader;
+///
+/// let mut reader = Utf8Reader::new(b"puppy \xc0 doggy".as_slice());
+/// assert_eq!(reader.read_str().unwrap(), Some("puppy "));
+///
+/// let err = reader.read_str().unwrap_err();
+/// assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+/// let err = err.get_ref().unwrap().downcast_ref::<StreamDecodeError>().unwrap();
+/// assert_eq!(err.valid_up_to(), 6);
+/// assert_eq!(
+///     err.to_string(),
+///     "Output contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy � doggy\""
+/// );
+/// ```
+#[derive(Debug)]
+pub struct Utf8Reader<R> {
+    inner: R,
+    buffer: DecodeBuffer,
+}
+
+impl<R: Read> Utf8Reader<R> {
+    /// Construct a reader which decodes the bytes read from `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buffer: DecodeBuffer::default(),
+        }
+    }
+
+    /// Replace invalid UTF-8 sequences with U+FFFD REPLACEMENT CHARACTER rather than failing,
+    /// like [`String::from_utf8_lossy`].
+    ///
+    /// An incomplete sequence at the end of the stream is replaced, too.
+    ///
+    /// ```
+    /// use utf8_command::Utf8Reader;
+    ///
+    /// let mut reader = Utf8Reader::new(b"puppy \xc0 doggy \xf0\x9f".as_slice()).lossy();
+    /// let mut decoded = String::new();
+    /// while let Some(chunk) = reader.read_str().unwrap() {
+    ///     decoded.push_str(chunk);
+    /// }
+    /// assert_eq!(decoded, "puppy \u{FFFD} doggy \u{FFFD}");
+    /// ```
+    pub fn lossy(mut self) -> Self {
+        self.buffer.lossy = true;
+        self
+    }
+
+    /// Read and decode the next chunk of the stream, or `None` at the end of the stream.
+    ///
+    /// Returned chunks are never empty.
+    pub fn read_str(&mut self) -> std::io::Result<Option<&str>> {
+        loop {
+            let decoded = self.buffer.decode()?;
+            if decoded != Decoded::Pending {
+                return Ok(self.buffer.get(decoded));
+            }
+            let inner = &mut self.inner;
+            match self.buffer.fill(|buf| Poll::Ready(inner.read(buf))) {
+                Poll::Ready(result) => result?,
+                Poll::Pending => unreachable!("Blocking reads are always ready"),
+            }
+        }
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner reader.
+    ///
+    /// Reading from the inner reader directly may corrupt the decoded stream.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Get the inner reader, discarding any buffered bytes.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+/// The result of decoding the next chunk of a [`DecodeBuffer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Decoded {
+    /// Valid text in a range of the buffer.
+    Text(Range<usize>),
+    /// An invalid sequence, replaced with U+FFFD REPLACEMENT CHARACTER.
+    Replacement,
+    /// The end of the stream.
+    End,
+    /// More bytes must be read.
+    Pending,
+}
+
+/// The buffering and decoding shared by [`Utf8Reader`] and the asynchronous readers.
+#[derive(Debug, Default)]
+pub(crate) struct DecodeBuffer {
+    /// Bytes read from the inner reader, starting with some which have already been returned.
+    buffer: Vec<u8>,
+    /// The index in `buffer` of the first byte which hasn't been returned.
+    start: usize,
+    /// The index in the stream of the first byte in `buffer`.
+    offset: usize,
+    /// Whether the inner reader has reached EOF.
+    eof: bool,
+    /// Whether to replace invalid sequences rather than failing.
+    pub(crate) lossy: bool,
+}
+
+impl DecodeBuffer {
+    /// Decode the next chunk of the buffered bytes.
+    pub(crate) fn decode(&mut self) -> std::io::Result<Decoded> {
+        let pending = &self.buffer[self.start..];
+        if !pending.is_empty() {
+            match std::str::from_utf8(pending) {
+                Ok(_) => {
+                    let start = std::mem::replace(&mut self.start, self.buffer.len());
+                    return Ok(Decoded::Text(start..self.start));
+                }
+                Err(err) if err.valid_up_to() > 0 => {
+                    let start = self.start;
+                    self.start += err.valid_up_to();
+                    return Ok(Decoded::Text(start..self.start));
+                }
+                Err(err) => {
+                    if let Some(len) = err.error_len() {
+                        return self.invalid(len);
+                    }
+                    // An incomplete sequence; wait for more bytes.
+                }
+            }
+        }
+
+        if self.eof {
+            return match self.buffer.len() - self.start {
+                0 => Ok(Decoded::End),
+                len => self.invalid(len),
+            };
+        }
+        Ok(Decoded::Pending)
+    }
+
+    /// Handle an invalid sequence of `len` bytes at the start of the pending bytes.
+    fn invalid(&mut self, len: usize) -> std::io::Result<Decoded> {
+        if self.lossy {
+            self.start += len;
+            return Ok(Decoded::Replacement);
+        }
+        let inner = String::from_utf8(self.buffer.clone())
+            .expect_err("The pending bytes should be invalid UTF-8");
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            StreamDecodeError::at_offset(inner, self.offset),
+        ))
+    }
+
+    /// Get the text for a decoded chunk, or `None` at the end of the stream.
+    pub(crate) fn get(&self, decoded: Decoded) -> Option<&str> {
+        match decoded {
+            Decoded::Text(range) => Some(
+                std::str::from_utf8(&self.buffer[range]).expect("Bytes should have been validated"),
+            ),
+            Decoded::Replacement => Some("\u{FFFD}"),
+            Decoded::End => None,
+            Decoded::Pending => unreachable!("Pending chunks should be read before they're used"),
+        }
+    }
+
+    /// Discard the bytes which have been returned, and read more with `read`.
+    pub(crate) fn fill(
+        &mut self,
+        mut read: impl FnMut(&mut [u8]) -> Poll<std::io::Result<usize>>,
+    ) -> Poll<std::io::Result<()>> {
+        self.buffer.drain(..self.start);
+        self.offset += self.start;
+        self.start = 0;
+
+        let len = self.buffer.len();
+        self.buffer.resize(len + CHUNK_SIZE, 0);
+        let poll = loop {
+            match read(&mut self.buffer[len..]) {
+                Poll::Ready(Err(err)) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                poll => break poll,
+            }
+        };
+        let read = match &poll {
+            Poll::Ready(Ok(read)) => *read,
+            _ => 0,
+        };
+        self.buffer.truncate(len + read);
+        if read == 0 && matches!(poll, Poll::Ready(Ok(_))) {
+            self.eof = true;
+        }
+        poll.map(|result| result.map(|_| ()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A reader which returns one byte at a time.
+    struct ByteAtATime<'a>(&'a [u8]);
+
+    impl Read for ByteAtATime<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.0.split_first() {
+                Some((first, rest)) if !buf.is_empty() => {
+                    buf[0] = *first;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    fn read_all<R: Read>(reader: &mut Utf8Reader<R>) -> std::io::Result<Vec<String>> {
+        let mut chunks = Vec::new();
+        while let Some(chunk) = reader.read_str()? {
+            chunks.push(chunk.to_owned());
+        }
+        Ok(chunks)
+    }
+
+    #[test]
+    fn test_byte_at_a_time() {
+        let mut reader = Utf8Reader::new(ByteAtATime("a🐶b".as_bytes()));
+        assert_eq!(read_all(&mut reader).unwrap(), ["a", "🐶", "b"]);
+    }
+
+    #[test]
+    fn test_incomplete_at_eof() {
+        let mut reader = Utf8Reader::new(ByteAtATime(b"puppy \xf0\x9f\x90"));
+        let mut decoded = String::new();
+        let err = loop {
+            match reader.read_str() {
+                Ok(Some(chunk)) => decoded.push_str(chunk),
+                Ok(None) => unreachable!("The stream ends with an incomplete sequence"),
+                Err(err) => break err,
+            }
+        };
+        assert_eq!(decoded, "puppy ");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let err = err
+            .get_ref()
+            .unwrap()
+            .downcast_ref::<StreamDecodeError>()
+            .unwrap();
+        assert_eq!(err.valid_up_to(), 6);
+        assert_eq!(
+            err.to_string(),
+            "Output contained incomplete utf-8 byte sequence from index 6: [6 bytes] \"�\""
+        );
+
+        let mut reader = Utf8Reader::new(ByteAtATime(b"puppy \xf0\x9f\x90")).lossy();
+        assert_eq!(read_all(&mut reader).unwrap().concat(), "puppy \u{FFFD}");
+    }
+
+    #[test]
+    fn test_invalid_offset() {
+        let mut bytes = "puppy ".repeat(2000).into_bytes();
+        bytes.push(0xc0);
+        let mut reader = Utf8Reader::new(bytes.as_slice());
+        assert_eq!(reader.read_str().unwrap().unwrap().len(), CHUNK_SIZE);
+        assert_eq!(
+            reader.read_str().unwrap().unwrap().len(),
+            12000 - CHUNK_SIZE
+        );
+        let err = reader.read_str().unwrap_err().into_inner().unwrap();
+        let err = err.downcast::<StreamDecodeError>().unwrap();
+        assert_eq!(err.valid_up_to(), 12000);
+        assert!(err
+            .to_string()
+            .starts_with("Output contained invalid utf-8 sequence of 1 bytes from index 12000: "));
+    }
+
+    #[test]
+    fn test_lossy() {
+        let mut reader = Utf8Reader::new(ByteAtATime(b"\xc0pup\xf0\x9fpy\x80")).lossy();
+        assert_eq!(
+            read_all(&mut reader).unwrap().concat(),
+            "\u{FFFD}pup\u{FFFD}py\u{FFFD}"
+        );
+        assert_eq!(
+            read_all(&mut reader).unwrap(),
+            Vec::<String>::new(),
+            "Reading after EOF returns `None`"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_child_stdout() {
+        use std::process::Command;
+        use std::process::Stdio;
+
+        let mut child = Command::new("printf")
+            .arg("puppy 🐶\\n")
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut reader = Utf8Reader::new(child.stdout.take().unwrap());
+        assert_eq!(read_all(&mut reader).unwrap().concat(), "puppy 🐶\n");
+        assert!(child.wait().unwrap().success());
+    }
+}
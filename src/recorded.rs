@@ -0,0 +1,136 @@
+use std::fmt::Display;
+use std::ops::Deref;
+use std::ops::DerefMut;
+
+use crate::CommandInfo;
+use crate::Utf8Output;
+
+/// A [`Utf8Output`] along with the command which produced it, from
+/// [`CommandExt::output_utf8_recorded`][crate::CommandExt::output_utf8_recorded].
+///
+/// This dereferences to the [`Utf8Output`], and displays as a one-line summary suitable for
+/// logs:
+///
+/// ```
+/// # use std::process::Command;
+/// use utf8_command::prelude::*;
+///
+/// let output = Command::new("echo")
+///     .arg("puppy")
+///     .current_dir("/")
+///     .output_utf8_recorded()
+///     .unwrap();
+/// assert_eq!(output.stdout, "puppy\n");
+/// assert_eq!(
+///     output.to_string(),
+///     "`echo puppy` in /: exit status: 0, stdout: 6 bytes, stderr: 0 bytes"
+/// );
+/// ```
+///
+/// With the `serde` feature, this serializes as a struct with `command` and `output` fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Utf8OutputWithCommand {
+    /// The command which produced the output.
+    pub command: CommandInfo,
+    /// The command's decoded output.
+    pub output: Utf8Output,
+}
+
+impl Utf8OutputWithCommand {
+    /// Get the decoded output, discarding the command.
+    pub fn into_output(self) -> Utf8Output {
+        self.output
+    }
+}
+
+impl Deref for Utf8OutputWithCommand {
+    type Target = Utf8Output;
+
+    fn deref(&self) -> &Self::Target {
+        &self.output
+    }
+}
+
+impl DerefMut for Utf8OutputWithCommand {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.output
+    }
+}
+
+impl From<Utf8OutputWithCommand> for Utf8Output {
+    fn from(value: Utf8OutputWithCommand) -> Self {
+        value.output
+    }
+}
+
+impl Display for Utf8OutputWithCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}`", self.command)?;
+        if let Some(dir) = self.command.current_dir_lossy() {
+            write!(f, " in {dir}")?;
+        }
+        write!(
+            f,
+            ": {}, stdout: {} bytes, stderr: {} bytes",
+            self.output.status,
+            self.output.stdout.len(),
+            self.output.stderr.len()
+        )
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Utf8OutputWithCommand {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Utf8OutputWithCommand", 2)?;
+        state.serialize_field("command", &self.command)?;
+        state.serialize_field("output", &self.output)?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::utf8_output;
+
+    fn recorded() -> Utf8OutputWithCommand {
+        Utf8OutputWithCommand {
+            command: CommandInfo::new("echo", ["puppy doggy"]),
+            output: utf8_output("puppy doggy\n", ""),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            recorded().to_string(),
+            "`echo 'puppy doggy'`: exit status: 0, stdout: 12 bytes, stderr: 0 bytes"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize() {
+        let mut recorded = recorded();
+        recorded.command = recorded.command.with_current_dir("/tmp");
+        assert_eq!(
+            serde_json::to_value(&recorded).unwrap(),
+            serde_json::json!({
+                "command": {
+                    "program": "echo",
+                    "args": ["puppy doggy"],
+                    "current_dir": "/tmp",
+                },
+                "output": {
+                    "status": 0,
+                    "stdout": "puppy doggy\n",
+                    "stderr": "",
+                },
+            })
+        );
+    }
+}
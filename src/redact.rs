@@ -0,0 +1,174 @@
+/// The text secrets are replaced with.
+pub(crate) const REDACTED: &str = "[REDACTED]";
+
+/// A secret to redact from the streams.
+#[derive(Debug, Clone)]
+pub(crate) enum Redaction {
+    /// A literal string.
+    Literal(String),
+    /// A regular expression.
+    #[cfg(feature = "regex")]
+    Regex(regex::bytes::Regex),
+}
+
+impl PartialEq for Redaction {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Redaction::Literal(left), Redaction::Literal(right)) => left == right,
+            #[cfg(feature = "regex")]
+            (Redaction::Regex(left), Redaction::Regex(right)) => left.as_str() == right.as_str(),
+            #[cfg(feature = "regex")]
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Redaction {}
+
+/// Replace each secret in `bytes` with [`REDACTED`].
+///
+/// This works on the raw bytes, so secrets are redacted whether or not the stream is valid
+/// UTF-8. Literal secrets and [`REDACTED`] are valid UTF-8, so redacting a literal never makes
+/// invalid UTF-8 valid or vice versa. Regular expressions have no such guarantee: a match can
+/// include invalid bytes or part of a multi-byte character (for example, with `(?-u:\xff)`), so
+/// redacting it can change whether the stream is valid UTF-8.
+pub(crate) fn redact(bytes: &mut Vec<u8>, redactions: &[Redaction]) {
+    for redaction in redactions {
+        match redaction {
+            Redaction::Literal(secret) => {
+                if let Some(redacted) = replace_literal(bytes, secret.as_bytes()) {
+                    *bytes = redacted;
+                }
+            }
+            #[cfg(feature = "regex")]
+            Redaction::Regex(regex) => {
+                if let std::borrow::Cow::Owned(redacted) =
+                    regex.replace_all(bytes, regex::bytes::NoExpand(REDACTED.as_bytes()))
+                {
+                    *bytes = redacted;
+                }
+            }
+        }
+    }
+}
+
+/// Replace each secret in a stream which was transcoded while decoding (from UTF-16 or CESU-8),
+/// where the secrets may not have matched the raw bytes.
+///
+/// A regular expression match which splits a character is decoded lossily.
+pub(crate) fn redact_decoded(text: &mut String, redactions: &[Redaction]) {
+    if redactions.is_empty() {
+        return;
+    }
+    let mut bytes = std::mem::take(text).into_bytes();
+    redact(&mut bytes, redactions);
+    *text = String::from_utf8(bytes)
+        .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned());
+}
+
+/// Replace each occurrence of `secret` in `bytes` with [`REDACTED`], or return `None` if there
+/// aren't any.
+fn replace_literal(bytes: &[u8], secret: &[u8]) -> Option<Vec<u8>> {
+    if secret.is_empty() {
+        return None;
+    }
+
+    let find = |haystack: &[u8]| {
+        haystack
+            .windows(secret.len())
+            .position(|window| window == secret)
+    };
+
+    let mut index = find(bytes)?;
+    let mut redacted = Vec::with_capacity(bytes.len());
+    let mut rest = bytes;
+    loop {
+        redacted.extend_from_slice(&rest[..index]);
+        redacted.extend_from_slice(REDACTED.as_bytes());
+        rest = &rest[index + secret.len()..];
+        match find(rest) {
+            Some(next) => index = next,
+            None => break,
+        }
+    }
+    redacted.extend_from_slice(rest);
+    Some(redacted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redacted(bytes: &[u8], redactions: &[Redaction]) -> Vec<u8> {
+        let mut bytes = bytes.to_vec();
+        redact(&mut bytes, redactions);
+        bytes
+    }
+
+    #[test]
+    fn test_literal() {
+        let hunter2 = [Redaction::Literal(String::from("hunter2"))];
+        assert_eq!(redacted(b"puppy", &hunter2), b"puppy");
+        assert_eq!(
+            redacted(b"hunter2 puppy hunter2hunter2", &hunter2),
+            b"[REDACTED] puppy [REDACTED][REDACTED]"
+        );
+        // Invalid UTF-8 around a secret.
+        assert_eq!(
+            redacted(b"\xc0hunter2\xf0\x9f", &hunter2),
+            b"\xc0[REDACTED]\xf0\x9f"
+        );
+        assert_eq!(redacted(b"hunter", &hunter2), b"hunter");
+        // Occurrences don't overlap.
+        assert_eq!(
+            redacted(b"aaa", &[Redaction::Literal(String::from("aa"))]),
+            b"[REDACTED]a"
+        );
+        // Empty secrets are ignored.
+        assert_eq!(
+            redacted(b"puppy", &[Redaction::Literal(String::new())]),
+            b"puppy"
+        );
+    }
+
+    #[test]
+    fn test_decoded() {
+        let mut text = String::from("puppy 🐶 hunter2");
+        redact_decoded(&mut text, &[Redaction::Literal(String::from("🐶"))]);
+        assert_eq!(text, "puppy [REDACTED] hunter2");
+        redact_decoded(&mut text, &[]);
+        assert_eq!(text, "puppy [REDACTED] hunter2");
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex() {
+        let token = [Redaction::Regex(
+            regex::bytes::Regex::new("ghp_[A-Za-z0-9]+").unwrap(),
+        )];
+        assert_eq!(
+            redacted(b"token: ghp_abc123\xc0 and ghp_$1", &token),
+            b"token: [REDACTED]\xc0 and ghp_$1"
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex_invalid_utf8() {
+        // A match which includes an invalid sequence makes the stream valid UTF-8...
+        let invalid = [Redaction::Regex(
+            regex::bytes::Regex::new(r"(?-u)key=[^ ]*").unwrap(),
+        )];
+        let bytes = redacted(b"key=hunter\xc0 puppy", &invalid);
+        assert_eq!(bytes, b"[REDACTED] puppy");
+        assert!(std::str::from_utf8(&bytes).is_ok());
+
+        // ...and a match which splits a multi-byte character makes it invalid.
+        let partial = [Redaction::Regex(
+            regex::bytes::Regex::new(r"(?-u)\xf0\x9f").unwrap(),
+        )];
+        let bytes = redacted("puppy 🐶".as_bytes(), &partial);
+        assert_eq!(bytes, b"puppy [REDACTED]\x90\xb6");
+        assert!(std::str::from_utf8(&bytes).is_err());
+    }
+}
@@ -0,0 +1,283 @@
+use std::fmt::Display;
+use std::process::Command;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+use crate::CheckedError;
+use crate::CommandError;
+use crate::CommandExt;
+use crate::Utf8Output;
+
+/// Run a batch of commands concurrently, with at most `max_parallel` running at once, and
+/// decode each of their outputs as UTF-8.
+///
+/// The results are in the same order as the commands. Each command runs to completion
+/// regardless of whether the others fail, and unsuccessful exit statuses aren't treated as
+/// errors; see [`try_run_all_utf8`] to stop at the first failure.
+///
+/// Each command runs on its own thread, which drains the command's `stdout` and `stderr`, so
+/// commands never block on full pipes.
+///
+/// ```
+/// # use std::process::Command;
+/// let mut commands = Vec::new();
+/// for name in ["puppy", "doggy", "kitty"] {
+///     let mut command = Command::new("echo");
+///     command.arg(name);
+///     commands.push(command);
+/// }
+///
+/// let outputs = utf8_command::run_all_utf8(commands, 2)
+///     .into_iter()
+///     .map(|result| result.unwrap().stdout)
+///     .collect::<Vec<_>>();
+/// assert_eq!(outputs, ["puppy\n", "doggy\n", "kitty\n"]);
+/// ```
+///
+/// # Panics
+///
+/// If `max_parallel` is 0.
+pub fn run_all_utf8(
+    commands: Vec<Command>,
+    max_parallel: usize,
+) -> Vec<Result<Utf8Output, CommandError>> {
+    run_all(commands, max_parallel, false, |command| {
+        command.output_utf8()
+    })
+    .into_iter()
+    .map(|result| result.expect("Every command should run"))
+    .collect()
+}
+
+/// Run a batch of commands concurrently, with at most `max_parallel` running at once, stopping
+/// at the first failure.
+///
+/// A command fails if it can't be run, if it exits unsuccessfully, or if its output isn't valid
+/// UTF-8. Once a command fails, no more commands are started, but commands which are already
+/// running are waited for. If several commands fail, the error is for the first of them, in the
+/// order of `commands`.
+///
+/// ```
+/// # use std::process::Command;
+/// let mut commands = vec![Command::new("true"), Command::new("false"), Command::new("true")];
+/// commands[1].arg("puppy");
+///
+/// let err = utf8_command::try_run_all_utf8(commands, 1).unwrap_err();
+/// assert_eq!(err.index(), 1);
+/// assert_eq!(
+///     err.to_string(),
+///     "Command 1: Command `false puppy` failed with exit status: 1"
+/// );
+/// ```
+///
+/// # Panics
+///
+/// If `max_parallel` is 0.
+pub fn try_run_all_utf8(
+    commands: Vec<Command>,
+    max_parallel: usize,
+) -> Result<Vec<Utf8Output>, RunAllError> {
+    let results = run_all(commands, max_parallel, true, |command| {
+        command.output_utf8_checked()
+    });
+    let mut outputs = Vec::with_capacity(results.len());
+    for (index, result) in results.into_iter().enumerate() {
+        match result {
+            Some(Ok(output)) => outputs.push(output),
+            Some(Err(error)) => return Err(RunAllError { index, error }),
+            // Commands are only skipped after an earlier failure.
+            None => {}
+        }
+    }
+    Ok(outputs)
+}
+
+/// Run `commands` on up to `max_parallel` threads.
+///
+/// Returns `None` for commands which weren't started because an earlier one failed.
+fn run_all<E: Send>(
+    commands: Vec<Command>,
+    max_parallel: usize,
+    fail_fast: bool,
+    run: impl Fn(&mut Command) -> Result<Utf8Output, E> + Sync,
+) -> Vec<Option<Result<Utf8Output, E>>> {
+    assert!(max_parallel > 0, "At least one command must run at once");
+    let mut results = Vec::with_capacity(commands.len());
+    results.resize_with(commands.len(), || None);
+    let workers = max_parallel.min(commands.len());
+    let queue = Mutex::new(commands.into_iter().enumerate());
+    let failed = AtomicBool::new(false);
+
+    let finished = std::thread::scope(|scope| {
+        let handles = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut finished = Vec::new();
+                    loop {
+                        if failed.load(Ordering::SeqCst) {
+                            break;
+                        }
+                        let next = queue.lock().expect("Command queue was poisoned").next();
+                        let Some((index, mut command)) = next else {
+                            break;
+                        };
+                        let result = run(&mut command);
+                        if fail_fast && result.is_err() {
+                            failed.store(true, Ordering::SeqCst);
+                        }
+                        finished.push((index, result));
+                    }
+                    finished
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("Command runner thread panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    for (index, result) in finished {
+        results[index] = Some(result);
+    }
+    results
+}
+
+/// An error produced by [`try_run_all_utf8`], recording which command failed.
+#[derive(Debug)]
+pub struct RunAllError {
+    index: usize,
+    error: CheckedError,
+}
+
+impl RunAllError {
+    /// The index of the command which failed.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Get a reference to the command's error.
+    pub fn error(&self) -> &CheckedError {
+        &self.error
+    }
+
+    /// Get the command's error.
+    pub fn into_error(self) -> CheckedError {
+        self.error
+    }
+}
+
+impl Display for RunAllError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Command {}: {}", self.index, self.error)
+    }
+}
+
+impl std::error::Error for RunAllError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use super::*;
+
+    fn sh(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        command
+    }
+
+    #[test]
+    fn test_run_all_utf8_mixed() {
+        let results = run_all_utf8(
+            vec![
+                sh("echo puppy"),
+                sh("echo doggy >&2; exit 2"),
+                sh("printf 'kitty \\300'"),
+                Command::new("utf8-command-this-program-does-not-exist"),
+                sh("echo birdy"),
+            ],
+            2,
+        );
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0].as_ref().unwrap().stdout, "puppy\n");
+
+        let failed = results[1].as_ref().unwrap();
+        assert_eq!(failed.status.code(), Some(2));
+        assert_eq!(failed.stderr, "doggy\n");
+
+        let utf8_error = results[2].as_ref().unwrap_err().utf8_error().unwrap();
+        assert_eq!(utf8_error.stdout_error().unwrap().valid_up_to(), 6);
+
+        let io_error = results[3].as_ref().unwrap_err().io_error().unwrap();
+        assert_eq!(io_error.kind(), std::io::ErrorKind::NotFound);
+
+        assert_eq!(results[4].as_ref().unwrap().stdout, "birdy\n");
+    }
+
+    #[test]
+    fn test_run_all_utf8_large_output() {
+        // Each command writes more than fits in a pipe buffer to both streams at once.
+        let commands = (0..8)
+            .map(|i| sh(&format!("seq {i} 100000; seq {i} 100000 >&2")))
+            .collect();
+        let results = run_all_utf8(commands, 4);
+        for (i, result) in results.into_iter().enumerate() {
+            let output = result.unwrap();
+            assert!(output.stdout.starts_with(&format!("{i}\n")));
+            assert!(output.stdout.ends_with("\n100000\n"));
+            assert_eq!(output.stdout, output.stderr);
+        }
+    }
+
+    #[test]
+    fn test_run_all_utf8_parallel() {
+        let commands = (0..4).map(|_| sh("sleep 1")).collect();
+        let start = Instant::now();
+        let results = run_all_utf8(commands, 4);
+        // Running the commands one after another would take 4 seconds.
+        assert!(start.elapsed() < Duration::from_secs(3));
+        assert!(results.iter().all(|result| result.is_ok()));
+    }
+
+    #[test]
+    fn test_run_all_utf8_empty() {
+        assert!(run_all_utf8(Vec::new(), 4).is_empty());
+    }
+
+    #[test]
+    fn test_try_run_all_utf8() {
+        let outputs = try_run_all_utf8(vec![sh("echo puppy"), sh("echo doggy")], 4).unwrap();
+        assert_eq!(outputs[0].stdout, "puppy\n");
+        assert_eq!(outputs[1].stdout, "doggy\n");
+    }
+
+    #[test]
+    fn test_try_run_all_utf8_fail_fast() {
+        let dir = std::env::temp_dir().join(format!("utf8-command-run-all-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let marker = dir.join("marker");
+
+        let err = try_run_all_utf8(
+            vec![
+                sh("echo puppy"),
+                sh("printf 'doggy \\300'"),
+                sh(&format!("touch {}", marker.display())),
+            ],
+            1,
+        )
+        .unwrap_err();
+        assert_eq!(err.index(), 1);
+        assert!(matches!(err.error(), CheckedError::Utf8(_)));
+        // The command after the failure never ran.
+        assert!(!marker.exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,188 @@
+use std::process::Command;
+
+use crate::CheckedError;
+use crate::CommandError;
+use crate::CommandExt;
+use crate::Utf8Output;
+
+/// Run `script` with the platform's shell, and decode its output as UTF-8.
+///
+/// On Unix, this runs `/bin/sh -c script`, and on Windows, `cmd /C script`. See [`Shell`] to
+/// use a different shell or to set environment variables.
+///
+/// # Quoting
+///
+/// The script is interpreted by the shell, so any untrusted text interpolated into it (like a
+/// file name) can run arbitrary commands. Prefer [`Command::arg`] for untrusted text. With `sh`
+/// and PowerShell, untrusted text can also be passed as an environment variable and referred to
+/// as `"$NAME"` or `$env:NAME`, which the shell treats as data. `cmd` has no safe way to
+/// interpolate text: it expands `%NAME%` before parsing the script, so a value containing `&`
+/// or `|` still runs commands. Quoting rules also differ between shells, so a script which works
+/// with `sh` may not work with `cmd`.
+///
+/// ```
+/// let output = utf8_command::sh("echo puppy").unwrap();
+/// assert_eq!(output.stdout.trim_end(), "puppy");
+/// ```
+pub fn sh(script: &str) -> Result<Utf8Output, CommandError> {
+    Shell::default().command(script).output_utf8()
+}
+
+/// Run `script` with the platform's shell, require it to exit successfully, and decode its
+/// output as UTF-8.
+///
+/// See [`sh`].
+///
+/// ```
+/// let err = utf8_command::sh_checked("echo puppy >&2 && exit 3").unwrap_err();
+/// assert_eq!(err.output().unwrap().status.code(), Some(3));
+/// ```
+pub fn sh_checked(script: &str) -> Result<Utf8Output, CheckedError> {
+    Shell::default().command(script).output_utf8_checked()
+}
+
+/// A shell to run scripts with.
+///
+/// ```
+/// use utf8_command::prelude::*;
+/// use utf8_command::Shell;
+///
+/// let output = Shell::Sh
+///     .command("echo \"$PUPPY\"")
+///     .env("PUPPY", "doggy")
+///     .output_utf8()
+///     .unwrap();
+/// assert_eq!(output.stdout, "doggy\n");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    /// The POSIX shell, as `/bin/sh -c script`.
+    ///
+    /// This is the default on Unix.
+    Sh,
+    /// The Windows command interpreter, as `cmd /C script`.
+    ///
+    /// The script is passed to `cmd` verbatim, without the quoting which is usually applied to
+    /// arguments on Windows, because `cmd` doesn't parse its command line like other programs.
+    ///
+    /// This is the default on Windows.
+    Cmd,
+    /// PowerShell, as `powershell -NoProfile -NonInteractive -Command script` on Windows and
+    /// `pwsh -NoProfile -NonInteractive -Command script` elsewhere.
+    PowerShell,
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        if cfg!(windows) {
+            Shell::Cmd
+        } else {
+            Shell::Sh
+        }
+    }
+}
+
+impl Shell {
+    /// Construct a [`Command`] which runs `script` with this shell.
+    ///
+    /// The command can be configured further (with environment variables, a working directory,
+    /// and so on) before it's run. See [`sh`] for the hazards of quoting.
+    pub fn command(self, script: &str) -> Command {
+        match self {
+            Shell::Sh => {
+                let mut command = Command::new("/bin/sh");
+                command.arg("-c").arg(script);
+                command
+            }
+            Shell::Cmd => {
+                let mut command = Command::new("cmd");
+                command.arg("/C");
+                #[cfg(windows)]
+                {
+                    use std::os::windows::process::CommandExt as _;
+                    command.raw_arg(script);
+                }
+                #[cfg(not(windows))]
+                command.arg(script);
+                command
+            }
+            Shell::PowerShell => {
+                let mut command = Command::new(if cfg!(windows) { "powershell" } else { "pwsh" });
+                command
+                    .args(["-NoProfile", "-NonInteractive", "-Command"])
+                    .arg(script);
+                command
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sh() {
+        let output = sh("echo puppy").unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout.trim_end(), "puppy");
+    }
+
+    #[test]
+    fn test_sh_quoting() {
+        if cfg!(windows) {
+            // `cmd` keeps the quotes.
+            let output = sh(r#"echo "puppy  doggy" && echo kitty"#).unwrap();
+            assert_eq!(output.stdout, "\"puppy  doggy\" \r\nkitty\r\n");
+        } else {
+            let output = sh(r#"echo "puppy  doggy" 'kitty''s'"#).unwrap();
+            assert_eq!(output.stdout, "puppy  doggy kittys\n");
+        }
+    }
+
+    #[test]
+    fn test_sh_failed() {
+        let output = sh("exit 3").unwrap();
+        assert_eq!(output.status.code(), Some(3));
+
+        let err = sh_checked("echo puppy >&2 && exit 3").unwrap_err();
+        let output = err.output().unwrap();
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(output.stderr.trim_end(), "puppy");
+    }
+
+    #[test]
+    fn test_env() {
+        // `cmd` re-parses expanded variables, so only `sh` and PowerShell can take arbitrary text.
+        let (shell, script, value) = if cfg!(windows) {
+            (
+                Shell::PowerShell,
+                "Write-Output $env:PUPPY",
+                "doggy & kitty",
+            )
+        } else {
+            (
+                Shell::Sh,
+                "echo \"$PUPPY\"",
+                "doggy; $(echo kitty) `echo kitty`",
+            )
+        };
+        let output = shell
+            .command(script)
+            .env("PUPPY", value)
+            .output_utf8()
+            .unwrap();
+        assert_eq!(output.stdout.trim_end(), value);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_invalid() {
+        let err = sh("printf 'puppy \\300'").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`/bin/sh -c 'printf '\\''puppy \\300'\\'''`: \
+            Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+        );
+    }
+}
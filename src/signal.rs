@@ -0,0 +1,221 @@
+use std::fmt::Display;
+use std::process::ExitStatus;
+
+/// Displays how a command failed, like `failed with exit status: 1`, or on Unix,
+/// `was killed by SIGSEGV (core dumped)`, or on Windows,
+/// `failed with exit code: 0xc0000005 (STATUS_ACCESS_VIOLATION)`.
+pub(crate) struct FailedStatus(pub(crate) ExitStatus);
+
+impl Display for FailedStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(signal) = self.0.signal() {
+                match signal_name(signal) {
+                    Some(name) => write!(f, "was killed by {name}")?,
+                    None => write!(f, "was killed by signal {signal}")?,
+                }
+                if self.0.core_dumped() {
+                    write!(f, " (core dumped)")?;
+                }
+                return Ok(());
+            }
+        }
+
+        #[cfg(windows)]
+        {
+            if let Some(code) = self.0.code() {
+                if let Some(name) = ntstatus_name(code as u32) {
+                    return write!(f, "failed with exit code: {code:#x} ({name})");
+                }
+            }
+        }
+
+        write!(f, "failed with {}", self.0)
+    }
+}
+
+/// Get the name of a common Windows [`NTSTATUS`][ntstatus] error code, like
+/// `STATUS_ACCESS_VIOLATION` for `0xC0000005`.
+///
+/// Windows programs which crash exit with one of these codes, which are hard to recognize when
+/// they're printed as signed decimal numbers (like `-1073741819`). Failure messages for commands
+/// which exit with a known code include its name on Windows.
+///
+/// Returns `None` for codes which aren't known, including all ordinary exit codes.
+///
+/// ```
+/// # use utf8_command::ntstatus_name;
+/// assert_eq!(ntstatus_name(0xC0000005), Some("STATUS_ACCESS_VIOLATION"));
+/// assert_eq!(ntstatus_name(-1073741571i32 as u32), Some("STATUS_STACK_OVERFLOW"));
+/// assert_eq!(ntstatus_name(1), None);
+/// ```
+///
+/// [ntstatus]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-erref/596a1078-e883-4972-9bbc-49e60bebca55
+pub fn ntstatus_name(code: u32) -> Option<&'static str> {
+    Some(match code {
+        0x80000003 => "STATUS_BREAKPOINT",
+        0xC0000005 => "STATUS_ACCESS_VIOLATION",
+        0xC0000006 => "STATUS_IN_PAGE_ERROR",
+        0xC0000017 => "STATUS_NO_MEMORY",
+        0xC000001D => "STATUS_ILLEGAL_INSTRUCTION",
+        0xC000008C => "STATUS_ARRAY_BOUNDS_EXCEEDED",
+        0xC000008E => "STATUS_FLOAT_DIVIDE_BY_ZERO",
+        0xC0000094 => "STATUS_INTEGER_DIVIDE_BY_ZERO",
+        0xC0000095 => "STATUS_INTEGER_OVERFLOW",
+        0xC0000096 => "STATUS_PRIVILEGED_INSTRUCTION",
+        0xC00000FD => "STATUS_STACK_OVERFLOW",
+        0xC0000135 => "STATUS_DLL_NOT_FOUND",
+        0xC0000139 => "STATUS_ENTRYPOINT_NOT_FOUND",
+        0xC000013A => "STATUS_CONTROL_C_EXIT",
+        0xC0000142 => "STATUS_DLL_INIT_FAILED",
+        0xC0000374 => "STATUS_HEAP_CORRUPTION",
+        0xC0000409 => "STATUS_STACK_BUFFER_OVERRUN",
+        _ => return None,
+    })
+}
+
+/// Get the name of a Unix signal, like `SIGTERM`, if it's a common one.
+#[cfg(unix)]
+pub(crate) fn signal_name(signal: i32) -> Option<&'static str> {
+    // These numbers are the same on every Unix.
+    let name = match signal {
+        1 => "SIGHUP",
+        2 => "SIGINT",
+        3 => "SIGQUIT",
+        4 => "SIGILL",
+        5 => "SIGTRAP",
+        6 => "SIGABRT",
+        8 => "SIGFPE",
+        9 => "SIGKILL",
+        11 => "SIGSEGV",
+        13 => "SIGPIPE",
+        14 => "SIGALRM",
+        15 => "SIGTERM",
+        _ => return platform_signal_name(signal),
+    };
+    Some(name)
+}
+
+#[cfg(all(unix, any(target_os = "linux", target_os = "android")))]
+fn platform_signal_name(signal: i32) -> Option<&'static str> {
+    Some(match signal {
+        7 => "SIGBUS",
+        10 => "SIGUSR1",
+        12 => "SIGUSR2",
+        24 => "SIGXCPU",
+        25 => "SIGXFSZ",
+        31 => "SIGSYS",
+        _ => return None,
+    })
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn platform_signal_name(signal: i32) -> Option<&'static str> {
+    // The BSD numbering, which macOS uses too.
+    Some(match signal {
+        10 => "SIGBUS",
+        12 => "SIGSYS",
+        24 => "SIGXCPU",
+        25 => "SIGXFSZ",
+        30 => "SIGUSR1",
+        31 => "SIGUSR2",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    use std::os::unix::process::ExitStatusExt;
+    #[cfg(unix)]
+    use std::process::Command;
+
+    use super::*;
+    #[cfg(unix)]
+    use crate::CommandExt;
+
+    #[test]
+    fn test_ntstatus_name() {
+        assert_eq!(ntstatus_name(0xC0000005), Some("STATUS_ACCESS_VIOLATION"));
+        assert_eq!(ntstatus_name(0xC00000FD), Some("STATUS_STACK_OVERFLOW"));
+        assert_eq!(ntstatus_name(0xC0000135), Some("STATUS_DLL_NOT_FOUND"));
+        assert_eq!(ntstatus_name(0xC000013A), Some("STATUS_CONTROL_C_EXIT"));
+        assert_eq!(
+            ntstatus_name(-1073741819i32 as u32),
+            Some("STATUS_ACCESS_VIOLATION")
+        );
+        assert_eq!(ntstatus_name(0xC0000000), None);
+    }
+
+    #[test]
+    fn test_ntstatus_name_ordinary_codes() {
+        for code in [0, 1, 2, 127, 255, 256] {
+            assert_eq!(ntstatus_name(code), None);
+        }
+        assert_eq!(ntstatus_name(-1i32 as u32), None);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_failed_status_ntstatus() {
+        use std::os::windows::process::ExitStatusExt;
+
+        assert_eq!(
+            FailedStatus(ExitStatus::from_raw(0xC0000005)).to_string(),
+            "failed with exit code: 0xc0000005 (STATUS_ACCESS_VIOLATION)"
+        );
+        assert_eq!(
+            FailedStatus(ExitStatus::from_raw(3)).to_string(),
+            "failed with exit code: 3"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_signal_name() {
+        assert_eq!(signal_name(9), Some("SIGKILL"));
+        assert_eq!(signal_name(11), Some("SIGSEGV"));
+        assert_eq!(signal_name(15), Some("SIGTERM"));
+        assert_eq!(signal_name(0), None);
+        assert_eq!(signal_name(1000), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failed_status() {
+        assert_eq!(
+            FailedStatus(ExitStatus::from_raw(2 << 8)).to_string(),
+            "failed with exit status: 2"
+        );
+        assert_eq!(
+            FailedStatus(ExitStatus::from_raw(9)).to_string(),
+            "was killed by SIGKILL"
+        );
+        // The core dump flag.
+        assert_eq!(
+            FailedStatus(ExitStatus::from_raw(11 | 0x80)).to_string(),
+            "was killed by SIGSEGV (core dumped)"
+        );
+        assert_eq!(
+            FailedStatus(ExitStatus::from_raw(100)).to_string(),
+            "was killed by signal 100"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_killed_child() {
+        for (signal, name) in [("TERM", "SIGTERM"), ("KILL", "SIGKILL")] {
+            let err = Command::new("sh")
+                .args(["-c", &format!("kill -{signal} $$")])
+                .output_utf8_checked()
+                .unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("Command `sh -c 'kill -{signal} $$'` was killed by {name}")
+            );
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::fmt::Write;
+use std::process::ExitStatus;
+
+use snapbox::Data;
+
+use crate::Utf8Output;
+
+impl<S: AsRef<str>> Utf8Output<S> {
+    /// Render the output as [`snapbox::Data`], for snapshot testing with
+    /// [`snapbox::assert_data_eq!`].
+    ///
+    /// The exit status and both streams are written in a stable labeled format. The exit status
+    /// is rendered the same way on every platform, as `exit code: N` or, on Unix, `signal: N`
+    /// if the process was killed by a signal.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use snapbox::assert_data_eq;
+    /// use snapbox::str;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let output = Command::new("sh")
+    ///     .args(["-c", "echo puppy; echo doggy >&2; exit 3"])
+    ///     .output_utf8()
+    ///     .unwrap();
+    /// assert_data_eq!(
+    ///     output.to_snapbox_data(),
+    ///     str![[r#"
+    /// exit code: 3
+    /// --- stdout
+    /// puppy
+    /// --- stderr
+    /// doggy
+    ///
+    /// "#]]
+    /// );
+    /// ```
+    pub fn to_snapbox_data(&self) -> Data {
+        let mut rendered = String::new();
+        write_status(&mut rendered, self.status);
+        write_stream(&mut rendered, "stdout", self.stdout.as_ref());
+        write_stream(&mut rendered, "stderr", self.stderr.as_ref());
+        Data::text(rendered)
+    }
+}
+
+impl<S: AsRef<str>> From<&Utf8Output<S>> for Data {
+    fn from(value: &Utf8Output<S>) -> Self {
+        value.to_snapbox_data()
+    }
+}
+
+impl<S: AsRef<str>> From<Utf8Output<S>> for Data {
+    fn from(value: Utf8Output<S>) -> Self {
+        value.to_snapbox_data()
+    }
+}
+
+fn write_status(rendered: &mut String, status: ExitStatus) {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        let _ = writeln!(rendered, "signal: {signal}");
+        return;
+    }
+
+    match status.code() {
+        Some(code) => {
+            let _ = writeln!(rendered, "exit code: {code}");
+        }
+        None => rendered.push_str("exit code: none\n"),
+    }
+}
+
+/// Write a labeled stream, making sure the next label starts on its own line.
+fn write_stream(rendered: &mut String, label: &str, contents: &str) {
+    let _ = writeln!(rendered, "--- {label}");
+    rendered.push_str(contents);
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        rendered.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use snapbox::assert_data_eq;
+    use snapbox::str;
+
+    use super::*;
+
+    #[test]
+    fn test_to_snapbox_data() {
+        let output = Utf8Output {
+            status: ExitStatus::default(),
+            stdout: "puppy\ndoggy",
+            stderr: "",
+        };
+        assert_data_eq!(
+            output.to_snapbox_data(),
+            str![[r#"
+exit code: 0
+--- stdout
+puppy
+doggy
+--- stderr
+
+"#]]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_signal() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let output = Utf8Output {
+            status: ExitStatus::from_raw(9),
+            stdout: String::new(),
+            stderr: String::from("killed\n"),
+        };
+        assert_data_eq!(
+            Data::from(&output),
+            str![[r#"
+signal: 9
+--- stdout
+--- stderr
+killed
+
+"#]]
+        );
+    }
+}
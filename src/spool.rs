@@ -0,0 +1,381 @@
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::process::Command;
+use std::process::ExitStatus;
+
+use crate::capture::kill;
+use crate::capture::spawn_piped;
+use crate::capture::Pipes;
+use crate::CommandError;
+use crate::CommandInfo;
+use crate::Error;
+use crate::SpawnError;
+use crate::StderrError;
+use crate::StdoutError;
+use crate::Stream;
+use crate::StreamDecodeError;
+use crate::Utf8Output;
+use crate::Utf8Reader;
+
+/// Run `command`, spilling each stream to a temporary file once it's longer than `threshold`
+/// bytes, and validate its output as UTF-8.
+pub(crate) fn output_spooled(
+    command: &mut Command,
+    threshold: usize,
+) -> Result<SpooledUtf8Output, CommandError> {
+    let info = CommandInfo::from(&*command);
+    let io_error = |err| CommandError::Io(SpawnError::new(err, Some(info.clone())));
+
+    let mut child = spawn_piped(command).map_err(io_error)?;
+    let mut pipes = Pipes::new(&mut child);
+    let mut stdout = Spooler::new(threshold);
+    let mut stderr = Spooler::new(threshold);
+    let result = (|| {
+        while let Some((stream, bytes)) = pipes.recv()? {
+            stream.select(&mut stdout, &mut stderr).write(&bytes)?;
+        }
+        Ok(())
+    })();
+    if let Err(err) = result {
+        let _ = kill(&mut child);
+        return Err(io_error(err));
+    }
+    let status = child.wait().map_err(io_error)?;
+
+    let mut stdout = stdout.finish();
+    let mut stderr = stderr.finish();
+    let stdout_result = stdout.validate().map_err(io_error)?;
+    let stderr_result = stderr.validate().map_err(io_error)?;
+    Error::from_streams(
+        stdout_result.map_err(StdoutError::from),
+        stderr_result.map_err(StderrError::from),
+    )
+    .map_err(|err| CommandError::Utf8(err.with_status(status).with_command(info.clone())))?;
+
+    Ok(SpooledUtf8Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+/// The output of a command, with streams which may have been spilled to temporary files.
+///
+/// Produced by [`CommandExt::output_utf8_spooled`][crate::CommandExt::output_utf8_spooled]. The
+/// streams have already been validated as UTF-8, so they can be read back lazily without
+/// checking them again. Temporary files are deleted when this is dropped.
+///
+/// ```
+/// # use std::process::Command;
+/// use std::io::BufRead;
+/// use utf8_command::prelude::*;
+/// use utf8_command::Stream;
+///
+/// let mut output = Command::new("seq")
+///     .args(["1", "1000"])
+///     .output_utf8_spooled(1024)
+///     .unwrap();
+/// assert!(output.is_spilled(Stream::Stdout));
+/// assert_eq!(output.len(Stream::Stdout), 3893);
+///
+/// let last = output.stdout_reader().unwrap().lines().last().unwrap().unwrap();
+/// assert_eq!(last, "1000");
+/// ```
+#[derive(Debug)]
+pub struct SpooledUtf8Output {
+    /// The command's exit status.
+    pub status: ExitStatus,
+    stdout: Spool,
+    stderr: Spool,
+}
+
+impl SpooledUtf8Output {
+    /// Get the length of a stream, in bytes.
+    pub fn len(&self, stream: Stream) -> u64 {
+        stream.select(&self.stdout, &self.stderr).len()
+    }
+
+    /// Check if a stream was spilled to a temporary file.
+    pub fn is_spilled(&self, stream: Stream) -> bool {
+        matches!(
+            stream.select(&self.stdout, &self.stderr),
+            Spool::File { .. }
+        )
+    }
+
+    /// Read a stream from the beginning.
+    ///
+    /// The stream is valid UTF-8, so [`BufRead::lines`] and [`Read::read_to_string`] won't fail
+    /// to decode it.
+    pub fn reader(&mut self, stream: Stream) -> std::io::Result<impl BufRead + '_> {
+        stream.select(&mut self.stdout, &mut self.stderr).reader()
+    }
+
+    /// Read `stdout` from the beginning.
+    ///
+    /// See [`SpooledUtf8Output::reader`].
+    pub fn stdout_reader(&mut self) -> std::io::Result<impl BufRead + '_> {
+        self.stdout.reader()
+    }
+
+    /// Read `stderr` from the beginning.
+    ///
+    /// See [`SpooledUtf8Output::reader`].
+    pub fn stderr_reader(&mut self) -> std::io::Result<impl BufRead + '_> {
+        self.stderr.reader()
+    }
+
+    /// Read both streams into memory.
+    ///
+    /// Fails with [`std::io::ErrorKind::FileTooLarge`] if either stream is longer than
+    /// `max_len` bytes.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let output = Command::new("seq")
+    ///     .args(["1", "1000"])
+    ///     .output_utf8_spooled(1024)
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     output.into_utf8_output(1024).unwrap_err().to_string(),
+    ///     "Stdout is 3893 bytes long, which is more than the limit of 1024 bytes"
+    /// );
+    /// ```
+    pub fn into_utf8_output(mut self, max_len: u64) -> std::io::Result<Utf8Output> {
+        for stream in [Stream::Stdout, Stream::Stderr] {
+            let len = self.len(stream);
+            if len > max_len {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::FileTooLarge,
+                    format!(
+                        "{stream} is {len} bytes long, which is more than the limit of \
+                        {max_len} bytes"
+                    ),
+                ));
+            }
+        }
+        let stdout = self.stdout.read_to_string()?;
+        let stderr = self.stderr.read_to_string()?;
+        Ok(Utf8Output {
+            status: self.status,
+            stdout,
+            stderr,
+        })
+    }
+}
+
+/// A stream which is kept in memory until it's too long, and then spilled to a file.
+struct Spooler {
+    threshold: usize,
+    memory: Vec<u8>,
+    /// The temporary file and its length, once the stream is spilled.
+    file: Option<(File, u64)>,
+}
+
+impl Spooler {
+    fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            memory: Vec::new(),
+            file: None,
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        if self.file.is_none() && self.memory.len() + bytes.len() > self.threshold {
+            let mut file = tempfile::tempfile()?;
+            file.write_all(&self.memory)?;
+            self.file = Some((file, self.memory.len() as u64));
+            self.memory = Vec::new();
+        }
+        match &mut self.file {
+            Some((file, len)) => {
+                file.write_all(bytes)?;
+                *len += bytes.len() as u64;
+            }
+            None => self.memory.extend_from_slice(bytes),
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Spool {
+        match self.file {
+            Some((file, len)) => Spool::File { file, len },
+            None => Spool::Memory(self.memory),
+        }
+    }
+}
+
+/// A captured stream, in memory or in a temporary file.
+#[derive(Debug)]
+enum Spool {
+    Memory(Vec<u8>),
+    File { file: File, len: u64 },
+}
+
+impl Spool {
+    fn len(&self) -> u64 {
+        match self {
+            Spool::Memory(bytes) => bytes.len() as u64,
+            Spool::File { len, .. } => *len,
+        }
+    }
+
+    fn reader(&mut self) -> std::io::Result<SpoolReader<'_>> {
+        Ok(match self {
+            Spool::Memory(bytes) => SpoolReader::Memory(bytes),
+            Spool::File { file, len } => {
+                file.seek(SeekFrom::Start(0))?;
+                SpoolReader::File(BufReader::new(Read::take(&*file, *len)))
+            }
+        })
+    }
+
+    /// Check that the stream is valid UTF-8, reading it back from the file if it was spilled.
+    ///
+    /// The outer result is an I/O error, and the inner result is a decoding error.
+    fn validate(&mut self) -> std::io::Result<Result<(), StreamDecodeError>> {
+        if let Spool::Memory(bytes) = self {
+            return Ok(match std::str::from_utf8(bytes) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(StreamDecodeError::from(
+                    String::from_utf8(std::mem::take(bytes))
+                        .expect_err("Bytes should be invalid UTF-8"),
+                )),
+            });
+        }
+
+        let mut reader = Utf8Reader::new(self.reader()?);
+        loop {
+            match reader.read_str() {
+                Ok(Some(_)) => {}
+                Ok(None) => return Ok(Ok(())),
+                Err(err) => {
+                    return match err
+                        .get_ref()
+                        .and_then(|inner| inner.downcast_ref::<StreamDecodeError>())
+                    {
+                        Some(decode_error) => Ok(Err(decode_error.clone())),
+                        None => Err(err),
+                    }
+                }
+            }
+        }
+    }
+
+    fn read_to_string(&mut self) -> std::io::Result<String> {
+        let mut bytes = Vec::with_capacity(self.len() as usize);
+        self.reader()?.read_to_end(&mut bytes)?;
+        Ok(String::from_utf8(bytes).expect("Spooled streams should have been validated"))
+    }
+}
+
+/// A reader for a [`Spool`].
+enum SpoolReader<'a> {
+    Memory(&'a [u8]),
+    File(BufReader<std::io::Take<&'a File>>),
+}
+
+impl Read for SpoolReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            SpoolReader::Memory(bytes) => bytes.read(buf),
+            SpoolReader::File(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl BufRead for SpoolReader<'_> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        match self {
+            SpoolReader::Memory(bytes) => bytes.fill_buf(),
+            SpoolReader::File(reader) => reader.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amount: usize) {
+        match self {
+            SpoolReader::Memory(bytes) => bytes.consume(amount),
+            SpoolReader::File(reader) => reader.consume(amount),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::CommandExt;
+
+    use super::*;
+
+    #[test]
+    fn test_in_memory() {
+        let mut output = Command::new("sh")
+            .args(["-c", "echo puppy; echo doggy >&2"])
+            .output_utf8_spooled(1024)
+            .unwrap();
+        assert!(output.status.success());
+        assert!(!output.is_spilled(Stream::Stdout));
+        assert!(!output.is_spilled(Stream::Stderr));
+
+        let mut stdout = String::new();
+        output
+            .stdout_reader()
+            .unwrap()
+            .read_to_string(&mut stdout)
+            .unwrap();
+        assert_eq!(stdout, "puppy\n");
+
+        let output = output.into_utf8_output(1024).unwrap();
+        assert_eq!(output.stdout, "puppy\n");
+        assert_eq!(output.stderr, "doggy\n");
+    }
+
+    #[test]
+    fn test_spilled() {
+        let mut output = Command::new("sh")
+            .args(["-c", "seq 1 100000 >&2; echo puppy"])
+            .output_utf8_spooled(16)
+            .unwrap();
+        assert!(!output.is_spilled(Stream::Stdout));
+        assert!(output.is_spilled(Stream::Stderr));
+
+        // Readers start from the beginning every time.
+        for _ in 0..2 {
+            let mut lines = output.stderr_reader().unwrap().lines();
+            assert_eq!(lines.next().unwrap().unwrap(), "1");
+            assert_eq!(lines.last().unwrap().unwrap(), "100000");
+        }
+
+        let len = output.len(Stream::Stderr);
+        let output = output.into_utf8_output(len).unwrap();
+        assert_eq!(output.stdout, "puppy\n");
+        assert_eq!(output.stderr.len() as u64, len);
+        assert!(output.stderr.ends_with("99999\n100000\n"));
+    }
+
+    #[test]
+    fn test_spilled_invalid() {
+        let err = Command::new("sh")
+            .args(["-c", "seq 1 10000; printf 'puppy \\300'"])
+            .output_utf8_spooled(16)
+            .unwrap_err();
+        let stdout_error = err.utf8_error().unwrap().stdout_error().unwrap();
+        assert_eq!(stdout_error.valid_up_to(), 48900);
+        let message = err.to_string();
+        assert!(
+            message.starts_with(
+                "`sh -c 'seq 1 10000; printf '\\''puppy \\300'\\'''`: \
+                Stdout contained invalid utf-8 sequence of 1 bytes from index 48900: "
+            ),
+            "{message}"
+        );
+        assert!(message.ends_with("puppy �\""), "{message}");
+    }
+}
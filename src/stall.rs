@@ -0,0 +1,215 @@
+use std::fmt::Display;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::capture::decode_partial;
+use crate::capture::output_limited;
+use crate::capture::Limits;
+use crate::capture::Stopped;
+use crate::CommandInfo;
+use crate::Error;
+use crate::SpawnError;
+use crate::Utf8Output;
+
+/// Run `command` and decode its output, killing it if it doesn't write any output for
+/// `idle_timeout`.
+pub(crate) fn output_idle_timeout(
+    command: &mut Command,
+    idle_timeout: Duration,
+) -> Result<Utf8Output, StalledError> {
+    let info = CommandInfo::from(&*command);
+    let limits = Limits {
+        idle_timeout: Some(idle_timeout),
+        ..Default::default()
+    };
+    match output_limited(command, limits) {
+        Ok((output, None)) => {
+            Utf8Output::try_from(output).map_err(|err| StalledError::Utf8(err.with_command(info)))
+        }
+        Ok((output, Some(stopped))) => Err(StalledError::Stalled {
+            idle_timeout,
+            idle: match stopped {
+                Stopped::Stalled { idle } => idle,
+                Stopped::TimedOut | Stopped::Overflowed { .. } => {
+                    unreachable!("Only an idle timeout was set")
+                }
+            },
+            output: Utf8Output {
+                status: output.status,
+                stdout: decode_partial(output.stdout),
+                stderr: decode_partial(output.stderr),
+            },
+            command: Some(info),
+        }),
+        Err(err) => Err(StalledError::Io(SpawnError::new(err, Some(info)))),
+    }
+}
+
+/// An error produced by
+/// [`CommandExt::output_utf8_idle_timeout`][crate::CommandExt::output_utf8_idle_timeout].
+#[derive(Debug)]
+pub enum StalledError {
+    /// The command couldn't be run.
+    Io(SpawnError),
+    /// The command's output contained invalid UTF-8.
+    Utf8(Error),
+    /// The command didn't write any output for too long, and was killed.
+    Stalled {
+        /// How long the command was allowed to run for without writing any output.
+        idle_timeout: Duration,
+        /// How long the command had gone without writing any output when it was killed.
+        ///
+        /// This is at least `idle_timeout`.
+        idle: Duration,
+        /// The command's output up to when it was killed.
+        ///
+        /// Invalid UTF-8 is replaced with U+FFFD REPLACEMENT CHARACTER, and an incomplete
+        /// sequence at the end of a stream is removed.
+        output: Utf8Output,
+        /// The command which stalled, if known.
+        command: Option<CommandInfo>,
+    },
+}
+
+impl StalledError {
+    /// Get the command's partial output, if it stalled.
+    pub fn output(&self) -> Option<&Utf8Output> {
+        match self {
+            StalledError::Stalled { output, .. } => Some(output),
+            StalledError::Io(_) | StalledError::Utf8(_) => None,
+        }
+    }
+
+    /// Get the command's partial output, if it stalled.
+    pub fn into_output(self) -> Option<Utf8Output> {
+        match self {
+            StalledError::Stalled { output, .. } => Some(output),
+            StalledError::Io(_) | StalledError::Utf8(_) => None,
+        }
+    }
+
+    /// Get how long the command had gone without writing any output, if it stalled.
+    pub fn idle(&self) -> Option<Duration> {
+        match self {
+            StalledError::Stalled { idle, .. } => Some(*idle),
+            StalledError::Io(_) | StalledError::Utf8(_) => None,
+        }
+    }
+
+    /// Check if the command stalled.
+    pub fn is_stalled(&self) -> bool {
+        matches!(self, StalledError::Stalled { .. })
+    }
+
+    /// Get the command which produced the error, if known.
+    pub fn command(&self) -> Option<&CommandInfo> {
+        match self {
+            StalledError::Io(err) => err.command(),
+            StalledError::Utf8(err) => err.command(),
+            StalledError::Stalled { command, .. } => command.as_ref(),
+        }
+    }
+}
+
+impl From<std::io::Error> for StalledError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(SpawnError::from(value))
+    }
+}
+
+impl From<SpawnError> for StalledError {
+    fn from(value: SpawnError) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<Error> for StalledError {
+    fn from(value: Error) -> Self {
+        Self::Utf8(value)
+    }
+}
+
+impl Display for StalledError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StalledError::Io(err) => write!(f, "{err}"),
+            StalledError::Utf8(err) => write!(f, "{err}"),
+            StalledError::Stalled {
+                idle_timeout,
+                command,
+                ..
+            } => match command {
+                Some(command) => write!(
+                    f,
+                    "Command `{command}` stalled: no output for {idle_timeout:?}"
+                ),
+                None => write!(f, "Command stalled: no output for {idle_timeout:?}"),
+            },
+        }
+    }
+}
+
+impl std::error::Error for StalledError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StalledError::Io(err) => Some(err),
+            StalledError::Utf8(err) => Some(err),
+            StalledError::Stalled { .. } => None,
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::CommandExt;
+
+    use super::*;
+
+    #[test]
+    fn test_output_utf8_idle_timeout() {
+        // Runs for longer than the idle timeout in total, but never goes quiet for that long.
+        let output = Command::new("sh")
+            .args([
+                "-c",
+                "for i in 1 2 3 4 5 6; do echo puppy $i; echo doggy $i >&2; sleep 0.2; done",
+            ])
+            .output_utf8_idle_timeout(Duration::from_secs(1))
+            .unwrap();
+        assert!(output.status.success());
+        assert!(output.stdout.ends_with("puppy 6\n"));
+        assert!(output.stderr.ends_with("doggy 6\n"));
+    }
+
+    #[test]
+    fn test_output_utf8_idle_timeout_stalled() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let err = Command::new("sh")
+            .args(["-c", "echo puppy; sleep 0.2; echo doggy >&2; exec sleep 60"])
+            .output_utf8_idle_timeout(Duration::from_millis(500))
+            .unwrap_err();
+        assert!(err.is_stalled());
+        assert_eq!(
+            err.to_string(),
+            "Command `sh -c 'echo puppy; sleep 0.2; echo doggy >&2; exec sleep 60'` \
+            stalled: no output for 500ms"
+        );
+        let idle = err.idle().unwrap();
+        assert!(idle >= Duration::from_millis(500), "{idle:?}");
+        assert!(idle < Duration::from_secs(30), "{idle:?}");
+
+        let output = err.into_output().unwrap();
+        assert_eq!(output.status.signal(), Some(9));
+        assert_eq!(output.stdout, "puppy\n");
+        assert_eq!(output.stderr, "doggy\n");
+    }
+
+    #[test]
+    fn test_output_utf8_idle_timeout_resumed_too_late() {
+        let err = Command::new("sh")
+            .args(["-c", "echo puppy; sleep 2; echo doggy"])
+            .output_utf8_idle_timeout(Duration::from_millis(500))
+            .unwrap_err();
+        assert_eq!(err.output().unwrap().stdout, "puppy\n");
+    }
+}
@@ -0,0 +1,478 @@
+use std::fmt::Display;
+use std::process::ExitCode;
+use std::process::ExitStatus;
+
+use crate::command::fmt_failure;
+use crate::CheckedUtf8Output;
+use crate::ErrorContext;
+use crate::Utf8Output;
+use crate::ERROR_CONTEXT_BYTES;
+
+impl<S> Utf8Output<S> {
+    /// Check if the command exited successfully.
+    ///
+    /// See [`ExitStatus::success`][std::process::ExitStatus::success].
+    #[must_use]
+    pub fn success(&self) -> bool {
+        self.status.success()
+    }
+
+    /// Get the command's exit code, or `None` if it didn't exit normally (for example, if it was
+    /// killed by a signal).
+    ///
+    /// See [`ExitStatus::code`][std::process::ExitStatus::code].
+    #[must_use]
+    pub fn code(&self) -> Option<i32> {
+        self.status.code()
+    }
+
+    /// Get the signal which killed the command, or `None` if it exited normally.
+    ///
+    /// See [`ExitStatusExt::signal`][std::os::unix::process::ExitStatusExt::signal].
+    #[cfg(unix)]
+    #[must_use]
+    pub fn signal(&self) -> Option<i32> {
+        std::os::unix::process::ExitStatusExt::signal(&self.status)
+    }
+}
+
+impl Utf8Output {
+    /// Require the command to have exited successfully.
+    ///
+    /// The output is returned as a [`CheckedUtf8Output`], which records that it was checked.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let err = Command::new("sh")
+    ///     .args(["-c", "echo puppy >&2; exit 2"])
+    ///     .output_utf8()
+    ///     .unwrap()
+    ///     .exit_ok()
+    ///     .unwrap_err();
+    /// assert_eq!(err.output().code(), Some(2));
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Command failed with exit status: 2; stderr: \"puppy\\n\""
+    /// );
+    /// ```
+    pub fn exit_ok(self) -> Result<CheckedUtf8Output, NonZeroExit> {
+        if self.success() {
+            Ok(CheckedUtf8Output::new(self))
+        } else {
+            Err(NonZeroExit { output: self })
+        }
+    }
+
+    /// Require the command to have exited successfully, turning a failure into an error which
+    /// keeps the whole output.
+    ///
+    /// Unlike [`Utf8Output::exit_ok`], the error shows the end of `stdout` if `stderr` is empty,
+    /// because some programs report their errors on `stdout`.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let err = Command::new("sh")
+    ///     .args(["-c", "echo puppy; exit 2"])
+    ///     .output_utf8()
+    ///     .unwrap()
+    ///     .require_success()
+    ///     .unwrap_err();
+    /// assert_eq!(err.output().stdout, "puppy\n");
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Command failed with exit status: 2; stdout: \"puppy\\n\""
+    /// );
+    /// ```
+    pub fn require_success(self) -> Result<CheckedUtf8Output, FailedCommandError> {
+        if self.success() {
+            Ok(CheckedUtf8Output::new(self))
+        } else {
+            Err(FailedCommandError { output: self })
+        }
+    }
+
+    /// Require the command to have exited with one of `codes`, rather than with `0`.
+    ///
+    /// This is useful for commands which use exit codes to report results, like `grep`, which
+    /// exits with 1 if no lines match, and 2 if an error occurs. `0` only counts as success if
+    /// it's one of `codes`. A command killed by a signal never counts as success.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let output = Command::new("grep")
+    ///     .args(["puppy", "/dev/null"])
+    ///     .output_utf8()
+    ///     .unwrap()
+    ///     .require_status_in([0, 1])
+    ///     .unwrap();
+    /// assert_eq!(output.code(), Some(1));
+    /// ```
+    pub fn require_status_in(
+        self,
+        codes: impl IntoIterator<Item = i32>,
+    ) -> Result<Self, FailedCommandError> {
+        if status_in(self.status, codes) {
+            Ok(self)
+        } else {
+            Err(FailedCommandError { output: self })
+        }
+    }
+}
+
+/// Check if `status` is an exit with one of `codes`.
+pub(crate) fn status_in(status: ExitStatus, codes: impl IntoIterator<Item = i32>) -> bool {
+    status
+        .code()
+        .is_some_and(|code| codes.into_iter().any(|allowed| allowed == code))
+}
+
+/// An error produced by [`Utf8Output::exit_ok`] when a command exits unsuccessfully.
+///
+/// When displayed, only the end of `stderr` is shown if it's long (currently, more than 1024
+/// bytes).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NonZeroExit {
+    output: Utf8Output,
+}
+
+impl NonZeroExit {
+    /// Get a reference to the command's output.
+    pub fn output(&self) -> &Utf8Output {
+        &self.output
+    }
+
+    /// Get the command's output.
+    pub fn into_output(self) -> Utf8Output {
+        self.output
+    }
+}
+
+impl Display for NonZeroExit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_failure(f, None, self.output.status, &self.output.stderr)
+    }
+}
+
+impl std::error::Error for NonZeroExit {}
+
+/// An error produced by [`Utf8Output::require_success`] when a command exits unsuccessfully, or
+/// by [`Utf8Output::require_status_in`] when it exits with a status which isn't allowed.
+///
+/// When displayed, this shows the exit status and the end of `stderr`, or the end of `stdout` if
+/// `stderr` is empty. Only the end of the stream is shown if it's long (currently, more than 1024
+/// bytes).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedCommandError {
+    output: Utf8Output,
+}
+
+impl FailedCommandError {
+    /// Get a reference to the command's output.
+    pub fn output(&self) -> &Utf8Output {
+        &self.output
+    }
+
+    /// Get a mutable reference to the command's output.
+    pub fn output_mut(&mut self) -> &mut Utf8Output {
+        &mut self.output
+    }
+
+    /// Get the command's output.
+    pub fn into_output(self) -> Utf8Output {
+        self.output
+    }
+
+    /// Get an exit code for this process which mirrors the command's, so that a wrapper can
+    /// exit the same way as the command it wraps.
+    ///
+    /// - Exit codes from 1 to 255 are kept.
+    /// - Other exit codes (which are only possible on Windows) are clamped to 255, and an exit
+    ///   code of 0 becomes 1, so the exit code is never successful.
+    /// - On Unix, a command killed by a signal is reported as 128 plus the signal number, like
+    ///   shells do. For example, a command killed by `SIGKILL` (9) is reported as 137.
+    /// - Any other status (like a stopped process) is reported as 1.
+    ///
+    /// This is also available as `ExitCode::from(err)`:
+    ///
+    /// ```no_run
+    /// # use std::process::Command;
+    /// # use std::process::ExitCode;
+    /// use utf8_command::prelude::*;
+    ///
+    /// fn main() -> ExitCode {
+    ///     let output = Command::new("make").output_utf8().unwrap();
+    ///     match output.require_success() {
+    ///         Ok(output) => {
+    ///             print!("{}", output.stdout);
+    ///             ExitCode::SUCCESS
+    ///         }
+    ///         Err(err) => {
+    ///             eprintln!("{err}");
+    ///             err.into()
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn exit_code(&self) -> ExitCode {
+        #[cfg(unix)]
+        let signal = self.output.signal();
+        #[cfg(not(unix))]
+        let signal = None;
+        ExitCode::from(exit_code_byte(self.output.code(), signal))
+    }
+}
+
+/// See [`FailedCommandError::exit_code`].
+fn exit_code_byte(code: Option<i32>, signal: Option<i32>) -> u8 {
+    match (code, signal) {
+        (Some(0), _) => 1,
+        (Some(code @ 1..=255), _) => code as u8,
+        (Some(_), _) => 255,
+        (None, Some(signal)) => 128u8.saturating_add(signal.clamp(0, 127) as u8),
+        (None, None) => 1,
+    }
+}
+
+impl From<FailedCommandError> for ExitCode {
+    fn from(value: FailedCommandError) -> Self {
+        value.exit_code()
+    }
+}
+
+impl Display for FailedCommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Utf8Output {
+            status,
+            stdout,
+            stderr,
+        } = &self.output;
+        fmt_failure(f, None, *status, stderr)?;
+        if stderr.is_empty() && !stdout.is_empty() {
+            write!(
+                f,
+                "; stdout: {}",
+                ErrorContext::at(stdout.as_bytes(), stdout.len(), ERROR_CONTEXT_BYTES)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for FailedCommandError {}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::process::ExitStatusExt;
+
+    use super::*;
+    use crate::test_util::exit_code;
+    use crate::test_util::utf8_output_with_status;
+
+    #[test]
+    fn test_success() {
+        let output = utf8_output_with_status(exit_code(0), "puppy", "");
+        assert!(output.success());
+        assert_eq!(output.code(), Some(0));
+        assert_eq!(output.signal(), None);
+        assert_eq!(output.clone().exit_ok().unwrap().into_inner(), output);
+    }
+
+    #[test]
+    fn test_failure() {
+        // Exit code 3.
+        let output = utf8_output_with_status(exit_code(3), "puppy", "doggy\n");
+        assert!(!output.success());
+        assert_eq!(output.code(), Some(3));
+        assert_eq!(output.signal(), None);
+
+        let err = output.clone().exit_ok().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Command failed with exit status: 3; stderr: \"doggy\\n\""
+        );
+        assert_eq!(err.into_output(), output);
+    }
+
+    #[test]
+    fn test_signal() {
+        // Killed by `SIGKILL`.
+        let output = utf8_output_with_status(ExitStatus::from_raw(9), "puppy", "");
+        assert!(!output.success());
+        assert_eq!(output.code(), None);
+        assert_eq!(output.signal(), Some(9));
+        assert_eq!(
+            output.exit_ok().unwrap_err().to_string(),
+            "Command was killed by SIGKILL"
+        );
+    }
+
+    #[test]
+    fn test_long_stderr() {
+        let stderr = format!("{}kitty", "puppy ".repeat(1000));
+        let message = utf8_output_with_status(exit_code(1), "puppy", &stderr)
+            .exit_ok()
+            .unwrap_err()
+            .to_string();
+        assert!(message.len() < 1200, "{message}");
+        assert!(message.ends_with("puppy kitty\""), "{message}");
+
+        let message = utf8_output_with_status(exit_code(1), "puppy", &stderr)
+            .require_success()
+            .unwrap_err()
+            .to_string();
+        assert!(
+            message.starts_with("Command failed with exit status: 1; stderr: ["),
+            "{message}"
+        );
+        assert!(message.len() < 1200, "{message}");
+        assert!(message.ends_with("puppy kitty\""), "{message}");
+    }
+
+    #[test]
+    fn test_require_success() {
+        let output = utf8_output_with_status(exit_code(0), "puppy", "doggy");
+        assert_eq!(
+            output.clone().require_success().unwrap().into_inner(),
+            output
+        );
+    }
+
+    #[test]
+    fn test_require_success_stderr() {
+        let err = utf8_output_with_status(exit_code(2), "puppy", "doggy\n")
+            .require_success()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Command failed with exit status: 2; stderr: \"doggy\\n\""
+        );
+        assert_eq!(err.output().stderr, "doggy\n");
+        assert_eq!(err.into_output().stdout, "puppy");
+    }
+
+    #[test]
+    fn test_require_success_empty_stderr() {
+        let err = utf8_output_with_status(exit_code(2), "puppy", "")
+            .require_success()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Command failed with exit status: 2; stdout: \"puppy\""
+        );
+
+        let mut err = err;
+        err.output_mut().stdout.clear();
+        assert_eq!(err.to_string(), "Command failed with exit status: 2");
+    }
+
+    #[test]
+    fn test_require_success_signal() {
+        let err = utf8_output_with_status(ExitStatus::from_raw(15), "puppy", "")
+            .require_success()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Command was killed by SIGTERM; stdout: \"puppy\""
+        );
+        let source: &dyn std::error::Error = &err;
+        assert!(source.source().is_none());
+    }
+
+    #[test]
+    fn test_exit_code_byte() {
+        assert_eq!(exit_code_byte(Some(1), None), 1);
+        assert_eq!(exit_code_byte(Some(3), None), 3);
+        assert_eq!(exit_code_byte(Some(255), None), 255);
+        // Never successful.
+        assert_eq!(exit_code_byte(Some(0), None), 1);
+    }
+
+    #[test]
+    fn test_exit_code_byte_large() {
+        assert_eq!(exit_code_byte(Some(256), None), 255);
+        assert_eq!(exit_code_byte(Some(0xC0000005u32 as i32), None), 255);
+        assert_eq!(exit_code_byte(Some(-1), None), 255);
+    }
+
+    #[test]
+    fn test_exit_code_byte_signal() {
+        assert_eq!(exit_code_byte(None, Some(2)), 130);
+        assert_eq!(exit_code_byte(None, Some(9)), 137);
+        assert_eq!(exit_code_byte(None, Some(15)), 143);
+        assert_eq!(exit_code_byte(None, Some(1000)), 255);
+        assert_eq!(exit_code_byte(None, None), 1);
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let debug = |code: ExitCode| format!("{code:?}");
+        let err = utf8_output_with_status(ExitStatus::from_raw(9), "puppy", "")
+            .require_success()
+            .unwrap_err();
+        assert_eq!(debug(err.exit_code()), debug(ExitCode::from(137)));
+        // With a core dump.
+        let err = utf8_output_with_status(ExitStatus::from_raw(11 | 0x80), "puppy", "")
+            .require_success()
+            .unwrap_err();
+        assert_eq!(debug(err.exit_code()), debug(ExitCode::from(139)));
+
+        let err = utf8_output_with_status(exit_code(3), "puppy", "")
+            .require_success()
+            .unwrap_err();
+        assert_eq!(debug(err.exit_code()), debug(ExitCode::from(3)));
+        assert_eq!(debug(ExitCode::from(err)), debug(ExitCode::from(3)));
+    }
+
+    #[test]
+    fn test_require_status_in() {
+        // `grep` found matches, found no matches, and failed.
+        let output_0 = utf8_output_with_status(exit_code(0), "puppy", "");
+        let output_1 = utf8_output_with_status(exit_code(1), "puppy", "");
+        let output_2 = utf8_output_with_status(
+            exit_code(2),
+            "puppy",
+            "grep: puppy.txt: No such file or directory\n",
+        );
+
+        assert_eq!(
+            output_0.clone().require_status_in([0, 1]).unwrap(),
+            output_0
+        );
+        assert_eq!(
+            output_1.clone().require_status_in([0, 1]).unwrap(),
+            output_1
+        );
+        let err = output_2.clone().require_status_in([0, 1]).unwrap_err();
+        assert_eq!(err.output(), &output_2);
+        assert_eq!(
+            err.to_string(),
+            "Command failed with exit status: 2; \
+            stderr: \"grep: puppy.txt: No such file or directory\\n\""
+        );
+
+        // `0` must be listed to count as success.
+        assert!(output_0.require_status_in([1]).is_err());
+        assert!(output_1.require_status_in(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_require_status_in_signal() {
+        // The raw status of a process killed by signal 1 isn't exit code 1.
+        assert!(
+            utf8_output_with_status(ExitStatus::from_raw(1), "puppy", "")
+                .require_status_in([0, 1, 129])
+                .is_err()
+        );
+        assert!(
+            utf8_output_with_status(ExitStatus::from_raw(9), "puppy", "")
+                .require_status_in(0..=255)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt::Display;
+use std::process::Output;
+
+use crate::decode_stderr;
+use crate::decode_stdout;
+use crate::Error;
+use crate::StderrError;
+use crate::StdoutError;
+use crate::StreamContents;
+use crate::Utf8Output;
+
+impl Utf8Output {
+    /// Decode an [`Output`], reporting errors from both streams if both contain invalid UTF-8.
+    ///
+    /// Converting with [`TryFrom`] only reports the error for stdout in that case.
+    ///
+    /// ```
+    /// # use std::process::ExitStatus;
+    /// # use std::process::Output;
+    /// # use utf8_command::Utf8Output;
+    /// let invalid = Output {
+    ///     status: ExitStatus::default(),
+    ///     stdout: Vec::from(b"puppy \xc0"),
+    ///     stderr: Vec::from(b"doggy \xc0"),
+    /// };
+    ///
+    /// let err = Utf8Output::try_from_reporting_both(invalid).unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\"\n\
+    ///     Stderr contained invalid utf-8 sequence of 1 bytes from index 6: \"doggy �\""
+    /// );
+    /// ```
+    pub fn try_from_reporting_both(
+        Output {
+            status,
+            stdout,
+            stderr,
+        }: Output,
+    ) -> Result<Self, StreamErrors> {
+        match (decode_stdout(stdout), decode_stderr(stderr)) {
+            (Ok(stdout), Ok(stderr)) => Ok(Utf8Output {
+                status,
+                stdout,
+                stderr,
+            }),
+            (Err(stdout), Err(stderr)) => {
+                // Each error keeps the other stream, so either can be turned back into the
+                // `Output`.
+                let stdout_bytes = StreamContents::Raw(stdout.as_bytes().to_vec());
+                let stderr_bytes = StreamContents::Raw(stderr.as_bytes().to_vec());
+                Err(StreamErrors {
+                    stdout: Some(stdout.with_output(status, Some(stderr_bytes))),
+                    stderr: Some(stderr.with_output(status, Some(stdout_bytes))),
+                })
+            }
+            (stdout, stderr) => Err(Error::from_owned_results(status, stdout, stderr)
+                .expect_err("At least one stream failed to decode")
+                .into()),
+        }
+    }
+}
+
+/// An error produced by [`Utf8Output::try_from_reporting_both`], with the error for each stream
+/// which contained invalid UTF-8.
+///
+/// At least one of the errors is present. When displayed, each error is shown on its own line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamErrors {
+    stdout: Option<StdoutError>,
+    stderr: Option<StderrError>,
+}
+
+impl StreamErrors {
+    /// Get the error for stdout, if stdout contained invalid UTF-8.
+    pub fn stdout_error(&self) -> Option<&StdoutError> {
+        self.stdout.as_ref()
+    }
+
+    /// Get the error for stderr, if stderr contained invalid UTF-8.
+    pub fn stderr_error(&self) -> Option<&StderrError> {
+        self.stderr.as_ref()
+    }
+
+    /// Get the errors for stdout and stderr.
+    pub fn into_errors(self) -> (Option<StdoutError>, Option<StderrError>) {
+        (self.stdout, self.stderr)
+    }
+}
+
+impl From<Error> for StreamErrors {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::Stdout(err) => Self {
+                stdout: Some(err),
+                stderr: None,
+            },
+            Error::Stderr(err) => Self {
+                stdout: None,
+                stderr: Some(err),
+            },
+        }
+    }
+}
+
+/// If both streams contained invalid UTF-8, this returns the error for stdout, like converting an
+/// [`Output`] with [`TryFrom`].
+impl From<StreamErrors> for Error {
+    fn from(value: StreamErrors) -> Self {
+        match value {
+            StreamErrors {
+                stdout: Some(stdout),
+                ..
+            } => Error::Stdout(stdout),
+            StreamErrors {
+                stdout: None,
+                stderr: Some(stderr),
+            } => Error::Stderr(stderr),
+            StreamErrors {
+                stdout: None,
+                stderr: None,
+            } => unreachable!("At least one stream should have failed to decode"),
+        }
+    }
+}
+
+impl Display for StreamErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.stdout, &self.stderr) {
+            (Some(stdout), Some(stderr)) => write!(f, "{stdout}\n{stderr}"),
+            (Some(stdout), None) => write!(f, "{stdout}"),
+            (None, Some(stderr)) => write!(f, "{stderr}"),
+            (None, None) => unreachable!("At least one stream should have failed to decode"),
+        }
+    }
+}
+
+impl std::error::Error for StreamErrors {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::output;
+
+    #[test]
+    fn test_reporting_both_valid() {
+        let output = Utf8Output::try_from_reporting_both(output(b"puppy", b"doggy")).unwrap();
+        assert_eq!(output.stdout, "puppy");
+        assert_eq!(output.stderr, "doggy");
+    }
+
+    #[test]
+    fn test_reporting_both_one_stream() {
+        let err = Utf8Output::try_from_reporting_both(output(b"puppy", b"doggy\xc0")).unwrap_err();
+        assert!(err.stdout_error().is_none());
+        assert_eq!(err.stderr_error().unwrap().as_bytes(), b"doggy\xc0");
+        assert_eq!(
+            err.to_string(),
+            "Stderr contained invalid utf-8 sequence of 1 bytes from index 5: \"doggy�\""
+        );
+
+        // The other stream is kept, like when converting with `TryFrom`.
+        let err = Error::from(err);
+        assert_eq!(err.partial_stdout(), Some("puppy"));
+        assert_eq!(err.into_output(), output(b"puppy", b"doggy\xc0"));
+    }
+
+    #[test]
+    fn test_reporting_both_both_streams() {
+        let err =
+            Utf8Output::try_from_reporting_both(output(b"puppy\xc0", b"doggy\xc0")).unwrap_err();
+        assert_eq!(err.stdout_error().unwrap().valid_up_to(), 5);
+        assert_eq!(err.stderr_error().unwrap().valid_up_to(), 5);
+
+        // Converting with `TryFrom` only reports stdout.
+        let plain = Utf8Output::try_from(output(b"puppy\xc0", b"doggy\xc0")).unwrap_err();
+        assert!(matches!(plain, Error::Stdout(_)));
+        assert_eq!(Error::from(err.clone()).inner(), plain.inner());
+
+        // Either error can be turned back into the original output.
+        assert_eq!(
+            Error::from(err.clone()).into_output(),
+            output(b"puppy\xc0", b"doggy\xc0")
+        );
+        let (stdout, stderr) = err.into_errors();
+        assert_eq!(
+            Error::Stderr(stderr.unwrap()).into_output(),
+            output(b"puppy\xc0", b"doggy\xc0")
+        );
+        assert!(stdout.is_some());
+    }
+}
@@ -0,0 +1,268 @@
+use std::fmt::Display;
+
+use serde::de::DeserializeOwned;
+
+use crate::ErrorContext;
+use crate::Utf8Output;
+use crate::ERROR_CONTEXT_BYTES;
+
+impl<S: AsRef<str>> Utf8Output<S> {
+    /// Deserialize each record of `stdout` as CSV with a header row.
+    ///
+    /// Fields are matched to `T`'s fields by the names in the header. Use
+    /// [`Utf8Output::stdout_csv_with`] to set the delimiter or parse output without a header.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    ///
+    /// let output = Command::new("printf")
+    ///     .arg("name,age\\npuppy,1\\ndoggy,5\\n")
+    ///     .output_utf8()
+    ///     .unwrap();
+    /// let rows: Vec<(String, u8)> = output.stdout_csv().unwrap();
+    /// assert_eq!(rows, [("puppy".to_owned(), 1), ("doggy".to_owned(), 5)]);
+    /// ```
+    pub fn stdout_csv<T: DeserializeOwned>(&self) -> Result<Vec<T>, CsvOutputError> {
+        self.stdout_csv_with(&CsvOptions::new())
+    }
+
+    /// Deserialize each record of `stdout` as delimited text, with the given `options`.
+    ///
+    /// ```
+    /// # use std::process::Command;
+    /// use utf8_command::prelude::*;
+    /// use utf8_command::CsvOptions;
+    ///
+    /// let output = Command::new("printf")
+    ///     .arg("puppy\\t1\\ndoggy\\t5\\n")
+    ///     .output_utf8()
+    ///     .unwrap();
+    /// let options = CsvOptions::new().delimiter(b'\t').has_headers(false);
+    /// let rows: Vec<(String, u8)> = output.stdout_csv_with(&options).unwrap();
+    /// assert_eq!(rows, [("puppy".to_owned(), 1), ("doggy".to_owned(), 5)]);
+    /// ```
+    pub fn stdout_csv_with<T: DeserializeOwned>(
+        &self,
+        options: &CsvOptions,
+    ) -> Result<Vec<T>, CsvOutputError> {
+        let stdout = self.stdout.as_ref();
+        csv::ReaderBuilder::new()
+            .delimiter(options.delimiter)
+            .has_headers(options.has_headers)
+            .from_reader(stdout.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .map_err(|error| CsvOutputError {
+                stdout: stdout.to_owned(),
+                error,
+            })
+    }
+}
+
+/// Options for [`Utf8Output::stdout_csv_with`].
+///
+/// The defaults parse comma-separated values with a header row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    delimiter: u8,
+    has_headers: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            has_headers: true,
+        }
+    }
+}
+
+impl CsvOptions {
+    /// Construct the default options, which parse comma-separated values with a header row.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the field delimiter, like `b'\t'` for tab-separated values. The default is `b','`.
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Set whether the first record is a header row. The default is `true`.
+    ///
+    /// With a header row, records are deserialized into structs and maps by field name.
+    /// Without one, fields are deserialized by position, into structs, tuples, or sequences.
+    pub fn has_headers(mut self, has_headers: bool) -> Self {
+        self.has_headers = has_headers;
+        self
+    }
+}
+
+/// An error produced by [`Utf8Output::stdout_csv`] or [`Utf8Output::stdout_csv_with`].
+///
+/// When displayed, this shows the line of `stdout` where the failing record starts.
+#[derive(Debug)]
+pub struct CsvOutputError {
+    stdout: String,
+    error: csv::Error,
+}
+
+impl CsvOutputError {
+    /// Get the `stdout` which failed to parse.
+    pub fn stdout(&self) -> &str {
+        &self.stdout
+    }
+
+    /// Get the index of the record which failed to parse, if the error is at a particular record.
+    ///
+    /// Like in the error message, records are counted from 0, and the header row is counted.
+    pub fn record(&self) -> Option<u64> {
+        self.error.position().map(|position| position.record())
+    }
+
+    /// Get the 1-based line number where the failing record starts, if the error is at a
+    /// particular record.
+    pub fn line(&self) -> Option<u64> {
+        self.error.position().map(|position| position.line())
+    }
+
+    /// Get the line of `stdout` where the failing record starts, without its line ending, if the
+    /// error is at a particular record.
+    pub fn line_text(&self) -> Option<&str> {
+        let start = self.error.position()?.byte() as usize;
+        let line = self.stdout.get(start..)?.lines().next().unwrap_or_default();
+        Some(line)
+    }
+
+    /// Get a reference to the CSV error.
+    pub fn error(&self) -> &csv::Error {
+        &self.error
+    }
+
+    /// Get the CSV error.
+    pub fn into_error(self) -> csv::Error {
+        self.error
+    }
+}
+
+impl Display for CsvOutputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Stdout couldn't be parsed: {}", self.error)?;
+        if let Some(line) = self.line_text() {
+            write!(
+                f,
+                ": {}",
+                ErrorContext::at(line.as_bytes(), 0, ERROR_CONTEXT_BYTES)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CsvOutputError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::test_util::utf8_output;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Disk {
+        name: String,
+        size: u64,
+        mountpoint: Option<String>,
+    }
+
+    const DISKS: &str = "\
+name\tsize\tmountpoint
+sda\t512110190592\t
+sda1\t1073741824\t/boot
+sda2\t511035400192\t/
+";
+
+    fn tsv() -> CsvOptions {
+        CsvOptions::new().delimiter(b'\t')
+    }
+
+    #[test]
+    fn test_stdout_csv_tsv() {
+        let disks: Vec<Disk> = utf8_output(DISKS, "").stdout_csv_with(&tsv()).unwrap();
+        assert_eq!(
+            disks,
+            [
+                Disk {
+                    name: "sda".to_owned(),
+                    size: 512110190592,
+                    mountpoint: None,
+                },
+                Disk {
+                    name: "sda1".to_owned(),
+                    size: 1073741824,
+                    mountpoint: Some("/boot".to_owned()),
+                },
+                Disk {
+                    name: "sda2".to_owned(),
+                    size: 511035400192,
+                    mountpoint: Some("/".to_owned()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stdout_csv_no_headers() {
+        let output = utf8_output("puppy,1\r\n\"doggy, kitty\",2\r\n", "");
+        let rows: Vec<(String, u8)> = output
+            .stdout_csv_with(&CsvOptions::new().has_headers(false))
+            .unwrap();
+        assert_eq!(
+            rows,
+            [("puppy".to_owned(), 1), ("doggy, kitty".to_owned(), 2)]
+        );
+        // With a header, the first row is skipped.
+        assert_eq!(output.stdout_csv::<(String, u8)>().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_stdout_csv_empty() {
+        assert!(utf8_output("", "").stdout_csv::<Disk>().unwrap().is_empty());
+        assert!(utf8_output("name\tsize\tmountpoint\n", "")
+            .stdout_csv_with::<Disk>(&tsv())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_stdout_csv_malformed_row() {
+        let stdout = DISKS.replace("1073741824", "1 GiB");
+        let err = utf8_output(&stdout, "")
+            .stdout_csv_with::<Disk>(&tsv())
+            .unwrap_err();
+        assert_eq!(err.record(), Some(2));
+        assert_eq!(err.line(), Some(3));
+        assert_eq!(err.line_text(), Some("sda1\t1 GiB\t/boot"));
+        assert_eq!(
+            err.to_string(),
+            "Stdout couldn't be parsed: CSV deserialize error: record 2 (line: 3, byte: 39): \
+             field 1: invalid digit found in string: \"sda1\\t1 GiB\\t/boot\""
+        );
+    }
+
+    #[test]
+    fn test_stdout_csv_wrong_field_count() {
+        let stdout = format!("{DISKS}sdb\t0\n");
+        let err = utf8_output(&stdout, "")
+            .stdout_csv_with::<Disk>(&tsv())
+            .unwrap_err();
+        assert_eq!(err.record(), Some(4));
+        assert_eq!(err.line_text(), Some("sdb\t0"));
+    }
+}
@@ -0,0 +1,50 @@
+//! Fixtures shared by the tests of every module.
+
+use std::process::ExitStatus;
+use std::process::Output;
+
+use crate::Utf8Output;
+
+/// An [`Output`] which exited successfully.
+pub(crate) fn output(stdout: &[u8], stderr: &[u8]) -> Output {
+    Output {
+        status: ExitStatus::default(),
+        stdout: stdout.to_vec(),
+        stderr: stderr.to_vec(),
+    }
+}
+
+/// A [`Utf8Output`] which exited successfully.
+pub(crate) fn utf8_output(stdout: &str, stderr: &str) -> Utf8Output {
+    Utf8Output {
+        status: ExitStatus::default(),
+        stdout: stdout.to_owned(),
+        stderr: stderr.to_owned(),
+    }
+}
+
+/// A [`Utf8Output`] with the given exit status.
+pub(crate) fn utf8_output_with_status(
+    status: ExitStatus,
+    stdout: &str,
+    stderr: &str,
+) -> Utf8Output {
+    Utf8Output {
+        status,
+        ..utf8_output(stdout, stderr)
+    }
+}
+
+/// An exit status with the given exit code, on every platform.
+pub(crate) fn exit_code(code: u32) -> ExitStatus {
+    #[cfg(unix)]
+    use std::os::unix::process::ExitStatusExt;
+    #[cfg(windows)]
+    use std::os::windows::process::ExitStatusExt;
+
+    if cfg!(unix) {
+        ExitStatus::from_raw((code << 8) as _)
+    } else {
+        ExitStatus::from_raw(code as _)
+    }
+}
@@ -0,0 +1,219 @@
+use std::fmt::Display;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::capture::decode_partial;
+use crate::capture::output_limited;
+use crate::capture::Limits;
+use crate::CommandInfo;
+use crate::Error;
+use crate::SpawnError;
+use crate::Utf8Output;
+
+/// Run `command` and decode its output, killing it if it doesn't finish within `timeout`.
+pub(crate) fn output_timeout(
+    command: &mut Command,
+    timeout: Duration,
+) -> Result<Utf8Output, TimeoutError> {
+    let info = CommandInfo::from(&*command);
+    let limits = Limits {
+        timeout: Some(timeout),
+        ..Default::default()
+    };
+    match output_limited(command, limits) {
+        Ok((output, None)) => {
+            Utf8Output::try_from(output).map_err(|err| TimeoutError::Utf8(err.with_command(info)))
+        }
+        Ok((output, Some(_))) => Err(TimeoutError::TimedOut {
+            timeout,
+            output: Utf8Output {
+                status: output.status,
+                stdout: decode_partial(output.stdout),
+                stderr: decode_partial(output.stderr),
+            },
+            command: Some(info),
+        }),
+        Err(err) => Err(TimeoutError::Io(SpawnError::new(err, Some(info)))),
+    }
+}
+
+/// An error produced by [`CommandExt::output_utf8_timeout`][crate::CommandExt::output_utf8_timeout].
+#[derive(Debug)]
+pub enum TimeoutError {
+    /// The command couldn't be run.
+    Io(SpawnError),
+    /// The command's output contained invalid UTF-8.
+    Utf8(Error),
+    /// The command didn't finish in time, and was killed.
+    TimedOut {
+        /// How long the command was allowed to run for.
+        timeout: Duration,
+        /// The command's output up to when it was killed.
+        ///
+        /// Invalid UTF-8 is replaced with U+FFFD REPLACEMENT CHARACTER, and an incomplete
+        /// sequence at the end of a stream is removed.
+        output: Utf8Output,
+        /// The command which timed out, if known.
+        command: Option<CommandInfo>,
+    },
+}
+
+impl TimeoutError {
+    /// Get the command's partial output, if it timed out.
+    pub fn output(&self) -> Option<&Utf8Output> {
+        match self {
+            TimeoutError::TimedOut { output, .. } => Some(output),
+            TimeoutError::Io(_) | TimeoutError::Utf8(_) => None,
+        }
+    }
+
+    /// Get the command's partial output, if it timed out.
+    pub fn into_output(self) -> Option<Utf8Output> {
+        match self {
+            TimeoutError::TimedOut { output, .. } => Some(output),
+            TimeoutError::Io(_) | TimeoutError::Utf8(_) => None,
+        }
+    }
+
+    /// Check if the command timed out.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, TimeoutError::TimedOut { .. })
+    }
+
+    /// Get the command which produced the error, if known.
+    pub fn command(&self) -> Option<&CommandInfo> {
+        match self {
+            TimeoutError::Io(err) => err.command(),
+            TimeoutError::Utf8(err) => err.command(),
+            TimeoutError::TimedOut { command, .. } => command.as_ref(),
+        }
+    }
+}
+
+impl From<std::io::Error> for TimeoutError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(SpawnError::from(value))
+    }
+}
+
+impl From<SpawnError> for TimeoutError {
+    fn from(value: SpawnError) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<Error> for TimeoutError {
+    fn from(value: Error) -> Self {
+        Self::Utf8(value)
+    }
+}
+
+impl Display for TimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeoutError::Io(err) => write!(f, "{err}"),
+            TimeoutError::Utf8(err) => write!(f, "{err}"),
+            TimeoutError::TimedOut {
+                timeout, command, ..
+            } => match command {
+                Some(command) => write!(f, "Command `{command}` timed out after {timeout:?}"),
+                None => write!(f, "Command timed out after {timeout:?}"),
+            },
+        }
+    }
+}
+
+impl std::error::Error for TimeoutError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TimeoutError::Io(err) => Some(err),
+            TimeoutError::Utf8(err) => Some(err),
+            TimeoutError::TimedOut { .. } => None,
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::CommandExt;
+
+    use super::*;
+
+    #[test]
+    fn test_output_utf8_timeout() {
+        let output = Command::new("sh")
+            .args(["-c", "echo puppy; echo doggy >&2"])
+            .output_utf8_timeout(Duration::from_secs(60))
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, "puppy\n");
+        assert_eq!(output.stderr, "doggy\n");
+    }
+
+    #[test]
+    fn test_output_utf8_timeout_invalid() {
+        let err = Command::new("printf")
+            .arg("puppy \\300")
+            .output_utf8_timeout(Duration::from_secs(60))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`printf 'puppy \\300'`: \
+            Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+        );
+    }
+
+    #[test]
+    fn test_output_utf8_timeout_timed_out() {
+        use std::os::unix::process::ExitStatusExt;
+        use std::time::Instant;
+
+        let start = Instant::now();
+        let err = Command::new("sh")
+            // `exec` so the shell is replaced by `sleep`, rather than leaving it running after the
+            // shell is killed.
+            .args([
+                "-c",
+                "printf 'puppy \\360\\237'; echo doggy >&2; exec sleep 60",
+            ])
+            .output_utf8_timeout(Duration::from_millis(500))
+            .unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(30));
+        assert!(err.is_timeout());
+        assert_eq!(
+            err.to_string(),
+            "Command `sh -c 'printf '\\''puppy \\360\\237'\\''; echo doggy >&2; exec sleep 60'` \
+            timed out after 500ms"
+        );
+
+        let output = err.into_output().unwrap();
+        assert_eq!(output.status.signal(), Some(9));
+        assert_eq!(output.stdout, "puppy ");
+        assert_eq!(output.stderr, "doggy\n");
+    }
+
+    #[test]
+    fn test_output_utf8_timeout_kills_child() {
+        let dir = std::env::temp_dir().join(format!("utf8-command-timeout-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pid_file = dir.join("pid");
+
+        let err = Command::new("sh")
+            .arg("-c")
+            .arg(format!("echo $$ > {}; exec sleep 60", pid_file.display()))
+            .output_utf8_timeout(Duration::from_millis(500))
+            .unwrap_err();
+        assert!(err.is_timeout());
+
+        // The child has been killed and reaped, so signalling it fails.
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let status = Command::new("kill")
+            .args(["-0", pid.trim()])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(!status.success());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,204 @@
+use std::future::Future;
+
+use tokio::process::Child;
+use tokio::process::Command;
+
+use crate::command::check_success;
+use crate::command::decode_output;
+use crate::CheckedError;
+use crate::CommandError;
+use crate::CommandInfo;
+use crate::SpawnError;
+use crate::TokioUtf8Lines;
+use crate::Utf8Output;
+
+/// Extension methods for running a [`tokio::process::Command`] and decoding its output as
+/// UTF-8.
+///
+/// These are the asynchronous equivalents of [`CommandExt`][crate::CommandExt], with the same
+/// error types.
+///
+/// Like [`Command::output`], the child process is only killed when the future is dropped if
+/// [`Command::kill_on_drop`] is set.
+///
+/// ```
+/// # tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(async {
+/// use tokio::process::Command;
+/// use utf8_command::TokioCommandExt;
+///
+/// let output = Command::new("echo").arg("puppy").output_utf8().await.unwrap();
+/// assert_eq!(output.stdout, "puppy\n");
+/// # });
+/// ```
+pub trait TokioCommandExt {
+    /// Run the command and decode its output streams as UTF-8.
+    ///
+    /// See [`CommandExt::output_utf8`][crate::CommandExt::output_utf8].
+    fn output_utf8(&mut self) -> impl Future<Output = Result<Utf8Output, CommandError>> + Send;
+
+    /// Run the command, decode its output streams as UTF-8, and require it to exit
+    /// successfully.
+    ///
+    /// See [`CommandExt::output_utf8_checked`][crate::CommandExt::output_utf8_checked].
+    fn output_utf8_checked(
+        &mut self,
+    ) -> impl Future<Output = Result<Utf8Output, CheckedError>> + Send;
+
+    /// Run the command and decode its output streams as UTF-8, replacing invalid sequences
+    /// with U+FFFD REPLACEMENT CHARACTER.
+    ///
+    /// See [`Utf8Output::from_utf8_lossy`].
+    fn output_utf8_lossy(&mut self) -> impl Future<Output = Result<Utf8Output, SpawnError>> + Send;
+}
+
+impl TokioCommandExt for Command {
+    async fn output_utf8(&mut self) -> Result<Utf8Output, CommandError> {
+        let info = CommandInfo::from(self.as_std());
+        decode_output(self.output().await, Some(info), Utf8Output::try_from)
+    }
+
+    async fn output_utf8_checked(&mut self) -> Result<Utf8Output, CheckedError> {
+        let output = self.output_utf8().await?;
+        check_success(output, Some(CommandInfo::from(self.as_std())))
+    }
+
+    async fn output_utf8_lossy(&mut self) -> Result<Utf8Output, SpawnError> {
+        let info = CommandInfo::from(self.as_std());
+        let output = self
+            .output()
+            .await
+            .map_err(|err| SpawnError::new(err, Some(info)))?;
+        Ok(Utf8Output::from_utf8_lossy(output))
+    }
+}
+
+/// Extension methods for waiting for a [`tokio::process::Child`] and decoding its output as
+/// UTF-8.
+///
+/// The [`Child`] doesn't know which command it was spawned from, so errors don't include the
+/// command line.
+pub trait TokioChildExt {
+    /// Wait for the child to exit and decode its output streams as UTF-8.
+    ///
+    /// See [`Child::wait_with_output`].
+    fn wait_with_output_utf8(self)
+        -> impl Future<Output = Result<Utf8Output, CommandError>> + Send;
+
+    /// Wait for the child to exit, decode its output streams as UTF-8, and require it to exit
+    /// successfully.
+    fn wait_with_output_utf8_checked(
+        self,
+    ) -> impl Future<Output = Result<Utf8Output, CheckedError>> + Send;
+
+    /// Wait for the child to exit and decode its output streams as UTF-8, replacing invalid
+    /// sequences with U+FFFD REPLACEMENT CHARACTER.
+    fn wait_with_output_utf8_lossy(
+        self,
+    ) -> impl Future<Output = Result<Utf8Output, SpawnError>> + Send;
+
+    /// Read the child's `stdout` and `stderr` as a [`Stream`][futures_core::Stream] of decoded
+    /// lines.
+    ///
+    /// See [`TokioUtf8Lines`].
+    fn lines_utf8(self) -> TokioUtf8Lines;
+}
+
+impl TokioChildExt for Child {
+    async fn wait_with_output_utf8(self) -> Result<Utf8Output, CommandError> {
+        decode_output(self.wait_with_output().await, None, Utf8Output::try_from)
+    }
+
+    async fn wait_with_output_utf8_checked(self) -> Result<Utf8Output, CheckedError> {
+        check_success(self.wait_with_output_utf8().await?, None)
+    }
+
+    async fn wait_with_output_utf8_lossy(self) -> Result<Utf8Output, SpawnError> {
+        Ok(Utf8Output::from_utf8_lossy(self.wait_with_output().await?))
+    }
+
+    fn lines_utf8(self) -> TokioUtf8Lines {
+        TokioUtf8Lines::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    use std::process::Stdio;
+
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_output_utf8() {
+        let output = Command::new("echo")
+            .arg("puppy")
+            .output_utf8()
+            .await
+            .unwrap();
+        assert_eq!(output.stdout, "puppy\n");
+
+        let output = Command::new("echo")
+            .arg("puppy")
+            .output_utf8_checked()
+            .await
+            .unwrap();
+        assert_eq!(output.stdout, "puppy\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_output_utf8_invalid() {
+        let mut command = Command::new("printf");
+        command.arg("puppy \\300");
+
+        let err = command.output_utf8().await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`printf 'puppy \\300'`: \
+            Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+        );
+
+        let output = command.output_utf8_lossy().await.unwrap();
+        assert_eq!(output.stdout, "puppy \u{FFFD}");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_output_utf8_failed() {
+        let err = Command::new("sh")
+            .args(["-c", "echo doggy >&2; exit 1"])
+            .output_utf8_checked()
+            .await
+            .unwrap_err();
+        assert_eq!(err.output().unwrap().stderr, "doggy\n");
+    }
+
+    #[tokio::test]
+    async fn test_output_utf8_missing_binary() {
+        let mut command = Command::new("utf8-command-this-program-does-not-exist");
+        let err = command.output_utf8().await.unwrap_err();
+        assert!(matches!(err, CommandError::Io(_)));
+        assert!(command.output_utf8_lossy().await.is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_child() {
+        let child = Command::new("printf")
+            .arg("puppy \\300")
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let output = child.wait_with_output_utf8_lossy().await.unwrap();
+        assert_eq!(output.stdout, "puppy \u{FFFD}");
+
+        let child = Command::new("echo")
+            .arg("puppy")
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let output = child.wait_with_output_utf8_checked().await.unwrap();
+        assert_eq!(output.stdout, "puppy\n");
+    }
+}
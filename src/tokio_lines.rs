@@ -0,0 +1,335 @@
+use std::pin::Pin;
+use std::process::ExitStatus;
+use std::task::Context;
+use std::task::Poll;
+
+use futures_core::Stream as AsyncStream;
+use tokio::io::AsyncRead;
+use tokio::io::ReadBuf;
+use tokio::process::Child;
+use tokio::process::ChildStderr;
+use tokio::process::ChildStdout;
+
+use crate::lines::decode_line;
+use crate::LineError;
+use crate::Stream;
+
+/// The number of bytes to read from a pipe at a time.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// A [`Stream`][futures_core::Stream] of the decoded lines of a [`tokio::process::Child`]'s
+/// `stdout` and `stderr`, in the order they're read.
+///
+/// Lines are split and decoded like [`Utf8Lines`][crate::Utf8Lines], and each is labeled with
+/// the [`Stream`] it came from. Errors are reported with their line number in that stream, and
+/// reading continues with the next line. The stream ends when both pipes are closed; pipes which
+/// weren't [piped][std::process::Stdio::piped] are treated as already closed.
+///
+/// Afterwards, [`TokioUtf8Lines::wait`] gets the child's exit status. If this is dropped before
+/// the child exits, the child is killed.
+///
+/// ```
+/// # tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(async {
+/// use std::process::Stdio;
+/// use futures_lite::StreamExt;
+/// use tokio::process::Command;
+/// use utf8_command::Stream;
+/// use utf8_command::TokioChildExt;
+///
+/// let child = Command::new("sh")
+///     .args(["-c", "echo puppy; printf 'doggy \\300\\n' >&2"])
+///     .stdout(Stdio::piped())
+///     .stderr(Stdio::piped())
+///     .spawn()
+///     .unwrap();
+/// let mut lines = child.lines_utf8();
+/// let mut stdout = Vec::new();
+/// let mut errors = Vec::new();
+/// while let Some(line) = lines.next().await {
+///     match line {
+///         Ok((Stream::Stdout, line)) => stdout.push(line),
+///         Ok((Stream::Stderr, line)) => unreachable!("Stderr isn't valid UTF-8: {line}"),
+///         Err(err) => errors.push(err.to_string()),
+///     }
+/// }
+/// assert_eq!(stdout, ["puppy"]);
+/// assert_eq!(
+///     errors,
+///     ["Line 1: Output contained invalid utf-8 sequence of 1 bytes from index 6: \"doggy �\""]
+/// );
+/// assert!(lines.wait().await.unwrap().success());
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct TokioUtf8Lines {
+    child: Child,
+    stdout: Option<PipeLines<ChildStdout>>,
+    stderr: Option<PipeLines<ChildStderr>>,
+    /// The stream to poll first, alternated so neither pipe starves the other.
+    next: Stream,
+}
+
+impl TokioUtf8Lines {
+    /// Read the lines of the child's `stdout` and `stderr`, taking them from the child.
+    pub fn new(mut child: Child) -> Self {
+        Self {
+            stdout: child.stdout.take().map(PipeLines::new),
+            stderr: child.stderr.take().map(PipeLines::new),
+            child,
+            next: Stream::Stdout,
+        }
+    }
+
+    /// Wait for the child to exit and get its exit status.
+    ///
+    /// Any lines which haven't been read are discarded, and the pipes are closed first, so a
+    /// child which is blocked writing to a full pipe won't deadlock.
+    pub async fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        self.stdout = None;
+        self.stderr = None;
+        self.child.wait().await
+    }
+
+    /// Get a reference to the child.
+    pub fn child(&self) -> &Child {
+        &self.child
+    }
+
+    /// Get a mutable reference to the child, to kill it or check its status.
+    pub fn child_mut(&mut self) -> &mut Child {
+        &mut self.child
+    }
+
+    /// Poll one pipe for a line, closing it when it's exhausted.
+    fn poll_stream(
+        &mut self,
+        stream: Stream,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<(Stream, String), LineError>>> {
+        let poll = match stream {
+            Stream::Stdout => poll_pipe(&mut self.stdout, cx),
+            Stream::Stderr => poll_pipe(&mut self.stderr, cx),
+        };
+        poll.map(|line| line.map(|line| line.map(|line| (stream, line))))
+    }
+}
+
+impl AsyncStream for TokioUtf8Lines {
+    type Item = Result<(Stream, String), LineError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let first = self.next;
+        let second = first.select(Stream::Stderr, Stream::Stdout);
+        self.next = second;
+
+        let mut pending = false;
+        for stream in [first, second] {
+            match self.poll_stream(stream, cx) {
+                Poll::Ready(Some(line)) => return Poll::Ready(Some(line)),
+                Poll::Ready(None) => {}
+                Poll::Pending => pending = true,
+            }
+        }
+        if pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(None)
+        }
+    }
+}
+
+impl Drop for TokioUtf8Lines {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            // Tokio reaps the killed child in the background.
+            let _ = self.child.start_kill();
+        }
+    }
+}
+
+/// Poll a pipe for a line, setting it to `None` when it's exhausted.
+fn poll_pipe<R: AsyncRead + Unpin>(
+    pipe: &mut Option<PipeLines<R>>,
+    cx: &mut Context<'_>,
+) -> Poll<Option<Result<String, LineError>>> {
+    let Some(lines) = pipe else {
+        return Poll::Ready(None);
+    };
+    let poll = lines.poll_line(cx);
+    if let Poll::Ready(None | Some(Err(LineError::Io { .. }))) = poll {
+        *pipe = None;
+    }
+    poll
+}
+
+/// The lines of one pipe.
+#[derive(Debug)]
+struct PipeLines<R> {
+    reader: R,
+    /// Bytes read from `reader`, starting with some which have already been returned.
+    buffer: Vec<u8>,
+    /// The index in `buffer` of the first byte which hasn't been returned.
+    start: usize,
+    /// The index in `buffer` up to which there's no newline.
+    scanned: usize,
+    /// The number of lines read so far.
+    line: usize,
+    /// The index in the stream of the first byte which hasn't been returned.
+    offset: usize,
+}
+
+impl<R: AsyncRead + Unpin> PipeLines<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+            start: 0,
+            scanned: 0,
+            line: 0,
+            offset: 0,
+        }
+    }
+
+    fn poll_line(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<String, LineError>>> {
+        loop {
+            if let Some(index) = self.buffer[self.scanned..].iter().position(|b| *b == b'\n') {
+                return Poll::Ready(Some(self.decode(self.scanned + index + 1)));
+            }
+            self.buffer.drain(..self.start);
+            self.start = 0;
+            self.scanned = self.buffer.len();
+
+            let mut chunk = [0; CHUNK_SIZE];
+            let mut chunk = ReadBuf::new(&mut chunk);
+            match Pin::new(&mut self.reader).poll_read(cx, &mut chunk) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(error)) => {
+                    return Poll::Ready(Some(Err(LineError::Io {
+                        line: self.line + 1,
+                        error,
+                    })));
+                }
+                Poll::Ready(Ok(())) if chunk.filled().is_empty() => {
+                    if self.buffer.is_empty() {
+                        return Poll::Ready(None);
+                    }
+                    return Poll::Ready(Some(self.decode(self.buffer.len())));
+                }
+                Poll::Ready(Ok(())) => self.buffer.extend_from_slice(chunk.filled()),
+            }
+        }
+    }
+
+    /// Decode the line from `start` up to `end`.
+    fn decode(&mut self, end: usize) -> Result<String, LineError> {
+        let line = self.buffer[self.start..end].to_vec();
+        self.line += 1;
+        let start = self.offset;
+        self.offset += line.len();
+        self.start = end;
+        self.scanned = end;
+        decode_line(line, self.line, start)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::process::Stdio;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use futures_lite::StreamExt;
+    use tokio::process::Command;
+
+    use super::*;
+    use crate::TokioChildExt;
+
+    fn spawn(script: &str) -> Child {
+        Command::new("sh")
+            .args(["-c", script])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_interleaved() {
+        let mut lines = spawn(
+            "echo puppy; sleep 0.1; echo doggy >&2; sleep 0.1; \
+            printf 'kitty\\r\\n'; sleep 0.1; echo birdy >&2; sleep 0.1; printf bunny",
+        )
+        .lines_utf8();
+        let mut all = Vec::new();
+        while let Some(line) = lines.next().await {
+            all.push(line.unwrap());
+        }
+        assert_eq!(
+            all,
+            [
+                (Stream::Stdout, "puppy".to_owned()),
+                (Stream::Stderr, "doggy".to_owned()),
+                (Stream::Stdout, "kitty".to_owned()),
+                (Stream::Stderr, "birdy".to_owned()),
+                (Stream::Stdout, "bunny".to_owned()),
+            ]
+        );
+        assert!(lines.wait().await.unwrap().success());
+    }
+
+    #[tokio::test]
+    async fn test_line_numbers() {
+        let mut lines = spawn("printf 'puppy\\ndoggy\\n\\360\\237\\n' >&2; exit 3").lines_utf8();
+        assert_eq!(
+            lines.next().await.unwrap().unwrap(),
+            (Stream::Stderr, "puppy".to_owned())
+        );
+        assert_eq!(
+            lines.next().await.unwrap().unwrap(),
+            (Stream::Stderr, "doggy".to_owned())
+        );
+        let err = lines.next().await.unwrap().unwrap_err();
+        assert_eq!(err.line(), 3);
+        assert_eq!(err.utf8_error().unwrap().valid_up_to(), 12);
+        assert!(lines.next().await.is_none());
+        assert_eq!(lines.wait().await.unwrap().code(), Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_large_output() {
+        let mut lines = spawn("seq 1 100000; seq 1 100000 >&2").lines_utf8();
+        let mut counts = [0usize; 2];
+        while let Some(line) = lines.next().await {
+            let (stream, line) = line.unwrap();
+            let count = &mut counts[stream.select(0, 1)];
+            *count += 1;
+            assert_eq!(line, count.to_string());
+        }
+        assert_eq!(counts, [100000, 100000]);
+    }
+
+    #[tokio::test]
+    async fn test_drop_kills_child() {
+        let mut lines = spawn("echo $$; exec sleep 60").lines_utf8();
+        let (_, pid) = lines.next().await.unwrap().unwrap();
+        drop(lines);
+
+        // The child is killed and reaped in the background.
+        let start = Instant::now();
+        loop {
+            let output = std::process::Command::new("ps")
+                .args(["-o", "stat=", "-p", &pid])
+                .output()
+                .unwrap();
+            let stat = String::from_utf8(output.stdout).unwrap();
+            if stat.trim().is_empty() || stat.starts_with('Z') {
+                break;
+            }
+            assert!(
+                start.elapsed() < Duration::from_secs(30),
+                "Child {pid} is still running: {stat}"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
@@ -0,0 +1,158 @@
+/// The byte order of UTF-16 text.
+///
+/// See [`ConversionOptions::detect_utf16`][crate::ConversionOptions::detect_utf16].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Utf16Endianness {
+    /// Little-endian UTF-16 (UTF-16LE), as produced by most Windows tools.
+    Little,
+    /// Big-endian UTF-16 (UTF-16BE).
+    Big,
+}
+
+/// The minimum number of code units in a stream without a byte order mark for it to be guessed to
+/// be UTF-16.
+///
+/// Short UTF-8 streams with NULs, like the output of `find -print0` for a single file, look a lot
+/// like UTF-16.
+const MIN_GUESS_UNITS: usize = 8;
+
+/// Guess if `bytes` are UTF-16.
+///
+/// Returns the byte order and the length of the byte order mark, if any. Without a byte order
+/// mark, text at least 16 bytes long is guessed to be UTF-16 if at least half of its code units
+/// have a NUL high byte (which is true of ASCII text, for example) and few have a NUL low byte.
+///
+/// Guessing has false positives: NUL-separated ASCII in UTF-8, like `a\0b\0c\0...` from
+/// `find -print0` with one-character file names, is indistinguishable from UTF-16LE. Callers
+/// should only trust a guess if the stream transcodes without replacements.
+pub(crate) fn detect(bytes: &[u8]) -> Option<(Utf16Endianness, usize)> {
+    match bytes {
+        [0xff, 0xfe, ..] => return Some((Utf16Endianness::Little, 2)),
+        [0xfe, 0xff, ..] => return Some((Utf16Endianness::Big, 2)),
+        _ => {}
+    }
+
+    let units = bytes.len() / 2;
+    if units < MIN_GUESS_UNITS {
+        return None;
+    }
+    let (mut even_nuls, mut odd_nuls) = (0, 0);
+    for unit in bytes.chunks_exact(2) {
+        even_nuls += usize::from(unit[0] == 0);
+        odd_nuls += usize::from(unit[1] == 0);
+    }
+
+    let mostly_nul = |count: usize| count * 2 >= units;
+    let few_nul = |count: usize| count * 4 < units;
+    if mostly_nul(odd_nuls) && few_nul(even_nuls) {
+        Some((Utf16Endianness::Little, 0))
+    } else if mostly_nul(even_nuls) && few_nul(odd_nuls) {
+        Some((Utf16Endianness::Big, 0))
+    } else {
+        None
+    }
+}
+
+/// Transcode UTF-16 `bytes` to UTF-8.
+///
+/// Unpaired surrogates (and a trailing odd byte) are replaced with U+FFFD REPLACEMENT
+/// CHARACTER. Returns the decoded text and the number of replacements.
+pub(crate) fn transcode(bytes: &[u8], endianness: Utf16Endianness) -> (String, usize) {
+    let chunks = bytes.chunks_exact(2);
+    let trailing = !chunks.remainder().is_empty();
+    let units = chunks.map(|unit| {
+        let unit = [unit[0], unit[1]];
+        match endianness {
+            Utf16Endianness::Little => u16::from_le_bytes(unit),
+            Utf16Endianness::Big => u16::from_be_bytes(unit),
+        }
+    });
+
+    let mut decoded = String::with_capacity(bytes.len());
+    let mut replacements = 0;
+    for c in char::decode_utf16(units) {
+        decoded.push(c.unwrap_or_else(|_| {
+            replacements += 1;
+            char::REPLACEMENT_CHARACTER
+        }));
+    }
+    if trailing {
+        decoded.push(char::REPLACEMENT_CHARACTER);
+        replacements += 1;
+    }
+    (decoded, replacements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    fn utf16be(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_be_bytes).collect()
+    }
+
+    #[test]
+    fn test_detect_bom() {
+        assert_eq!(detect(b"\xff\xfe"), Some((Utf16Endianness::Little, 2)));
+        assert_eq!(
+            detect(&[b"\xfe\xff".as_slice(), &utf16be("🐶")].concat()),
+            Some((Utf16Endianness::Big, 2))
+        );
+        // A UTF-8 byte order mark isn't UTF-16.
+        assert_eq!(detect("\u{feff}puppy".as_bytes()), None);
+    }
+
+    #[test]
+    fn test_detect_without_bom() {
+        assert_eq!(
+            detect(&utf16le("Node - puppy\r\n")),
+            Some((Utf16Endianness::Little, 0))
+        );
+        assert_eq!(
+            detect(&utf16be("puppy doggy")),
+            Some((Utf16Endianness::Big, 0))
+        );
+        // Mostly non-Latin text doesn't have enough NULs to tell.
+        assert_eq!(detect(&utf16le("こんにちは")), None);
+
+        for bytes in [
+            b"".as_slice(),
+            b"p\0",
+            b"puppy",
+            b"puppy\0doggy\0",
+            b"\0\0\0\0\0\0",
+            // Too short to guess.
+            b"a\0b\0",
+            b"p\0u\0p\0p\0y\0",
+            "🐶🐶".as_bytes(),
+        ] {
+            assert_eq!(detect(bytes), None, "{bytes:?}");
+        }
+    }
+
+    #[test]
+    fn test_transcode() {
+        assert_eq!(
+            transcode(&utf16le("puppy 🐶"), Utf16Endianness::Little),
+            (String::from("puppy 🐶"), 0)
+        );
+        assert_eq!(
+            transcode(&utf16be("puppy 🐶"), Utf16Endianness::Big),
+            (String::from("puppy 🐶"), 0)
+        );
+        // An unpaired high surrogate, then an unpaired low surrogate.
+        assert_eq!(
+            transcode(b"p\0\x3d\xd8u\0\x36\xdc", Utf16Endianness::Little),
+            (String::from("p\u{fffd}u\u{fffd}"), 2)
+        );
+        // A trailing odd byte.
+        assert_eq!(
+            transcode(b"p\0u", Utf16Endianness::Little),
+            (String::from("p\u{fffd}"), 1)
+        );
+    }
+}
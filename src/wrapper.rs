@@ -0,0 +1,299 @@
+use std::ffi::OsStr;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::path::Path;
+use std::process::Stdio;
+
+use crate::command::output_with;
+use crate::CommandError;
+use crate::Strictness;
+use crate::Utf8Output;
+
+/// An error produced by [`Command::output`].
+pub type OutputError = CommandError;
+
+/// A wrapper around [`std::process::Command`] whose [`output`][Command::output] is decoded as
+/// UTF-8.
+///
+/// The usual builder methods are available and return the wrapper, so they can be chained like
+/// with [`std::process::Command`]:
+///
+/// ```
+/// use utf8_command::Command;
+///
+/// let output = Command::new("sh")
+///     .arg("-c")
+///     .arg("echo hello")
+///     .output()
+///     .expect("failed to execute process");
+/// assert_eq!(output.stdout, "hello\n");
+/// ```
+///
+/// Everything else is available through [`Deref`] to [`std::process::Command`], including
+/// [`status`][std::process::Command::status] and [`spawn`][std::process::Command::spawn]:
+///
+/// ```
+/// use utf8_command::Command;
+///
+/// let status = Command::new("ls")
+///     .arg("-l")
+///     .arg("-a")
+///     .status()
+///     .expect("failed to execute process");
+/// assert!(status.success());
+/// ```
+///
+/// Streams are decoded strictly by default; see [`Command::strictness`].
+#[derive(Debug)]
+pub struct Command {
+    inner: std::process::Command,
+    stdout_strictness: Strictness,
+    stderr_strictness: Strictness,
+}
+
+impl Command {
+    /// Construct a new command for launching the program at path `program`.
+    ///
+    /// See [`std::process::Command::new`].
+    pub fn new(program: impl AsRef<OsStr>) -> Self {
+        Self::from(std::process::Command::new(program))
+    }
+
+    /// Set how strictly both output streams are decoded.
+    ///
+    /// Only [`Strictness::Strict`] and [`Strictness::Lossy`] are distinguished; the other
+    /// variants decode like they do as a [`DecodePolicy`][crate::DecodePolicy].
+    ///
+    /// ```
+    /// use utf8_command::Command;
+    /// use utf8_command::Strictness;
+    ///
+    /// let output = Command::new("printf")
+    ///     .arg("puppy \\300")
+    ///     .strictness(Strictness::Lossy)
+    ///     .output()
+    ///     .unwrap();
+    /// assert_eq!(output.stdout, "puppy �");
+    /// ```
+    pub fn strictness(&mut self, strictness: Strictness) -> &mut Self {
+        self.stdout_strictness = strictness;
+        self.stderr_strictness = strictness;
+        self
+    }
+
+    /// Set how strictly the `stdout` stream is decoded.
+    pub fn stdout_strictness(&mut self, strictness: Strictness) -> &mut Self {
+        self.stdout_strictness = strictness;
+        self
+    }
+
+    /// Set how strictly the `stderr` stream is decoded.
+    pub fn stderr_strictness(&mut self, strictness: Strictness) -> &mut Self {
+        self.stderr_strictness = strictness;
+        self
+    }
+
+    /// Add an argument to pass to the program.
+    ///
+    /// See [`std::process::Command::arg`].
+    pub fn arg(&mut self, arg: impl AsRef<OsStr>) -> &mut Self {
+        self.inner.arg(arg);
+        self
+    }
+
+    /// Add multiple arguments to pass to the program.
+    ///
+    /// See [`std::process::Command::args`].
+    pub fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.inner.args(args);
+        self
+    }
+
+    /// Insert or update an environment variable mapping.
+    ///
+    /// See [`std::process::Command::env`].
+    pub fn env(&mut self, key: impl AsRef<OsStr>, val: impl AsRef<OsStr>) -> &mut Self {
+        self.inner.env(key, val);
+        self
+    }
+
+    /// Insert or update multiple environment variable mappings.
+    ///
+    /// See [`std::process::Command::envs`].
+    pub fn envs<I, K, V>(&mut self, vars: I) -> &mut Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.inner.envs(vars);
+        self
+    }
+
+    /// Remove an environment variable mapping.
+    ///
+    /// See [`std::process::Command::env_remove`].
+    pub fn env_remove(&mut self, key: impl AsRef<OsStr>) -> &mut Self {
+        self.inner.env_remove(key);
+        self
+    }
+
+    /// Clear all environment variable mappings.
+    ///
+    /// See [`std::process::Command::env_clear`].
+    pub fn env_clear(&mut self) -> &mut Self {
+        self.inner.env_clear();
+        self
+    }
+
+    /// Set the working directory for the child process.
+    ///
+    /// See [`std::process::Command::current_dir`].
+    pub fn current_dir(&mut self, dir: impl AsRef<Path>) -> &mut Self {
+        self.inner.current_dir(dir);
+        self
+    }
+
+    /// Configure the child process's standard input.
+    ///
+    /// See [`std::process::Command::stdin`].
+    pub fn stdin(&mut self, cfg: impl Into<Stdio>) -> &mut Self {
+        self.inner.stdin(cfg);
+        self
+    }
+
+    /// Configure the child process's standard output.
+    ///
+    /// See [`std::process::Command::stdout`].
+    pub fn stdout(&mut self, cfg: impl Into<Stdio>) -> &mut Self {
+        self.inner.stdout(cfg);
+        self
+    }
+
+    /// Configure the child process's standard error.
+    ///
+    /// See [`std::process::Command::stderr`].
+    pub fn stderr(&mut self, cfg: impl Into<Stdio>) -> &mut Self {
+        self.inner.stderr(cfg);
+        self
+    }
+
+    /// Run the command, collecting its output and decoding it as UTF-8.
+    ///
+    /// See [`std::process::Command::output`].
+    ///
+    /// ```
+    /// use utf8_command::Command;
+    ///
+    /// let output = Command::new("printf")
+    ///     .arg("puppy \\300")
+    ///     .output()
+    ///     .unwrap_err();
+    /// assert_eq!(
+    ///     output.to_string(),
+    ///     "`printf 'puppy \\300'`: \
+    ///     Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+    /// );
+    /// ```
+    pub fn output(&mut self) -> Result<Utf8Output, OutputError> {
+        let stdout_strictness = self.stdout_strictness;
+        let stderr_strictness = self.stderr_strictness;
+        output_with(&mut self.inner, |output| {
+            Utf8Output::try_from_with_policy(output, &stdout_strictness, &stderr_strictness)
+        })
+    }
+
+    /// Get the wrapped [`std::process::Command`].
+    pub fn into_inner(self) -> std::process::Command {
+        self.inner
+    }
+}
+
+impl From<std::process::Command> for Command {
+    fn from(inner: std::process::Command) -> Self {
+        Self {
+            inner,
+            stdout_strictness: Strictness::Strict,
+            stderr_strictness: Strictness::Strict,
+        }
+    }
+}
+
+impl From<Command> for std::process::Command {
+    fn from(value: Command) -> Self {
+        value.inner
+    }
+}
+
+impl Deref for Command {
+    type Target = std::process::Command;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl DerefMut for Command {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output() {
+        let output = Command::new("sh")
+            .args(["-c", "echo $PUPPY; echo doggy >&2; exit 1"])
+            .env("PUPPY", "puppy")
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(output.stdout, "puppy\n");
+        assert_eq!(output.stderr, "doggy\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_strictness() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "printf 'puppy \\300'; printf 'doggy \\300' >&2"]);
+
+        let err = command.output().unwrap_err();
+        assert!(err.utf8_error().unwrap().stdout_error().is_some());
+
+        let err = command
+            .stdout_strictness(Strictness::Lossy)
+            .output()
+            .unwrap_err();
+        assert!(err.utf8_error().unwrap().stderr_error().is_some());
+
+        let output = command
+            .stderr_strictness(Strictness::Lossy)
+            .output()
+            .unwrap();
+        assert_eq!(output.stdout, "puppy \u{FFFD}");
+        assert_eq!(output.stderr, "doggy \u{FFFD}");
+    }
+
+    #[test]
+    fn test_from_std() {
+        let mut std_command = std::process::Command::new("utf8-command-does-not-exist");
+        std_command.arg("puppy");
+        let mut command = Command::from(std_command);
+        assert_eq!(
+            command.get_program(),
+            OsStr::new("utf8-command-does-not-exist")
+        );
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["puppy"]);
+        assert!(command.output().unwrap_err().io_error().is_some());
+        assert!(command.status().is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt::Display;
+
+use xshell::Cmd;
+
+use crate::Error;
+use crate::Utf8Output;
+
+/// Extension methods for running an [`xshell::Cmd`] and decoding its output as UTF-8.
+///
+/// The command is run by `xshell`, so it's echoed and its exit status is checked like with
+/// [`Cmd::output`], but invalid UTF-8 is reported with this crate's error messages.
+///
+/// ```
+/// use utf8_command::XshellCmdExt;
+/// use xshell::cmd;
+/// use xshell::Shell;
+///
+/// let sh = Shell::new().unwrap();
+/// let output = cmd!(sh, "echo puppy").quiet().output_utf8().unwrap();
+/// assert_eq!(output.stdout, "puppy\n");
+/// ```
+pub trait XshellCmdExt {
+    /// Run the command, capturing both of its output streams and decoding them as UTF-8.
+    fn output_utf8(&self) -> Result<Utf8Output, XshellError>;
+}
+
+impl XshellCmdExt for Cmd<'_> {
+    fn output_utf8(&self) -> Result<Utf8Output, XshellError> {
+        Ok(Utf8Output::try_from(self.output()?)?)
+    }
+}
+
+/// An error produced by [`XshellCmdExt::output_utf8`].
+#[derive(Debug)]
+pub enum XshellError {
+    /// `xshell` failed to run the command, or the command exited unsuccessfully.
+    Xshell(xshell::Error),
+    /// The command's output contained invalid UTF-8.
+    Utf8(Error),
+}
+
+impl XshellError {
+    /// Get the `xshell` error, if the command failed.
+    pub fn xshell_error(&self) -> Option<&xshell::Error> {
+        match self {
+            XshellError::Xshell(err) => Some(err),
+            XshellError::Utf8(_) => None,
+        }
+    }
+
+    /// Get the UTF-8 decoding error, if the command's output contained invalid UTF-8.
+    pub fn utf8_error(&self) -> Option<&Error> {
+        match self {
+            XshellError::Xshell(_) => None,
+            XshellError::Utf8(err) => Some(err),
+        }
+    }
+}
+
+impl From<xshell::Error> for XshellError {
+    fn from(value: xshell::Error) -> Self {
+        Self::Xshell(value)
+    }
+}
+
+impl From<Error> for XshellError {
+    fn from(value: Error) -> Self {
+        Self::Utf8(value)
+    }
+}
+
+impl Display for XshellError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XshellError::Xshell(err) => write!(f, "{err}"),
+            XshellError::Utf8(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for XshellError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            XshellError::Xshell(err) => Some(err),
+            XshellError::Utf8(err) => Some(err),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use xshell::cmd;
+    use xshell::Shell;
+
+    use super::*;
+
+    #[test]
+    fn test_output_utf8() {
+        let sh = Shell::new().unwrap();
+        let name = "puppy doggy";
+        let output = cmd!(sh, "echo {name}").quiet().output_utf8().unwrap();
+        assert_eq!(output.stdout, "puppy doggy\n");
+        assert_eq!(output.stderr, "");
+    }
+
+    #[test]
+    fn test_output_utf8_invalid() {
+        let sh = Shell::new().unwrap();
+        let dir = sh.create_temp_dir().unwrap();
+        let script = dir.path().join("invalid.sh");
+        sh.write_file(&script, "printf 'puppy \\300'\n").unwrap();
+
+        let err = cmd!(sh, "sh {script}").quiet().output_utf8().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Stdout contained invalid utf-8 sequence of 1 bytes from index 6: \"puppy �\""
+        );
+        assert!(err.utf8_error().is_some());
+    }
+
+    #[test]
+    fn test_output_utf8_failed() {
+        let sh = Shell::new().unwrap();
+        let err = cmd!(sh, "false").quiet().output_utf8().unwrap_err();
+        assert!(err.xshell_error().is_some());
+
+        let output = cmd!(sh, "false")
+            .quiet()
+            .ignore_status()
+            .output_utf8()
+            .unwrap();
+        assert!(!output.status.success());
+    }
+}
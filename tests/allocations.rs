@@ -0,0 +1,122 @@
+//! Tests which count allocations, with their own global allocator.
+
+use std::alloc::GlobalAlloc;
+use std::alloc::Layout;
+use std::alloc::System;
+use std::cell::Cell;
+use std::process::ExitStatus;
+use std::process::Output;
+
+use utf8_command::validate;
+use utf8_command::Error;
+use utf8_command::Utf8Output;
+
+/// Large enough to stand out from every other allocation the tests make.
+const SIZE: usize = 1024 * 1024;
+
+/// An allocator which records the largest allocation made on each thread.
+struct LargestAllocation;
+
+thread_local! {
+    static LARGEST_ALLOCATION: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for LargestAllocation {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ =
+            LARGEST_ALLOCATION.try_with(|largest| largest.set(largest.get().max(layout.size())));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: LargestAllocation = LargestAllocation;
+
+/// Get the size of the largest allocation made on this thread while running `f`.
+fn largest_allocation(f: impl FnOnce()) -> usize {
+    LARGEST_ALLOCATION.with(|largest| largest.set(0));
+    f();
+    LARGEST_ALLOCATION.with(|largest| largest.get())
+}
+
+#[test]
+fn test_try_from_ref_error_does_not_copy_valid_stream() {
+    let output = Output {
+        status: ExitStatus::default(),
+        stdout: vec![b'a'; SIZE],
+        stderr: Vec::from(b"doggy\xc0".as_slice()),
+    };
+
+    let mut result = None;
+    let largest = largest_allocation(|| {
+        result = Some(Utf8Output::try_from(&output));
+    });
+    assert!(matches!(result, Some(Err(Error::Stderr(_)))));
+    assert!(largest < SIZE, "Allocated {largest} bytes");
+}
+
+#[test]
+fn test_try_from_ref_error_copies_invalid_stream() {
+    let mut stdout = vec![b'a'; SIZE];
+    stdout[SIZE - 1] = 0xc0;
+    let output = Output {
+        status: ExitStatus::default(),
+        stdout,
+        stderr: Vec::from(b"doggy".as_slice()),
+    };
+
+    let mut result = None;
+    let largest = largest_allocation(|| {
+        result = Some(Utf8Output::try_from(&output));
+    });
+    let err = result.unwrap().unwrap_err();
+    assert_eq!(err.inner().as_bytes(), output.stdout);
+    // Only the invalid stream is copied, once.
+    assert_eq!(largest, SIZE);
+}
+
+#[test]
+fn test_validate_does_not_allocate() {
+    let output = Output {
+        status: ExitStatus::default(),
+        stdout: Vec::from("puppy 🐶"),
+        stderr: Vec::from("doggy 🐕"),
+    };
+    let mut result = None;
+    let largest = largest_allocation(|| result = Some(validate(&output)));
+    assert_eq!(result, Some(Ok(())));
+    assert_eq!(largest, 0);
+}
+
+#[test]
+fn test_clone_error_does_not_copy() {
+    let mut stdout = vec![b'a'; SIZE];
+    stdout[SIZE - 1] = 0xc0;
+    let stdout_ptr = stdout.as_ptr();
+
+    let err = Utf8Output::try_from(Output {
+        status: ExitStatus::default(),
+        stdout,
+        stderr: Vec::from(b"doggy".as_slice()),
+    })
+    .unwrap_err();
+
+    let mut clones = Vec::new();
+    let largest = largest_allocation(|| {
+        clones = vec![err.clone(), err.clone(), err.clone()];
+    });
+    assert!(largest < SIZE, "Allocated {largest} bytes");
+    for clone in &clones {
+        assert_eq!(clone, &err);
+        assert_eq!(clone.inner().as_bytes().as_ptr(), stdout_ptr);
+    }
+
+    // Once the clones are gone, the buffer is moved out of the error without being copied.
+    drop(clones);
+    let output = err.into_output();
+    assert_eq!(output.stdout.as_ptr(), stdout_ptr);
+}